@@ -14,6 +14,30 @@ pub enum ArtifactKind {
     Archive,
     /// A repository the render published to.
     Repository,
+    /// A file a script wrote, appended to, moved, or copied through the
+    /// `file` module.
+    File,
+    /// A path a script deleted, or moved away from.
+    Removed,
+}
+
+/// What became of a file the render wrote, decided by whoever wrote it and
+/// the existing-file policy it was written under.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOutcome {
+    /// Nothing was there before.
+    Created,
+    /// Something different was there, and was replaced.
+    Overwritten,
+    /// The file already held exactly what the render produced.
+    Unchanged,
+    /// Something different was there, and was kept.
+    Preserved,
+    /// The render's changes were merged into what was there.
+    Merged,
+    /// Merged, but with conflict markers left for someone to resolve.
+    Conflicted,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
@@ -25,15 +49,37 @@ pub struct Artifact {
     /// Locator for artifacts that live outside the destination — a repo URL.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uri: Option<String>,
+    /// For a written file, what became of it. Absent when the writer didn't
+    /// say, or hasn't yet: a transactional render only knows at commit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<FileOutcome>,
+    /// For a written file, the hex SHA-256 of the contents the render
+    /// produced — which, for a preserved or merged file, may not be what
+    /// ended up on disk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
 }
 
 impl Artifact {
     pub fn archive<P: Into<String>>(path: P) -> Self {
-        Artifact { kind: ArtifactKind::Archive, path: Some(path.into()), uri: None }
+        Artifact { kind: ArtifactKind::Archive, path: Some(path.into()), uri: None, outcome: None, sha256: None }
     }
 
     pub fn repository<U: Into<String>>(uri: U) -> Self {
-        Artifact { kind: ArtifactKind::Repository, path: None, uri: Some(uri.into()) }
+        Artifact { kind: ArtifactKind::Repository, path: None, uri: Some(uri.into()), outcome: None, sha256: None }
+    }
+
+    pub fn file<P: Into<String>>(path: P) -> Self {
+        Artifact { kind: ArtifactKind::File, path: Some(path.into()), uri: None, outcome: None, sha256: None }
+    }
+
+    pub fn removed<P: Into<String>>(path: P) -> Self {
+        Artifact { kind: ArtifactKind::Removed, path: Some(path.into()), uri: None, outcome: None, sha256: None }
+    }
+
+    /// A file the render wrote, with the hash of what it wrote.
+    pub fn written<P: Into<String>>(path: P, sha256: String, outcome: Option<FileOutcome>) -> Self {
+        Artifact { kind: ArtifactKind::File, path: Some(path.into()), uri: None, outcome, sha256: Some(sha256) }
     }
 
     /// How this artifact reads in a one-line report.
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 
 pub use crate::commands::bool_prompt_info::BoolPromptInfo;
 pub use crate::commands::editor_prompt_info::EditorPromptInfo;
+pub use crate::commands::file_operation::FileOperation;
 pub use crate::commands::int_prompt_info::IntPromptInfo;
 pub use crate::commands::list_prompt_info::ListPromptInfo;
 pub use crate::commands::multiselect_prompt_info::MultiSelectPromptInfo;
@@ -18,9 +19,11 @@ pub use crate::commands::select_prompt_info::SelectPromptInfo;
 pub use crate::commands::text_prompt_info::TextPromptInfo;
 pub use crate::commands::write_directory_info::WriteDirectoryInfo;
 pub use crate::commands::write_file_info::{ExistingFilePolicy, WriteFileInfo};
+pub use crate::commands::write_symlink_info::WriteSymlinkInfo;
 
 mod bool_prompt_info;
 mod editor_prompt_info;
+mod file_operation;
 mod int_prompt_info;
 mod list_prompt_info;
 mod multiselect_prompt_info;
@@ -31,6 +34,7 @@ mod select_prompt_info;
 mod text_prompt_info;
 mod write_directory_info;
 mod write_file_info;
+mod write_symlink_info;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum ScriptMessage {
@@ -71,6 +75,23 @@ pub enum ScriptMessage {
     WriteFile(WriteFileInfo),
     /// Create a directory at the destination
     WriteDirectory(WriteDirectoryInfo),
+    /// Create a symbolic link at the destination
+    WriteSymlink(WriteSymlinkInfo),
+    /// Append to, delete, move, or copy something in the destination
+    FileOperation(FileOperation),
+    /// Read back a destination file as the render has left it so far, staged
+    /// writes included. Expects `String` with its contents, `None` if there
+    /// is no such file, or `Error`.
+    ReadFile(String),
+    /// List every file, symlink, and directory beneath a destination
+    /// directory as the render has left it so far, staged writes included.
+    /// Expects `Array` of their paths, empty if there is no such directory,
+    /// or `Error`.
+    ListFiles(String),
+    /// Stage every following write for the given destination, committing
+    /// them on `CompleteSuccess` and discarding them on `CompleteError`.
+    /// Expects `Ack`, or `Error` if the writes can't be staged.
+    BeginTransaction(String),
     /// Signal successful completion, carrying what the render produced
     CompleteSuccess(Vec<crate::Artifact>),
     /// Signal completion with an error
@@ -88,6 +109,9 @@ pub enum ClientMessage {
     Abort,
     /// Acknowledge receipt of a write operation
     Ack,
+    /// Acknowledge a file write, saying what became of the file. Writers
+    /// that can't say yet — one staging a transaction — send `Ack` instead.
+    Written(crate::FileOutcome),
     /// Initialize a session with configuration
     Initialize {
         answers_yaml: String,
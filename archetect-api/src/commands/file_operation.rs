@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::commands::write_file_info::ExistingFilePolicy;
+
+/// A change to what is already in the destination, where `WriteFile` only
+/// ever lays down whole files. Paths are absolute, as with `WriteFileInfo`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum FileOperation {
+    /// Append to a file, creating it (and its parents) if it is missing.
+    Append { path: String, contents: Vec<u8> },
+    /// Remove a file, or a directory and everything beneath it. Removing
+    /// something that is not there is not an error.
+    Delete { path: String },
+    /// Rename a file or directory. The policy decides what happens when
+    /// something is already at `destination`.
+    Move {
+        source: String,
+        destination: String,
+        existing_file_policy: ExistingFilePolicy,
+    },
+    /// Copy a file or directory. The policy applies to each file copied.
+    Copy {
+        source: String,
+        destination: String,
+        existing_file_policy: ExistingFilePolicy,
+    },
+}
+
+impl FileOperation {
+    /// The path the operation leaves changed: the destination of a move or
+    /// copy, the file itself otherwise.
+    pub fn path(&self) -> &str {
+        match self {
+            FileOperation::Append { path, .. } | FileOperation::Delete { path } => path,
+            FileOperation::Move { destination, .. } | FileOperation::Copy { destination, .. } => destination,
+        }
+    }
+}
@@ -5,6 +5,17 @@ pub struct WriteFileInfo {
     pub destination: String,
     pub contents: Vec<u8>,
     pub existing_file_policy: ExistingFilePolicy,
+    /// Where the pristine copy of this file from the last recorded render is
+    /// kept. When present, the handler merges against it for
+    /// `ExistingFilePolicy::Merge` and refreshes it with `contents` once the
+    /// write has been resolved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<String>,
+    /// Unix permission bits to give the file once written, e.g. `0o755`.
+    /// Unset leaves them to the client's umask. Ignored where the platform
+    /// has no such bits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -16,4 +27,8 @@ pub enum ExistingFilePolicy {
     /// Useful for CI / idempotent pipelines where a collision should
     /// block the build rather than silently resolve either way.
     Error,
+    /// Three-way merge `contents` into the existing file, using the shadow
+    /// copy as the common ancestor. Hunks that collide are written with
+    /// git-style conflict markers for the user to resolve.
+    Merge,
 }
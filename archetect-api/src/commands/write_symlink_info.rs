@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+use crate::commands::write_file_info::ExistingFilePolicy;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WriteSymlinkInfo {
+    /// Where the link itself goes.
+    pub path: String,
+    /// What the link points at, verbatim — usually relative to the link's
+    /// own directory.
+    pub target: String,
+    pub existing_file_policy: ExistingFilePolicy,
+}
@@ -1,6 +1,6 @@
 use std::io;
 
-use clap::builder::BoolishValueParser;
+use clap::builder::{BoolishValueParser, TypedValueParser};
 use clap::{command, value_parser, Arg, ArgAction, ArgMatches, Command};
 use clap_complete::{generate, Shell};
 use log::Level;
@@ -36,6 +36,29 @@ pub fn command() -> Command {
                 )
                 .args(render_args(true)),
         )
+        .subcommand(
+            Command::new("regenerate")
+                .about("Re-render a project from the archetype and answers it was generated with")
+                .long_about(
+                    "Re-render a project from the archetype and answers recorded in its\n\
+                     .archetect/manifest.yaml, following the recorded ref to its current version.\n\
+                     Files you have edited are three-way merged against the previous render;\n\
+                     collisions are left as conflict markers. Explicit answers override recorded ones."
+                )
+                .arg(
+                    Arg::new("destination-pos")
+                        .help("The project to regenerate. Overrides --destination when both are supplied.")
+                        .action(ArgAction::Set)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("source")
+                        .help("Render from this source instead of the recorded one, e.g. a local checkout")
+                        .long("source")
+                        .action(ArgAction::Set),
+                )
+                .args(render_args(true)),
+        )
         .subcommand(
             Command::new("global")
                 .about("Run a catalog action from the global config, bypassing any project .archetect.yaml")
@@ -368,6 +391,52 @@ pub fn command() -> Command {
                         .help("The Lua snippet ('-' or omitted reads stdin). Runs headless in a synthesized temp archetype; shell/git need the configured exec policy")
                 )
         )
+        .subcommand(
+            Command::new("repl")
+                .about("Explore an archetype's scripting runtime interactively — Lua with history, tab completion, and `:render <template>`")
+                .long_about(
+                    "Open a Lua prompt in the runtime an archetype's script runs in: Context, catalog,\n\
+                     directory, file, template, format, and its staged libraries. Without a source,\n\
+                     the bare runtime. Writes are a dry run unless --write is given."
+                )
+                .arg(
+                    Arg::new("source")
+                        .help("The archetype directory or git URL whose runtime to load")
+                        .action(ArgAction::Set)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("write")
+                        .help("Let writes through to the destination instead of dry-running them")
+                        .long("write")
+                        .action(ArgAction::SetTrue),
+                )
+                .args(render_args(true)),
+        )
+        .subcommand(
+            Command::new("debug")
+                .about("Debug an archetype's render from an editor — a Debug Adapter Protocol server on stdio")
+                .long_about(
+                    "Render an archetype under a debugger: breakpoints in archetype.lua, lib/ modules, and\n\
+                     ATL templates, stepping, stack frames, and locals and Context data. The editor speaks\n\
+                     the Debug Adapter Protocol over stdin and stdout; its launch request may name the\n\
+                     source, destination, and answers, over the command line's. Prompts still go to the terminal."
+                )
+                .arg(
+                    Arg::new("dap")
+                        .help("Speak the Debug Adapter Protocol over stdio (the only way to debug, for now)")
+                        .long("dap")
+                        .action(ArgAction::SetTrue)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("source")
+                        .help("The archetype directory or git URL to render, unless the launch request names one")
+                        .action(ArgAction::Set)
+                        .required(false),
+                )
+                .args(render_args(true)),
+        )
         .subcommand(
             Command::new("skill")
                 .about("Print the embedded agent skill (--install writes .claude/skills/archetect/SKILL.md)")
@@ -591,6 +660,17 @@ fn render_args(global: bool) -> Vec<Arg> {
             .value_parser(BoolishValueParser::new())
             .global(global),
     );
+    args.push(
+        Arg::new("sandbox")
+            .help("Run archetype scripts in the sandboxed Lua runtime (on by default for `server` and `mcp`)")
+            .long("sandbox")
+            .env("ARCHETECT_SANDBOX")
+            .action(ArgAction::Set)
+            .num_args(0..=1)
+            .default_missing_value("true")
+            .value_parser(BoolishValueParser::new())
+            .global(global),
+    );
     args.push(
             Arg::new("headless")
                 .help("Expect all inputs to be resolved by answers, defaults, and optional values, never waiting on interactive user input.")
@@ -626,6 +706,60 @@ fn render_args(global: bool) -> Vec<Arg> {
             .action(ArgAction::SetTrue)
             .global(global),
     );
+    args.push(
+        Arg::new("patch")
+            .help("With --dry-run, write what the render would change as a patch `git apply` accepts")
+            .long("patch")
+            .value_name("path")
+            .action(ArgAction::Set)
+            .global(global),
+    );
+    args.push(
+        Arg::new("atomic")
+            .help("Stage all writes and only apply them once the render succeeds")
+            .long("atomic")
+            .env("ARCHETECT_ATOMIC")
+            .action(ArgAction::SetTrue)
+            .global(global),
+    );
+    args.push(
+        Arg::new("reproducible")
+            .help("Pin the clock to SOURCE_DATE_EPOCH and seed all randomness, so renders are byte-for-byte repeatable")
+            .long("reproducible")
+            .env("ARCHETECT_REPRODUCIBLE")
+            .action(ArgAction::SetTrue)
+            .global(global),
+    );
+    args.push(
+        Arg::new("seed")
+            .help("Seed for UUIDs and math.random in a reproducible render (implies --reproducible)")
+            .long("seed")
+            .value_name("n")
+            .env("ARCHETECT_SEED")
+            .value_parser(value_parser!(u64).map(|seed| seed.to_string()))
+            .action(ArgAction::Set)
+            .global(global),
+    );
+    args.push(
+        Arg::new("report")
+            .help("Write a report of every file the render produced, with its outcome and content hash")
+            .long("report")
+            .value_name("path")
+            .env("ARCHETECT_REPORT")
+            .action(ArgAction::Set)
+            .global(global),
+    );
+    args.push(
+        Arg::new("report-format")
+            .help("Format of the --report file")
+            .long("report-format")
+            .value_name("format")
+            .env("ARCHETECT_REPORT_FORMAT")
+            .value_parser(["json", "yaml"])
+            .default_value("json")
+            .action(ArgAction::Set)
+            .global(global),
+    );
     args
 }
 
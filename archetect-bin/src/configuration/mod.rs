@@ -346,12 +346,31 @@ pub fn load_user_config_with_cwd<L: SystemLayout>(
             path: "dry_run".into(),
         },
     );
+    mappings.insert(
+        "atomic".into(),
+        ArgExtractor::Flag {
+            path: "atomic".into(),
+        },
+    );
+    mappings.insert(
+        "reproducible".into(),
+        ArgExtractor::Flag {
+            path: "reproducible".into(),
+        },
+    );
+    mappings.insert("seed".into(), ArgExtractor::String { path: "seed".into() });
     mappings.insert(
         "local".into(),
         ArgExtractor::Flag {
             path: "locals.enabled".into(),
         },
     );
+    mappings.insert(
+        "sandbox".into(),
+        ArgExtractor::Bool {
+            path: "security.sandbox".into(),
+        },
+    );
     mappings.insert(
         "allow-exec".into(),
         ArgExtractor::Bool {
@@ -426,7 +445,6 @@ impl Source for ClapSource {
 
 #[derive(Clone, Debug)]
 enum ArgExtractor {
-    #[allow(dead_code)]
     String {
         path: String,
     },
@@ -693,4 +693,51 @@ switches:
         let config = load_user_config_with_cwd(&ctx.layout, Some(ctx.cwd()), &args).unwrap();
         assert!(config.switches().is_empty());
     }
+
+    #[test]
+    fn test_seed_argument_implies_reproducible() {
+        let ctx = TestContext::new();
+
+        use clap::{Arg, Command};
+        let cmd = Command::new("test")
+            .arg(Arg::new("reproducible").long("reproducible").action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("seed").long("seed").action(clap::ArgAction::Set));
+
+        let args = cmd.clone().try_get_matches_from(vec!["test"]).unwrap();
+        let config = load_user_config_with_cwd(&ctx.layout, Some(ctx.cwd()), &args).unwrap();
+        assert!(!config.reproducible());
+
+        let args = cmd.try_get_matches_from(vec!["test", "--seed", "42"]).unwrap();
+        let config = load_user_config_with_cwd(&ctx.layout, Some(ctx.cwd()), &args).unwrap();
+        assert!(config.reproducible());
+        assert_eq!(config.seed(), Some(42));
+    }
+
+    #[test]
+    fn test_sandbox_argument_sets_security_sandbox() {
+        let ctx = TestContext::new();
+
+        use clap::{Arg, Command};
+        use clap::builder::BoolishValueParser;
+        let cmd = Command::new("test").arg(
+            Arg::new("sandbox")
+                .long("sandbox")
+                .action(clap::ArgAction::Set)
+                .num_args(0..=1)
+                .default_missing_value("true")
+                .value_parser(BoolishValueParser::new()),
+        );
+
+        let args = cmd.clone().try_get_matches_from(vec!["test"]).unwrap();
+        let config = load_user_config_with_cwd(&ctx.layout, Some(ctx.cwd()), &args).unwrap();
+        assert_eq!(config.security().sandbox(), None);
+
+        let args = cmd.clone().try_get_matches_from(vec!["test", "--sandbox"]).unwrap();
+        let config = load_user_config_with_cwd(&ctx.layout, Some(ctx.cwd()), &args).unwrap();
+        assert!(config.sandboxed());
+
+        let args = cmd.try_get_matches_from(vec!["test", "--sandbox=false"]).unwrap();
+        let config = load_user_config_with_cwd(&ctx.layout, Some(ctx.cwd()), &args).unwrap();
+        assert_eq!(config.security().sandbox(), Some(false));
+    }
 }
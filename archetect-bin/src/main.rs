@@ -2,17 +2,19 @@ use std::collections::HashSet;
 
 use camino::Utf8PathBuf;
 use clap::ArgMatches;
-use archetect_api::{ContextMap, ScriptMessage, ScriptIoHandle};
+use archetect_api::{Artifact, ContextMap, ScriptMessage, ScriptIoHandle};
 use archetect_core::{self};
 use archetect_core::Archetect;
 use archetect_core::archetype::archetype::Archetype;
 use archetect_core::archetype::render_context::RenderContext;
 use archetect_core::configuration::Configuration;
+use archetect_core::debugger::Debugger;
 use archetect_core::errors::{ArchetectError, ArchetypeError, CatalogError, SourceError};
 use archetect_core::flags::overlay_flag_tokens;
+use archetect_core::generation::GenerationManifest;
 use archetect_core::source::SourceContents;
 use archetect_core::system::{SystemLayout, XdgSystemLayout};
-use archetect_terminal_io::TerminalScriptIoHandle;
+use archetect_terminal_io::{Report, ReportFormat, TerminalScriptIoHandle};
 use ArchetypeError::{PromptAborted, ScriptAbortError};
 
 use crate::answers::{insert_dotted, parse_answer_pair, parse_answer_value};
@@ -46,12 +48,40 @@ fn resolve_destination(args: &ArgMatches) -> String {
         .unwrap_or_else(|| ".".to_string())
 }
 
+/// The `--report` destination, on the subcommands that take one.
+fn resolve_report(args: &ArgMatches) -> Option<Report> {
+    let path = args.try_get_one::<String>("report").ok().flatten()?;
+    let format = args
+        .try_get_one::<String>("report-format")
+        .ok()
+        .flatten()
+        .and_then(|format| ReportFormat::from_name(format))
+        .unwrap_or_default();
+    Some(Report::new(path.as_str(), format))
+}
+
+/// The arguments render flags are read from: the subcommand's, or the top
+/// level's for a bare `archetect <action>`.
+fn subcommand_matches(matches: &ArgMatches) -> &ArgMatches {
+    matches.subcommand().map_or(matches, |(_, args)| args)
+}
+
 fn main() {
     let matches = cli::command()
         .get_matches();
     cli::configure(&matches);
 
-    let driver = TerminalScriptIoHandle::default();
+    // `connect` reports from its own client, which sees what the server
+    // rendered; this driver only sees the local session, which rendered
+    // nothing.
+    let mut driver = TerminalScriptIoHandle::default();
+    let report = match matches.subcommand() {
+        Some(("connect", _)) => None,
+        _ => resolve_report(subcommand_matches(&matches)),
+    };
+    if let Some(report) = report {
+        driver = driver.with_report(report);
+    }
     let layout = match XdgSystemLayout::new() {
         Ok(layout) => layout,
         Err(err) => {
@@ -60,9 +90,27 @@ fn main() {
         }
     };
 
-    match execute(matches, driver.clone(), layout) {
-        Ok(()) => (),
+    // Completion tells the driver the session is over: a transactional render
+    // commits its staged writes on success and throws them away otherwise.
+    // `debug` speaks the Debug Adapter Protocol on stdout, so what a script
+    // prints goes to the editor instead.
+    let outcome = match matches.subcommand() {
+        Some(("debug", _)) => {
+            let debugger = Debugger::new(std::io::stdout());
+            execute(matches, debugger.driver(driver.clone()), layout, Some(debugger))
+        }
+        _ => execute(matches, driver.clone(), layout, None),
+    };
+    match outcome {
+        Ok(artifacts) => {
+            // A transactional render only lands on disk as it completes; the
+            // driver has already said why if it didn't.
+            if driver.send(ScriptMessage::CompleteSuccess(artifacts)).is_err() {
+                std::process::exit(-1);
+            }
+        }
         Err(error) => {
+            let _ = driver.send(ScriptMessage::CompleteError(error.to_string()));
             match error {
                 // Handled when the script ends by the IO Driver
                 ArchetectError::ArchetypeError(ScriptAbortError) => {}
@@ -82,7 +130,13 @@ fn main() {
     }
 }
 
-fn execute<D: ScriptIoHandle, L: SystemLayout>(matches: ArgMatches, driver: D, layout: L) -> Result<(), ArchetectError> {
+/// Returns what the session produced, for the completion report.
+fn execute<D: ScriptIoHandle, L: SystemLayout>(
+    matches: ArgMatches,
+    driver: D,
+    layout: L,
+    debugger: Option<Debugger>,
+) -> Result<Vec<Artifact>, ArchetectError> {
     // The `global` subcommand bypasses project config detection so users can
     // access the global catalog from inside a project that has its own .archetect.yaml.
     let is_global_subcommand = matches!(matches.subcommand(), Some(("global", _)));
@@ -107,6 +161,15 @@ fn execute<D: ScriptIoHandle, L: SystemLayout>(matches: ArgMatches, driver: D, l
         configuration
     };
 
+    // Server and MCP sessions run archetypes on someone else's behalf, so
+    // their scripts are sandboxed unless the configuration says otherwise.
+    let configuration = match matches.subcommand() {
+        Some(("mcp", _)) | Some(("server", _)) if configuration.security().sandbox().is_none() => {
+            configuration.with_sandbox(true)
+        }
+        _ => configuration,
+    };
+
     // If --allow-exec is set (or env var, or config), emit a prominent warning.
     if matches!(
         configuration.shell_exec_policy(),
@@ -119,17 +182,56 @@ fn execute<D: ScriptIoHandle, L: SystemLayout>(matches: ArgMatches, driver: D, l
         );
     }
 
-    let archetect = Archetect::builder()
+    // `--patch` implies `--dry-run`: the patch is what the render would
+    // have written.
+    let patch = subcommand_matches(&matches).try_get_one::<String>("patch").ok().flatten().cloned();
+    let configuration = if patch.is_some() { configuration.with_dry_run(true) } else { configuration };
+
+    // The REPL is for finding out; what it would write is only reported,
+    // unless asked otherwise.
+    let configuration = match matches.subcommand() {
+        Some(("repl", args)) if !args.get_flag("write") => configuration.with_dry_run(true),
+        _ => configuration,
+    };
+
+    // A debugged render's writes land as it goes, so the editor sees them
+    // at each stop; there's no completion summary to commit them at, since
+    // stdout is the protocol's.
+    let configuration = match matches.subcommand() {
+        Some(("debug", _)) => configuration.with_atomic(false),
+        _ => configuration,
+    };
+
+    // The same sessions are held to the configured resource limits; the
+    // local CLI leaves a runaway script to Ctrl-C.
+    let limits = match matches.subcommand() {
+        Some(("mcp", _)) | Some(("server", _)) => configuration.server().and_then(|server| server.limits()),
+        _ => None,
+    };
+
+    let mut builder = Archetect::builder()
         .with_configuration(configuration)
         .with_driver(driver)
-        .with_layout(layout)
-        .build()?;
+        .with_layout(layout);
+    if let Some(limits) = limits {
+        builder = builder.with_limits(limits);
+    }
+    if patch.is_some() {
+        builder = builder.with_dry_run_patch();
+    }
+    if let Some(debugger) = &debugger {
+        builder = builder.with_debugger(debugger.clone());
+    }
+    let archetect = builder.build()?;
+    // Most subcommands take the session by value; the journal is shared.
+    let session = archetect.clone();
 
     match matches.subcommand() {
         Some(("completions", args)) => cli::completions(args)?,
         Some(("ls", args)) => handle_commands_subcommand(args, &archetect),
         Some(("search", args)) => subcommands::handle_search_subcommand(args, &archetect),
         Some(("render", args)) => render(args, archetect, answers)?,
+        Some(("regenerate", args)) => regenerate(args, archetect, answers)?,
         Some(("global", args)) => execute_global_dispatch(args, archetect, answers)?,
         Some(("config", args)) => subcommands::handle_config_subcommand(args, &archetect)?,
         Some(("cache", args)) => subcommands::handle_cache_subcommand(args, &archetect)?,
@@ -169,6 +271,41 @@ fn execute<D: ScriptIoHandle, L: SystemLayout>(matches: ArgMatches, driver: D, l
         }
         Some(("learn", args)) => subcommands::handle_learn_subcommand(args, &archetect)?,
         Some(("eval", args)) => subcommands::handle_eval_subcommand(args, &archetect)?,
+        Some(("repl", args)) => {
+            let destination = shellexpand::full(&resolve_destination(args))?.to_string();
+            let render_context = configure_render_context(
+                RenderContext::new(Utf8PathBuf::from(destination), answers),
+                &archetect,
+                args,
+            )?;
+            subcommands::handle_repl_subcommand(args, archetect, render_context)?
+        }
+        Some(("debug", args)) => {
+            let Some(debugger) = debugger else {
+                return Err(ArchetectError::GeneralError("debug: no debugger attached".to_string()));
+            };
+            // The editor went away before launching: nothing to debug.
+            let Some(launch) = debugger.listen(std::io::stdin()) else {
+                return Ok(Vec::new());
+            };
+            let source = launch
+                .source
+                .or_else(|| args.get_one::<String>("source").cloned())
+                .unwrap_or_else(|| ".".to_string());
+            let destination = launch.destination.unwrap_or_else(|| resolve_destination(args));
+            let destination = shellexpand::full(&destination)?.to_string();
+            let mut answers = answers;
+            answers.extend(launch.answers);
+            let render_context = configure_render_context(
+                RenderContext::new(Utf8PathBuf::from(destination), answers),
+                &archetect,
+                args,
+            )?;
+            subcommands::handle_debug_subcommand(&source, archetect, &debugger, render_context)?;
+            // The editor has heard how the render went; a summary on stdout
+            // would land in its protocol stream.
+            return Ok(Vec::new());
+        }
         Some(("introspect", args)) => subcommands::handle_introspect_subcommand(args)?,
         Some(("skill", args)) => subcommands::handle_skill_subcommand(args)?,
         Some(("mcp", _)) => subcommands::handle_mcp_subcommand(archetect)?,
@@ -184,7 +321,9 @@ fn execute<D: ScriptIoHandle, L: SystemLayout>(matches: ArgMatches, driver: D, l
             )?;
             let client_cfg = archetect.configuration().client().cloned();
             let endpoint = subcommands::resolve_endpoint(args, client_cfg.as_ref())?;
-            let options = subcommands::resolve_client_options(args, client_cfg.as_ref());
+            let mut options = subcommands::resolve_client_options(args, client_cfg.as_ref());
+            options.atomic = archetect.configuration().atomic();
+            options.report = resolve_report(args);
             // `connect <endpoint> <path>` addresses a catalog leaf on the
             // server (Initialize.catalog_path). An unset action (clap's
             // "default" fill-in) sends the empty path — the server's own
@@ -206,7 +345,11 @@ fn execute<D: ScriptIoHandle, L: SystemLayout>(matches: ArgMatches, driver: D, l
         }
     }
 
-    Ok(())
+    if let (Some(path), Some(contents)) = (patch, session.dry_run_patch()) {
+        std::fs::write(&path, contents)?;
+    }
+
+    Ok(session.artifacts())
 }
 
 /// Dispatch for `archetect global [path]`. The configuration was loaded without
@@ -344,6 +487,36 @@ pub fn render(matches: &ArgMatches, archetect: Archetect, answers: ContextMap) -
     }
 }
 
+/// Replay a recorded generation: the manifest's answers and switches, under
+/// whatever this invocation supplies, against the recorded source.
+pub fn regenerate(matches: &ArgMatches, archetect: Archetect, answers: ContextMap) -> Result<(), ArchetectError> {
+    let destination = shellexpand::full(&resolve_destination(matches))?.to_string();
+    let destination = Utf8PathBuf::from(destination);
+    let manifest = GenerationManifest::load(&destination)?;
+
+    let source = match matches.get_one::<String>("source") {
+        Some(source) => source.clone(),
+        None => manifest.source_locator(),
+    };
+    let source = archetect.new_source(&source)?;
+
+    let mut replayed = manifest.answers.clone();
+    replayed.extend(answers);
+    let render_context = configure_render_context(RenderContext::new(destination, replayed), &archetect, matches)?;
+    let mut switches: HashSet<String> = manifest.switches.iter().cloned().collect();
+    switches.extend(render_context.switches().iter().cloned());
+    let render_context = render_context.with_switches(switches);
+
+    match source.source_contents() {
+        SourceContents::Archetype => {
+            let archetype = Archetype::new(archetect, source)?;
+            archetype.check_requirements()?;
+            Ok(archetype.render(render_context).map(|_| ())?)
+        }
+        SourceContents::Unknown => Err(SourceError::UnknownSourceContent.into()),
+    }
+}
+
 fn configure_render_context(
     render_context: RenderContext,
     archetect: &Archetect,
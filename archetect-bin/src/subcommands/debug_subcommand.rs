@@ -0,0 +1,26 @@
+//! `archetect debug --dap [source]` — render an archetype under an editor's debugger.
+//!
+//! The editor starts the adapter and speaks the Debug Adapter Protocol over its stdin and stdout.
+//! Its `launch` request may name a `source`, `destination`, and `answers`; each wins over the
+//! command line's, so one launch configuration can cover several archetypes. Once the editor has
+//! set its breakpoints, the render runs as `render` would, and the editor hears how it ended.
+
+use archetect_core::archetype::render_context::RenderContext;
+use archetect_core::debugger::Debugger;
+use archetect_core::errors::ArchetectError;
+use archetect_core::Archetect;
+
+/// Render `source` for the launched session. Its failure is the editor's to show — stdout is the
+/// protocol's — so the command itself succeeds once the editor has heard.
+pub fn handle_debug_subcommand(
+    source: &str,
+    archetect: Archetect,
+    debugger: &Debugger,
+    render_context: RenderContext,
+) -> Result<(), ArchetectError> {
+    let rendered = archetect
+        .new_archetype(source)
+        .and_then(|archetype| archetype.render(render_context).map_err(ArchetectError::from));
+    debugger.finish(rendered.map(|_| ()).map_err(|err| err.to_string()));
+    Ok(())
+}
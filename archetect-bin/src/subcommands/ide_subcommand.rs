@@ -43,6 +43,7 @@ impl Manage {
 pub fn handle_ide_subcommand(layout: &dyn SystemLayout, manage: Manage) -> Result<(), ArchetectError> {
     let annotations_dir = install_annotations(layout)?;
     maybe_manage_luarc(&annotations_dir, manage)?;
+    eprintln!("archetect: to debug renders, point an editor's DAP launch configuration at `archetect debug --dap`");
     Ok(())
 }
 
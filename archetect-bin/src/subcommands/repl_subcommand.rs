@@ -0,0 +1,279 @@
+//! `archetect repl [source]` — poke at an archetype's runtime interactively.
+//!
+//! Where `eval` runs one snippet and throws the state away, the REPL keeps an archetype's Lua state
+//! — `Context`, `catalog`, `directory`, `file`, `template`, `format`, its staged libraries — open
+//! between inputs, so an author can build up a context, prompt, and render against it one line at a
+//! time. Without a source, it's the bare runtime every archetype starts from.
+//!
+//! Writes are a dry run unless `--write` is given: the REPL is for finding out, and `directory.render`
+//! typed to see what it does shouldn't leave files behind. Prompts are live, as in a render.
+
+use std::io::{BufRead, IsTerminal, Write};
+use std::rc::Rc;
+
+use camino::Utf8PathBuf;
+use clap::ArgMatches;
+use inquire::autocompletion::{Autocomplete, Replacement};
+use inquire::ui::{RenderConfig, Styled};
+use inquire::{CustomUserError, InquireError, Text};
+
+use archetect_core::archetype::archetype::Archetype;
+use archetect_core::archetype::render_context::RenderContext;
+use archetect_core::errors::ArchetectError;
+use archetect_core::script::lua::{Evaluation, Repl};
+use archetect_core::Archetect;
+
+/// The REPL's own commands, as completion offers them.
+const COMMANDS: &[&str] = &[":render ", ":help", ":quit"];
+
+/// How many inputs the history file keeps.
+const HISTORY_LIMIT: usize = 500;
+
+const HELP: &str = "\
+Lua, in the archetype's runtime. An expression prints its value; tables and Contexts print as YAML.
+`context` is a Context to set values on and prompt with (`context = Context.new()` starts over).
+
+  :render <template>   render an ATL template against `context`
+  :help                this
+  :quit                leave (or Ctrl-C, or exit())
+
+Tab completes what `archetect introspect` documents and the globals you've defined; on an empty
+line, it offers your history.";
+
+pub fn handle_repl_subcommand(
+    args: &ArgMatches,
+    archetect: Archetect,
+    render_context: RenderContext,
+) -> Result<(), ArchetectError> {
+    // Without a source: a bare archetype, staged in a temp dir like `eval`'s probe. The TempDir
+    // lives as long as the session.
+    let mut _bare = None;
+    let source = match args.get_one::<String>("source") {
+        Some(source) => source.clone(),
+        None => {
+            let root = tempfile::TempDir::with_prefix("archetect-repl-")
+                .map_err(|e| ArchetectError::GeneralError(format!("repl: temp dir: {e}")))?;
+            std::fs::write(root.path().join("archetype.yaml"), "description: archetect repl\n")
+                .map_err(|e| ArchetectError::GeneralError(format!("repl: staging the archetype: {e}")))?;
+            let path = root
+                .path()
+                .to_str()
+                .ok_or_else(|| ArchetectError::GeneralError("repl: non-UTF-8 temp path".to_string()))?
+                .to_string();
+            _bare = Some(root);
+            path
+        }
+    };
+
+    let history_path = archetect.layout().data_dir().join("repl_history");
+    let source = archetect.new_source(&source)?;
+    let archetype = Archetype::new(archetect.clone(), source)?;
+    archetype.check_requirements()?;
+    let repl = Rc::new(Repl::new(&archetype, render_context)?);
+
+    if !std::io::stdin().is_terminal() {
+        return run_piped(&repl);
+    }
+
+    if archetect.is_dry_run() {
+        eprintln!("archetect repl — writes are a dry run (--write lets them through). :help for help.");
+    } else {
+        eprintln!("archetect repl — writes go to the destination. :help for help.");
+    }
+
+    let mut history = History::load(history_path);
+    let render_config = RenderConfig::default_colored()
+        .with_prompt_prefix(Styled::new(""))
+        .with_answered_prompt_prefix(Styled::new(""));
+    let mut buffer = String::new();
+    loop {
+        let prompt = if buffer.is_empty() { "lua>" } else { "...>" };
+        let line = match Text::new(prompt)
+            .with_render_config(render_config)
+            .with_autocomplete(Completer {
+                repl: repl.clone(),
+                history: Rc::new(history.entries.clone()),
+            })
+            .prompt()
+        {
+            Ok(line) => line,
+            // Esc abandons the input in progress; Ctrl-C leaves.
+            Err(InquireError::OperationCanceled) => {
+                buffer.clear();
+                continue;
+            }
+            Err(InquireError::OperationInterrupted) => break,
+            Err(e) => return Err(ArchetectError::GeneralError(format!("repl: {e}"))),
+        };
+
+        if buffer.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        if !buffer.is_empty() {
+            buffer.push('\n');
+        }
+        buffer.push_str(&line);
+        if !buffer.trim_start().starts_with(':') && repl.is_incomplete(&buffer) {
+            continue;
+        }
+
+        let input = std::mem::take(&mut buffer);
+        history.push(&input);
+        if !respond(&repl, &input) {
+            break;
+        }
+    }
+    history.save();
+    Ok(())
+}
+
+/// Answer one input, printing what it came to. False when it's time to leave.
+fn respond(repl: &Repl, input: &str) -> bool {
+    let trimmed = input.trim();
+    if let Some(command) = trimmed.strip_prefix(':') {
+        let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        match name {
+            "q" | "quit" | "exit" => return false,
+            "h" | "help" => println!("{HELP}"),
+            "render" if !argument.trim().is_empty() => match repl.render(argument.trim_start()) {
+                Ok(rendered) => println!("{rendered}"),
+                Err(message) => eprintln!("error: {message}"),
+            },
+            "render" => eprintln!("usage: :render <template>"),
+            other => eprintln!("unknown command `:{other}` — :help lists them"),
+        }
+        return true;
+    }
+    match repl.eval(input) {
+        Ok(Evaluation::Values(values)) => {
+            for value in values {
+                println!("{value}");
+            }
+        }
+        Ok(Evaluation::Incomplete) => eprintln!("error: the input ends mid-statement"),
+        Ok(Evaluation::Exit) => return false,
+        Err(message) => eprintln!("error: {message}"),
+    }
+    true
+}
+
+/// Without a terminal, read inputs from stdin, one statement at a time, with no prompt to echo.
+fn run_piped(repl: &Repl) -> Result<(), ArchetectError> {
+    let mut buffer = String::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|e| ArchetectError::GeneralError(format!("repl: reading stdin: {e}")))?;
+        if !buffer.is_empty() {
+            buffer.push('\n');
+        }
+        buffer.push_str(&line);
+        if buffer.trim().is_empty() {
+            buffer.clear();
+            continue;
+        }
+        if !buffer.trim_start().starts_with(':') && repl.is_incomplete(&buffer) {
+            continue;
+        }
+        let input = std::mem::take(&mut buffer);
+        if !respond(repl, &input) {
+            break;
+        }
+        let _ = std::io::stdout().flush();
+    }
+    if !buffer.trim().is_empty() {
+        respond(repl, &buffer);
+    }
+    Ok(())
+}
+
+/// Past inputs, newest last, kept across sessions in the data directory.
+struct History {
+    path: Utf8PathBuf,
+    entries: Vec<String>,
+}
+
+impl History {
+    fn load(path: Utf8PathBuf) -> History {
+        // Each entry is one JSON string per line, so multi-line inputs survive the round trip.
+        let entries = std::fs::read_to_string(&path)
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        History { path, entries }
+    }
+
+    fn push(&mut self, input: &str) {
+        self.entries.retain(|entry| entry != input);
+        self.entries.push(input.to_string());
+    }
+
+    /// Best effort: a REPL that can't remember is still a REPL.
+    fn save(&self) {
+        let start = self.entries.len().saturating_sub(HISTORY_LIMIT);
+        let contents: String = self.entries[start..]
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .map(|line| line + "\n")
+            .collect();
+        if let Some(parent) = self.path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = std::fs::write(&self.path, contents);
+    }
+}
+
+#[derive(Clone)]
+struct Completer {
+    repl: Rc<Repl>,
+    history: Rc<Vec<String>>,
+}
+
+impl Autocomplete for Completer {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        if input.trim().is_empty() {
+            return Ok(self.history.iter().rev().cloned().collect());
+        }
+        if input.starts_with(':') {
+            return Ok(COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(input))
+                .map(|command| command.to_string())
+                .collect());
+        }
+        let completions = self.repl.completions(input);
+        if !completions.is_empty() {
+            return Ok(completions);
+        }
+        Ok(self
+            .history
+            .iter()
+            .rev()
+            .filter(|entry| entry.starts_with(input) && *entry != input)
+            .cloned()
+            .collect())
+    }
+
+    fn get_completion(&mut self, input: &str, highlighted: Option<String>) -> Result<Replacement, CustomUserError> {
+        if highlighted.is_some() {
+            return Ok(highlighted);
+        }
+        // Nothing highlighted: finish as much as every suggestion agrees on.
+        let suggestions = self.get_suggestions(input)?;
+        let common =
+            suggestions
+                .iter()
+                .skip(1)
+                .fold(suggestions.first().cloned().unwrap_or_default(), |common, next| {
+                    common
+                        .chars()
+                        .zip(next.chars())
+                        .take_while(|(a, b)| a == b)
+                        .map(|(a, _)| a)
+                        .collect()
+                });
+        Ok((common.len() > input.len()).then_some(common))
+    }
+}
@@ -437,6 +437,8 @@ function directory.render(path, context, opts) end
 ---@class DirectoryRenderOpts
 ---@field destination? string Subdirectory to render into (relative to current destination)
 ---@field if_exists? ExistingPolicy How to handle existing files (e.g., `Existing.Overwrite`)
+---@field exclude? string|string[] Globs of entries to skip, matched against source and rendered paths (`.archetectignore` files add more)
+---@field include? string|string[] Globs of files to render; everything else is skipped
 
 --
 -- file (single-file counterparts to directory.*)
@@ -477,12 +479,134 @@ function file.read(path, opts) end
 ---@return string? result Rendered string when no destination; nil when written to disk
 function file.render(path, context, opts) end
 
+---Insert `content` into an existing destination file, once: if the file
+---already contains it (or `opts.unless_present`), nothing is written and
+---this returns false. Whole lines; errors if the anchor matches no line.
+---Under `--dry-run`, prints the diff it would apply.
+---@param path string Path relative to the render destination
+---@param content string Snippet to insert
+---@param opts? FileInjectOpts
+---@return boolean changed
+function file.inject(path, content, opts) end
+
+---Edit a TOML, JSON, or YAML file in the destination through `edit`, which
+---receives a `StructuredDocument`. Only what changes is rewritten: comments,
+---key order, and layout elsewhere in the file are kept. The format comes
+---from the extension unless `opts.format` is given. Under `--dry-run`,
+---prints the diff it would apply.
+---@param path string Path relative to the render destination
+---@param edit fun(doc: StructuredDocument)
+---@param opts? FileEditStructuredOpts
+---@return boolean changed
+function file.edit_structured(path, edit, opts) end
+
+---Write `contents` to a file in the destination, creating its directory.
+---Goes through the IO channel like a rendered file: honors `--dry-run`
+---and `if_exists` (default `Existing.Preserve`).
+---@param path string Path relative to the render destination
+---@param contents string File contents
+---@param opts? FileWriteOpts
+function file.write(path, contents, opts) end
+
+---Append to a file in the destination, creating it if missing.
+---@param path string Path relative to the render destination
+---@param contents string Text to append
+function file.append(path, contents) end
+
+---Delete a file, or a directory and everything in it, from the destination.
+---Deleting something that isn't there is not an error.
+---@param path string Path relative to the render destination
+function file.delete(path) end
+
+---Move a file or directory within the destination. When something is
+---already at `destination`, `if_exists` decides; whenever it keeps what is
+---there, the source stays put too.
+---@param source string Path relative to the render destination
+---@param destination string Path relative to the render destination
+---@param opts? FileTransferOpts
+function file.move(source, destination, opts) end
+
+---Copy a file or directory within the destination. `if_exists` applies to
+---each file copied.
+---@param source string Path relative to the render destination
+---@param destination string Path relative to the render destination
+---@param opts? FileTransferOpts
+function file.copy(source, destination, opts) end
+
+---Names of the entries in a destination directory, sorted. A directory
+---that doesn't exist lists as empty.
+---@param path? string Directory relative to the render destination. Default: the destination itself.
+---@return string[] names
+function file.list(path) end
+
+---Destination-relative paths matching a glob, sorted. `*` stops at `/`;
+---`**` crosses it.
+---@param pattern string Glob, e.g. `"src/**/*.rs"`
+---@return string[] paths
+function file.glob(pattern) end
+
 ---@class FileOpts
 ---@field within? LocationPolicy Where to resolve the path. Default: `Location.Archetype`.
 
 ---@class FileRenderOpts
 ---@field destination? string Destination path (relative to render destination). When present, write instead of returning.
 ---@field if_exists? ExistingPolicy How to handle existing files (e.g., `Existing.Overwrite`)
+---@field mode? integer|string Permission bits, read as octal (`755` or `"0755"`). Default: executable sources stay executable.
+
+---@class FileWriteOpts
+---@field if_exists? ExistingPolicy How to handle an existing file. Default: `Existing.Preserve`.
+---@field mode? integer|string Permission bits, read as octal (`755` or `"0755"`).
+
+---@alias TextPattern string|{ regex: string } A literal string, or a regex.
+
+---@class FileInjectOpts
+---@field after? TextPattern Insert after the first line matching this
+---@field before? TextPattern Insert before the first line matching this
+---@field at? "start"|"end" Insert at the start or end of the file. Default: `"end"` when no anchor is given.
+---@field unless_present? TextPattern Skip when the file already contains this. Default: the snippet itself.
+
+---@class FileEditStructuredOpts
+---@field format? "toml"|"json"|"yaml" Read the file as this format. Default: from its extension.
+
+---A dotted path (`"dependencies.tokio"`) or a list of segments
+---(`{ "tool", "poetry", 1 }`) for keys containing dots. Array elements count from 1.
+---@alias StructuredPath string|(string|integer)[]
+
+---@class StructuredDocument
+local StructuredDocument = {}
+
+---@param path StructuredPath
+---@return any value nil when nothing is there
+function StructuredDocument:get(path) end
+
+---@param path StructuredPath
+---@return boolean
+function StructuredDocument:has(path) end
+
+---Set a value, creating missing tables on the way. Setting a value to what
+---it already is leaves the file untouched.
+---@param path StructuredPath
+---@param value any
+function StructuredDocument:set(path, value) end
+
+---@param path StructuredPath
+---@return boolean removed false when nothing was there
+function StructuredDocument:remove(path) end
+
+---Append to the array at `path` (creating it) unless an equal element is already there.
+---@param path StructuredPath
+---@param value any
+---@return boolean added
+function StructuredDocument:add(path, value) end
+
+---Deep-merge a table into the document, or beneath `path` when given.
+---@overload fun(self: StructuredDocument, value: table)
+---@param path StructuredPath
+---@param value table
+function StructuredDocument:merge(path, value) end
+
+---@class FileTransferOpts
+---@field if_exists? ExistingPolicy How to handle something already at the destination. Default: `Existing.Preserve`.
 
 ---@class LocationPolicy
 ---Typed enum for file-resolution scope.
@@ -578,6 +702,7 @@ function exit() end
 ---@field Preserve ExistingPolicy Keep existing files unchanged (default)
 ---@field Prompt ExistingPolicy Ask the user what to do (interactive)
 ---@field Error ExistingPolicy Fail the render — useful for CI / idempotent pipelines
+---@field Merge ExistingPolicy Three-way merge against the last recorded render, marking conflicts
 Existing = {}
 
 --
@@ -127,6 +127,59 @@ function archive.tar_gz(source, destination) end
 ---@param destination string Output archive path
 function archive.tar(source, destination) end
 
+--
+-- archetect.fs
+--
+
+---@class archetect.fs
+---Read-only inspection, for archetypes that add to a project: what's there,
+---and where its root is. Paths resolve as `file.*` paths do — against the
+---archetype root unless `opts.within` says otherwise — and absolute paths,
+---`..` traversal, and `~` expansion are rejected. Symlinks are reported, not
+---followed.
+local fs = {}
+
+---Root-relative paths matching a glob, sorted. `*` stops at `/`; `**`
+---crosses it.
+---@param pattern string Glob, e.g. `"src/**/*.rs"`
+---@param opts? FileOpts
+---@return string[] paths
+function fs.glob(pattern, opts) end
+
+---Names of the entries in a directory, sorted. A directory that doesn't
+---exist lists as empty.
+---@param path? string Directory path. Default: the root itself.
+---@param opts? FileOpts
+---@return string[] names
+function fs.list_dir(path, opts) end
+
+---What's at a path, or nil when nothing is. `mode` is nil off Unix.
+---@param path string Relative path
+---@param opts? FileOpts
+---@return {size: integer, mtime: integer, is_dir: boolean, is_file: boolean, is_symlink: boolean, mode?: integer}?
+function fs.stat(path, opts) end
+
+---Everything beneath a directory, parents before their children and
+---siblings by name. Paths are root-relative; `depth` 1 is the directory's
+---own entries.
+---@param path? string Directory path. Default: the root itself.
+---@param opts? FsWalkOpts
+---@return {path: string, is_dir: boolean, depth: integer}[] entries
+function fs.walk(path, opts) end
+
+---The nearest directory holding `name`, searching from the root upwards —
+---`fs.find_up("Cargo.toml", { within = Location.Destination })` finds the
+---enclosing project. Returns an absolute path, or nil. In the sandbox, the
+---search stays within the root unless the session grants `filesystem`.
+---@param name string File or directory name to look for
+---@param opts? FileOpts
+---@return string? directory
+function fs.find_up(name, opts) end
+
+---@class FsWalkOpts
+---@field within? LocationPolicy Where to resolve the path. Default: `Location.Archetype`.
+---@field max_depth? integer How many levels to descend. Default: all of them.
+
 --
 -- archetect.model — AML (Archetect Modeling Language) model loading
 --
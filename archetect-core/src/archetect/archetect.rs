@@ -1,16 +1,28 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use camino::{Utf8Path, Utf8PathBuf};
 use semver::Version;
+use sha2::{Digest, Sha256};
 
-use archetect_api::{Artifact, ClientMessage, IoError, ScriptIoHandle, ScriptMessage};
+use archetect_api::{
+    Artifact, ClientMessage, ContextMap, ContextValue, FileOperation, FileOutcome, IoError, ScriptIoHandle,
+    ScriptMessage,
+};
 use archetect_terminal_io::TerminalScriptIoHandle;
 
 use crate::archive::ArchiveEntry;
 
+use archetect_terminal_io::overlay::Overlay;
+
 use crate::archetype::archetype::Archetype;
 use crate::configuration::Configuration;
+use crate::debugger::Debugger;
 use crate::errors::ArchetectError;
+use crate::generation::Recording;
+use crate::limits::ResourceLimits;
+use crate::reproducible::Reproducible;
 use crate::source::Source;
 use crate::system::{RootedSystemLayout, SystemLayout, XdgSystemLayout};
 
@@ -26,11 +38,34 @@ struct Inner {
     layout: Box<dyn SystemLayout>,
     configuration: Configuration,
     journal: Mutex<RenderJournal>,
+    /// The generation being recorded, while a regeneration-enabled archetype
+    /// renders. Writes beneath its destination take their existing-file
+    /// policy from it rather than from the script.
+    generation: Mutex<Option<Recording>>,
+    /// Set once the client has been asked to stage writes. It holds the
+    /// transaction until the session completes, so a session opens one at most.
+    transaction: AtomicBool,
     /// Capabilities this session grants. Unset means unrestricted — the local
     /// CLI, where the user *is* the trust boundary. Once set, anything not
     /// named is denied. `OnceLock` because a session's grants are established
     /// at initialization and must never widen afterwards.
     capabilities: std::sync::OnceLock<std::collections::HashSet<String>>,
+    /// Where the session's first render writes to. Artifacts are reported
+    /// relative to it; composed archetypes render beneath it.
+    destination: std::sync::OnceLock<Utf8PathBuf>,
+    /// What a dry run would have left in the destination, kept for every
+    /// dry run: later edits read back what earlier writes would have made.
+    overlay: Mutex<Option<Overlay>>,
+    /// Whether the dry run is to be written up as a patch of the overlay.
+    patch: AtomicBool,
+    /// The pinned clock and seeded randomness of a reproducible session.
+    reproducible: std::sync::OnceLock<Reproducible>,
+    /// What the session's scripts may consume. Unset means no limits.
+    limits: std::sync::OnceLock<ResourceLimits>,
+    /// When the session's time is up, counted from its first script.
+    deadline: std::sync::OnceLock<std::time::Instant>,
+    /// The debugger the session's scripts stop for, under `archetect debug`.
+    debugger: std::sync::OnceLock<Debugger>,
 }
 
 /// What this render has produced so far.
@@ -42,6 +77,45 @@ struct Inner {
 struct RenderJournal {
     files: Vec<(Utf8PathBuf, Vec<u8>)>,
     artifacts: Vec<Artifact>,
+    /// What each prompt settled on, keyed by answer key.
+    answers: ContextMap,
+}
+
+impl RenderJournal {
+    /// Keep the journal matching what a file operation does to the tree, so
+    /// an archive built afterwards holds what is actually there. Only files
+    /// the render wrote are known; the rest of the tree is out of sight.
+    fn apply(&mut self, operation: &FileOperation) {
+        match operation {
+            FileOperation::Append { path, contents } => {
+                match self.files.iter_mut().rev().find(|(file, _)| file.as_str() == path) {
+                    Some((_, existing)) => existing.extend_from_slice(contents),
+                    None => self.files.push((Utf8PathBuf::from(path), contents.clone())),
+                }
+            }
+            FileOperation::Delete { path } => {
+                self.files.retain(|(file, _)| !file.starts_with(path));
+            }
+            FileOperation::Move { source, destination, .. } => {
+                for (file, _) in self.files.iter_mut() {
+                    if let Ok(relative) = file.strip_prefix(source) {
+                        *file = Utf8Path::new(destination).join(relative);
+                    }
+                }
+            }
+            FileOperation::Copy { source, destination, .. } => {
+                let copies: Vec<_> = self
+                    .files
+                    .iter()
+                    .filter_map(|(file, contents)| {
+                        let relative = file.strip_prefix(source).ok()?;
+                        Some((Utf8Path::new(destination).join(relative), contents.clone()))
+                    })
+                    .collect();
+                self.files.extend(copies);
+            }
+        }
+    }
 }
 
 pub struct ArchetectBuilder {
@@ -49,6 +123,9 @@ pub struct ArchetectBuilder {
     layout: Option<Box<dyn SystemLayout>>,
     driver: Option<Box<dyn ScriptIoHandle>>,
     capabilities: Option<std::collections::HashSet<String>>,
+    limits: Option<ResourceLimits>,
+    debugger: Option<Debugger>,
+    patch: bool,
 }
 
 impl ArchetectBuilder {
@@ -60,6 +137,26 @@ impl ArchetectBuilder {
         self
     }
 
+    /// Hold the session's scripts to `limits`. Not calling this leaves them
+    /// unlimited, as the local CLI does.
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Attach `debugger` to every script the session runs.
+    pub fn with_debugger(mut self, debugger: Debugger) -> Self {
+        self.debugger = Some(debugger);
+        self
+    }
+
+    /// Under `--dry-run`, describe what the writes would have done as a
+    /// patch when the session is over, rather than one by one.
+    pub fn with_dry_run_patch(mut self) -> Self {
+        self.patch = true;
+        self
+    }
+
     pub fn with_layout<L: Into<Box<dyn SystemLayout>>>(mut self, layout: L) -> Self {
         self.layout = Some(layout.into());
         self
@@ -91,6 +188,21 @@ impl ArchetectBuilder {
         if let Some(capabilities) = self.capabilities {
             archetect.restrict_capabilities(capabilities);
         }
+        if let Some(limits) = self.limits {
+            let _ = archetect.inner.limits.set(limits);
+        }
+        if let Some(debugger) = self.debugger {
+            let _ = archetect.inner.debugger.set(debugger);
+        }
+        if self.patch {
+            archetect.inner.patch.store(true, Ordering::SeqCst);
+            *archetect.inner.overlay.lock().expect("Lock Error") = Some(Overlay::default());
+        }
+        if archetect.configuration().reproducible() {
+            let reproducible =
+                Reproducible::from_env(archetect.configuration().seed()).map_err(ArchetectError::ConfigError)?;
+            let _ = archetect.inner.reproducible.set(reproducible);
+        }
         Ok(archetect)
     }
 }
@@ -102,6 +214,9 @@ impl Default for ArchetectBuilder {
             layout: None,
             driver: None,
             capabilities: None,
+            limits: None,
+            debugger: None,
+            patch: false,
         }
     }
 }
@@ -112,6 +227,7 @@ impl Archetect {
         driver: T,
         layout: L,
     ) -> Archetect {
+        let overlay = configuration.dry_run().then(Overlay::default);
         Archetect {
             inner: Arc::new(Inner {
                 version: Version::parse(env!("CARGO_PKG_VERSION"))
@@ -120,7 +236,16 @@ impl Archetect {
                 layout: layout.into(),
                 configuration,
                 journal: Mutex::new(RenderJournal::default()),
+                generation: Mutex::new(None),
+                transaction: AtomicBool::new(false),
                 capabilities: std::sync::OnceLock::new(),
+                destination: std::sync::OnceLock::new(),
+                overlay: Mutex::new(overlay),
+                patch: AtomicBool::new(false),
+                reproducible: std::sync::OnceLock::new(),
+                limits: std::sync::OnceLock::new(),
+                deadline: std::sync::OnceLock::new(),
+                debugger: std::sync::OnceLock::new(),
             }),
         }
     }
@@ -135,6 +260,17 @@ impl Archetect {
             .is_none_or(|granted| granted.contains(capability))
     }
 
+    /// Whether this session enumerated its grants and named `capability`
+    /// among them. The sandbox asks this rather than [`Archetect::grants`]: a
+    /// sandboxed session that never enumerated any — MCP, or a local render
+    /// with `security.sandbox` on — is granted nothing.
+    pub(crate) fn grants_explicitly(&self, capability: &str) -> bool {
+        self.inner
+            .capabilities
+            .get()
+            .is_some_and(|granted| granted.contains(capability))
+    }
+
     /// Restrict this session to `capabilities`. Idempotent-by-construction: a
     /// second call is ignored, so a session cannot widen its own grants after
     /// initialization.
@@ -174,6 +310,29 @@ impl Archetect {
         self.inner.configuration.dry_run()
     }
 
+    /// The pinned clock and seeded randomness every time and random value
+    /// must come from, in a reproducible session.
+    pub fn reproducible(&self) -> Option<&Reproducible> {
+        self.inner.reproducible.get()
+    }
+
+    /// The resource limits the session's scripts are held to.
+    pub fn limits(&self) -> ResourceLimits {
+        self.inner.limits.get().copied().unwrap_or_default()
+    }
+
+    /// When the session's time is up, if it has a timeout. The clock starts
+    /// with the first to ask — the host starting the render, or else its
+    /// first script.
+    pub fn deadline(&self) -> Option<std::time::Instant> {
+        let timeout = self.limits().timeout()?;
+        Some(*self.inner.deadline.get_or_init(|| std::time::Instant::now() + timeout))
+    }
+
+    pub(crate) fn debugger(&self) -> Option<&Debugger> {
+        self.inner.debugger.get()
+    }
+
     pub fn version(&self) -> &Version {
         &self.inner.version
     }
@@ -182,14 +341,24 @@ impl Archetect {
         &self.inner.layout
     }
 
-    pub fn request(&self, command: ScriptMessage) -> Result<(), IoError> {
-        if let ScriptMessage::WriteFile(info) = &command {
+    pub fn request(&self, mut command: ScriptMessage) -> Result<(), IoError> {
+        if let ScriptMessage::WriteFile(info) = &mut command {
+            if let Ok(generation) = self.inner.generation.lock() {
+                if let Some(recording) = generation.as_ref() {
+                    recording.apply(info);
+                }
+            }
             if let Ok(mut journal) = self.inner.journal.lock() {
                 journal
                     .files
                     .push((Utf8PathBuf::from(&info.destination), info.contents.clone()));
             }
         }
+        if let ScriptMessage::FileOperation(operation) = &command {
+            if let Ok(mut journal) = self.inner.journal.lock() {
+                journal.apply(operation);
+            }
+        }
         self.inner.io_driver.send(command)
     }
 
@@ -221,6 +390,121 @@ impl Archetect {
         }
     }
 
+    /// Record a file the client wrote, hashing the contents the render
+    /// produced. The outcome is whatever the client said became of it.
+    pub(crate) fn record_written(&self, path: &Utf8Path, contents: &[u8], outcome: Option<FileOutcome>) {
+        let hash = format!("{:x}", Sha256::digest(contents));
+        self.record_artifact(Artifact::written(self.artifact_path(path), hash, outcome));
+    }
+
+    /// Settle the destination artifacts are reported relative to. Only the
+    /// first call counts, so a composed archetype doesn't move it.
+    pub(crate) fn set_destination(&self, destination: &Utf8Path) {
+        let _ = self.inner.destination.set(destination.to_path_buf());
+    }
+
+    /// `path` as an artifact names it: relative to the session's destination
+    /// when beneath it, as given otherwise.
+    pub(crate) fn artifact_path(&self, path: &Utf8Path) -> String {
+        self.inner
+            .destination
+            .get()
+            .and_then(|destination| path.strip_prefix(destination).ok())
+            .unwrap_or(path)
+            .as_str()
+            .replace('\\', "/")
+    }
+
+    /// Say what a dry run would have changed — unless the session is
+    /// writing it up as a patch, which says it again, in full.
+    pub(crate) fn report_dry_run(&self, description: String) {
+        if self.inner.patch.load(Ordering::SeqCst) {
+            return;
+        }
+        let _ = self.request(ScriptMessage::Display(description));
+    }
+
+    /// Apply a write the dry run skipped to the overlay, settling its
+    /// existing-file policy as [`request`](Self::request) would have.
+    pub(crate) fn capture(&self, mut command: ScriptMessage) {
+        let Ok(mut overlay) = self.inner.overlay.lock() else {
+            return;
+        };
+        let Some(overlay) = overlay.as_mut() else {
+            return;
+        };
+        if let ScriptMessage::WriteFile(info) = &mut command {
+            if let Ok(generation) = self.inner.generation.lock() {
+                if let Some(recording) = generation.as_ref() {
+                    recording.apply(info);
+                }
+            }
+        }
+        overlay.apply(&command);
+    }
+
+    /// Read a destination file as the render has left it so far. The client
+    /// holds the destination, and any transaction's staged writes, so it is
+    /// asked — unless a dry run wrote the file, which the client never heard
+    /// about.
+    pub(crate) fn read_destination(&self, path: &Utf8Path) -> std::io::Result<String> {
+        let overlaid = match self.inner.overlay.lock() {
+            Ok(overlay) => overlay.as_ref().and_then(|overlay| overlay.written(path)),
+            Err(_) => None,
+        };
+        let not_found = || std::io::Error::new(std::io::ErrorKind::NotFound, "No such file");
+        match overlaid {
+            Some(Some(contents)) => {
+                String::from_utf8(contents).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+            }
+            Some(None) => Err(not_found()),
+            None => {
+                self.request(ScriptMessage::ReadFile(path.to_string()))
+                    .map_err(std::io::Error::other)?;
+                match self.response().map_err(std::io::Error::other)? {
+                    ClientMessage::String(contents) => Ok(contents),
+                    ClientMessage::None => Err(not_found()),
+                    ClientMessage::Error(message) => Err(std::io::Error::other(message)),
+                    other => Err(std::io::Error::other(format!("Unexpected response to ReadFile: {:?}", other))),
+                }
+            }
+        }
+    }
+
+    /// Every path beneath a destination directory as the render has left it
+    /// so far. The client says what is there, staged writes included; a dry
+    /// run lays its own writes over that.
+    pub(crate) fn list_destination(&self, directory: &Utf8Path) -> std::io::Result<BTreeSet<Utf8PathBuf>> {
+        self.request(ScriptMessage::ListFiles(directory.to_string()))
+            .map_err(std::io::Error::other)?;
+        let listed = match self.response().map_err(std::io::Error::other)? {
+            ClientMessage::Array(paths) => paths.into_iter().map(Utf8PathBuf::from).collect(),
+            ClientMessage::Error(message) => return Err(std::io::Error::other(message)),
+            other => return Err(std::io::Error::other(format!("Unexpected response to ListFiles: {:?}", other))),
+        };
+        Ok(match self.inner.overlay.lock() {
+            Ok(overlay) => match overlay.as_ref() {
+                Some(overlay) => overlay.list_over(directory, listed),
+                None => listed,
+            },
+            Err(_) => listed,
+        })
+    }
+
+    /// The `git apply`-compatible patch from the destination as it stands to
+    /// what the dry run would have left, or `None` if no patch was asked for.
+    pub fn dry_run_patch(&self) -> Option<String> {
+        if !self.inner.patch.load(Ordering::SeqCst) {
+            return None;
+        }
+        let overlay = self.inner.overlay.lock().ok()?;
+        let overlay = overlay.as_ref()?;
+        Some(match self.inner.destination.get() {
+            Some(destination) => overlay.patch(destination),
+            None => String::new(),
+        })
+    }
+
     pub fn artifacts(&self) -> Vec<Artifact> {
         self.inner
             .journal
@@ -229,6 +513,56 @@ impl Archetect {
             .unwrap_or_default()
     }
 
+    pub fn record_answer(&self, key: &str, value: ContextValue) {
+        if let Ok(mut journal) = self.inner.journal.lock() {
+            journal.answers.insert(key.to_string(), value);
+        }
+    }
+
+    pub fn recorded_answers(&self) -> ContextMap {
+        self.inner
+            .journal
+            .lock()
+            .map(|journal| journal.answers.clone())
+            .unwrap_or_default()
+    }
+
+    /// Start recording a generation. Returns false, leaving the current one in
+    /// place, if one is already underway — a composed archetype renders into
+    /// its parent's generation.
+    pub(crate) fn begin_generation(&self, recording: Recording) -> bool {
+        let Ok(mut generation) = self.inner.generation.lock() else {
+            return false;
+        };
+        if generation.is_some() {
+            return false;
+        }
+        *generation = Some(recording);
+        true
+    }
+
+    pub(crate) fn end_generation(&self) -> Option<Recording> {
+        self.inner.generation.lock().ok().and_then(|mut generation| generation.take())
+    }
+
+    /// Ask the client to stage every write from here on, committing them when
+    /// the session completes successfully. Only the first call in a session
+    /// sends anything — a composed archetype renders into its parent's
+    /// transaction.
+    pub(crate) fn begin_transaction(&self, destination: &Utf8Path) -> Result<(), IoError> {
+        if self.inner.transaction.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.request(ScriptMessage::BeginTransaction(destination.to_string()))?;
+        match self.response()? {
+            ClientMessage::Ack => Ok(()),
+            ClientMessage::Error(message) => Err(IoError::ClientError { message }),
+            other => Err(IoError::ClientError {
+                message: format!("Unexpected response to BeginTransaction: {:?}", other),
+            }),
+        }
+    }
+
     pub fn configuration(&self) -> &Configuration {
         &self.inner.configuration
     }
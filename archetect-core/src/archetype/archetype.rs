@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
 
 use archetect_api::{ContextValue, ExistingFilePolicy};
 
@@ -9,6 +10,7 @@ use crate::archetype::archetype_directory::ArchetypeDirectory;
 use crate::archetype::archetype_manifest::ArchetypeManifest;
 use crate::archetype::render_context::RenderContext;
 use crate::errors::{ArchetectError, ArchetypeError};
+use crate::generation::Recording;
 use crate::source::Source;
 
 #[derive(Clone)]
@@ -82,7 +84,40 @@ impl Archetype {
         // leaves a half-written tree and tells the caller nothing useful, so
         // the manifest declares what it needs and we settle it here.
         self.manifest().requires().check_capabilities(&self.archetect)?;
+        self.archetect.set_destination(render_context.destination());
 
+        if (self.archetect.configuration().atomic() || self.manifest().atomic()) && !self.archetect.is_dry_run() {
+            self.archetect
+                .begin_transaction(render_context.destination())
+                .map_err(|e| ArchetypeError::IoError(std::io::Error::other(e.to_string())))?;
+        }
+
+        let recording = match self.manifest().regeneration() {
+            Some(config) => self
+                .archetect
+                .begin_generation(Recording::new(render_context.destination(), config)?)
+                .then(|| render_context.clone()),
+            None => None,
+        };
+
+        let result = self.dispatch(render_context, action);
+
+        if let Some(render_context) = recording {
+            if let Some(recording) = self.archetect.end_generation() {
+                if result.is_ok() {
+                    crate::generation::write_manifest(&self.archetect, self, &recording, &render_context)?;
+                }
+            }
+        }
+
+        result
+    }
+
+    fn dispatch(
+        &self,
+        render_context: RenderContext,
+        action: Option<&str>,
+    ) -> Result<ContextValue, ArchetypeError> {
         match self.directory().script() {
             Some(script_path) => {
                 // Check for .rhai scripts and emit a helpful error
@@ -158,7 +193,8 @@ impl Archetype {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OverwritePolicy {
     Overwrite,
     Preserve,
@@ -167,6 +203,10 @@ pub enum OverwritePolicy {
     /// exists. Intended for CI / idempotent renders where a collision
     /// means the invocation was misconfigured.
     Error,
+    /// Three-way merge the generated contents into the existing file, using
+    /// the copy recorded by the previous render as the common ancestor. Only
+    /// meaningful for archetypes that opt into `regeneration`.
+    Merge,
 }
 
 impl Default for OverwritePolicy {
@@ -182,6 +222,7 @@ impl From<OverwritePolicy> for ExistingFilePolicy {
             OverwritePolicy::Preserve => ExistingFilePolicy::Preserve,
             OverwritePolicy::Prompt => ExistingFilePolicy::Prompt,
             OverwritePolicy::Error => ExistingFilePolicy::Error,
+            OverwritePolicy::Merge => ExistingFilePolicy::Merge,
         }
     }
 }
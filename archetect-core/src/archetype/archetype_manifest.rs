@@ -3,10 +3,12 @@ use linked_hash_map::LinkedHashMap;
 use serde::{Deserialize, Serialize};
 
 pub use crate::archetype::archetype_manifest::requirements::RuntimeRequirements;
+use crate::archetype::archetype_manifest::regeneration::RegenerationConfig;
 use crate::archetype::archetype_manifest::templating::TemplatingConfig;
 use crate::errors::ArchetypeError;
 use crate::manifest::{CatalogEntry, Manifest};
 
+pub mod regeneration;
 pub mod requirements;
 pub mod templating;
 
@@ -24,6 +26,10 @@ pub struct ArchetypeManifest {
     requires: RuntimeRequirements,
     #[serde(default = "TemplatingConfig::default")]
     templating: TemplatingConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regeneration: Option<RegenerationConfig>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    atomic: bool,
     /// Catalog entries (populated from unified Manifest).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     catalog: Option<LinkedHashMap<String, CatalogEntry>>,
@@ -109,6 +115,16 @@ impl ArchetypeManifest {
         &self.templating
     }
 
+    /// Present when the archetype opts into regeneration.
+    pub fn regeneration(&self) -> Option<&RegenerationConfig> {
+        self.regeneration.as_ref()
+    }
+
+    /// Whether the archetype asks for its renders to be transactional.
+    pub fn atomic(&self) -> bool {
+        self.atomic
+    }
+
     /// Returns catalog entries if this manifest declares any.
     pub fn catalog(&self) -> Option<&LinkedHashMap<String, CatalogEntry>> {
         self.catalog.as_ref()
@@ -133,6 +149,8 @@ impl From<Manifest> for ArchetypeManifest {
             tags: if m.tags.is_empty() { None } else { Some(m.tags) },
             requires: m.requires,
             templating: m.templating,
+            regeneration: m.regeneration,
+            atomic: m.atomic,
             catalog: m.catalog,
         }
     }
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::archetype::archetype::OverwritePolicy;
+
+/// Regeneration settings declared in `archetype.yaml`.
+///
+/// Declaring the block opts an archetype into regeneration. Every render then
+/// records `.archetect/manifest.yaml` (source, answers, per-file checksums) and
+/// a shadow copy of each generated file under `.archetect/shadow/`, so that a
+/// later `archetect regenerate` can three-way merge the archetype's changes
+/// into a project the user has since edited.
+///
+/// While recording, this block — not the script's `if_exists` — decides how a
+/// generated file meets an existing one: the first `strategies` rule whose
+/// glob matches the destination-relative path wins, and `default_strategy`
+/// covers the rest.
+///
+/// ```yaml
+/// regeneration:
+///   default_strategy: merge
+///   strategies:
+///     - path: ".github/**"
+///       strategy: overwrite
+///     - path: "src/main.rs"
+///       strategy: preserve
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RegenerationConfig {
+    #[serde(default = "default_strategy")]
+    default_strategy: OverwritePolicy,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    strategies: Vec<StrategyRule>,
+}
+
+/// A glob over destination-relative paths and the policy applied to the
+/// files it matches.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StrategyRule {
+    path: String,
+    strategy: OverwritePolicy,
+}
+
+fn default_strategy() -> OverwritePolicy {
+    OverwritePolicy::Merge
+}
+
+impl Default for RegenerationConfig {
+    fn default() -> Self {
+        RegenerationConfig {
+            default_strategy: default_strategy(),
+            strategies: Vec::new(),
+        }
+    }
+}
+
+impl RegenerationConfig {
+    pub fn default_strategy(&self) -> OverwritePolicy {
+        self.default_strategy
+    }
+
+    pub fn strategies(&self) -> &[StrategyRule] {
+        &self.strategies
+    }
+}
+
+impl StrategyRule {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn strategy(&self) -> OverwritePolicy {
+        self.strategy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_default_strategy_is_merge() {
+        let config = RegenerationConfig::default();
+        assert_eq!(config.default_strategy(), OverwritePolicy::Merge);
+        assert!(config.strategies().is_empty());
+    }
+
+    #[test]
+    fn test_empty_block_defaults_to_merge() {
+        let config: RegenerationConfig = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(config.default_strategy(), OverwritePolicy::Merge);
+    }
+
+    #[test]
+    fn test_parse_strategies() {
+        let yaml = indoc! {r#"
+            default_strategy: prompt
+            strategies:
+              - path: ".github/**"
+                strategy: overwrite
+              - path: "src/main.rs"
+                strategy: preserve
+        "#};
+        let config: RegenerationConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.default_strategy(), OverwritePolicy::Prompt);
+        assert_eq!(config.strategies().len(), 2);
+        assert_eq!(config.strategies()[0].path(), ".github/**");
+        assert_eq!(config.strategies()[0].strategy(), OverwritePolicy::Overwrite);
+        assert_eq!(config.strategies()[1].strategy(), OverwritePolicy::Preserve);
+    }
+
+    #[test]
+    fn test_rejects_unknown_strategy() {
+        let yaml = indoc! {r#"
+            strategies:
+              - path: "**"
+                strategy: clobber
+        "#};
+        assert!(serde_yaml::from_str::<RegenerationConfig>(yaml).is_err());
+    }
+}
@@ -1,5 +1,9 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::templating::atl::Escaper;
+
 /// Templating configuration declared in `archetype.yaml`.
 ///
 /// Phase 1 of catalog-driven dependencies removed the `content` and
@@ -14,7 +18,8 @@ use serde::{Deserialize, Serialize};
 ///   include dirs via the catalog `library: true` mechanism.
 ///
 /// What's left in this block is purely template-engine behavior:
-/// undefined-variable resolution and Jinja-style whitespace controls.
+/// undefined-variable resolution, Jinja-style whitespace controls, and
+/// output escaping.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct TemplatingConfig {
     /// Resolution policy for undefined context variables. `Lenient` (the
@@ -29,6 +34,12 @@ pub struct TemplatingConfig {
     /// Off by default.
     #[serde(default)]
     lstrip_blocks: bool,
+    /// The escaper applied to every interpolation in templates with a given
+    /// file extension, e.g. `{ json: json, sh: shell }`. A leading dot on
+    /// the extension is optional. Files with other extensions aren't
+    /// escaped.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    autoescape: BTreeMap<String, Escaper>,
 }
 
 impl TemplatingConfig {
@@ -43,6 +54,10 @@ impl TemplatingConfig {
     pub fn lstrip_blocks(&self) -> bool {
         self.lstrip_blocks
     }
+
+    pub fn autoescape(&self) -> &BTreeMap<String, Escaper> {
+        &self.autoescape
+    }
 }
 
 /// Resolution policy for undefined context variables in templates.
@@ -87,6 +102,23 @@ mod tests {
         assert!(config.lstrip_blocks());
     }
 
+    #[test]
+    fn test_parse_autoescape_by_extension() {
+        let yaml = indoc! {r#"
+            autoescape:
+              json: json
+              .sh: shell
+              html: html
+        "#};
+        let config: TemplatingConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.autoescape().get("json"), Some(&Escaper::Json));
+        assert_eq!(config.autoescape().get(".sh"), Some(&Escaper::Shell));
+        assert_eq!(config.autoescape().get("html"), Some(&Escaper::Xml));
+
+        let result: Result<TemplatingConfig, _> = serde_yaml::from_str("autoescape: { csv: csv }");
+        assert!(result.unwrap_err().to_string().contains("unknown escaper `csv`"));
+    }
+
     #[test]
     fn test_parse_minimal_templating_block_uses_defaults() {
         let yaml = "{}";
@@ -94,6 +126,7 @@ mod tests {
         assert_eq!(config.undefined(), UndefinedMode::Lenient);
         assert!(!config.trim_blocks());
         assert!(!config.lstrip_blocks());
+        assert!(config.autoescape().is_empty());
     }
 
     #[test]
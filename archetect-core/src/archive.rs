@@ -18,15 +18,23 @@ pub struct ArchiveEntry {
 }
 
 /// Build a ZIP archive whose members are nested under `root`.
-pub fn build_zip(root: &str, entries: &[ArchiveEntry]) -> io::Result<Vec<u8>> {
+///
+/// `mtime` — seconds since the Unix epoch — stamps every member, so a
+/// reproducible render produces the same bytes twice; without one, members
+/// carry the current time. ZIP can't record times before 1980 or after
+/// 2107, so those are clamped.
+pub fn build_zip(root: &str, entries: &[ArchiveEntry], mtime: Option<i64>) -> io::Result<Vec<u8>> {
     use std::io::Cursor;
     use zip::write::SimpleFileOptions;
     use zip::ZipWriter;
 
     let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
-    let options = SimpleFileOptions::default()
+    let mut options = SimpleFileOptions::default()
         .compression_method(zip::CompressionMethod::Deflated)
         .unix_permissions(0o755);
+    if let Some(mtime) = mtime {
+        options = options.last_modified_time(zip_time(mtime));
+    }
 
     zip.add_directory(format!("{}/", root), options)?;
 
@@ -54,8 +62,10 @@ pub fn build_zip(root: &str, entries: &[ArchiveEntry]) -> io::Result<Vec<u8>> {
 }
 
 /// Build a tar archive (optionally gzipped) whose members are nested under `root`.
-pub fn build_tar(root: &str, entries: &[ArchiveEntry], compress: bool) -> io::Result<Vec<u8>> {
-    let tarball = write_tar(root, entries)?;
+///
+/// Members are stamped with `mtime`, or the epoch without one.
+pub fn build_tar(root: &str, entries: &[ArchiveEntry], compress: bool, mtime: Option<i64>) -> io::Result<Vec<u8>> {
+    let tarball = write_tar(root, entries, mtime.unwrap_or_default())?;
     if !compress {
         return Ok(tarball);
     }
@@ -65,7 +75,26 @@ pub fn build_tar(root: &str, entries: &[ArchiveEntry], compress: bool) -> io::Re
     encoder.finish()
 }
 
-fn write_tar(root: &str, entries: &[ArchiveEntry]) -> io::Result<Vec<u8>> {
+fn zip_time(mtime: i64) -> zip::DateTime {
+    use chrono::{Datelike, Timelike};
+
+    let at = chrono::DateTime::from_timestamp(mtime, 0).unwrap_or_default();
+    let clamped = match at.year() {
+        ..1980 => zip::DateTime::from_date_and_time(1980, 1, 1, 0, 0, 0),
+        2108.. => zip::DateTime::from_date_and_time(2107, 12, 31, 23, 59, 58),
+        year => zip::DateTime::from_date_and_time(
+            year as u16,
+            at.month() as u8,
+            at.day() as u8,
+            at.hour() as u8,
+            at.minute() as u8,
+            at.second() as u8,
+        ),
+    };
+    clamped.unwrap_or_default()
+}
+
+fn write_tar(root: &str, entries: &[ArchiveEntry], mtime: i64) -> io::Result<Vec<u8>> {
     use tar::{Builder, Header};
 
     let mut builder = Builder::new(Vec::new());
@@ -73,6 +102,7 @@ fn write_tar(root: &str, entries: &[ArchiveEntry]) -> io::Result<Vec<u8>> {
         let mut header = Header::new_gnu();
         header.set_size(entry.contents.len() as u64);
         header.set_mode(0o644);
+        header.set_mtime(mtime.max(0) as u64);
         builder.append_data(
             &mut header,
             format!("{}/{}", root, entry.path),
@@ -95,7 +125,7 @@ mod tests {
 
     #[test]
     fn zip_nests_members_under_the_root_and_keeps_contents() {
-        let bytes = build_zip("orders", &entries()).expect("zip builds");
+        let bytes = build_zip("orders", &entries(), None).expect("zip builds");
         let mut archive =
             zip::ZipArchive::new(std::io::Cursor::new(bytes)).expect("zip reads back");
 
@@ -112,7 +142,7 @@ mod tests {
 
     #[test]
     fn tar_gz_round_trips() {
-        let bytes = build_tar("orders", &entries(), true).expect("tar.gz builds");
+        let bytes = build_tar("orders", &entries(), true, None).expect("tar.gz builds");
         let decoder = flate2::read::GzDecoder::new(std::io::Cursor::new(bytes));
         let mut archive = tar::Archive::new(decoder);
         let paths: Vec<String> = archive
@@ -124,9 +154,21 @@ mod tests {
         assert!(paths.contains(&"orders/src/main.rs".to_string()), "{:?}", paths);
     }
 
+    #[test]
+    fn a_fixed_mtime_makes_archives_byte_identical() {
+        let zip = || build_zip("orders", &entries(), Some(1_700_000_000)).expect("zip builds");
+        assert_eq!(zip(), zip());
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(zip())).expect("zip reads back");
+        let modified = archive.by_name("orders/README.md").expect("member").last_modified().expect("mtime");
+        assert_eq!((modified.year(), modified.month(), modified.day()), (2023, 11, 14));
+
+        let tar = || build_tar("orders", &entries(), true, Some(1_700_000_000)).expect("tar.gz builds");
+        assert_eq!(tar(), tar());
+    }
+
     #[test]
     fn an_empty_render_still_produces_a_readable_archive() {
-        let bytes = build_zip("orders", &[]).expect("zip builds");
+        let bytes = build_zip("orders", &[], None).expect("zip builds");
         let archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).expect("reads back");
         assert_eq!(archive.len(), 1, "just the root directory member");
     }
@@ -253,7 +253,10 @@ fn build_client_options_for_entry(
     archetect: &Archetect,
     server: &CatalogEntryServer,
 ) -> ClientOptions {
-    let mut options = ClientOptions::default();
+    let mut options = ClientOptions {
+        atomic: archetect.configuration().atomic(),
+        ..ClientOptions::default()
+    };
 
     // Start with any top-level client config (timeouts, keepalive, default TLS).
     if let Some(client) = archetect.configuration().client() {
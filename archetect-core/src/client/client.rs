@@ -6,7 +6,7 @@ use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity
 use tracing::{debug, warn};
 
 use archetect_api::ClientMessage;
-use archetect_terminal_io::TerminalClient;
+use archetect_terminal_io::{Report, TerminalClient};
 
 use crate::archetype::render_context::RenderContext;
 use crate::errors::ArchetectError;
@@ -56,6 +56,12 @@ pub struct ClientOptions {
     /// archetype does not get to reach outside the destination — publish to a
     /// repository, say — just because it asked. The operator opts in.
     pub capabilities: Vec<String>,
+    /// Stage every write the server sends and apply them only once it reports
+    /// success. The transaction is the client's own, so this works whether or
+    /// not the server's archetype asks for one.
+    pub atomic: bool,
+    /// Write a report of what the render produced once it completes.
+    pub report: Option<Report>,
 }
 
 impl Default for ClientOptions {
@@ -70,6 +76,8 @@ impl Default for ClientOptions {
             http2_keepalive_timeout: Some(Duration::from_secs(10)),
             tls: None,
             capabilities: Vec::new(),
+            atomic: false,
+            report: None,
         }
     }
 }
@@ -173,10 +181,19 @@ async fn start_async(
     let mut response_stream = client.streaming_api(request_stream).await?.into_inner();
 
     // Spawn terminal client handler in a blocking thread
-    let terminal_client = TerminalClient::new(client_handle);
+    let mut terminal_client = TerminalClient::new(client_handle);
+    if options.atomic {
+        terminal_client = terminal_client
+            .with_transaction(render_context.destination())
+            .map_err(anyhow::Error::msg)?;
+    }
+    if let Some(report) = options.report.clone() {
+        terminal_client = terminal_client.with_report(report);
+    }
     let handle = tokio::task::spawn_blocking(move || {
-        terminal_client.run();
+        let committed = terminal_client.run();
         debug!("Disconnecting from server");
+        committed
     });
 
     // Send Initialize message
@@ -207,11 +224,16 @@ async fn start_async(
         }
     }
 
-    handle.await?;
+    let committed = handle.await?;
 
     if let Some(message) = render_error {
         anyhow::bail!("Render failed: {}", message);
     }
+    // The server rendered, but what it sent was staged here and never
+    // reached the destination.
+    if let Err(message) = committed {
+        anyhow::bail!("Render failed: {}", message);
+    }
     Ok(())
 }
 
@@ -25,6 +25,12 @@ pub struct Configuration {
     headless: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     dry_run: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    atomic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reproducible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
     answers: ContextMap,
     updates: ConfigurationUpdateSection,
     locals: ConfigurationLocalsSection,
@@ -64,6 +70,37 @@ impl Configuration {
         self.dry_run = Some(value);
         self
     }
+
+    /// Stage every write and commit only when the render succeeds, whether or
+    /// not the archetype asks for it.
+    pub fn atomic(&self) -> bool {
+        self.atomic.unwrap_or_default()
+    }
+
+    pub fn with_atomic(mut self, value: bool) -> Self {
+        self.atomic = Some(value);
+        self
+    }
+
+    /// Whether renders pin the clock and seed randomness. Setting a seed
+    /// implies it.
+    pub fn reproducible(&self) -> bool {
+        self.reproducible.unwrap_or_default() || self.seed.is_some()
+    }
+
+    pub fn with_reproducible(mut self, value: bool) -> Self {
+        self.reproducible = Some(value);
+        self
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
     pub fn updates(&self) -> &ConfigurationUpdateSection {
         &self.updates
     }
@@ -100,6 +137,18 @@ impl Configuration {
         self
     }
 
+    /// Whether archetype scripts run in the sandboxed Lua runtime.
+    pub fn sandboxed(&self) -> bool {
+        self.security.sandbox().unwrap_or_default()
+    }
+
+    /// Override the sandbox setting. Used by `server` and `mcp` to turn it on
+    /// when the configuration leaves it unset.
+    pub fn with_sandbox(mut self, sandbox: bool) -> Self {
+        self.security.set_sandbox(sandbox);
+        self
+    }
+
     /// Returns the unified catalog if set.
     pub fn catalog(&self) -> Option<&LinkedHashMap<String, CatalogEntry>> {
         self.catalog.as_ref()
@@ -164,6 +213,9 @@ impl Default for Configuration {
             headless: Default::default(),
             offline: Default::default(),
             dry_run: Default::default(),
+            atomic: Default::default(),
+            reproducible: Default::default(),
+            seed: Default::default(),
             updates: Default::default(),
             security: Default::default(),
             answers: default_answers(),
@@ -254,6 +306,10 @@ mod tests {
               cert: /etc/archetect/server.crt
               key: /etc/archetect/server.key
               client_ca: /etc/archetect/clients-ca.crt
+            limits:
+              instructions: 50000000
+              memory_mb: 256
+              timeout: 120
         "#};
         let section: ConfigurationServerSection = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(section.host(), Some("127.0.0.1"));
@@ -265,6 +321,10 @@ mod tests {
             tls.client_ca().and_then(|p| p.to_str()),
             Some("/etc/archetect/clients-ca.crt")
         );
+        let limits = section.limits().expect("limits subsection");
+        assert_eq!(limits.instructions(), Some(50_000_000));
+        assert_eq!(limits.memory_mb(), Some(256));
+        assert_eq!(limits.timeout(), Some(std::time::Duration::from_secs(120)));
     }
 
     #[test]
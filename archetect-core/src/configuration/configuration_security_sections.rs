@@ -29,6 +29,10 @@ pub struct ConfigurationSecuritySection {
     /// Explicit override that takes precedence over `allow_exec` when present.
     #[serde(skip_serializing_if = "Option::is_none")]
     shell_exec_policy: Option<ShellExecPolicy>,
+    /// Run archetype scripts in the sandboxed Lua runtime. Unset, the
+    /// entry point decides: on for `server` and `mcp`, off locally.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sandbox: Option<bool>,
 }
 
 impl ConfigurationSecuritySection {
@@ -53,6 +57,15 @@ impl ConfigurationSecuritySection {
     pub fn set_shell_exec_policy(&mut self, policy: ShellExecPolicy) {
         self.shell_exec_policy = Some(policy);
     }
+
+    /// The configured sandbox setting, `None` when left to the entry point.
+    pub fn sandbox(&self) -> Option<bool> {
+        self.sandbox
+    }
+
+    pub fn set_sandbox(&mut self, sandbox: bool) {
+        self.sandbox = Some(sandbox);
+    }
 }
 
 #[cfg(test)]
@@ -103,6 +116,13 @@ mod tests {
         assert_eq!(security.shell_exec_policy(), ShellExecPolicy::Allowed);
     }
 
+    #[test]
+    fn sandbox_is_unset_unless_configured() {
+        assert_eq!(ConfigurationSecuritySection::default().sandbox(), None);
+        let security: ConfigurationSecuritySection = serde_yaml::from_str("sandbox: false").unwrap();
+        assert_eq!(security.sandbox(), Some(false));
+    }
+
     #[test]
     fn with_shell_exec_policy_builder() {
         let security = ConfigurationSecuritySection::default()
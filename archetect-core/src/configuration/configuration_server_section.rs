@@ -2,9 +2,12 @@ use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
-/// Server-side config. Applies to `archetect server`. Every field is
-/// optional — CLI flags and environment variables override what's set
-/// here, and what's left falls through to the hardcoded defaults.
+use crate::limits::ResourceLimits;
+
+/// Server-side config. Applies to `archetect server`, and `limits` to
+/// `archetect mcp` as well. Every field is optional — CLI flags and
+/// environment variables override what's set here, and what's left falls
+/// through to the hardcoded defaults.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct ConfigurationServerSection {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -13,6 +16,9 @@ pub struct ConfigurationServerSection {
     port: Option<u16>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     tls: Option<ConfigurationServerTlsSection>,
+    /// What each render's scripts may consume.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limits: Option<ResourceLimits>,
 }
 
 impl ConfigurationServerSection {
@@ -27,6 +33,10 @@ impl ConfigurationServerSection {
     pub fn tls(&self) -> Option<&ConfigurationServerTlsSection> {
         self.tls.as_ref()
     }
+
+    pub fn limits(&self) -> Option<ResourceLimits> {
+        self.limits
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
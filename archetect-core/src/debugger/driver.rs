@@ -0,0 +1,50 @@
+//! The IO driver of a debugged render.
+
+use log::Level;
+
+use archetect_api::{ClientMessage, IoError, ScriptIoHandle, ScriptMessage};
+
+use super::Debugger;
+
+/// A `ScriptIoHandle` that sends what a script prints, displays, and logs to
+/// the debugger's client as `output` events — stdout carries the protocol,
+/// so it can't go there — and everything else, prompts and writes included,
+/// to the driver it wraps.
+#[derive(Debug)]
+pub struct DebugIoHandle<D> {
+    driver: D,
+    debugger: Debugger,
+}
+
+impl<D: ScriptIoHandle> DebugIoHandle<D> {
+    pub(super) fn new(driver: D, debugger: Debugger) -> Self {
+        DebugIoHandle { driver, debugger }
+    }
+
+    fn log(&self, level: Level, message: &str) {
+        if log::log_enabled!(level) {
+            self.debugger.output("console", message);
+        }
+    }
+}
+
+impl<D: ScriptIoHandle> ScriptIoHandle for DebugIoHandle<D> {
+    fn send(&self, request: ScriptMessage) -> Result<(), IoError> {
+        match request {
+            ScriptMessage::Print(message) => self.debugger.output("stdout", &message),
+            ScriptMessage::Display(message) => self.debugger.output("console", &message),
+            ScriptMessage::LogError(message) => self.debugger.output("stderr", &message),
+            // The rest at the verbosity the terminal would show them at.
+            ScriptMessage::LogWarn(message) => self.log(Level::Warn, &message),
+            ScriptMessage::LogInfo(message) => self.log(Level::Info, &message),
+            ScriptMessage::LogDebug(message) => self.log(Level::Debug, &message),
+            ScriptMessage::LogTrace(message) => self.log(Level::Trace, &message),
+            request => return self.driver.send(request),
+        }
+        Ok(())
+    }
+
+    fn receive(&self) -> Result<ClientMessage, IoError> {
+        self.driver.receive()
+    }
+}
@@ -0,0 +1,721 @@
+//! The debugger inside each Lua state: the line hook that decides when to
+//! stop, and the frames, variables, and expressions a stopped render shows.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use camino::Utf8Path;
+use mlua::{
+    ffi, DebugEvent, Error as LuaError, Function, HookTriggers, Lua, MultiValue, Table, Value, VmState, WeakLua,
+};
+use serde_json::{json, Value as Json};
+
+use archetect_api::ContextValue;
+
+use crate::script::lua::context_map;
+use crate::templating::atl::{SourceMap, CHUNK_NAME};
+
+use super::{Command, Debugger, Step, StepKind, THREAD_ID};
+
+/// How many entries of a table or Context the client is shown.
+const ENTRIES_SHOWN: usize = 1_000;
+
+thread_local! {
+    /// The states attached on this thread, outermost first. A composed
+    /// archetype's script runs in a state of its own, called from inside its
+    /// parent's, and the client sees one stack through them all.
+    static ATTACHED: RefCell<Vec<(usize, WeakLua)>> = const { RefCell::new(Vec::new()) };
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// What the debugger keeps in each state it's attached to, as app data.
+struct Attachment {
+    id: usize,
+    debugger: Debugger,
+    /// The templates running, innermost last.
+    templates: RefCell<Vec<Template>>,
+    /// Each chunk name and partial path seen, resolved to the file it is.
+    files: RefCell<HashMap<String, Option<PathBuf>>>,
+}
+
+impl Attachment {
+    /// The file `name` is, if it's one. A chunk loaded from a string has a
+    /// name that's no file at all.
+    fn file(&self, name: &str) -> Option<PathBuf> {
+        self.files
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_insert_with(|| Path::new(name).canonicalize().ok().filter(|path| path.is_file()))
+            .clone()
+    }
+
+    /// Where a line of the chunk `source` is: the file and line of a
+    /// script, or for a compiled template, the template's.
+    fn locate(&self, source: &str, line: usize) -> Option<(PathBuf, usize)> {
+        if source == CHUNK_NAME {
+            let templates = self.templates.borrow();
+            return templates.last()?.locate(self, line).map(|(path, line, _)| (path, line));
+        }
+        let name = source.strip_prefix('@').unwrap_or(source);
+        if name.starts_with('=') {
+            return None;
+        }
+        Some((self.file(name)?, line))
+    }
+}
+
+/// A running template: where it is, how its Lua maps back to it, and the
+/// function its compiled chunk returned, which marks where its frames end.
+struct Template {
+    path: Option<PathBuf>,
+    source_map: SourceMap,
+    function: *const c_void,
+}
+
+impl Template {
+    /// The file, line, and column of the template a line of its Lua came
+    /// from. Lines the template didn't write — the preamble, literal text —
+    /// have none.
+    fn locate(&self, attachment: &Attachment, line: usize) -> Option<(PathBuf, usize, usize)> {
+        let location = self.source_map.locate(line)?;
+        let path = match self.source_map.path(&location.file) {
+            Some(partial) => attachment.file(partial.as_str()),
+            None => self.path.clone(),
+        }?;
+        Some((path, location.line, location.column))
+    }
+}
+
+/// Attach `debugger` to `lua`, before its script runs.
+pub(crate) fn attach(lua: &Lua, debugger: &Debugger) -> mlua::Result<()> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    lua.set_app_data(Attachment {
+        id,
+        debugger: debugger.clone(),
+        templates: RefCell::default(),
+        files: RefCell::default(),
+    });
+    ATTACHED.with_borrow_mut(|attached| {
+        attached.retain(|(_, state)| state.try_upgrade().is_some());
+        attached.push((id, lua.weak()));
+    });
+    lua.set_global_hook(HookTriggers::EVERY_LINE, on_line)
+}
+
+/// Note that the template compiled into `function` is running, from `path`,
+/// for as long as the guard lives. Nothing, unless a debugger is attached.
+pub(crate) fn enter_template(
+    lua: &Lua,
+    path: &Utf8Path,
+    source_map: &SourceMap,
+    function: &Function,
+) -> Option<TemplateGuard> {
+    let attachment = lua.app_data_ref::<Attachment>()?;
+    let path = attachment.file(path.as_str());
+    attachment.templates.borrow_mut().push(Template {
+        path,
+        source_map: source_map.clone(),
+        function: function.to_pointer(),
+    });
+    Some(TemplateGuard { lua: lua.clone() })
+}
+
+pub(crate) struct TemplateGuard {
+    lua: Lua,
+}
+
+impl Drop for TemplateGuard {
+    fn drop(&mut self) {
+        if let Some(attachment) = self.lua.app_data_ref::<Attachment>() {
+            attachment.templates.borrow_mut().pop();
+        }
+    }
+}
+
+fn on_line(lua: &Lua, debug: &mlua::Debug) -> mlua::Result<VmState> {
+    if debug.event() != DebugEvent::Line {
+        return Ok(VmState::Continue);
+    }
+    let (Some(line), Some(source)) = (
+        debug.current_line(),
+        debug.source().source.map(|source| source.into_owned()),
+    ) else {
+        return Ok(VmState::Continue);
+    };
+    let Some(attachment) = lua.app_data_ref::<Attachment>() else {
+        return Ok(VmState::Continue);
+    };
+    // States attached after this one, and still on the stack, have ended.
+    ATTACHED.with_borrow_mut(|attached| {
+        if let Some(index) = attached.iter().position(|(id, _)| *id == attachment.id) {
+            attached.truncate(index + 1);
+        }
+    });
+
+    let debugger = attachment.debugger.clone();
+    let mut control = debugger.shared.control.lock().expect("Lock Error");
+    if control.disconnected {
+        drop(control);
+        drop(attachment);
+        return Err(disconnected(lua));
+    }
+    let template = source == CHUNK_NAME;
+    let interested = control.pause
+        || control.stop_on_entry
+        || control.step.is_some()
+        || control.stopped_at.is_some()
+        || control.lines.contains(&line)
+        || (template && !control.breakpoints.is_empty());
+    if !interested {
+        return Ok(VmState::Continue);
+    }
+    let Some(location) = attachment.locate(&source, line) else {
+        return Ok(VmState::Continue);
+    };
+    drop(attachment);
+
+    if control
+        .stopped_at
+        .as_ref()
+        .is_some_and(|stopped_at| *stopped_at != location)
+    {
+        control.stopped_at = None;
+    }
+    let breakpoint = control.stopped_at.is_none()
+        && control
+            .breakpoints
+            .get(&location.0)
+            .is_some_and(|lines| lines.contains(&location.1));
+    let reason = if control.pause {
+        "pause"
+    } else if control.stop_on_entry {
+        "entry"
+    } else if breakpoint {
+        "breakpoint"
+    } else if let Some(step) = &control.step {
+        let moved = step.from.as_ref() != Some(&location);
+        let arrived = match step.kind {
+            StepKind::In => moved,
+            StepKind::Over => {
+                let depth = depth();
+                depth < step.depth || (depth == step.depth && moved)
+            }
+            StepKind::Out => depth() < step.depth,
+        };
+        if !arrived {
+            return Ok(VmState::Continue);
+        }
+        "step"
+    } else {
+        return Ok(VmState::Continue);
+    };
+    control.pause = false;
+    control.stop_on_entry = false;
+    control.step = None;
+    control.stopped_at = Some(location.clone());
+    control.stopped = true;
+    drop(control);
+    stop(lua, &debugger, reason, location)
+}
+
+/// Stay stopped, answering the client, until it says to go on.
+fn stop(lua: &Lua, debugger: &Debugger, reason: &str, location: (PathBuf, usize)) -> mlua::Result<VmState> {
+    let writer = &debugger.shared.writer;
+    let mut inspection = Inspection::new(lua);
+    writer.event(
+        "stopped",
+        json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+    );
+    let commands = debugger.shared.commands.lock().expect("Lock Error");
+    loop {
+        match commands.recv() {
+            Ok(Command::Inspect(request)) => {
+                let answer = inspection.answer(&request);
+                writer.respond(&request, answer);
+            }
+            Ok(Command::Resume(request, step)) => {
+                let mut control = debugger.shared.control.lock().expect("Lock Error");
+                control.stopped = false;
+                control.step = step.map(|kind| Step {
+                    kind,
+                    depth: inspection.depth,
+                    from: Some(location),
+                });
+                drop(control);
+                writer.respond(&request, Ok(json!({ "allThreadsContinued": true })));
+                return Ok(VmState::Continue);
+            }
+            Ok(Command::Disconnect) | Err(_) => {
+                debugger.shared.control.lock().expect("Lock Error").stopped = false;
+                return Err(disconnected(lua));
+            }
+        }
+    }
+}
+
+/// The error that ends a render whose client has gone. From here on every
+/// instruction raises it again, so a `pcall` can't carry on regardless.
+fn disconnected(lua: &Lua) -> LuaError {
+    const MESSAGE: &str = "the debugger disconnected";
+    let _ = lua.set_global_hook(HookTriggers::new().every_nth_instruction(1), |_, _| {
+        Err(LuaError::runtime(MESSAGE))
+    });
+    LuaError::runtime(MESSAGE)
+}
+
+/// How deep the stack is, through every attached state.
+fn depth() -> usize {
+    ATTACHED.with_borrow(|attached| {
+        attached
+            .iter()
+            .filter_map(|(_, state)| state.try_upgrade())
+            .map(|state| {
+                let mut levels = 0;
+                while state.inspect_stack(levels, |_| ()).is_some() {
+                    levels += 1;
+                }
+                levels
+            })
+            .sum()
+    })
+}
+
+/// One frame of a stopped render's stack.
+struct Frame {
+    lua: Lua,
+    level: usize,
+    name: String,
+    /// Its file, line, and column, when it has them.
+    location: Option<(PathBuf, usize, usize)>,
+    /// A Lua function, with locals to show; a Rust one has none.
+    lua_function: bool,
+}
+
+/// What a variables reference stands for.
+enum Reference {
+    Locals(usize),
+    Upvalues(usize),
+    Value(Value),
+    Context(ContextValue),
+}
+
+/// A stopped render, as the client is shown it: its frames, and the
+/// variables references handed out since it stopped.
+struct Inspection {
+    frames: Vec<Frame>,
+    references: Vec<Reference>,
+    depth: usize,
+}
+
+impl Inspection {
+    fn new(lua: &Lua) -> Inspection {
+        let states: Vec<Lua> = ATTACHED.with_borrow(|attached| {
+            attached
+                .iter()
+                .rev()
+                .filter_map(|(_, state)| state.try_upgrade())
+                .collect()
+        });
+        let states = if states.is_empty() { vec![lua.clone()] } else { states };
+        let mut frames = Vec::new();
+        for state in states {
+            collect_frames(&state, &mut frames);
+        }
+        Inspection {
+            frames,
+            references: Vec::new(),
+            depth: depth(),
+        }
+    }
+
+    fn answer(&mut self, request: &Json) -> Result<Json, String> {
+        let arguments = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "stackTrace" => Ok(self.stack_trace(arguments)),
+            "scopes" => {
+                let id = frame_id(arguments)?;
+                let frame = self.frames.get(id).ok_or("no such frame")?;
+                if !frame.lua_function {
+                    return Ok(json!({ "scopes": [] }));
+                }
+                let locals = self.reference(Reference::Locals(id));
+                let upvalues = self.reference(Reference::Upvalues(id));
+                Ok(json!({ "scopes": [
+                    { "name": "Locals", "presentationHint": "locals", "variablesReference": locals, "expensive": false },
+                    { "name": "Upvalues", "variablesReference": upvalues, "expensive": false },
+                ]}))
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or_default() as usize;
+                let variables = self.variables(reference)?;
+                let variables: Vec<Json> = variables
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let (shown, kind, reference) = self.show(value);
+                        json!({ "name": name, "value": shown, "type": kind, "variablesReference": reference })
+                    })
+                    .collect();
+                Ok(json!({ "variables": variables }))
+            }
+            "evaluate" => {
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                let frame = arguments["frameId"].as_u64().map(|id| id as usize);
+                let value = self.evaluate(frame, expression)?;
+                let (shown, kind, reference) = self.show(value);
+                Ok(json!({ "result": shown, "type": kind, "variablesReference": reference }))
+            }
+            other => Err(format!("`{}` isn't supported", other)),
+        }
+    }
+
+    fn stack_trace(&self, arguments: &Json) -> Json {
+        let start = arguments["startFrame"].as_u64().unwrap_or_default() as usize;
+        let levels = match arguments["levels"].as_u64().unwrap_or_default() as usize {
+            0 => usize::MAX,
+            levels => levels,
+        };
+        let frames: Vec<Json> = self
+            .frames
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(levels)
+            .map(|(id, frame)| {
+                let mut shown = json!({ "id": id, "name": frame.name, "line": 0, "column": 0 });
+                match &frame.location {
+                    Some((path, line, column)) => {
+                        shown["source"] = json!({
+                            "name": path.file_name().map(|name| name.to_string_lossy()),
+                            "path": path,
+                        });
+                        shown["line"] = json!(line);
+                        shown["column"] = json!(column);
+                    }
+                    None => shown["presentationHint"] = json!("subtle"),
+                }
+                shown
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": self.frames.len() })
+    }
+
+    /// Hand out a variables reference for `reference`.
+    fn reference(&mut self, reference: Reference) -> usize {
+        self.references.push(reference);
+        self.references.len()
+    }
+
+    fn variables(&self, reference: usize) -> Result<Vec<(String, Value)>, String> {
+        let reference = reference
+            .checked_sub(1)
+            .and_then(|index| self.references.get(index))
+            .ok_or("no such variables reference")?;
+        let frame = |id: usize| self.frames.get(id).ok_or("no such frame".to_string());
+        Ok(match reference {
+            Reference::Locals(id) => {
+                let frame = frame(*id)?;
+                locals(&frame.lua, frame.level)
+            }
+            Reference::Upvalues(id) => {
+                let frame = frame(*id)?;
+                upvalues(&frame.lua, frame.level)
+            }
+            Reference::Value(Value::Table(table)) => entries(table),
+            Reference::Value(_) => Vec::new(),
+            Reference::Context(value) => {
+                let lua = &self.frames.first().ok_or("no frames")?.lua;
+                let children: Vec<(String, ContextValue)> = match value {
+                    ContextValue::Map(map) => map.iter().map(|(key, value)| (key.clone(), value.clone())).collect(),
+                    ContextValue::Array(items) => items
+                        .iter()
+                        .enumerate()
+                        .map(|(index, value)| (format!("[{}]", index + 1), value.clone()))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                children
+                    .into_iter()
+                    .take(ENTRIES_SHOWN)
+                    .map(|(key, value)| (key, context_value(lua, value)))
+                    .collect()
+            }
+        })
+    }
+
+    /// Evaluate `expression` where the frame `frame` is stopped: its locals
+    /// and upvalues in scope, then its `_ENV`. Without a frame, against the
+    /// globals of the innermost state.
+    fn evaluate(&self, frame: Option<usize>, expression: &str) -> Result<Value, String> {
+        let message = |err: LuaError| {
+            let message = err.to_string();
+            message
+                .split("\nstack traceback:")
+                .next()
+                .unwrap_or(&message)
+                .to_string()
+        };
+        let first = self.frames.first().ok_or("no frames")?;
+        let (lua, scope) = match frame.and_then(|id| self.frames.get(id)) {
+            Some(frame) if frame.lua_function => {
+                let scope = frame.lua.create_table().map_err(message)?;
+                let mut fallback = Value::Table(frame.lua.globals());
+                for (name, value) in upvalues(&frame.lua, frame.level)
+                    .into_iter()
+                    .chain(locals(&frame.lua, frame.level))
+                {
+                    if name == "_ENV" {
+                        fallback = value;
+                    } else {
+                        scope.raw_set(name, value).map_err(message)?;
+                    }
+                }
+                let meta = frame.lua.create_table().map_err(message)?;
+                meta.raw_set("__index", fallback).map_err(message)?;
+                scope.set_metatable(Some(meta)).map_err(message)?;
+                (&frame.lua, scope)
+            }
+            _ => (&first.lua, first.lua.globals()),
+        };
+        let function = lua
+            .load(format!("return {}", expression))
+            .set_name("=evaluate")
+            .set_environment(scope.clone())
+            .into_function()
+            .or_else(|_| {
+                lua.load(expression)
+                    .set_name("=evaluate")
+                    .set_environment(scope)
+                    .into_function()
+            })
+            .map_err(message)?;
+        let values = function.call::<MultiValue>(()).map_err(message)?;
+        Ok(values.into_iter().next().unwrap_or(Value::Nil))
+    }
+
+    /// `value` as the client shows it, its type, and a reference to what's
+    /// inside it, if anything is.
+    fn show(&mut self, value: Value) -> (String, &'static str, usize) {
+        match value {
+            Value::Nil => ("nil".to_string(), "nil", 0),
+            Value::String(string) => (format!("{:?}", string.to_string_lossy()), "string", 0),
+            Value::Table(table) => {
+                let shown = match table.raw_len() {
+                    0 => "table".to_string(),
+                    length => format!("table [{}]", length),
+                };
+                (shown, "table", self.reference(Reference::Value(Value::Table(table))))
+            }
+            Value::UserData(userdata) => match context_map(&userdata) {
+                Some(map) => {
+                    let shown = format!("Context {{{} keys}}", map.len());
+                    (
+                        shown,
+                        "Context",
+                        self.reference(Reference::Context(ContextValue::Map(map))),
+                    )
+                }
+                None => (display(&Value::UserData(userdata)), "userdata", 0),
+            },
+            Value::Function(_) => ("function".to_string(), "function", 0),
+            Value::Boolean(_) => (display(&value), "boolean", 0),
+            Value::Integer(_) | Value::Number(_) => (display(&value), "number", 0),
+            other => (display(&other), other.type_name(), 0),
+        }
+    }
+}
+
+/// Every frame of `lua`'s stack, innermost first. A compiled template's
+/// frames are shown at the template's lines.
+fn collect_frames(lua: &Lua, frames: &mut Vec<Frame>) {
+    let attachment = lua.app_data_ref::<Attachment>();
+    let templates = attachment.as_ref().map(|attachment| attachment.templates.borrow());
+    let mut template = templates.as_ref().map_or(0, |templates| templates.len());
+    let mut level = 0;
+    while let Some((source, what, line, name, function)) = lua.inspect_stack(level, |debug| {
+        let source = debug.source();
+        (
+            source.source.map(|source| source.into_owned()),
+            source.what,
+            debug.current_line(),
+            debug.names().name.map(|name| name.into_owned()),
+            debug.function().to_pointer(),
+        )
+    }) {
+        let location = match (&attachment, &source, line) {
+            (Some(attachment), Some(source), Some(line)) if source == CHUNK_NAME => {
+                let running = templates
+                    .as_ref()
+                    .and_then(|templates| templates.get(template.checked_sub(1)?));
+                let location = running.and_then(|running| running.locate(attachment, line));
+                // Frames further out belong to the template that ran this one.
+                if running.is_some_and(|running| running.function == function) {
+                    template -= 1;
+                }
+                location
+            }
+            (Some(attachment), Some(source), Some(line)) => {
+                attachment.locate(source, line).map(|(path, line)| (path, line, 1))
+            }
+            _ => None,
+        };
+        let lua_function = what != "C";
+        let name = match (name, what) {
+            (Some(name), _) => name,
+            (None, "main") => "main chunk".to_string(),
+            (None, _) if source.as_deref() == Some(CHUNK_NAME) => "template".to_string(),
+            (None, _) => "?".to_string(),
+        };
+        // An unnamed Rust function is only in the way.
+        if lua_function || name != "?" {
+            frames.push(Frame {
+                lua: lua.clone(),
+                level,
+                name,
+                location,
+                lua_function,
+            });
+        }
+        level += 1;
+    }
+}
+
+fn frame_id(arguments: &Json) -> Result<usize, String> {
+    arguments["frameId"]
+        .as_u64()
+        .map(|id| id as usize)
+        .ok_or_else(|| "no frameId".to_string())
+}
+
+/// The locals of the function `level` frames down `lua`'s stack, in the
+/// order they were declared. Lua's temporaries are left out.
+fn locals(lua: &Lua, level: usize) -> Vec<(String, Value)> {
+    // SAFETY: `exec_raw` keeps the stack balanced around the closure and
+    // returns the values it leaves above the arguments (there are none).
+    // The closure checks the stack has room before each push, and reads
+    // only the frame `lua_getstack` just found.
+    let values = unsafe {
+        lua.exec_raw::<MultiValue>((), |state| {
+            let mut frame: ffi::lua_Debug = std::mem::zeroed();
+            // The closure runs in a Rust function of its own: one level up.
+            if ffi::lua_getstack(state, (level + 1) as c_int, &mut frame) == 0 {
+                return;
+            }
+            for n in 1.. {
+                if ffi::lua_checkstack(state, 2) == 0 {
+                    break;
+                }
+                let name = ffi::lua_getlocal(state, &frame, n);
+                if name.is_null() {
+                    break;
+                }
+                ffi::lua_pushstring(state, name);
+                ffi::lua_insert(state, -2);
+            }
+        })
+    };
+    named(values)
+}
+
+/// The upvalues of the function `level` frames down `lua`'s stack.
+fn upvalues(lua: &Lua, level: usize) -> Vec<(String, Value)> {
+    // SAFETY: as for `locals`; the function pushed to read from is removed
+    // before the closure returns.
+    let values = unsafe {
+        lua.exec_raw::<MultiValue>((), |state| {
+            let mut frame: ffi::lua_Debug = std::mem::zeroed();
+            if ffi::lua_getstack(state, (level + 1) as c_int, &mut frame) == 0 {
+                return;
+            }
+            if ffi::lua_checkstack(state, 1) == 0 || ffi::lua_getinfo(state, c"f".as_ptr(), &mut frame) == 0 {
+                return;
+            }
+            let function = ffi::lua_gettop(state);
+            for n in 1.. {
+                if ffi::lua_checkstack(state, 2) == 0 {
+                    break;
+                }
+                let name = ffi::lua_getupvalue(state, function, n);
+                if name.is_null() {
+                    break;
+                }
+                ffi::lua_pushstring(state, name);
+                ffi::lua_insert(state, -2);
+            }
+            ffi::lua_remove(state, function);
+        })
+    };
+    named(values)
+}
+
+/// Pair up names and values, leaving out Lua's temporaries, `(for state)`
+/// and the like.
+fn named(values: mlua::Result<MultiValue>) -> Vec<(String, Value)> {
+    let values: Vec<Value> = values.map(|values| values.into_iter().collect()).unwrap_or_default();
+    values
+        .chunks(2)
+        .filter_map(|pair| match pair {
+            [Value::String(name), value] => Some((name.to_string_lossy(), value.clone())),
+            _ => None,
+        })
+        .filter(|(name, _)| !name.is_empty() && !name.starts_with('('))
+        .collect()
+}
+
+/// A table's entries, its sequence first, then its other keys in order.
+fn entries(table: &Table) -> Vec<(String, Value)> {
+    let mut sequence = Vec::new();
+    let mut named = Vec::new();
+    for pair in table.pairs::<Value, Value>().take(ENTRIES_SHOWN) {
+        let Ok((key, value)) = pair else { continue };
+        match key {
+            Value::Integer(index) => sequence.push((index, value)),
+            Value::String(name) => named.push((name.to_string_lossy(), value)),
+            other => named.push((format!("[{}]", display(&other)), value)),
+        }
+    }
+    sequence.sort_by_key(|(index, _)| *index);
+    named.sort_by(|(a, _), (b, _)| a.cmp(b));
+    sequence
+        .into_iter()
+        .map(|(index, value)| (format!("[{}]", index), value))
+        .chain(named)
+        .collect()
+}
+
+/// A Context's value as Lua would hold it, so it's shown like any other.
+fn context_value(lua: &Lua, value: ContextValue) -> Value {
+    match value {
+        ContextValue::Nil => Value::Nil,
+        ContextValue::Boolean(boolean) => Value::Boolean(boolean),
+        ContextValue::Integer(integer) => Value::Integer(integer),
+        ContextValue::Float(float) => Value::Number(float),
+        ContextValue::String(string) => lua.create_string(&string).map(Value::String).unwrap_or(Value::Nil),
+        nested => {
+            // Nested maps and lists stay Context data, shown the same way.
+            let table = lua.create_table().ok();
+            match (nested, table) {
+                (ContextValue::Map(map), Some(table)) => {
+                    for (key, value) in map {
+                        let _ = table.raw_set(key, context_value(lua, value));
+                    }
+                    Value::Table(table)
+                }
+                (ContextValue::Array(items), Some(table)) => {
+                    for value in items {
+                        let _ = table.raw_push(context_value(lua, value));
+                    }
+                    Value::Table(table)
+                }
+                _ => Value::Nil,
+            }
+        }
+    }
+}
+
+fn display(value: &Value) -> String {
+    value.to_string().unwrap_or_else(|_| format!("{:?}", value))
+}
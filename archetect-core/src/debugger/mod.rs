@@ -0,0 +1,303 @@
+//! `archetect debug --dap`: a Debug Adapter Protocol server for archetype
+//! scripts.
+//!
+//! The client speaks DAP over the adapter's stdin and stdout; a thread reads
+//! its requests while the render runs on the caller's. Every Lua state the
+//! render creates — the archetype's script, and a composed archetype's, from
+//! inside its parent's — is attached with a line hook, which stops at
+//! breakpoints and steps, and while stopped answers the client's questions
+//! about frames, variables, and expressions from the render's own thread.
+//! Template lines come back through each compiled template's source map, so
+//! a breakpoint on a line of an `.atl` file stops there.
+//!
+//! Stdout is the protocol's, so what a script prints or logs reaches the
+//! client as `output` events, through [`DebugIoHandle`]. Prompts go to the
+//! driver it wraps, as they would in a render.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+
+use archetect_api::{ContextMap, ContextValue};
+
+mod driver;
+mod hook;
+mod protocol;
+
+pub use driver::DebugIoHandle;
+pub(crate) use hook::{attach, enter_template};
+
+use protocol::{read_message, Writer};
+
+/// The one thread a debugged render shows the client.
+const THREAD_ID: i64 = 1;
+
+#[derive(Clone, Debug)]
+pub struct Debugger {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    writer: Writer,
+    control: Mutex<Control>,
+    /// Requests only a stopped render can answer, handed to it while it's
+    /// stopped.
+    commands: Mutex<Receiver<Command>>,
+    /// Taken by the thread reading requests, once it starts.
+    senders: Mutex<Option<(Sender<Command>, Sender<Startup>)>>,
+    startup: Mutex<Receiver<Startup>>,
+}
+
+/// What the reading thread and the render thread both look at.
+#[derive(Debug, Default)]
+struct Control {
+    /// Lines with a breakpoint, by canonical file path.
+    breakpoints: HashMap<PathBuf, BTreeSet<usize>>,
+    /// Every line with a breakpoint in any file, so most lines are passed
+    /// over without working out which file they're in.
+    lines: HashSet<usize>,
+    stop_on_entry: bool,
+    /// The client asked to pause.
+    pause: bool,
+    step: Option<Step>,
+    /// Where the render last stopped, until it moves on: a template line
+    /// compiles to several lines of Lua, and one stop is enough.
+    stopped_at: Option<(PathBuf, usize)>,
+    /// Whether the render is stopped, answering questions.
+    stopped: bool,
+    /// The client has gone; the render should end.
+    disconnected: bool,
+}
+
+impl Control {
+    fn set_breakpoints(&mut self, path: PathBuf, lines: BTreeSet<usize>) {
+        if lines.is_empty() {
+            self.breakpoints.remove(&path);
+        } else {
+            self.breakpoints.insert(path, lines);
+        }
+        self.lines = self.breakpoints.values().flatten().copied().collect();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StepKind {
+    In,
+    Over,
+    Out,
+}
+
+/// A step in progress: its kind, and the stack depth and line it started
+/// from.
+#[derive(Clone, Debug)]
+struct Step {
+    kind: StepKind,
+    depth: usize,
+    from: Option<(PathBuf, usize)>,
+}
+
+/// A request for the stopped render.
+#[derive(Debug)]
+enum Command {
+    /// `stackTrace`, `scopes`, `variables`, or `evaluate`.
+    Inspect(Value),
+    /// `continue`, or a step, to answer once the render is on its way.
+    Resume(Value, Option<StepKind>),
+    Disconnect,
+}
+
+/// How the session gets going, and ends.
+#[derive(Debug)]
+enum Startup {
+    Launch(Value),
+    Configured,
+    Ended,
+}
+
+/// What the client's `launch` asked for. Each field it leaves out falls back
+/// to the adapter's command line.
+#[derive(Debug, Default)]
+pub struct Launch {
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub answers: ContextMap,
+}
+
+impl Debugger {
+    /// A debugger speaking to its client through `output`.
+    pub fn new<W: Write + Send + 'static>(output: W) -> Debugger {
+        let (commands_tx, commands_rx) = mpsc::channel();
+        let (startup_tx, startup_rx) = mpsc::channel();
+        Debugger {
+            shared: Arc::new(Shared {
+                writer: Writer::new(Box::new(output)),
+                control: Mutex::new(Control::default()),
+                commands: Mutex::new(commands_rx),
+                senders: Mutex::new(Some((commands_tx, startup_tx))),
+                startup: Mutex::new(startup_rx),
+            }),
+        }
+    }
+
+    /// Wrap `driver`, so script output reaches the client.
+    pub fn driver<D: archetect_api::ScriptIoHandle>(&self, driver: D) -> DebugIoHandle<D> {
+        DebugIoHandle::new(driver, self.clone())
+    }
+
+    /// Read the client's requests from `input`, and wait for it to launch
+    /// the render and finish configuring breakpoints. `None` if it went
+    /// away first.
+    pub fn listen<R: Read + Send + 'static>(&self, input: R) -> Option<Launch> {
+        let (commands, startup) = self.shared.senders.lock().expect("Lock Error").take()?;
+        let shared = self.shared.clone();
+        std::thread::spawn(move || serve(&shared, BufReader::new(input), commands, startup));
+
+        let mut launch = None;
+        let mut configured = false;
+        let events = self.shared.startup.lock().expect("Lock Error");
+        while launch.is_none() || !configured {
+            match events.recv() {
+                Ok(Startup::Launch(arguments)) => launch = Some(arguments),
+                Ok(Startup::Configured) => configured = true,
+                Ok(Startup::Ended) | Err(_) => return None,
+            }
+        }
+        let arguments = launch.unwrap_or_default();
+        self.shared.control.lock().expect("Lock Error").stop_on_entry =
+            arguments["stopOnEntry"].as_bool().unwrap_or(false);
+        let answers = match arguments.get("answers").cloned().map(ContextValue::from) {
+            Some(ContextValue::Map(answers)) => answers,
+            _ => ContextMap::new(),
+        };
+        Some(Launch {
+            source: arguments["source"].as_str().map(str::to_string),
+            destination: arguments["destination"].as_str().map(str::to_string),
+            answers,
+        })
+    }
+
+    /// Tell the client the render is over — with what went wrong, if it
+    /// failed — and wait for it to disconnect.
+    pub fn finish(&self, outcome: Result<(), String>) {
+        if let Err(message) = &outcome {
+            self.output("stderr", message);
+        }
+        self.shared.writer.event("terminated", json!({}));
+        self.shared
+            .writer
+            .event("exited", json!({ "exitCode": if outcome.is_ok() { 0 } else { 1 } }));
+        let events = self.shared.startup.lock().expect("Lock Error");
+        while !matches!(events.recv(), Ok(Startup::Ended) | Err(_)) {}
+    }
+
+    fn output(&self, category: &str, message: &str) {
+        self.shared.writer.event(
+            "output",
+            json!({ "category": category, "output": format!("{}\n", message) }),
+        );
+    }
+}
+
+/// Read requests until the client disconnects or closes the stream. What
+/// only the stopped render can answer goes to it; the rest is answered here.
+fn serve<R: Read>(shared: &Shared, mut input: BufReader<R>, commands: Sender<Command>, startup: Sender<Startup>) {
+    let writer = &shared.writer;
+    while let Ok(Some(request)) = read_message(&mut input) {
+        if request["type"] != "request" {
+            continue;
+        }
+        let arguments = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                writer.respond(
+                    &request,
+                    Ok(json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsEvaluateForHovers": true,
+                        "supportsTerminateRequest": true,
+                    })),
+                );
+                writer.event("initialized", json!({}));
+            }
+            "launch" => {
+                writer.respond(&request, Ok(Value::Null));
+                let _ = startup.send(Startup::Launch(arguments.clone()));
+            }
+            "attach" => writer.respond(&request, Err("archetect debug launches its renders".to_string())),
+            "setBreakpoints" => {
+                let path = canonical(arguments["source"]["path"].as_str().unwrap_or_default());
+                let lines: BTreeSet<usize> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                let breakpoints: Vec<Value> = lines
+                    .iter()
+                    .map(|line| json!({ "verified": true, "line": line }))
+                    .collect();
+                shared.control.lock().expect("Lock Error").set_breakpoints(path, lines);
+                writer.respond(&request, Ok(json!({ "breakpoints": breakpoints })));
+            }
+            "setExceptionBreakpoints" => writer.respond(&request, Ok(json!({ "breakpoints": [] }))),
+            "configurationDone" => {
+                writer.respond(&request, Ok(Value::Null));
+                let _ = startup.send(Startup::Configured);
+            }
+            "threads" => writer.respond(
+                &request,
+                Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "archetype" }] })),
+            ),
+            "pause" => {
+                shared.control.lock().expect("Lock Error").pause = true;
+                writer.respond(&request, Ok(Value::Null));
+            }
+            command @ ("continue" | "next" | "stepIn" | "stepOut") => {
+                let step = match command {
+                    "next" => Some(StepKind::Over),
+                    "stepIn" => Some(StepKind::In),
+                    "stepOut" => Some(StepKind::Out),
+                    _ => None,
+                };
+                if shared.control.lock().expect("Lock Error").stopped {
+                    let _ = commands.send(Command::Resume(request.clone(), step));
+                } else {
+                    writer.respond(&request, Ok(Value::Null));
+                }
+            }
+            "stackTrace" | "scopes" | "variables" | "evaluate" => {
+                if shared.control.lock().expect("Lock Error").stopped {
+                    let _ = commands.send(Command::Inspect(request.clone()));
+                } else {
+                    writer.respond(&request, Err("the render is running".to_string()));
+                }
+            }
+            command @ ("terminate" | "disconnect") => {
+                shared.control.lock().expect("Lock Error").disconnected = true;
+                let _ = commands.send(Command::Disconnect);
+                writer.respond(&request, Ok(Value::Null));
+                // After `terminate`, the client still says goodbye.
+                if command == "disconnect" {
+                    break;
+                }
+            }
+            other => writer.respond(&request, Err(format!("`{}` isn't supported", other))),
+        }
+    }
+    // The render may be stopped, waiting on a client that's no longer there.
+    shared.control.lock().expect("Lock Error").disconnected = true;
+    let _ = commands.send(Command::Disconnect);
+    let _ = startup.send(Startup::Ended);
+}
+
+/// `path` with its symlinks resolved, which is how a staged library's files
+/// meet their checkout's: a breakpoint on either is one on both.
+fn canonical(path: &str) -> PathBuf {
+    Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path))
+}
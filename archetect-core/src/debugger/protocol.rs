@@ -0,0 +1,108 @@
+//! The Debug Adapter Protocol's wire format: JSON messages, each behind a
+//! `Content-Length` header.
+
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+
+/// Read the next message, or `None` once the client has closed the stream.
+pub(crate) fn read_message<R: BufRead>(reader: &mut R) -> std::io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            // A stray blank line before any header is tolerated.
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let mut body = vec![0; length.unwrap_or_default()];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// The adapter's side of the stream. Responses come from the thread reading
+/// requests and from the render thread while it's stopped; events from
+/// either, and from script output. One lock keeps their messages whole.
+pub(crate) struct Writer {
+    output: Mutex<Box<dyn Write + Send>>,
+    seq: AtomicI64,
+}
+
+impl Writer {
+    pub(crate) fn new(output: Box<dyn Write + Send>) -> Writer {
+        Writer {
+            output: Mutex::new(output),
+            seq: AtomicI64::new(1),
+        }
+    }
+
+    pub(crate) fn event(&self, event: &str, body: Value) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    /// Answer `request`: with `body` on success, or with the message of
+    /// what went wrong.
+    pub(crate) fn respond(&self, request: &Value, result: Result<Value, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = Value::String(message),
+        }
+        self.send(response);
+    }
+
+    /// Best effort: a client that has gone away has nothing left to tell.
+    fn send(&self, mut message: Value) {
+        message["seq"] = json!(self.seq.fetch_add(1, Ordering::SeqCst));
+        let body = message.to_string();
+        if let Ok(mut output) = self.output.lock() {
+            let _ = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+            let _ = output.flush();
+        }
+    }
+}
+
+impl std::fmt::Debug for Writer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Writer").field("seq", &self.seq).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_through_their_framing() {
+        let mut input = std::io::Cursor::new(
+            "Content-Length: 40\r\n\r\n{\"seq\":1,\"type\":\"request\",\"command\":\"x\"}".to_string()
+                + "Content-Length: 2\r\nContent-Type: application/json\r\n\r\n{}",
+        );
+        let first = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(first["command"], "x");
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({})));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+}
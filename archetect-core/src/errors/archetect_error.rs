@@ -40,6 +40,16 @@ pub enum ArchetectError {
     HeadlessNoDefault,
     #[error("IO channel error: {0}")]
     ChannelError(#[from] IoError),
+    #[error(
+        "No generation manifest at `{0}` — regeneration needs a project rendered from an archetype \
+         that declares a `regeneration` section"
+    )]
+    GenerationManifestNotFound(camino::Utf8PathBuf),
+    #[error("Generation manifest `{path}` is invalid: {source}")]
+    GenerationManifestError {
+        path: camino::Utf8PathBuf,
+        source: serde_yaml::Error,
+    },
     #[error("Server error: {0}")]
     ServerError(String),
     #[error("Configuration error: {0}")]
@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use camino::Utf8PathBuf;
 
 use crate::errors::{RequirementsError, SourceError};
+use crate::limits::LimitExceeded;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ArchetypeError {
@@ -36,6 +37,11 @@ pub enum ArchetypeError {
     ValueRequired,
     #[error("Archetype requirements failure:\n\n{0}")]
     RequirementsError(#[from] RequirementsError),
+    #[error("Invalid regeneration path `{pattern}` in archetype manifest: {source}")]
+    RegenerationPathError {
+        pattern: String,
+        source: globset::Error,
+    },
     #[error("Archetype Script Aborted")]
     ScriptAbortError,
     /// A catalog dispatch inside a script-less archetype failed. Carries
@@ -60,4 +66,9 @@ pub enum ArchetypeError {
     /// trace / error dump).
     #[error("Cancelled")]
     PromptAborted,
+    /// A script ran into one of the session's resource limits. Kept apart
+    /// from `ScriptAbortError` so hosts can tell a runaway script from a
+    /// failing one — the server answers it with RESOURCE_EXHAUSTED.
+    #[error("Resource limit exceeded: {0}")]
+    ResourceLimitExceeded(#[from] LimitExceeded),
 }
@@ -68,6 +68,21 @@ pub enum RenderError {
         path: Utf8PathBuf,
         message: String,
     },
+    #[error("Invalid front matter in `{path}`: {message}")]
+    InvalidFrontMatter {
+        path: Utf8PathBuf,
+        message: String,
+    },
+    #[error("Invalid glob `{pattern}`: {source}")]
+    InvalidGlob {
+        pattern: String,
+        source: globset::Error,
+    },
+    #[error("Symlink `{path}` points outside the rendered directory: `{target}`")]
+    SymlinkEscape {
+        path: Utf8PathBuf,
+        target: String,
+    },
     #[error("Non-UTF-8 path encountered while rendering: {path}")]
     InvalidUtf8Path {
         path: PathBuf,
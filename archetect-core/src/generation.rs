@@ -0,0 +1,339 @@
+//! Generation records, for archetypes that opt into regeneration.
+//!
+//! A regeneration-enabled render leaves two things behind in its destination:
+//! `.archetect/manifest.yaml`, which says where the project came from and with
+//! what answers, and `.archetect/shadow/`, a pristine copy of every file the
+//! archetype generated. The shadow is the common ancestor for the next
+//! render's three-way merge — it is what lets an archetype change and a user
+//! edit to the same file both survive.
+//!
+//! The shadow is written by the client as a side effect of `WriteFile` (see
+//! `WriteFileInfo::shadow`) because that is where the destination lives; the
+//! manifest travels as an ordinary `WriteFile` at the end of the render for
+//! the same reason.
+
+use std::collections::BTreeMap;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use globset::{Glob, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use archetect_api::{ClientMessage, ContextMap, ScriptMessage, WriteDirectoryInfo, WriteFileInfo};
+
+use crate::archetype::archetype::{Archetype, OverwritePolicy};
+use crate::archetype::archetype_manifest::regeneration::RegenerationConfig;
+use crate::archetype::render_context::RenderContext;
+use crate::errors::{ArchetectError, ArchetypeError};
+use crate::source::SourceType;
+use crate::Archetect;
+
+/// Directory, relative to the destination, holding the generation records.
+pub const GENERATION_DIR: &str = ".archetect";
+const MANIFEST_FILE: &str = "manifest.yaml";
+const SHADOW_DIR: &str = "shadow";
+
+/// What a regeneration-enabled render produced, and how to produce it again.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GenerationManifest {
+    pub archetype: GenerationSource,
+    pub generated_at: String,
+    pub archetect_version: String,
+    #[serde(default, skip_serializing_if = "ContextMap::is_empty")]
+    pub answers: ContextMap,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub switches: Vec<String>,
+    /// Keyed by destination-relative path, with `/` separators.
+    #[serde(default)]
+    pub files: BTreeMap<String, GeneratedFile>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GenerationSource {
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gitref: Option<String>,
+    /// The commit the source resolved to, for git sources.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oid: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GeneratedFile {
+    pub checksum: String,
+}
+
+impl GenerationManifest {
+    /// Where the manifest for a project rendered into `destination` lives.
+    pub fn path(destination: &Utf8Path) -> Utf8PathBuf {
+        destination.join(GENERATION_DIR).join(MANIFEST_FILE)
+    }
+
+    pub fn load(destination: &Utf8Path) -> Result<GenerationManifest, ArchetectError> {
+        let path = GenerationManifest::path(destination);
+        if !path.is_file() {
+            return Err(ArchetectError::GenerationManifestNotFound(path));
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        serde_yaml::from_str(&contents).map_err(|source| ArchetectError::GenerationManifestError { path, source })
+    }
+
+    /// The source as it would be given on the command line — the recorded
+    /// location, on the recorded ref. Regenerating follows that ref to
+    /// wherever it points now, which is the point.
+    pub fn source_locator(&self) -> String {
+        match &self.archetype.gitref {
+            Some(gitref) => format!("{}#{}", self.archetype.source, gitref),
+            None => self.archetype.source.clone(),
+        }
+    }
+}
+
+/// `sha256:`-prefixed hex digest of `contents`.
+pub fn checksum(contents: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(contents))
+}
+
+/// An in-progress generation: the destination being recorded and the policy
+/// rules from the archetype's `regeneration` section, compiled.
+#[derive(Debug)]
+pub(crate) struct Recording {
+    root: Utf8PathBuf,
+    rules: Vec<(GlobMatcher, OverwritePolicy)>,
+    default_strategy: OverwritePolicy,
+}
+
+impl Recording {
+    pub(crate) fn new(root: &Utf8Path, config: &RegenerationConfig) -> Result<Recording, ArchetypeError> {
+        let rules = config
+            .strategies()
+            .iter()
+            .map(|rule| {
+                Glob::new(rule.path())
+                    .map(|glob| (glob.compile_matcher(), rule.strategy()))
+                    .map_err(|source| ArchetypeError::RegenerationPathError {
+                        pattern: rule.path().to_string(),
+                        source,
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Recording {
+            root: root.to_path_buf(),
+            rules,
+            default_strategy: config.default_strategy(),
+        })
+    }
+
+    pub(crate) fn root(&self) -> &Utf8Path {
+        &self.root
+    }
+
+    /// Settle how a write beneath the destination meets an existing file, and
+    /// point it at its shadow. Writes elsewhere, and the records themselves,
+    /// pass through untouched.
+    pub(crate) fn apply(&self, info: &mut WriteFileInfo) {
+        let Some(relative) = self.relative(Utf8Path::new(&info.destination)) else {
+            return;
+        };
+        let strategy = self
+            .rules
+            .iter()
+            .find(|(matcher, _)| matcher.is_match(&relative))
+            .map(|(_, strategy)| *strategy)
+            .unwrap_or(self.default_strategy);
+        info.existing_file_policy = strategy.into();
+        info.shadow = Some(self.root.join(GENERATION_DIR).join(SHADOW_DIR).join(&relative).to_string());
+    }
+
+    fn relative(&self, path: &Utf8Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        if relative.starts_with(GENERATION_DIR) {
+            return None;
+        }
+        Some(relative.as_str().replace('\\', "/"))
+    }
+}
+
+/// Write the manifest for a finished recording. A dry run leaves the
+/// destination alone, records included.
+pub(crate) fn write_manifest(
+    archetect: &Archetect,
+    archetype: &Archetype,
+    recording: &Recording,
+    render_context: &RenderContext,
+) -> Result<(), ArchetypeError> {
+    if archetect.is_dry_run() {
+        return Ok(());
+    }
+
+    let archetype_source = match archetype.source() {
+        Some(source) => match source.source_type() {
+            SourceType::RemoteGit { url, gitref, tree_dir, .. } => GenerationSource {
+                source: url.clone(),
+                gitref: gitref.clone(),
+                oid: tree_dir.file_name().map(str::to_string),
+            },
+            other => GenerationSource {
+                source: other.source().to_string(),
+                gitref: None,
+                oid: None,
+            },
+        },
+        None => GenerationSource {
+            source: archetype.root().to_string(),
+            gitref: None,
+            oid: None,
+        },
+    };
+
+    let mut answers = render_context.answers().clone();
+    answers.extend(archetect.recorded_answers());
+    let mut switches: Vec<String> = render_context.switches().iter().cloned().collect();
+    switches.sort();
+
+    let files = archetect
+        .archive_entries_under(recording.root())
+        .into_iter()
+        .filter(|entry| !Utf8Path::new(&entry.path).starts_with(GENERATION_DIR))
+        .map(|entry| (entry.path, GeneratedFile { checksum: checksum(&entry.contents) }))
+        .collect();
+
+    let manifest = GenerationManifest {
+        archetype: archetype_source,
+        generated_at: archetect
+            .reproducible()
+            .map_or_else(chrono::Utc::now, |reproducible| reproducible.now())
+            .to_rfc3339(),
+        archetect_version: archetect.version().to_string(),
+        answers,
+        switches,
+        files,
+    };
+    let contents = serde_yaml::to_string(&manifest).map_err(|source| ArchetypeError::YamlError {
+        path: GenerationManifest::path(recording.root()).into(),
+        source,
+    })?;
+
+    let directory = recording.root().join(GENERATION_DIR);
+    send(archetect, ScriptMessage::WriteDirectory(WriteDirectoryInfo { path: directory.to_string() }))?;
+    send(
+        archetect,
+        ScriptMessage::WriteFile(WriteFileInfo {
+            destination: directory.join(MANIFEST_FILE).to_string(),
+            contents: contents.into_bytes(),
+            existing_file_policy: archetect_api::ExistingFilePolicy::Overwrite,
+            shadow: None,
+            mode: None,
+        }),
+    )
+}
+
+fn send(archetect: &Archetect, message: ScriptMessage) -> Result<(), ArchetypeError> {
+    let io_error = |message: String| ArchetypeError::IoError(std::io::Error::other(message));
+    archetect.request(message).map_err(|e| io_error(e.to_string()))?;
+    match archetect.response().map_err(|e| io_error(e.to_string()))? {
+        ClientMessage::Ack | ClientMessage::Written(_) => Ok(()),
+        ClientMessage::Error(message) => Err(io_error(message)),
+        other => Err(io_error(format!("Unexpected response: {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use archetect_api::ExistingFilePolicy;
+    use indoc::indoc;
+
+    fn recording(yaml: &str) -> Recording {
+        let config: RegenerationConfig = serde_yaml::from_str(yaml).unwrap();
+        Recording::new(Utf8Path::new("/project"), &config).unwrap()
+    }
+
+    fn write(destination: &str) -> WriteFileInfo {
+        WriteFileInfo {
+            destination: destination.to_string(),
+            contents: b"contents".to_vec(),
+            existing_file_policy: ExistingFilePolicy::Preserve,
+            shadow: None,
+            mode: None,
+        }
+    }
+
+    #[test]
+    fn test_checksum_is_prefixed_sha256() {
+        assert_eq!(
+            checksum(b""),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_apply_uses_default_strategy_and_shadow() {
+        let recording = recording("{}");
+        let mut info = write("/project/src/lib.rs");
+        recording.apply(&mut info);
+        assert!(matches!(info.existing_file_policy, ExistingFilePolicy::Merge));
+        assert_eq!(info.shadow.as_deref(), Some("/project/.archetect/shadow/src/lib.rs"));
+    }
+
+    #[test]
+    fn test_apply_first_matching_rule_wins() {
+        let recording = recording(indoc! {r#"
+            strategies:
+              - path: "src/main.rs"
+                strategy: preserve
+              - path: "src/**"
+                strategy: overwrite
+        "#});
+        let mut main = write("/project/src/main.rs");
+        recording.apply(&mut main);
+        assert!(matches!(main.existing_file_policy, ExistingFilePolicy::Preserve));
+
+        let mut lib = write("/project/src/lib.rs");
+        recording.apply(&mut lib);
+        assert!(matches!(lib.existing_file_policy, ExistingFilePolicy::Overwrite));
+    }
+
+    #[test]
+    fn test_apply_ignores_writes_outside_destination_and_records() {
+        let recording = recording("{}");
+        let mut outside = write("/elsewhere/file.txt");
+        recording.apply(&mut outside);
+        assert!(matches!(outside.existing_file_policy, ExistingFilePolicy::Preserve));
+        assert!(outside.shadow.is_none());
+
+        let mut manifest = write("/project/.archetect/manifest.yaml");
+        recording.apply(&mut manifest);
+        assert!(manifest.shadow.is_none());
+    }
+
+    #[test]
+    fn test_invalid_rule_glob_is_an_error() {
+        let config: RegenerationConfig = serde_yaml::from_str(indoc! {r#"
+            strategies:
+              - path: "src/[unclosed"
+                strategy: overwrite
+        "#})
+        .unwrap();
+        let result = Recording::new(Utf8Path::new("/project"), &config);
+        assert!(matches!(result, Err(ArchetypeError::RegenerationPathError { .. })));
+    }
+
+    #[test]
+    fn test_source_locator_pins_recorded_ref() {
+        let manifest: GenerationManifest = serde_yaml::from_str(indoc! {r#"
+            archetype:
+              source: https://github.com/archetect/example.git
+              gitref: v2
+              oid: 0123abcd
+            generated_at: "2026-01-01T00:00:00Z"
+            archetect_version: "3.5.0"
+            answers:
+              name: demo
+        "#})
+        .unwrap();
+        assert_eq!(manifest.source_locator(), "https://github.com/archetect/example.git#v2");
+        assert_eq!(manifest.answers.get("name").and_then(|v| v.as_str()), Some("demo"));
+        assert!(manifest.files.is_empty());
+    }
+}
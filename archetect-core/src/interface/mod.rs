@@ -512,6 +512,7 @@ fn run_probe(
     let archetect = Archetect::builder()
         .with_driver(driver)
         .with_configuration(configuration)
+        .with_limits(base.limits())
         .with_layout(layout_factory()?)
         .build()?;
 
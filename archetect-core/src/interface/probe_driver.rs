@@ -6,6 +6,7 @@ use std::sync::{Arc, Mutex};
 use archetect_api::{
     ClientMessage, IoError, PromptEnvelope, ScriptIoHandle, ScriptMessage, SegmentInfo, SegmentRef,
 };
+use archetect_terminal_io::overlay::Overlay;
 
 /// One thing the probe saw, in the order the script did it. Prompts alone
 /// give a flat list; the container events are what let the probe hand back a
@@ -162,11 +163,28 @@ impl ScriptIoHandle for ProbeDriver {
                 state.open.pop();
                 state.events.push(ProbeEvent::Exit);
             }
-            ScriptMessage::WriteFile(_) | ScriptMessage::WriteDirectory(_) => {
+            ScriptMessage::WriteFile(_)
+            | ScriptMessage::WriteDirectory(_)
+            | ScriptMessage::WriteSymlink(_)
+            | ScriptMessage::FileOperation(_)
+            | ScriptMessage::BeginTransaction(_) => {
                 // Acknowledged, never written — the probe observes, it
                 // does not scaffold.
                 state.queued.push_back(ClientMessage::Ack);
             }
+            // Read and list from disk: the probe never wrote anything over it.
+            ScriptMessage::ReadFile(path) => {
+                state.queued.push_back(match std::fs::read_to_string(&path) {
+                    Ok(contents) => ClientMessage::String(contents),
+                    Err(_) => ClientMessage::None,
+                });
+            }
+            ScriptMessage::ListFiles(path) => {
+                let listed = Overlay::default().list(camino::Utf8Path::new(&path));
+                state
+                    .queued
+                    .push_back(ClientMessage::Array(listed.into_iter().map(|path| path.into_string()).collect()));
+            }
             // Logs, prints, completion signals: no reply expected.
             _ => {}
         }
@@ -1,20 +1,24 @@
 use std::sync::{Arc, Mutex};
 
 use tokio::sync::mpsc::{Receiver, Sender};
+use tonic::Status;
 
 use archetect_api::{ClientMessage, IoError, ScriptIoHandle, ScriptMessage};
 
 use crate::proto::grpc;
 
+/// The script's end of a gRPC stream. Its channel carries the stream's
+/// items as they go out, so whoever holds another sender can end the
+/// stream with a status instead.
 #[derive(Clone, Debug)]
 pub struct AsyncScriptIoHandle {
-    script_tx: Sender<grpc::ScriptMessage>,
+    script_tx: Sender<Result<grpc::ScriptMessage, Status>>,
     client_rx: Arc<Mutex<Receiver<grpc::ClientMessage>>>,
 }
 
 impl AsyncScriptIoHandle {
     pub fn from_channels(
-        script_tx: Sender<grpc::ScriptMessage>,
+        script_tx: Sender<Result<grpc::ScriptMessage, Status>>,
         client_rx: Receiver<grpc::ClientMessage>,
     ) -> Self {
         Self {
@@ -27,7 +31,7 @@ impl AsyncScriptIoHandle {
 impl ScriptIoHandle for AsyncScriptIoHandle {
     fn send(&self, request: ScriptMessage) -> Result<(), IoError> {
         self.script_tx
-            .blocking_send(request.into())
+            .blocking_send(Ok(request.into()))
             .map_err(|_| IoError::ClientDisconnected)
     }
 
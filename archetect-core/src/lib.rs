@@ -1,9 +1,12 @@
 pub mod archetype;
 pub mod catalog;
 pub mod library;
+pub mod limits;
 pub mod configuration;
+pub mod debugger;
 pub mod errors;
 pub mod flags;
+pub mod generation;
 pub mod help;
 pub mod interface;
 pub mod learn;
@@ -20,6 +23,7 @@ pub(crate) mod check;
 pub mod client;
 pub mod io;
 pub mod proto;
+pub mod reproducible;
 pub mod server;
 
 pub use archetect::*;
@@ -0,0 +1,190 @@
+//! Resource limits for archetype scripts: how many Lua instructions a
+//! script may run, how large its Lua heap may grow, and how long a render
+//! may take on the wall clock.
+//!
+//! A runaway `while true do end` or a template that recurses without end
+//! would otherwise hold an `archetect server` worker or an MCP session
+//! forever. Limits are set on `Archetect`'s builder; the CLI fills them in
+//! from the configuration's `server.limits` for `server` and `mcp`.
+//!
+//! The instruction count and heap size are per script — each archetype in
+//! a composition gets its own. The deadline is per session, which for the
+//! server and MCP is one render, and it runs on while a prompt waits for an
+//! answer. A breach ends the render with
+//! [`ArchetypeError::ResourceLimitExceeded`](crate::errors::ArchetypeError::ResourceLimitExceeded).
+//!
+//! Scripts only check the deadline between Lua instructions, which does
+//! nothing for a render blocked in Rust — on a prompt nobody answers, or in
+//! a long `shell` call. The server and MCP hold renders to it themselves as
+//! well, and stop waiting on them once it passes.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use mlua::{Error as LuaError, HookTriggers, Lua, Result as LuaResult, VmState};
+use serde::{Deserialize, Serialize};
+
+/// How many instructions run between checks. Breaches are noticed within
+/// this many instructions of happening.
+const CHECK_INTERVAL: u32 = 1_000;
+
+/// Limits on what one render's scripts may consume. Unset limits don't
+/// apply.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ResourceLimits {
+    /// Lua VM instructions per script.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    instructions: Option<u64>,
+    /// Lua heap size per script, in mebibytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memory_mb: Option<u64>,
+    /// Wall-clock seconds per render, time spent waiting on prompts included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn with_instructions(mut self, instructions: u64) -> Self {
+        self.instructions = Some(instructions);
+        self
+    }
+
+    pub fn with_memory_mb(mut self, memory_mb: u64) -> Self {
+        self.memory_mb = Some(memory_mb);
+        self
+    }
+
+    pub fn with_timeout(mut self, seconds: u64) -> Self {
+        self.timeout = Some(seconds);
+        self
+    }
+
+    pub fn instructions(&self) -> Option<u64> {
+        self.instructions
+    }
+
+    pub fn memory_mb(&self) -> Option<u64> {
+        self.memory_mb
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    /// Enforce these limits on `lua`, with the session's `deadline`.
+    /// Installed last, once archetect's own modules are loaded, so the heap
+    /// limit is the script's to spend.
+    pub(crate) fn install(&self, lua: &Lua, deadline: Option<Instant>) -> LuaResult<()> {
+        lua.set_app_data(Breach::default());
+        if let Some(memory_mb) = self.memory_mb {
+            lua.set_memory_limit((memory_mb as usize).saturating_mul(1024 * 1024))?;
+        }
+        if self.instructions.is_none() && deadline.is_none() {
+            return Ok(());
+        }
+        let interval = self.instructions.map_or(CHECK_INTERVAL, |max| max.clamp(1, CHECK_INTERVAL as u64) as u32);
+        let instructions = self.instructions;
+        let timeout = self.timeout.unwrap_or_default();
+        let ran = Cell::new(0u64);
+        lua.set_global_hook(HookTriggers::new().every_nth_instruction(interval), move |lua, _| {
+            ran.set(ran.get() + interval as u64);
+            let breach = match (instructions, deadline) {
+                (Some(max), _) if ran.get() > max => LimitExceeded::Instructions(max),
+                (_, Some(deadline)) if Instant::now() >= deadline => LimitExceeded::Timeout(timeout),
+                _ => return Ok(VmState::Continue),
+            };
+            if let Some(record) = lua.app_data_ref::<Breach>() {
+                record.0.set(Some(breach));
+            }
+            // A `pcall` could catch the error and carry on, so from here on
+            // every instruction raises it again, until nothing is left to
+            // catch it.
+            lua.set_global_hook(HookTriggers::new().every_nth_instruction(1), move |_, _| {
+                Err(LuaError::external(breach))
+            })?;
+            Err(LuaError::external(breach))
+        })
+    }
+}
+
+/// The limit a script ran into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum LimitExceeded {
+    #[error("the script ran more than {0} Lua instructions")]
+    Instructions(u64),
+    #[error("the script's Lua heap grew past {0} MiB")]
+    Memory(u64),
+    #[error("the render ran past its {0}-second deadline")]
+    Timeout(u64),
+}
+
+/// The breach the hook saw, kept as app data so it's recognized however
+/// the error it raised was wrapped, stringified, or caught on the way out.
+#[derive(Default)]
+struct Breach(Cell<Option<LimitExceeded>>);
+
+/// The limit behind a script's failure, if a limit is what ended it.
+pub(crate) fn exceeded(lua: &Lua, limits: &ResourceLimits, error: &LuaError) -> Option<LimitExceeded> {
+    if let Some(breach) = lua.app_data_ref::<Breach>().and_then(|record| record.0.get()) {
+        return Some(breach);
+    }
+    let memory = LimitExceeded::Memory(limits.memory_mb.unwrap_or_default());
+    match error {
+        LuaError::ExternalError(cause) => cause.downcast_ref::<LimitExceeded>().copied(),
+        LuaError::CallbackError { cause, .. } | LuaError::WithContext { cause, .. } => {
+            exceeded(lua, limits, cause)
+        }
+        LuaError::MemoryError(_) if limits.memory_mb.is_some() => Some(memory),
+        // Lua's own message, for an allocation failure that reached a
+        // script as a string — a template's, say.
+        LuaError::RuntimeError(message) if limits.memory_mb.is_some() && message.contains("not enough memory") => {
+            Some(memory)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(limits: ResourceLimits, deadline: Option<Instant>, code: &str) -> Option<LimitExceeded> {
+        let lua = Lua::new();
+        limits.install(&lua, deadline).unwrap();
+        let error = lua.load(code).exec().expect_err("the script should be stopped");
+        exceeded(&lua, &limits, &error)
+    }
+
+    #[test]
+    fn a_runaway_loop_hits_the_instruction_limit() {
+        let limits = ResourceLimits::default().with_instructions(100_000);
+        assert_eq!(run(limits, None, "while true do end"), Some(LimitExceeded::Instructions(100_000)));
+        // Catching the error doesn't buy more time.
+        let caught = "while true do pcall(function() while true do end end) end";
+        assert_eq!(run(limits, None, caught), Some(LimitExceeded::Instructions(100_000)));
+    }
+
+    #[test]
+    fn a_growing_table_hits_the_memory_limit() {
+        let limits = ResourceLimits::default().with_memory_mb(8);
+        let grow = "local t = {} for i = 1, 1e9 do t[i] = string.rep('x', 64) .. i end";
+        assert_eq!(run(limits, None, grow), Some(LimitExceeded::Memory(8)));
+    }
+
+    #[test]
+    fn the_deadline_stops_a_script_in_time() {
+        let limits = ResourceLimits::default().with_timeout(1);
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let started = Instant::now();
+        assert_eq!(run(limits, Some(deadline), "while true do end"), Some(LimitExceeded::Timeout(1)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn limits_load_from_configuration() {
+        let limits: ResourceLimits = serde_yaml::from_str("instructions: 5000000\ntimeout: 30").unwrap();
+        assert_eq!(limits.instructions(), Some(5_000_000));
+        assert_eq!(limits.memory_mb(), None);
+        assert_eq!(limits.timeout(), Some(Duration::from_secs(30)));
+    }
+}
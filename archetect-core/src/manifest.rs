@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use archetect_api::ContextMap;
 
 use crate::archetype::archetype_manifest::requirements::RuntimeRequirements;
+use crate::archetype::archetype_manifest::regeneration::RegenerationConfig;
 use crate::archetype::archetype_manifest::templating::TemplatingConfig;
 use crate::errors::ArchetypeError;
 
@@ -58,6 +59,12 @@ pub struct Manifest {
     // ── Archetype ──
     #[serde(default)]
     pub templating: TemplatingConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regeneration: Option<RegenerationConfig>,
+    /// When true, every render of this archetype is transactional: writes are
+    /// staged and only applied once the render succeeds. Default: false.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub atomic: bool,
 }
 
 /// A recursive catalog entry. Either a leaf (has `source`) or a group (has `catalog`).
@@ -1,7 +1,7 @@
 use archetect_api::{
-    Artifact, ArtifactKind, BoolPromptInfo, EditorPromptInfo, ExistingFilePolicy, IntPromptInfo,
-    ListPromptInfo, MultiSelectPromptInfo, SegmentEnd, SegmentInfo, SegmentKind, SelectPromptInfo,
-    TextPromptInfo, WriteDirectoryInfo, WriteFileInfo,
+    Artifact, ArtifactKind, BoolPromptInfo, EditorPromptInfo, ExistingFilePolicy, FileOperation, FileOutcome,
+    IntPromptInfo, ListPromptInfo, MultiSelectPromptInfo, SegmentEnd, SegmentInfo, SegmentKind, SelectPromptInfo,
+    TextPromptInfo, WriteDirectoryInfo, WriteFileInfo, WriteSymlinkInfo,
 };
 
 use super::grpc;
@@ -122,10 +122,23 @@ impl From<ApiScriptMessage> for grpc::script_message::Message {
                 destination: info.destination,
                 contents: info.contents,
                 existing_files: api_policy_to_proto(info.existing_file_policy).into(),
+                shadow: info.shadow,
+                mode: info.mode,
             }),
             ApiScriptMessage::WriteDirectory(info) => {
                 Message::WriteDirectory(grpc::WriteDirectory { path: info.path })
             }
+            ApiScriptMessage::WriteSymlink(info) => Message::WriteSymlink(grpc::WriteSymlink {
+                path: info.path,
+                target: info.target,
+                existing_files: api_policy_to_proto(info.existing_file_policy).into(),
+            }),
+            ApiScriptMessage::FileOperation(operation) => {
+                Message::FileOperation(api_file_operation_to_proto(operation))
+            }
+            ApiScriptMessage::ReadFile(path) => Message::ReadFile(path),
+            ApiScriptMessage::ListFiles(path) => Message::ListFiles(path),
+            ApiScriptMessage::BeginTransaction(destination) => Message::BeginTransaction(destination),
             ApiScriptMessage::BeginSegment(info) => Message::BeginSegment(grpc::SegmentInfo {
                 kind: api_segment_kind_to_proto(info.kind).into(),
                 key: info.key,
@@ -274,10 +287,26 @@ impl From<grpc::ScriptMessage> for ApiScriptMessage {
                 destination: wf.destination,
                 contents: wf.contents,
                 existing_file_policy: proto_policy_to_api(wf.existing_files),
+                shadow: wf.shadow,
+                mode: wf.mode,
             }),
             Message::WriteDirectory(wd) => {
                 ApiScriptMessage::WriteDirectory(WriteDirectoryInfo { path: wd.path })
             }
+            Message::WriteSymlink(ws) => ApiScriptMessage::WriteSymlink(WriteSymlinkInfo {
+                path: ws.path,
+                target: ws.target,
+                existing_file_policy: proto_policy_to_api(ws.existing_files),
+            }),
+            Message::FileOperation(operation) => match proto_file_operation_to_api(operation) {
+                Some(operation) => ApiScriptMessage::FileOperation(operation),
+                None => ApiScriptMessage::LogError(
+                    "received malformed gRPC FileOperation (missing oneof)".to_string(),
+                ),
+            },
+            Message::ReadFile(path) => ApiScriptMessage::ReadFile(path),
+            Message::ListFiles(path) => ApiScriptMessage::ListFiles(path),
+            Message::BeginTransaction(destination) => ApiScriptMessage::BeginTransaction(destination),
             Message::BeginSegment(s) => ApiScriptMessage::BeginSegment(SegmentInfo {
                 kind: proto_segment_kind_to_api(s.kind),
                 key: s.key,
@@ -310,6 +339,7 @@ impl From<ApiClientMessage> for grpc::ClientMessage {
             ApiClientMessage::Error(msg) => Message::Error(msg),
             ApiClientMessage::Abort => Message::Abort(()),
             ApiClientMessage::Ack => Message::Ack(()),
+            ApiClientMessage::Written(outcome) => Message::Written(api_file_outcome_to_proto(Some(outcome)) as i32),
             ApiClientMessage::Initialize {
                 answers_yaml,
                 switches,
@@ -368,6 +398,10 @@ impl From<grpc::ClientMessage> for ApiClientMessage {
             Message::None(_) => ApiClientMessage::None,
             Message::Abort(_) => ApiClientMessage::Abort,
             Message::Ack(_) => ApiClientMessage::Ack,
+            Message::Written(outcome) => match proto_file_outcome_to_api(outcome) {
+                Some(outcome) => ApiClientMessage::Written(outcome),
+                None => ApiClientMessage::Ack,
+            },
         }
     }
 }
@@ -378,6 +412,8 @@ fn api_artifact_to_proto(artifact: Artifact) -> grpc::Artifact {
     let kind = match artifact.kind {
         ArtifactKind::Archive => grpc::ArtifactKind::Archive,
         ArtifactKind::Repository => grpc::ArtifactKind::Repository,
+        ArtifactKind::File => grpc::ArtifactKind::File,
+        ArtifactKind::Removed => grpc::ArtifactKind::Removed,
     };
     grpc::Artifact {
         kind: kind as i32,
@@ -385,27 +421,110 @@ fn api_artifact_to_proto(artifact: Artifact) -> grpc::Artifact {
         // the API side round-trips it back to None.
         path: artifact.path.unwrap_or_default(),
         uri: artifact.uri.unwrap_or_default(),
+        outcome: api_file_outcome_to_proto(artifact.outcome) as i32,
+        sha256: artifact.sha256.unwrap_or_default(),
     }
 }
 
 fn proto_artifact_to_api(artifact: grpc::Artifact) -> Artifact {
     let kind = match grpc::ArtifactKind::try_from(artifact.kind) {
         Ok(grpc::ArtifactKind::Repository) => ArtifactKind::Repository,
+        Ok(grpc::ArtifactKind::File) => ArtifactKind::File,
+        Ok(grpc::ArtifactKind::Removed) => ArtifactKind::Removed,
         _ => ArtifactKind::Archive,
     };
     Artifact {
         kind,
         path: Some(artifact.path).filter(|p| !p.is_empty()),
         uri: Some(artifact.uri).filter(|u| !u.is_empty()),
+        outcome: proto_file_outcome_to_api(artifact.outcome),
+        sha256: Some(artifact.sha256).filter(|h| !h.is_empty()),
+    }
+}
+
+fn api_file_outcome_to_proto(outcome: Option<FileOutcome>) -> grpc::FileOutcome {
+    match outcome {
+        None => grpc::FileOutcome::Unspecified,
+        Some(FileOutcome::Created) => grpc::FileOutcome::Created,
+        Some(FileOutcome::Overwritten) => grpc::FileOutcome::Overwritten,
+        Some(FileOutcome::Unchanged) => grpc::FileOutcome::Unchanged,
+        Some(FileOutcome::Preserved) => grpc::FileOutcome::Preserved,
+        Some(FileOutcome::Merged) => grpc::FileOutcome::Merged,
+        Some(FileOutcome::Conflicted) => grpc::FileOutcome::Conflicted,
+    }
+}
+
+fn proto_file_outcome_to_api(outcome: i32) -> Option<FileOutcome> {
+    match grpc::FileOutcome::try_from(outcome) {
+        Ok(grpc::FileOutcome::Created) => Some(FileOutcome::Created),
+        Ok(grpc::FileOutcome::Overwritten) => Some(FileOutcome::Overwritten),
+        Ok(grpc::FileOutcome::Unchanged) => Some(FileOutcome::Unchanged),
+        Ok(grpc::FileOutcome::Preserved) => Some(FileOutcome::Preserved),
+        Ok(grpc::FileOutcome::Merged) => Some(FileOutcome::Merged),
+        Ok(grpc::FileOutcome::Conflicted) => Some(FileOutcome::Conflicted),
+        _ => None,
+    }
+}
+
+fn api_file_operation_to_proto(operation: FileOperation) -> grpc::FileOperation {
+    use grpc::file_operation::Operation;
+
+    let operation = match operation {
+        FileOperation::Append { path, contents } => Operation::Append(grpc::AppendFile { path, contents }),
+        FileOperation::Delete { path } => Operation::Delete(grpc::DeletePath { path }),
+        FileOperation::Move {
+            source,
+            destination,
+            existing_file_policy,
+        } => Operation::MovePath(grpc::TransferPath {
+            source,
+            destination,
+            existing_files: api_policy_to_proto(existing_file_policy).into(),
+        }),
+        FileOperation::Copy {
+            source,
+            destination,
+            existing_file_policy,
+        } => Operation::CopyPath(grpc::TransferPath {
+            source,
+            destination,
+            existing_files: api_policy_to_proto(existing_file_policy).into(),
+        }),
+    };
+    grpc::FileOperation {
+        operation: Some(operation),
     }
 }
 
+fn proto_file_operation_to_api(operation: grpc::FileOperation) -> Option<FileOperation> {
+    use grpc::file_operation::Operation;
+
+    Some(match operation.operation? {
+        Operation::Append(append) => FileOperation::Append {
+            path: append.path,
+            contents: append.contents,
+        },
+        Operation::Delete(delete) => FileOperation::Delete { path: delete.path },
+        Operation::MovePath(transfer) => FileOperation::Move {
+            source: transfer.source,
+            destination: transfer.destination,
+            existing_file_policy: proto_policy_to_api(transfer.existing_files),
+        },
+        Operation::CopyPath(transfer) => FileOperation::Copy {
+            source: transfer.source,
+            destination: transfer.destination,
+            existing_file_policy: proto_policy_to_api(transfer.existing_files),
+        },
+    })
+}
+
 fn api_policy_to_proto(policy: ExistingFilePolicy) -> grpc::ExistingFilePolicy {
     match policy {
         ExistingFilePolicy::Overwrite => grpc::ExistingFilePolicy::Overwrite,
         ExistingFilePolicy::Preserve => grpc::ExistingFilePolicy::Preserve,
         ExistingFilePolicy::Prompt => grpc::ExistingFilePolicy::Prompt,
         ExistingFilePolicy::Error => grpc::ExistingFilePolicy::Error,
+        ExistingFilePolicy::Merge => grpc::ExistingFilePolicy::Merge,
     }
 }
 
@@ -414,6 +533,7 @@ fn proto_policy_to_api(value: i32) -> ExistingFilePolicy {
         Ok(grpc::ExistingFilePolicy::Overwrite) => ExistingFilePolicy::Overwrite,
         Ok(grpc::ExistingFilePolicy::Prompt) => ExistingFilePolicy::Prompt,
         Ok(grpc::ExistingFilePolicy::Error) => ExistingFilePolicy::Error,
+        Ok(grpc::ExistingFilePolicy::Merge) => ExistingFilePolicy::Merge,
         _ => ExistingFilePolicy::Preserve,
     }
 }
@@ -0,0 +1,179 @@
+//! Reproducible renders: one pinned clock and one seeded random source for
+//! everything a render would otherwise take from the system.
+//!
+//! Under `--reproducible`, the clock stands still at `SOURCE_DATE_EPOCH`
+//! (the Unix epoch when that isn't set), in UTC, and every random value —
+//! UUIDs, `math.random` — comes from a generator seeded by `--seed`
+//! (default 0). The same archetype with the same answers then renders the
+//! same bytes, which is what golden tests and stable re-render diffs need.
+//!
+//! The session's [`Reproducible`] lives on `Archetect`; a script's Lua
+//! state carries a copy as app data, which is where the template builtins
+//! find it.
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use mlua::{Lua, MultiValue, Result as LuaResult, Table, Value, Variadic};
+
+/// The environment variable reproducible-builds tooling agrees on for a
+/// build's timestamp, in seconds since the Unix epoch.
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// A pinned clock and a seeded random source. Clones share the generator,
+/// so draws continue one sequence however many holders there are.
+#[derive(Clone, Debug)]
+pub struct Reproducible {
+    epoch: i64,
+    seed: u64,
+    state: Arc<Mutex<u64>>,
+}
+
+impl Reproducible {
+    pub fn new(epoch: i64, seed: u64) -> Reproducible {
+        Reproducible {
+            epoch,
+            seed,
+            state: Arc::new(Mutex::new(seed)),
+        }
+    }
+
+    /// The clock from `SOURCE_DATE_EPOCH`, if set, and the given seed.
+    pub fn from_env(seed: Option<u64>) -> Result<Reproducible, String> {
+        let epoch = match std::env::var(SOURCE_DATE_EPOCH) {
+            Ok(value) => value.trim().parse::<i64>().map_err(|_| {
+                format!("{} must be a number of seconds since the Unix epoch, got `{}`", SOURCE_DATE_EPOCH, value)
+            })?,
+            Err(_) => 0,
+        };
+        Ok(Reproducible::new(epoch, seed.unwrap_or_default()))
+    }
+
+    /// The pinned time, in seconds since the Unix epoch.
+    pub fn epoch(&self) -> i64 {
+        self.epoch
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The pinned time. It does not advance.
+    pub fn now(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.epoch, 0).unwrap_or_default()
+    }
+
+    /// The next value of the seeded sequence (SplitMix64).
+    pub fn next_u64(&self) -> u64 {
+        let mut state = self.state.lock().expect("Lock Error");
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn fill_bytes(&self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let next = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&next[..chunk.len()]);
+        }
+    }
+
+    /// The session's `Reproducible`, as a Lua state carries it; `None`
+    /// outside reproducible mode.
+    pub fn of(lua: &Lua) -> Option<Reproducible> {
+        lua.app_data_ref::<Reproducible>().map(|reproducible| reproducible.clone())
+    }
+
+    /// Make `lua` reproducible: the template builtins read the clock and
+    /// generator from its app data, and `os.time`, `os.date`, and
+    /// `math.random` are replaced by versions that use them.
+    /// `math.randomseed` becomes a no-op, so `--seed` alone decides.
+    pub fn install(&self, lua: &Lua) -> LuaResult<()> {
+        lua.set_app_data(self.clone());
+
+        let os: Table = lua.globals().get("os")?;
+        let time: mlua::Function = os.get("time")?;
+        let epoch = self.epoch;
+        os.set(
+            "time",
+            lua.create_function(move |_, args: MultiValue| match args.front() {
+                // A date table converts as it always did; only "now" is pinned.
+                Some(Value::Table(_)) => time.call::<Value>(args),
+                _ => Ok(Value::Integer(epoch)),
+            })?,
+        )?;
+        let date: mlua::Function = os.get("date")?;
+        os.set(
+            "date",
+            lua.create_function(move |_, (format, at): (Option<String>, Option<i64>)| {
+                // Local time depends on the machine; the pinned clock is UTC.
+                let format = format.unwrap_or_else(|| "%c".to_string());
+                let format = if format.starts_with('!') { format } else { format!("!{}", format) };
+                date.call::<Value>((format, at.unwrap_or(epoch)))
+            })?,
+        )?;
+
+        let math: Table = lua.globals().get("math")?;
+        let reproducible = self.clone();
+        math.set(
+            "random",
+            lua.create_function(move |_, bounds: Variadic<i64>| reproducible.random(&bounds))?,
+        )?;
+        math.set("randomseed", lua.create_function(|_, _: MultiValue| Ok(()))?)?;
+        Ok(())
+    }
+
+    /// `math.random()`, `math.random(n)`, and `math.random(m, n)`, drawn
+    /// from the seeded sequence.
+    fn random(&self, bounds: &[i64]) -> LuaResult<Value> {
+        let (low, high) = match *bounds {
+            [] => return Ok(Value::Number((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64)),
+            [0] => return Ok(Value::Integer(self.next_u64() as i64)),
+            [n] => (1, n),
+            [m, n] => (m, n),
+            _ => return Err(mlua::Error::RuntimeError("wrong number of arguments to 'random'".into())),
+        };
+        if low > high {
+            return Err(mlua::Error::RuntimeError("bad argument to 'random' (interval is empty)".into()));
+        }
+        let span = (high as i128 - low as i128 + 1) as u128;
+        let offset = (self.next_u64() as u128 % span) as i128;
+        Ok(Value::Integer((low as i128 + offset) as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_draws_the_same_sequence() {
+        let a = Reproducible::new(0, 42);
+        let b = Reproducible::new(0, 42);
+        let draws = |r: &Reproducible| (0..4).map(|_| r.next_u64()).collect::<Vec<_>>();
+        assert_eq!(draws(&a), draws(&b));
+        assert_ne!(draws(&Reproducible::new(0, 43)), draws(&Reproducible::new(0, 42)));
+    }
+
+    #[test]
+    fn lua_time_and_randomness_are_pinned() {
+        let run = || {
+            let lua = Lua::new();
+            Reproducible::new(1_700_000_000, 7).install(&lua).unwrap();
+            lua.load(
+                r#"
+                math.randomseed()
+                return table.concat({ os.time(), os.date("%Y-%m-%d %H:%M"), math.random(1, 6),
+                    math.random(100), string.format("%.6f", math.random()) }, " ")
+                "#,
+            )
+            .eval::<String>()
+            .unwrap()
+        };
+        let first = run();
+        assert!(first.starts_with("1700000000 2023-11-14 22:13 "), "{}", first);
+        assert_eq!(first, run());
+    }
+}
@@ -132,6 +132,14 @@ impl Context {
             .map_err(|e| LuaError::RuntimeError(format!("IO error: {}", e)))
     }
 
+    /// Note the value a prompt settled on, under the key that would supply
+    /// it as an answer next time. Regeneration replays these.
+    fn record_answer(&self, answer_key: &str, key: &str) {
+        if let Some(value) = self.data.get(key) {
+            self.archetect.record_answer(answer_key, value.clone());
+        }
+    }
+
     fn store_string_with_cases(&mut self, key: &str, value: &str, cases: &[CaseSpec]) {
         self.data.insert(key.to_string(), ContextValue::String(value.to_string()));
         for spec in cases {
@@ -486,15 +494,17 @@ fn multiselect_prompt(
                     })
                     .collect();
                 if answer_key != key {
-                    this.data.insert(key, ContextValue::Array(arr));
+                    this.data.insert(key.clone(), ContextValue::Array(arr));
                 }
+                this.record_answer(&answer_key, &key);
                 return Ok(Some(strings));
             }
             ContextValue::String(s) => {
                 let strings: Vec<String> = s.split(',').map(|s| s.trim().to_string()).collect();
                 let items: Vec<ContextValue> =
                     strings.iter().cloned().map(ContextValue::String).collect();
-                this.data.insert(key, ContextValue::Array(items));
+                this.data.insert(key.clone(), ContextValue::Array(items));
+                this.record_answer(&answer_key, &key);
                 return Ok(Some(strings));
             }
             _ => {}
@@ -508,7 +518,8 @@ fn multiselect_prompt(
                 .cloned()
                 .map(ContextValue::String)
                 .collect();
-            this.data.insert(key, ContextValue::Array(arr));
+            this.data.insert(key.clone(), ContextValue::Array(arr));
+            this.record_answer(&answer_key, &key);
             return Ok(Some(defaults.clone()));
         }
         if info.optional {
@@ -524,7 +535,8 @@ fn multiselect_prompt(
     if let Some(value) = handle_response_array(response)? {
         let arr: Vec<ContextValue> =
             value.iter().cloned().map(ContextValue::String).collect();
-        this.data.insert(key, ContextValue::Array(arr));
+        this.data.insert(key.clone(), ContextValue::Array(arr));
+        this.record_answer(&answer_key, &key);
         Ok(Some(value))
     } else {
         Ok(None)
@@ -811,6 +823,7 @@ impl UserData for Context {
             if let Some(ContextValue::String(answer)) = this.data.get(&answer_key).cloned() {
                 validate_pattern(info.pattern.as_deref(), &key, &answer)?;
                 this.store_string_with_cases(&key, &answer, &cases);
+                this.record_answer(&answer_key, &key);
                 return Ok(Some(answer));
             }
 
@@ -818,6 +831,7 @@ impl UserData for Context {
                 if let Some(ref default) = info.default {
                     validate_pattern(info.pattern.as_deref(), &key, default)?;
                     this.store_string_with_cases(&key, default, &cases);
+                    this.record_answer(&answer_key, &key);
                     return Ok(Some(default.clone()));
                 }
                 if info.optional {
@@ -834,6 +848,7 @@ impl UserData for Context {
             if let Some(value) = handle_response_string(response)? {
                 validate_pattern(pattern.as_deref(), &key, &value)?;
                 this.store_string_with_cases(&key, &value, &cases);
+                this.record_answer(&answer_key, &key);
                 Ok(Some(value))
             } else {
                 Ok(None)
@@ -860,14 +875,16 @@ impl UserData for Context {
             let answer_key = get_answer_key(&opts, &key);
             if let Some(ContextValue::Integer(v)) = this.data.get(&answer_key).cloned() {
                 if answer_key != key {
-                    this.data.insert(key, ContextValue::Integer(v));
+                    this.data.insert(key.clone(), ContextValue::Integer(v));
                 }
+                this.record_answer(&answer_key, &key);
                 return Ok(Some(v));
             }
 
             if this.use_default(&key) {
                 if let Some(default) = info.default {
-                    this.data.insert(key, ContextValue::Integer(default));
+                    this.data.insert(key.clone(), ContextValue::Integer(default));
+                    this.record_answer(&answer_key, &key);
                     return Ok(Some(default));
                 }
                 if info.optional {
@@ -881,7 +898,8 @@ impl UserData for Context {
 
             let response = this.send_prompt(ScriptMessage::PromptForInt(info))?;
             if let Some(value) = handle_response_int(response)? {
-                this.data.insert(key, ContextValue::Integer(value));
+                this.data.insert(key.clone(), ContextValue::Integer(value));
+                this.record_answer(&answer_key, &key);
                 Ok(Some(value))
             } else {
                 Ok(None)
@@ -906,14 +924,16 @@ impl UserData for Context {
             let answer_key = get_answer_key(&opts, &key);
             if let Some(ContextValue::Boolean(v)) = this.data.get(&answer_key).cloned() {
                 if answer_key != key {
-                    this.data.insert(key, ContextValue::Boolean(v));
+                    this.data.insert(key.clone(), ContextValue::Boolean(v));
                 }
+                this.record_answer(&answer_key, &key);
                 return Ok(Some(v));
             }
 
             if this.use_default(&key) {
                 if let Some(default) = info.default {
-                    this.data.insert(key, ContextValue::Boolean(default));
+                    this.data.insert(key.clone(), ContextValue::Boolean(default));
+                    this.record_answer(&answer_key, &key);
                     return Ok(Some(default));
                 }
                 if info.optional {
@@ -927,7 +947,8 @@ impl UserData for Context {
 
             let response = this.send_prompt(ScriptMessage::PromptForBool(info))?;
             if let Some(value) = handle_response_bool(response)? {
-                this.data.insert(key, ContextValue::Boolean(value));
+                this.data.insert(key.clone(), ContextValue::Boolean(value));
+                this.record_answer(&answer_key, &key);
                 Ok(Some(value))
             } else {
                 Ok(None)
@@ -958,12 +979,14 @@ impl UserData for Context {
             let answer_key = get_answer_key(&opts, &key);
             if let Some(ContextValue::String(v)) = this.data.get(&answer_key).cloned() {
                 this.store_string_with_cases(&key, &v, &cases);
+                this.record_answer(&answer_key, &key);
                 return Ok(Some(v));
             }
 
             if this.use_default(&key) {
                 if let Some(ref default) = info.default {
                     this.store_string_with_cases(&key, default, &cases);
+                    this.record_answer(&answer_key, &key);
                     return Ok(Some(default.clone()));
                 }
                 if info.optional {
@@ -978,6 +1001,7 @@ impl UserData for Context {
             let response = this.send_prompt(ScriptMessage::PromptForSelect(info))?;
             if let Some(value) = handle_response_string(response)? {
                 this.store_string_with_cases(&key, &value, &cases);
+                this.record_answer(&answer_key, &key);
                 Ok(Some(value))
             } else {
                 Ok(None)
@@ -1031,8 +1055,9 @@ impl UserData for Context {
                             })
                             .collect();
                         if answer_key != key {
-                            this.data.insert(key, ContextValue::Array(arr));
+                            this.data.insert(key.clone(), ContextValue::Array(arr));
                         }
+                        this.record_answer(&answer_key, &key);
                         return Ok(Some(strings));
                     }
                     ContextValue::String(s) => {
@@ -1040,7 +1065,8 @@ impl UserData for Context {
                             s.split(',').map(|s| s.trim().to_string()).collect();
                         let items: Vec<ContextValue> =
                             strings.iter().cloned().map(ContextValue::String).collect();
-                        this.data.insert(key, ContextValue::Array(items));
+                        this.data.insert(key.clone(), ContextValue::Array(items));
+                        this.record_answer(&answer_key, &key);
                         return Ok(Some(strings));
                     }
                     _ => {}
@@ -1053,7 +1079,8 @@ impl UserData for Context {
                         .cloned()
                         .map(ContextValue::String)
                         .collect();
-                    this.data.insert(key, ContextValue::Array(arr));
+                    this.data.insert(key.clone(), ContextValue::Array(arr));
+                    this.record_answer(&answer_key, &key);
                     return Ok(Some(defaults.clone()));
                 }
                 if info.optional {
@@ -1069,7 +1096,8 @@ impl UserData for Context {
             if let Some(value) = handle_response_array(response)? {
                 let arr: Vec<ContextValue> =
                     value.iter().cloned().map(ContextValue::String).collect();
-                this.data.insert(key, ContextValue::Array(arr));
+                this.data.insert(key.clone(), ContextValue::Array(arr));
+                this.record_answer(&answer_key, &key);
                 Ok(Some(value))
             } else {
                 Ok(None)
@@ -1091,14 +1119,16 @@ impl UserData for Context {
             let answer_key = get_answer_key(&opts, &key);
             if let Some(ContextValue::String(v)) = this.data.get(&answer_key).cloned() {
                 if answer_key != key {
-                    this.data.insert(key, ContextValue::String(v.clone()));
+                    this.data.insert(key.clone(), ContextValue::String(v.clone()));
                 }
+                this.record_answer(&answer_key, &key);
                 return Ok(Some(v));
             }
 
             if this.use_default(&key) {
                 if let Some(ref default) = info.default {
-                    this.data.insert(key, ContextValue::String(default.clone()));
+                    this.data.insert(key.clone(), ContextValue::String(default.clone()));
+                    this.record_answer(&answer_key, &key);
                     return Ok(Some(default.clone()));
                 }
                 if info.optional {
@@ -1112,7 +1142,8 @@ impl UserData for Context {
 
             let response = this.send_prompt(ScriptMessage::PromptForEditor(info))?;
             if let Some(value) = handle_response_string(response)? {
-                this.data.insert(key, ContextValue::String(value.clone()));
+                this.data.insert(key.clone(), ContextValue::String(value.clone()));
+                this.record_answer(&answer_key, &key);
                 Ok(Some(value))
             } else {
                 Ok(None)
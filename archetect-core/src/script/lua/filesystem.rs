@@ -0,0 +1,292 @@
+//! `archetect.fs` — read-only inspection of the trees a render touches, for
+//! additive archetypes that have to find out what they've been dropped into:
+//! whether there's a `Cargo.toml`, what the crate is called, which modules
+//! already exist, where the enclosing project starts.
+//!
+//! Paths resolve as `file.*` resolves them: against the archetype root,
+//! unless `opts.within` names another `Location`, and never absolute, `..`,
+//! or `~`. Symlinks are reported, not followed, and a path that leads out
+//! of its root through one is refused. In the sandbox, a root beyond the
+//! archetype and the destination — `Location.Cwd` — needs the `filesystem`
+//! capability. Nothing here writes, so nothing crosses the IO channel.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use mlua::{Error as LuaError, Lua, Result as LuaResult, Table};
+
+use super::modules::{glob_destination, location_root, resolve_file_path, restrict_path};
+use super::sandbox::{self, real_path, FILESYSTEM};
+use crate::Archetect;
+
+/// The roots paths resolve against.
+#[derive(Clone)]
+struct Roots {
+    archetect: Archetect,
+    archetype: Utf8PathBuf,
+    destination: Utf8PathBuf,
+}
+
+impl Roots {
+    /// `path`, resolved against its root. The directories along it may not
+    /// lead out of the root through a symlink; the last component, which
+    /// is reported rather than followed, may be one.
+    fn resolve(&self, call: &str, path: &str, opts: &Option<Table>) -> LuaResult<Utf8PathBuf> {
+        let resolved = resolve_file_path(&self.archetype, &self.destination, path, opts)?;
+        let root = self.root(call, opts)?;
+        let contained = match (resolved.parent(), resolved.file_name()) {
+            _ if resolved.components().eq(root.components()) => real(&root),
+            (Some(parent), Some(name)) => real(parent).join(name),
+            _ => real(&resolved),
+        };
+        self.contain(call, path, &root, &contained)?;
+        Ok(resolved)
+    }
+
+    /// Like `resolve`, for a directory that is read through: a symlink at
+    /// the end of the path may not lead out of the root either.
+    fn resolve_directory(&self, call: &str, path: &str, opts: &Option<Table>) -> LuaResult<Utf8PathBuf> {
+        let resolved = self.resolve(call, path, opts)?;
+        let root = self.root(call, opts)?;
+        self.contain(call, path, &root, &real(&resolved))?;
+        Ok(resolved)
+    }
+
+    /// The root `opts.within` names, if the session may read there.
+    fn root(&self, call: &str, opts: &Option<Table>) -> LuaResult<Utf8PathBuf> {
+        let root = location_root(&self.archetype, &self.destination, opts)?;
+        sandbox::check_read(&self.archetect, &self.archetype, &self.destination, call, &root)?;
+        Ok(root)
+    }
+
+    /// Refuse `path`, which reaches `reached`, unless that is beneath `root`.
+    fn contain(&self, call: &str, path: &str, root: &Utf8Path, reached: &std::path::Path) -> LuaResult<()> {
+        if reached.starts_with(real(root)) {
+            return Ok(());
+        }
+        Err(LuaError::RuntimeError(format!(
+            "{}: {} leads outside its root through a symlink",
+            call, path
+        )))
+    }
+}
+
+/// `path` made absolute, with every symlink along it resolved.
+fn real(path: &Utf8Path) -> std::path::PathBuf {
+    real_path(&std::path::absolute(path).unwrap_or_else(|_| path.into()))
+}
+
+pub(super) fn create_fs_module(
+    lua: &Lua,
+    archetect: &Archetect,
+    archetype_root: &Utf8Path,
+    destination: &Utf8Path,
+) -> LuaResult<Table> {
+    let module = lua.create_table()?;
+    let roots = Roots {
+        archetect: archetect.clone(),
+        archetype: archetype_root.to_owned(),
+        destination: destination.to_owned(),
+    };
+
+    // fs.glob(pattern, opts?) — root-relative paths matching `pattern`,
+    // sorted. `*` stops at `/`; `**` crosses it.
+    {
+        let roots = roots.clone();
+        module.set(
+            "glob",
+            lua.create_function(
+                move |_, (pattern, opts): (String, Option<Table>)| -> LuaResult<Vec<String>> {
+                    restrict_path(&pattern)?;
+                    if pattern.starts_with('/') {
+                        return Err(LuaError::RuntimeError(format!(
+                            "fs.glob: absolute patterns not allowed: {}",
+                            pattern
+                        )));
+                    }
+                    let matcher = globset::GlobBuilder::new(&pattern)
+                        .literal_separator(true)
+                        .build()
+                        .map_err(|e| LuaError::RuntimeError(format!("fs.glob: {}", e)))?
+                        .compile_matcher();
+                    let root = roots.root("fs.glob", &opts)?;
+                    let mut matches = Vec::new();
+                    glob_destination(&root, &root, &matcher, &mut matches)
+                        .map_err(|e| LuaError::RuntimeError(format!("fs.glob: {}", e)))?;
+                    matches.sort();
+                    Ok(matches)
+                },
+            )?,
+        )?;
+    }
+
+    // fs.list_dir(path?, opts?) — names of a directory's entries, sorted. A
+    // directory that doesn't exist lists as empty.
+    {
+        let roots = roots.clone();
+        module.set(
+            "list_dir",
+            lua.create_function(
+                move |_, (path, opts): (Option<String>, Option<Table>)| -> LuaResult<Vec<String>> {
+                    let path = path.unwrap_or_else(|| ".".to_string());
+                    let directory = roots.resolve_directory("fs.list_dir", &path, &opts)?;
+                    if !directory.exists() {
+                        return Ok(Vec::new());
+                    }
+                    let mut names = read_dir(&directory)
+                        .map_err(|e| LuaError::RuntimeError(format!("fs.list_dir: {}: {}", path, e)))?
+                        .into_iter()
+                        .map(|(name, _)| name)
+                        .collect::<Vec<_>>();
+                    names.sort();
+                    Ok(names)
+                },
+            )?,
+        )?;
+    }
+
+    // fs.stat(path, opts?) — `{ size, mtime, is_dir, is_file, is_symlink,
+    // mode }`, or nil when nothing is there. `mode` is nil off Unix.
+    {
+        let roots = roots.clone();
+        module.set(
+            "stat",
+            lua.create_function(
+                move |lua, (path, opts): (String, Option<Table>)| -> LuaResult<Option<Table>> {
+                    let resolved = roots.resolve("fs.stat", &path, &opts)?;
+                    let metadata = match resolved.symlink_metadata() {
+                        Ok(metadata) => metadata,
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                        Err(e) => return Err(LuaError::RuntimeError(format!("fs.stat: {}: {}", path, e))),
+                    };
+                    let stat = lua.create_table()?;
+                    stat.set("size", metadata.len())?;
+                    let mtime = metadata
+                        .modified()
+                        .ok()
+                        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map(|since| since.as_secs());
+                    stat.set("mtime", mtime)?;
+                    stat.set("is_dir", metadata.is_dir())?;
+                    stat.set("is_file", metadata.is_file())?;
+                    stat.set("is_symlink", metadata.file_type().is_symlink())?;
+                    stat.set("mode", permissions(&metadata))?;
+                    Ok(Some(stat))
+                },
+            )?,
+        )?;
+    }
+
+    // fs.walk(path?, opts?) — every entry beneath a directory, as
+    // `{ path, is_dir, depth }`, parents before their children and siblings
+    // by name. `opts.max_depth = 1` stops at the directory's own entries.
+    {
+        let roots = roots.clone();
+        module.set(
+            "walk",
+            lua.create_function(
+                move |lua, (path, opts): (Option<String>, Option<Table>)| -> LuaResult<Table> {
+                    let path = path.unwrap_or_else(|| ".".to_string());
+                    let directory = roots.resolve_directory("fs.walk", &path, &opts)?;
+                    let root = roots.root("fs.walk", &opts)?;
+                    let max_depth = match &opts {
+                        Some(opts) => opts.get::<Option<usize>>("max_depth")?,
+                        None => None,
+                    };
+                    let mut entries = Vec::new();
+                    walk(&root, &directory, 1, max_depth, &mut entries)
+                        .map_err(|e| LuaError::RuntimeError(format!("fs.walk: {}: {}", path, e)))?;
+                    let table = lua.create_table()?;
+                    for (relative, is_dir, depth) in entries {
+                        let entry = lua.create_table()?;
+                        entry.set("path", relative)?;
+                        entry.set("is_dir", is_dir)?;
+                        entry.set("depth", depth)?;
+                        table.push(entry)?;
+                    }
+                    Ok(table)
+                },
+            )?,
+        )?;
+    }
+
+    // fs.find_up(name, opts?) — the nearest directory, from the root
+    // upwards, that holds `name`, as an absolute path; nil if none does. In
+    // the sandbox, the search stays within the root unless the session
+    // names `filesystem` among its grants.
+    {
+        let roots = roots.clone();
+        let climbs = !archetect.configuration().sandboxed() || archetect.grants_explicitly(FILESYSTEM);
+        module.set(
+            "find_up",
+            lua.create_function(
+                move |_, (name, opts): (String, Option<Table>)| -> LuaResult<Option<String>> {
+                    // Resolved for its checks; the search itself starts at the root.
+                    roots.resolve("fs.find_up", &name, &opts)?;
+                    let root = std::path::absolute(roots.root("fs.find_up", &opts)?)
+                        .map_err(|e| LuaError::RuntimeError(format!("fs.find_up: {}", e)))?;
+                    let mut directory = Some(root.as_path());
+                    while let Some(candidate) = directory {
+                        if candidate.join(&name).symlink_metadata().is_ok() {
+                            return Ok(Some(candidate.to_string_lossy().to_string()));
+                        }
+                        if !climbs {
+                            break;
+                        }
+                        directory = candidate.parent();
+                    }
+                    Ok(None)
+                },
+            )?,
+        )?;
+    }
+
+    Ok(module)
+}
+
+/// A directory's entries: their names, and whether each is a directory. A
+/// symlink to one is not.
+fn read_dir(directory: &Utf8Path) -> std::io::Result<Vec<(String, bool)>> {
+    directory
+        .read_dir_utf8()?
+        .map(|entry| {
+            let entry = entry?;
+            Ok((entry.file_name().to_string(), entry.file_type()?.is_dir()))
+        })
+        .collect()
+}
+
+/// Collect what's beneath `directory`, relative to `root`, down to
+/// `max_depth` levels. Symlinked directories are listed but not followed.
+fn walk(
+    root: &Utf8Path,
+    directory: &Utf8Path,
+    depth: usize,
+    max_depth: Option<usize>,
+    entries: &mut Vec<(String, bool, usize)>,
+) -> std::io::Result<()> {
+    if !directory.is_dir() || max_depth.is_some_and(|max| depth > max) {
+        return Ok(());
+    }
+    let mut children = read_dir(directory)?;
+    children.sort();
+    for (name, is_dir) in children {
+        let path = directory.join(&name);
+        let relative = path.strip_prefix(root).unwrap_or(&path).as_str().replace('\\', "/");
+        let relative = relative.strip_prefix("./").unwrap_or(&relative).to_string();
+        entries.push((relative, is_dir, depth));
+        if is_dir {
+            walk(root, &path, depth + 1, max_depth, entries)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn permissions(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn permissions(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
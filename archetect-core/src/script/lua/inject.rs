@@ -0,0 +1,211 @@
+//! `file.inject`: insert a snippet into a file that already exists, once.
+//!
+//! Injection works on whole lines. An anchor names the first line the
+//! snippet goes after or before; `Start` and `End` need no anchor. Running
+//! the same injection twice changes nothing the second time: unless told
+//! otherwise, the snippet's own lines, found as whole lines in a row, are
+//! what mark it as already present.
+
+use regex::Regex;
+
+/// How an anchor or presence check is written: a literal piece of text, or
+/// a regex. Literals are the default because the usual anchors —
+/// `[dependencies]`, `// routes:` — are full of regex metacharacters.
+#[derive(Debug)]
+pub(crate) enum Pattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+pub(crate) enum Anchor {
+    After(Pattern),
+    Before(Pattern),
+    Start,
+    End,
+}
+
+#[derive(Debug)]
+pub(crate) struct Injection {
+    pub content: String,
+    pub anchor: Anchor,
+    /// Skip the injection when this is found anywhere in the file; a regex
+    /// is multi-line, so `^` and `$` match at each line. Defaults to the
+    /// snippet itself, as whole lines.
+    pub unless_present: Option<Pattern>,
+}
+
+impl Pattern {
+    fn matches_line(&self, line: &str) -> bool {
+        let line = line.trim_end_matches(['\n', '\r']);
+        match self {
+            Pattern::Literal(text) => line.contains(text.as_str()),
+            Pattern::Regex(regex) => regex.is_match(line),
+        }
+    }
+
+    fn found_in(&self, text: &str) -> bool {
+        match self {
+            Pattern::Literal(literal) => text.contains(literal.as_str()),
+            Pattern::Regex(regex) => regex.is_match(text),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Pattern::Literal(text) => format!("'{}'", text),
+            Pattern::Regex(regex) => format!("regex '{}'", regex.as_str()),
+        }
+    }
+}
+
+impl Injection {
+    /// The file's new contents, or `None` if the snippet is already there.
+    /// Fails when the anchor matches no line.
+    pub fn apply(&self, existing: &str) -> Result<Option<String>, String> {
+        let present = match &self.unless_present {
+            Some(pattern) => pattern.found_in(existing),
+            None => self.snippet_present(existing),
+        };
+        if present {
+            return Ok(None);
+        }
+
+        let mut snippet = self.content.clone();
+        if !snippet.ends_with('\n') {
+            snippet.push('\n');
+        }
+
+        let (pattern, after) = match &self.anchor {
+            Anchor::Start => return Ok(Some(snippet + existing)),
+            Anchor::End => {
+                let mut updated = existing.to_string();
+                if !updated.is_empty() && !updated.ends_with('\n') {
+                    updated.push('\n');
+                }
+                return Ok(Some(updated + &snippet));
+            }
+            Anchor::After(pattern) => (pattern, true),
+            Anchor::Before(pattern) => (pattern, false),
+        };
+
+        let mut updated = String::with_capacity(existing.len() + snippet.len());
+        let mut inserted = false;
+        for line in existing.split_inclusive('\n') {
+            if !inserted && pattern.matches_line(line) {
+                inserted = true;
+                if after {
+                    updated.push_str(line);
+                    if !line.ends_with('\n') {
+                        updated.push('\n');
+                    }
+                    updated.push_str(&snippet);
+                    continue;
+                }
+                updated.push_str(&snippet);
+            }
+            updated.push_str(line);
+        }
+        if !inserted {
+            return Err(format!(
+                "anchor not found: no line matches {} {}",
+                if after { "after =" } else { "before =" },
+                pattern.describe()
+            ));
+        }
+        Ok(Some(updated))
+    }
+
+    /// Whether the snippet's lines already appear one after another in
+    /// `existing`, each a whole line. Trailing whitespace is ignored, and so
+    /// are blank lines around the snippet.
+    fn snippet_present(&self, existing: &str) -> bool {
+        let snippet = self.content.lines().map(str::trim_end).collect::<Vec<_>>();
+        let Some(first) = snippet.iter().position(|line| !line.is_empty()) else {
+            return true;
+        };
+        let last = snippet.iter().rposition(|line| !line.is_empty()).unwrap_or(first);
+        let snippet = &snippet[first..=last];
+        let lines = existing.lines().map(str::trim_end).collect::<Vec<_>>();
+        lines.windows(snippet.len()).any(|window| window == snippet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn injection(content: &str, anchor: Anchor) -> Injection {
+        Injection {
+            content: content.to_string(),
+            anchor,
+            unless_present: None,
+        }
+    }
+
+    #[test]
+    fn test_inject_after_literal_anchor() {
+        let existing = "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1\"\n";
+        let injection = injection("tokio = \"1\"", Anchor::After(Pattern::Literal("[dependencies]".into())));
+        assert_eq!(
+            injection.apply(existing).unwrap().unwrap(),
+            "[package]\nname = \"demo\"\n\n[dependencies]\ntokio = \"1\"\nserde = \"1\"\n"
+        );
+    }
+
+    #[test]
+    fn test_inject_before_regex_anchor() {
+        let existing = "mod a;\nmod b;\n\nfn main() {}\n";
+        let injection = injection("mod c;", Anchor::Before(Pattern::Regex(Regex::new(r"^fn main").unwrap())));
+        assert_eq!(injection.apply(existing).unwrap().unwrap(), "mod a;\nmod b;\n\nmod c;\nfn main() {}\n");
+    }
+
+    #[test]
+    fn test_inject_at_end_adds_missing_newline() {
+        let injection = injection("mod c;\n", Anchor::End);
+        assert_eq!(injection.apply("mod a;").unwrap().unwrap(), "mod a;\nmod c;\n");
+    }
+
+    #[test]
+    fn test_inject_is_idempotent() {
+        let injection = injection("mod c;", Anchor::Start);
+        let once = injection.apply("mod a;\n").unwrap().unwrap();
+        assert_eq!(once, "mod c;\nmod a;\n");
+        assert_eq!(injection.apply(&once).unwrap(), None);
+    }
+
+    #[test]
+    fn test_snippet_must_be_present_as_whole_lines() {
+        let injection = injection("mod api;\nmod web;\n", Anchor::End);
+        assert_eq!(
+            injection.apply("mod api_v2;\nmod web;\n").unwrap().unwrap(),
+            "mod api_v2;\nmod web;\nmod api;\nmod web;\n"
+        );
+        assert_eq!(injection.apply("mod api;\nmod other;\nmod web;\n").unwrap().unwrap().lines().count(), 5);
+        assert_eq!(injection.apply("// mods\nmod api;  \r\nmod web;\t\n").unwrap(), None);
+    }
+
+    #[test]
+    fn test_unless_present_regex_is_multi_line() {
+        let mut injection = injection("pub mod orders;", Anchor::End);
+        injection.unless_present = Some(Pattern::Regex(
+            regex::RegexBuilder::new(r"^pub mod orders").multi_line(true).build().unwrap(),
+        ));
+        assert_eq!(injection.apply("pub mod users;\npub mod orders_v2;\n").unwrap(), None);
+        assert!(injection.apply("pub mod users;\n// pub mod orders;\n").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_unless_present_overrides_the_snippet_check() {
+        let mut injection = injection("router.route(\"/orders\", orders);", Anchor::End);
+        injection.unless_present = Some(Pattern::Regex(Regex::new(r#""/orders""#).unwrap()));
+        assert_eq!(injection.apply("router.route(\"/orders\", legacy);\n").unwrap(), None);
+    }
+
+    #[test]
+    fn test_missing_anchor_is_an_error() {
+        let injection = injection("x", Anchor::After(Pattern::Literal("// routes".into())));
+        let err = injection.apply("fn main() {}\n").unwrap_err();
+        assert!(err.contains("'// routes'"), "{}", err);
+    }
+}
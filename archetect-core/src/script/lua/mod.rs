@@ -6,13 +6,22 @@ use archetect_api::ContextValue;
 
 use crate::archetype::archetype::Archetype;
 use crate::archetype::render_context::RenderContext;
+use crate::debugger;
 use crate::errors::ArchetypeError;
+use crate::limits;
 use crate::Archetect;
 
 pub(crate) mod cases;
 mod context;
+mod filesystem;
+mod inject;
+mod structured;
 mod modules;
+mod repl;
 mod require_modules;
+mod sandbox;
+
+pub use repl::{Evaluation, Repl};
 
 pub(crate) fn execute(
     archetype: &Archetype,
@@ -62,6 +71,12 @@ pub(crate) fn execute(
                 return Err(ArchetypeError::PromptAborted);
             }
 
+            // A breached limit is the host's to report: the CLI prints it,
+            // and the server answers with RESOURCE_EXHAUSTED.
+            if let Some(limit) = limits::exceeded(&lua, &archetect.limits(), &err) {
+                return Err(ArchetypeError::ResourceLimitExceeded(limit));
+            }
+
             let _ = archetect.request(archetect_api::ScriptMessage::LogError(format!("{}", err)));
             let _ = archetect.request(archetect_api::ScriptMessage::CompleteError(format!("{}", err)));
             Err(ArchetypeError::ScriptAbortError)
@@ -69,6 +84,11 @@ pub(crate) fn execute(
     }
 }
 
+/// The data of `userdata`, if it's a `Context`, for the debugger to show.
+pub(crate) fn context_map(userdata: &mlua::AnyUserData) -> Option<archetect_api::ContextMap> {
+    userdata.borrow::<context::Context>().ok().map(|context| context.to_context_map())
+}
+
 /// Check if a Lua error is a clean exit() call (not a real error).
 fn is_clean_exit(err: &mlua::Error) -> bool {
     match err {
@@ -94,12 +114,18 @@ fn create_lua(
 ) -> Result<Lua, ArchetypeError> {
     let lua = Lua::new();
 
+    // Before anything else runs, so the script and its templates only ever
+    // see the pinned clock and seeded randomness.
+    if let Some(reproducible) = archetect.reproducible() {
+        reproducible.install(&lua).map_err(|_| ArchetypeError::ScriptAbortError)?;
+    }
+
     // Register pre-loaded globals
     modules::register_all(&lua, archetype, archetect, render_context)
         .map_err(|_| ArchetypeError::ScriptAbortError)?;
 
     // Register require-based modules
-    require_modules::register_require_modules(&lua, archetect, render_context)
+    require_modules::register_require_modules(&lua, archetype, archetect, render_context)
         .map_err(|_| ArchetypeError::ScriptAbortError)?;
 
     // Add archetype's modules directory to Lua package.path
@@ -114,5 +140,19 @@ fn create_lua(
         .map_err(|_| ArchetypeError::ScriptAbortError)?;
     }
 
+    if archetect.configuration().sandboxed() {
+        sandbox::install(&lua, archetect, archetype.root(), render_context.destination())
+            .map_err(|_| ArchetypeError::ScriptAbortError)?;
+    }
+
+    // Last, so the instruction count and heap limit are the script's own. A
+    // debugged render is the CLI's, with no limits; its hook is the
+    // debugger's.
+    match archetect.debugger() {
+        Some(debugger) => debugger::attach(&lua, debugger),
+        None => archetect.limits().install(&lua, archetect.deadline()),
+    }
+    .map_err(|_| ArchetypeError::ScriptAbortError)?;
+
     Ok(lua)
 }
@@ -8,6 +8,10 @@ use crate::archetype::render_context::RenderContext;
 use crate::Archetect;
 
 use super::context::Context;
+use super::inject::{Anchor, Injection, Pattern};
+use super::sandbox;
+use super::structured::{self, Document, Format};
+use crate::templating::atl::entry_filter::EntryFilter;
 use crate::templating::atl::render::{self as lua_render, TemplateCache};
 
 pub fn register_all(
@@ -86,7 +90,9 @@ pub fn register_all(
             strict: matches!(templating.undefined(), UndefinedMode::Strict),
             trim_blocks: templating.trim_blocks(),
             lstrip_blocks: templating.lstrip_blocks(),
+            autoescape: None,
         })
+        .with_autoescape(templating.autoescape().clone())
         .with_includes_dirs(includes_dirs);
     let cache = Rc::new(RefCell::new(cache));
     register_lua_directory_module(lua, archetype, archetect, render_context, &filters, cache.clone(), &staged_libraries)?;
@@ -535,6 +541,10 @@ fn register_catalog_module(
                         crate::errors::ArchetectError::ArchetypeError(
                             crate::errors::ArchetypeError::PromptAborted,
                         ) => LuaError::RuntimeError("Prompt aborted".to_string()),
+                        // A component's breach ends the whole render.
+                        crate::errors::ArchetectError::ArchetypeError(
+                            crate::errors::ArchetypeError::ResourceLimitExceeded(limit),
+                        ) => LuaError::external(limit),
                         other => LuaError::RuntimeError(format!("Catalog error: {}", other)),
                     })?;
 
@@ -742,6 +752,13 @@ fn register_lua_directory_module(
                 }
 
                 let overwrite_policy = extract_overwrite_policy(&opts);
+                let filter = EntryFilter::new(
+                    &source,
+                    &destination,
+                    &extract_globs(&opts, "exclude")?,
+                    &extract_globs(&opts, "include")?,
+                )
+                .map_err(|e| LuaError::RuntimeError(format!("Render error: {}", e)))?;
                 let mut cache = cache.borrow_mut();
 
                 lua_render::lua_render_directory(
@@ -752,6 +769,7 @@ fn register_lua_directory_module(
                     source,
                     destination,
                     overwrite_policy,
+                    &filter,
                     &mut cache,
                 )
                 .map_err(|e| LuaError::RuntimeError(format!("Render error: {}", e)))
@@ -804,10 +822,12 @@ fn register_lua_file_module(
     {
         let archetype_root = archetype_root.clone();
         let destination = destination.clone();
+        let arc = archetect.clone();
         file_table.set(
             "exists",
             lua.create_function(move |_, (path, opts): (String, Option<Table>)| -> LuaResult<bool> {
                 let resolved = resolve_file_path(&archetype_root, &destination, &path, &opts)?;
+                sandbox::check_read(&arc, &archetype_root, &destination, "file.exists", &resolved)?;
                 Ok(resolved.exists())
             })?,
         )?;
@@ -817,10 +837,12 @@ fn register_lua_file_module(
     {
         let archetype_root = archetype_root.clone();
         let destination = destination.clone();
+        let arc = archetect.clone();
         file_table.set(
             "read",
             lua.create_function(move |_, (path, opts): (String, Option<Table>)| -> LuaResult<String> {
                 let resolved = resolve_file_path(&archetype_root, &destination, &path, &opts)?;
+                sandbox::check_read(&arc, &archetype_root, &destination, "file.read", &resolved)?;
                 if !resolved.is_file() {
                     return Err(LuaError::RuntimeError(format!(
                         "file.read: not a regular file: {}",
@@ -834,6 +856,167 @@ fn register_lua_file_module(
         )?;
     }
 
+    // file.write / append / delete / move / copy
+    //
+    // For archetypes that add to a tree that already exists. Paths are
+    // relative to the render destination and may not leave it. Every change
+    // crosses the IO channel, as a rendered file does — so dry-run, gRPC and
+    // MCP clients all see it — and is reported as an artifact on completion.
+    //
+    //   file.write("src/entities/order.rs", source, { if_exists = Existing.Overwrite })
+    //   file.append("src/entities/mod.rs", "pub mod order;\n")
+    //   file.move("src/entity.rs", "src/entities/entity.rs")
+
+    // file.write(path, contents, opts?)
+    {
+        let arc = archetect.clone();
+        let destination = destination.clone();
+        file_table.set(
+            "write",
+            lua.create_function(
+                move |_, (path, contents, opts): (String, mlua::String, Option<Table>)| -> LuaResult<()> {
+                    let target = destination_path(&destination, "file.write", &path)?;
+                    let overwrite_policy = extract_overwrite_policy(&opts);
+                    let mode = extract_mode(&opts)?;
+                    // As with file.render, the parent directory is created on the way.
+                    if let Some(parent) = target.parent() {
+                        lua_render::send_write_directory(&arc, parent)
+                            .map_err(|e| LuaError::RuntimeError(format!("file.write: {}", e)))?;
+                    }
+                    lua_render::send_write_file(&arc, &target, contents.as_bytes().to_vec(), overwrite_policy, mode)
+                        .map_err(|e| LuaError::RuntimeError(format!("file.write: {}", e)))?;
+                    Ok(())
+                },
+            )?,
+        )?;
+    }
+
+    // file.append(path, contents)
+    {
+        let arc = archetect.clone();
+        let destination = destination.clone();
+        file_table.set(
+            "append",
+            lua.create_function(move |_, (path, contents): (String, mlua::String)| -> LuaResult<()> {
+                let target = destination_path(&destination, "file.append", &path)?;
+                let operation = archetect_api::FileOperation::Append {
+                    path: target.to_string(),
+                    contents: contents.as_bytes().to_vec(),
+                };
+                lua_render::send_file_operation(&arc, operation)
+                    .map_err(|e| LuaError::RuntimeError(format!("file.append: {}", e)))?;
+                Ok(())
+            })?,
+        )?;
+    }
+
+    // file.delete(path)
+    {
+        let arc = archetect.clone();
+        let destination = destination.clone();
+        file_table.set(
+            "delete",
+            lua.create_function(move |_, path: String| -> LuaResult<()> {
+                let target = destination_path(&destination, "file.delete", &path)?;
+                if target == destination {
+                    return Err(LuaError::RuntimeError(
+                        "file.delete: refusing to delete the destination itself".to_string(),
+                    ));
+                }
+                let operation = archetect_api::FileOperation::Delete { path: target.to_string() };
+                lua_render::send_file_operation(&arc, operation)
+                    .map_err(|e| LuaError::RuntimeError(format!("file.delete: {}", e)))?;
+                Ok(())
+            })?,
+        )?;
+    }
+
+    // file.move(source, destination, opts?) and file.copy(source, destination, opts?)
+    for (name, moves) in [("move", true), ("copy", false)] {
+        let arc = archetect.clone();
+        let destination = destination.clone();
+        let function = format!("file.{}", name);
+        file_table.set(
+            name,
+            lua.create_function(move |_, (from, to, opts): (String, String, Option<Table>)| -> LuaResult<()> {
+                let source = destination_path(&destination, &function, &from)?;
+                let target = destination_path(&destination, &function, &to)?;
+                let existing_file_policy = extract_overwrite_policy(&opts).into();
+                let operation = if moves {
+                    archetect_api::FileOperation::Move {
+                        source: source.to_string(),
+                        destination: target.to_string(),
+                        existing_file_policy,
+                    }
+                } else {
+                    archetect_api::FileOperation::Copy {
+                        source: source.to_string(),
+                        destination: target.to_string(),
+                        existing_file_policy,
+                    }
+                };
+                lua_render::send_file_operation(&arc, operation)
+                    .map_err(|e| LuaError::RuntimeError(format!("{}: {}", function, e)))?;
+                Ok(())
+            })?,
+        )?;
+    }
+
+    // file.list(path?) — names of the entries in a destination directory,
+    // sorted. A directory that doesn't exist (yet) lists as empty. Like
+    // file.read, it sees what this render has written so far — staged or,
+    // in a dry run, only imagined — and asks the client, which holds the
+    // destination.
+    {
+        let destination = destination.clone();
+        let arc = archetect.clone();
+        file_table.set(
+            "list",
+            lua.create_function(move |_, path: Option<String>| -> LuaResult<Vec<String>> {
+                let directory = match path {
+                    Some(path) => destination_path(&destination, "file.list", &path)?,
+                    None => destination.clone(),
+                };
+                let listed = arc
+                    .list_destination(&directory)
+                    .map_err(|e| LuaError::RuntimeError(format!("file.list: {}: {}", directory, e)))?;
+                Ok(listed
+                    .iter()
+                    .filter(|path| path.parent() == Some(directory.as_path()))
+                    .filter_map(|path| path.file_name().map(str::to_string))
+                    .collect())
+            })?,
+        )?;
+    }
+
+    // file.glob(pattern) — destination-relative paths matching `pattern`,
+    // sorted. `*` stops at `/`; `**` crosses it. Sees what file.list does.
+    {
+        let destination = destination.clone();
+        let arc = archetect.clone();
+        file_table.set(
+            "glob",
+            lua.create_function(move |_, pattern: String| -> LuaResult<Vec<String>> {
+                let matcher = globset::GlobBuilder::new(&pattern)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| LuaError::RuntimeError(format!("file.glob: {}", e)))?
+                    .compile_matcher();
+                let listed = arc
+                    .list_destination(&destination)
+                    .map_err(|e| LuaError::RuntimeError(format!("file.glob: {}", e)))?;
+                let mut matches = listed
+                    .iter()
+                    .filter_map(|path| path.strip_prefix(&destination).ok())
+                    .map(|relative| relative.as_str().replace('\\', "/"))
+                    .filter(|relative| matcher.is_match(relative))
+                    .collect::<Vec<_>>();
+                matches.sort();
+                Ok(matches)
+            })?,
+        )?;
+    }
+
     // file.render(source, context, opts?)
     //
     // Symmetric with template.render: returns the rendered string by
@@ -908,6 +1091,7 @@ fn register_lua_file_module(
                     if let Some(dest_rel) = dest_opt {
                         let destination = ctx_default_dest.join(&dest_rel);
                         let overwrite_policy = extract_overwrite_policy(&opts);
+                        let mode = extract_mode(&opts)?;
                         crate::templating::atl::render::lua_render_file(
                             lua,
                             &arc,
@@ -916,6 +1100,7 @@ fn register_lua_file_module(
                             &source_path,
                             &destination,
                             overwrite_policy,
+                            mode,
                             &mut cache,
                             extra_include_dir.as_deref(),
                         )
@@ -930,6 +1115,7 @@ fn register_lua_file_module(
                             &filters,
                             &mut cache,
                             extra_include_dir.as_deref(),
+                            None,
                         )
                         .map_err(|e| LuaError::RuntimeError(format!("Render error: {}", e)))?;
                         Ok(Some(rendered))
@@ -939,6 +1125,76 @@ fn register_lua_file_module(
         )?;
     }
 
+    // file.inject(path, content, opts?)
+    //
+    // Insert a snippet into a destination file once — a `mod` line, a route,
+    // a dependency. Returns whether the file changed.
+    //
+    //   file.inject("Cargo.toml", 'tokio = "1"', { after = "[dependencies]" })
+    //   file.inject("src/main.rs", "mod orders;", { before = { regex = "^fn main" } })
+    {
+        let arc = archetect.clone();
+        let destination = destination.clone();
+        file_table.set(
+            "inject",
+            lua.create_function(
+                move |_, (path, content, opts): (String, String, Option<Table>)| -> LuaResult<bool> {
+                    let target = destination_path(&destination, "file.inject", &path)?;
+                    let injection = extract_injection(content, &opts)?;
+                    let existing = arc
+                        .read_destination(&target)
+                        .map_err(|e| LuaError::RuntimeError(format!("file.inject: {}: {}", path, e)))?;
+                    let Some(updated) = injection
+                        .apply(&existing)
+                        .map_err(|e| LuaError::RuntimeError(format!("file.inject: {}: {}", path, e)))?
+                    else {
+                        return Ok(false);
+                    };
+                    rewrite_destination_file(&arc, "inject", &target, &existing, updated)?;
+                    Ok(true)
+                },
+            )?,
+        )?;
+    }
+
+    // file.edit_structured(path, function(doc) ... end, opts?)
+    //
+    // Edit a TOML, JSON, or YAML file in the destination without disturbing
+    // its comments or layout. The format comes from the extension unless
+    // `opts.format` names it. Returns whether the file changed.
+    //
+    //   file.edit_structured("Cargo.toml", function(doc)
+    //       doc:set("dependencies.tokio", { version = "1", features = { "full" } })
+    //   end)
+    {
+        let arc = archetect.clone();
+        let destination = destination.clone();
+        file_table.set(
+            "edit_structured",
+            lua.create_function(
+                move |lua, (path, edit, opts): (String, mlua::Function, Option<Table>)| -> LuaResult<bool> {
+                    let target = destination_path(&destination, "file.edit_structured", &path)?;
+                    let format = extract_structured_format(&path, &opts)?;
+                    let existing = arc
+                        .read_destination(&target)
+                        .map_err(|e| LuaError::RuntimeError(format!("file.edit_structured: {}: {}", path, e)))?;
+                    let document = Document::parse(format, &existing).map_err(|e| {
+                        LuaError::RuntimeError(format!("file.edit_structured: {}: {}", path, e))
+                    })?;
+
+                    let handle = lua.create_userdata(StructuredDocument(document))?;
+                    edit.call::<()>(handle.clone())?;
+                    let updated = handle.take::<StructuredDocument>()?.0.render();
+                    if updated == existing {
+                        return Ok(false);
+                    }
+                    rewrite_destination_file(&arc, "edit", &target, &existing, updated)?;
+                    Ok(true)
+                },
+            )?,
+        )?;
+    }
+
     lua.globals().set("file", file_table)?;
     Ok(())
 }
@@ -989,7 +1245,7 @@ fn register_location_constants(lua: &Lua) -> LuaResult<()> {
 /// - `Location.Cwd`: path is relative to the actual process cwd,
 ///   regardless of `-d`. Escape hatch for rare cases where the script
 ///   genuinely needs the caller's shell position.
-fn resolve_file_path(
+pub(super) fn resolve_file_path(
     archetype_root: &camino::Utf8Path,
     destination: &camino::Utf8Path,
     path: &str,
@@ -1002,7 +1258,15 @@ fn resolve_file_path(
             path
         )));
     }
+    Ok(location_root(archetype_root, destination, opts)?.join(path))
+}
 
+/// The root `opts.within` names — the archetype's, unless it says otherwise.
+pub(super) fn location_root(
+    archetype_root: &camino::Utf8Path,
+    destination: &camino::Utf8Path,
+    opts: &Option<Table>,
+) -> LuaResult<camino::Utf8PathBuf> {
     let location = opts
         .as_ref()
         .and_then(|o| {
@@ -1016,7 +1280,7 @@ fn resolve_file_path(
         })
         .unwrap_or(FileLocation::Archetype);
 
-    let root: camino::Utf8PathBuf = match location {
+    Ok(match location {
         FileLocation::Archetype => archetype_root.to_owned(),
         FileLocation::Destination => destination.to_owned(),
         FileLocation::Cwd => {
@@ -1027,9 +1291,7 @@ fn resolve_file_path(
                 LuaError::RuntimeError(format!("file.*: cwd is not valid UTF-8: {:?}", bad))
             })?
         }
-    };
-
-    Ok(root.join(path))
+    })
 }
 
 // ── Lua-native template module ──────────────────────────────────────
@@ -1097,40 +1359,16 @@ fn register_lua_template_module(
                 let overwrite_policy = extract_overwrite_policy(&opts);
                 // Send the same WriteDirectory + WriteFile pair file.render
                 // uses, going through the IO channel so all the existing
-                // policy / hook handling applies.
+                // policy / hook handling — and dry-run — applies.
                 if let Some(parent) = destination.parent() {
                     if !parent.as_str().is_empty() {
-                        arc.request(archetect_api::ScriptMessage::WriteDirectory(
-                            archetect_api::WriteDirectoryInfo {
-                                path: parent.to_string(),
-                            },
-                        )).map_err(|e| LuaError::RuntimeError(format!("Write error: {}", e)))?;
-                        match arc.response().map_err(|e| LuaError::RuntimeError(format!("{}", e)))? {
-                            archetect_api::ClientMessage::Ack => {}
-                            archetect_api::ClientMessage::Error(msg) => {
-                                return Err(LuaError::RuntimeError(format!(
-                                    "template.render: failed to create destination dir {}: {}",
-                                    parent, msg
-                                )));
-                            }
-                            other => return Err(LuaError::RuntimeError(format!("Unexpected response: {:?}", other))),
-                        }
+                        lua_render::send_write_directory(&arc, parent)
+                            .map_err(|e| LuaError::RuntimeError(format!("template.render: {}", e)))?;
                     }
                 }
-                arc.request(archetect_api::ScriptMessage::WriteFile(
-                    archetect_api::WriteFileInfo {
-                        destination: destination.to_string(),
-                        contents: rendered.into_bytes(),
-                        existing_file_policy: overwrite_policy.into(),
-                    },
-                )).map_err(|e| LuaError::RuntimeError(format!("Write error: {}", e)))?;
-                match arc.response().map_err(|e| LuaError::RuntimeError(format!("{}", e)))? {
-                    archetect_api::ClientMessage::Ack => Ok(None),
-                    archetect_api::ClientMessage::Error(msg) => Err(LuaError::RuntimeError(format!(
-                        "template.render: write failed for {}: {}", destination, msg
-                    ))),
-                    other => Err(LuaError::RuntimeError(format!("Unexpected response: {:?}", other))),
-                }
+                lua_render::send_write_file(&arc, &destination, rendered.into_bytes(), overwrite_policy, None)
+                    .map_err(|e| LuaError::RuntimeError(format!("template.render: {}", e)))?;
+                Ok(None)
             } else {
                 Ok(Some(rendered))
             }
@@ -1470,6 +1708,7 @@ fn register_existing_constants(lua: &Lua) -> LuaResult<()> {
     table.set("Preserve", OverwritePolicy::Preserve)?;
     table.set("Prompt", OverwritePolicy::Prompt)?;
     table.set("Error", OverwritePolicy::Error)?;
+    table.set("Merge", OverwritePolicy::Merge)?;
     lua.globals().set("Existing", table)?;
     Ok(())
 }
@@ -1487,8 +1726,264 @@ fn extract_overwrite_policy(opts: &Option<Table>) -> OverwritePolicy {
     OverwritePolicy::Preserve
 }
 
+/// Read the `mode` option: permission bits written in octal, either as a
+/// string (`"755"`, `"0755"`) or as a number whose digits are read the same
+/// way, so `mode = 755` means what it looks like.
+fn extract_mode(opts: &Option<Table>) -> LuaResult<Option<u32>> {
+    let Some(opts) = opts else {
+        return Ok(None);
+    };
+    let digits = match opts.get::<Value>("mode")? {
+        Value::Nil => return Ok(None),
+        Value::String(mode) => mode.to_str()?.to_string(),
+        Value::Integer(mode) => mode.to_string(),
+        other => {
+            return Err(LuaError::RuntimeError(format!(
+                "'mode' must be octal permission bits such as \"755\", got {}",
+                other.type_name()
+            )))
+        }
+    };
+    match u32::from_str_radix(&digits, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(Some(mode)),
+        _ => Err(LuaError::RuntimeError(format!(
+            "'mode' must be octal permission bits such as \"755\", got '{}'",
+            digits
+        ))),
+    }
+}
+
+/// Read a glob list option: a string, or a list of strings. Absent is empty.
+fn extract_globs(opts: &Option<Table>, key: &str) -> LuaResult<Vec<String>> {
+    let Some(opts) = opts else {
+        return Ok(Vec::new());
+    };
+    match opts.get::<Value>(key)? {
+        Value::Nil => Ok(Vec::new()),
+        Value::String(glob) => Ok(vec![glob.to_str()?.to_string()]),
+        Value::Table(globs) => globs.sequence_values::<String>().collect(),
+        other => Err(LuaError::RuntimeError(format!(
+            "'{}' must be a glob or a list of globs, got {}",
+            key,
+            other.type_name()
+        ))),
+    }
+}
+
+/// Read `file.inject`'s options. At most one of `after`, `before`, and `at`
+/// may be given; with none, the snippet goes at the end.
+fn extract_injection(content: String, opts: &Option<Table>) -> LuaResult<Injection> {
+    let Some(opts) = opts else {
+        return Ok(Injection {
+            content,
+            anchor: Anchor::End,
+            unless_present: None,
+        });
+    };
+    let after = extract_pattern(opts, "after")?;
+    let before = extract_pattern(opts, "before")?;
+    let at: Option<String> = opts.get("at")?;
+    let anchor = match (after, before, at.as_deref()) {
+        (Some(pattern), None, None) => Anchor::After(pattern),
+        (None, Some(pattern), None) => Anchor::Before(pattern),
+        (None, None, Some("start")) => Anchor::Start,
+        (None, None, Some("end")) | (None, None, None) => Anchor::End,
+        (None, None, Some(other)) => {
+            return Err(LuaError::RuntimeError(format!(
+                "file.inject: 'at' must be \"start\" or \"end\", got '{}'",
+                other
+            )))
+        }
+        _ => {
+            return Err(LuaError::RuntimeError(
+                "file.inject: give only one of 'after', 'before', or 'at'".to_string(),
+            ))
+        }
+    };
+    Ok(Injection {
+        content,
+        anchor,
+        unless_present: extract_pattern(opts, "unless_present")?,
+    })
+}
+
+/// A text pattern option: a string matched literally, or `{ regex = "..." }`.
+fn extract_pattern(opts: &Table, key: &str) -> LuaResult<Option<Pattern>> {
+    match opts.get::<Value>(key)? {
+        Value::Nil => Ok(None),
+        Value::String(text) => Ok(Some(Pattern::Literal(text.to_str()?.to_string()))),
+        Value::Table(table) => {
+            let source: String = table.get("regex").map_err(|_| {
+                LuaError::RuntimeError(format!("file.inject: '{}' table must have a 'regex' string", key))
+            })?;
+            // Anchors are matched a line at a time, `unless_present` against
+            // the whole file: multi-line, `^` and `$` mean the same to both.
+            let regex = regex::RegexBuilder::new(&source)
+                .multi_line(true)
+                .build()
+                .map_err(|e| LuaError::RuntimeError(format!("file.inject: '{}': {}", key, e)))?;
+            Ok(Some(Pattern::Regex(regex)))
+        }
+        other => Err(LuaError::RuntimeError(format!(
+            "file.inject: '{}' must be a string or {{ regex = \"...\" }}, got {}",
+            key,
+            other.type_name()
+        ))),
+    }
+}
+
+/// Replace a destination file's contents with `updated`, or under
+/// `--dry-run` show the change as a diff.
+fn rewrite_destination_file(
+    archetect: &Archetect,
+    verb: &str,
+    target: &camino::Utf8Path,
+    existing: &str,
+    updated: String,
+) -> LuaResult<()> {
+    if archetect.is_dry_run() {
+        let diff = archetect_terminal_io::diff::format_diff(target, existing.as_bytes(), updated.as_bytes());
+        archetect.report_dry_run(format!("[dry-run] {}  {}\n{}", verb, target, diff.trim_end()));
+        archetect.capture(archetect_api::ScriptMessage::WriteFile(archetect_api::WriteFileInfo {
+            destination: target.to_string(),
+            contents: updated.into_bytes(),
+            existing_file_policy: archetect_api::ExistingFilePolicy::Overwrite,
+            shadow: None,
+            mode: None,
+        }));
+        return Ok(());
+    }
+    lua_render::send_write_file(archetect, target, updated.into_bytes(), OverwritePolicy::Overwrite, None)
+        .map_err(|e| LuaError::RuntimeError(format!("file.{}: {}", verb, e)))?;
+    Ok(())
+}
+
+/// The format `file.edit_structured` reads `path` as: `opts.format` if
+/// given, otherwise the file's extension.
+fn extract_structured_format(path: &str, opts: &Option<Table>) -> LuaResult<Format> {
+    let named: Option<String> = match opts {
+        Some(opts) => opts.get("format")?,
+        None => None,
+    };
+    match named {
+        Some(name) => Format::from_name(&name).ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "file.edit_structured: unknown format '{}'; expected \"toml\", \"json\", or \"yaml\"",
+                name
+            ))
+        }),
+        None => Format::from_path(path).ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "file.edit_structured: can't tell the format of '{}' from its extension; pass {{ format = ... }}",
+                path
+            ))
+        }),
+    }
+}
+
+/// The `doc` handed to a `file.edit_structured` function. Paths are dotted
+/// strings (`"dependencies.tokio"`) or tables of segments
+/// (`{ "tool", "poetry", 1 }`) for keys that contain dots; array elements
+/// count from 1.
+struct StructuredDocument(Document);
+
+impl mlua::UserData for StructuredDocument {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get", |lua, this, path: Value| match this.0.get(&structured_path(&path)?) {
+            Some(value) => json_to_lua_value(lua, &value),
+            None => Ok(Value::Nil),
+        });
+        methods.add_method("has", |_, this, path: Value| Ok(this.0.get(&structured_path(&path)?).is_some()));
+        methods.add_method_mut("set", |_, this, (path, value): (Value, Value)| {
+            let path = structured_path(&path)?;
+            this.0.set(&path, lua_value_to_json(&value)?).map_err(|e| structured_error(&path, e))
+        });
+        methods.add_method_mut("remove", |_, this, path: Value| {
+            let path = structured_path(&path)?;
+            this.0.remove(&path).map_err(|e| structured_error(&path, e))
+        });
+        methods.add_method_mut("add", |_, this, (path, value): (Value, Value)| {
+            let path = structured_path(&path)?;
+            this.0.add(&path, lua_value_to_json(&value)?).map_err(|e| structured_error(&path, e))
+        });
+        // doc:merge(table) merges into the whole document; doc:merge(path, table) beneath `path`.
+        methods.add_method_mut("merge", |_, this, (first, second): (Value, Option<Value>)| {
+            let (path, value) = match second {
+                Some(value) => (structured_path(&first)?, value),
+                None => (Vec::new(), first),
+            };
+            this.0.merge(&path, lua_value_to_json(&value)?).map_err(|e| structured_error(&path, e))
+        });
+    }
+}
+
+fn structured_path(path: &Value) -> LuaResult<Vec<String>> {
+    match path {
+        Value::Nil => Ok(Vec::new()),
+        Value::String(text) => Ok(structured::parse_path(&text.to_str()?)),
+        Value::Integer(index) => Ok(vec![index.to_string()]),
+        Value::Table(table) => table
+            .sequence_values::<Value>()
+            .map(|segment| match segment? {
+                Value::String(text) => Ok(text.to_str()?.to_string()),
+                Value::Integer(index) => Ok(index.to_string()),
+                other => Err(LuaError::RuntimeError(format!(
+                    "file.edit_structured: path segments must be strings or integers, got {}",
+                    other.type_name()
+                ))),
+            })
+            .collect(),
+        other => Err(LuaError::RuntimeError(format!(
+            "file.edit_structured: a path must be a string or a table, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+fn structured_error(path: &[String], message: String) -> LuaError {
+    LuaError::RuntimeError(format!("file.edit_structured: {}: {}", structured::display_path(path), message))
+}
+
+/// Resolve a path for one of the `file` module's writing functions. It must
+/// be relative, and must not climb out of the destination.
+fn destination_path(destination: &camino::Utf8Path, function: &str, path: &str) -> LuaResult<camino::Utf8PathBuf> {
+    let relative = camino::Utf8Path::new(path);
+    if relative.has_root() || relative.components().any(|c| c == camino::Utf8Component::ParentDir) {
+        return Err(LuaError::RuntimeError(format!(
+            "{}: path must be relative to the destination: {}",
+            function, path
+        )));
+    }
+    restrict_path(path)?;
+    Ok(destination.join(relative))
+}
+
+/// Collect the paths beneath `directory`, relative to `root`, that `matcher`
+/// accepts. Symlinked directories are listed but not followed.
+pub(super) fn glob_destination(
+    root: &camino::Utf8Path,
+    directory: &camino::Utf8Path,
+    matcher: &globset::GlobMatcher,
+    matches: &mut Vec<String>,
+) -> std::io::Result<()> {
+    if !directory.is_dir() {
+        return Ok(());
+    }
+    for entry in directory.read_dir_utf8()? {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path()).as_str().replace('\\', "/");
+        if matcher.is_match(&relative) {
+            matches.push(relative);
+        }
+        if entry.file_type()?.is_dir() {
+            glob_destination(root, entry.path(), matcher, matches)?;
+        }
+    }
+    Ok(())
+}
+
 /// Reject paths that attempt directory traversal or home-relative access.
-fn restrict_path(path: &str) -> LuaResult<&str> {
+pub(super) fn restrict_path(path: &str) -> LuaResult<&str> {
     if path.starts_with("~/") || path.starts_with("../") || path.contains("/../") || path.ends_with("/..") {
         return Err(LuaError::RuntimeError(format!(
             "Path manipulation not allowed: '{}'", path
@@ -1935,6 +2430,203 @@ mod tests {
         assert_eq!(out, "a,b,c");
     }
 
+    // ---------- collections: higher-order ----------
+
+    fn with_entities(lua: &Lua, ctx: &Table) {
+        let entities: Table = lua
+            .load(
+                r#"{
+                    { name = "Order", module = "sales", fields = 4, type = { name = "aggregate" } },
+                    { name = "Invoice", module = "billing", fields = 6, type = { name = "entity" } },
+                    { name = "Customer", module = "sales", fields = 3, type = { name = "aggregate" } },
+                }"#,
+            )
+            .eval()
+            .unwrap();
+        ctx.set("entities", entities).unwrap();
+    }
+
+    #[test]
+    fn test_map_select_reject_with_attribute_paths() {
+        let render = |template| render_with(template, with_entities);
+        assert_eq!(render(r#"{{ entities | map("type.name") | join(",") }}"#), "aggregate,entity,aggregate");
+        assert_eq!(render(r#"{{ entities | map("missing") | length }}"#), "0");
+        assert_eq!(
+            render(r#"{{ entities | select("module", "sales") | map("name") | join(",") }}"#),
+            "Order,Customer"
+        );
+        assert_eq!(
+            render(r#"{{ entities | reject("type.name", "aggregate") | map("name") | join(",") }}"#),
+            "Invoice"
+        );
+        assert_eq!(
+            render(r#"{{ map(entities, function(e) return e.name:lower() end) | join(",") }}"#),
+            "order,invoice,customer"
+        );
+    }
+
+    #[test]
+    fn test_select_still_answers_lua_select() {
+        assert_eq!(render_no_ctx(r##"{{ select("#", "a", "b", "c") }}|{{ select(2, "a", "b") }}"##), "3|b");
+        let out = render_with(r#"{{ select(flags) | length }}|{{ reject(flags) | length }}"#, |lua, ctx| {
+            let flags: Table = lua.load("{ true, false, 1, false }").eval().unwrap();
+            ctx.set("flags", flags).unwrap();
+        });
+        assert_eq!(out, "2|2");
+    }
+
+    #[test]
+    fn test_sort_by_and_group_by() {
+        let render = |template| render_with(template, with_entities);
+        assert_eq!(
+            render(r#"{{ entities | sort_by("fields") | map("name") | join(",") }}"#),
+            "Customer,Order,Invoice"
+        );
+        assert_eq!(
+            render(r#"{{ entities | sort_by("name", true) | map("name") | join(",") }}"#),
+            "Order,Invoice,Customer"
+        );
+        assert_eq!(
+            render(concat!(
+                r#"{% for _, g in ipairs(group_by(entities, "module")) do %}"#,
+                r#"{{ g.key }}={{ g.items | map("name") | join("+") }};{% end %}"#
+            )),
+            "sales=Order+Customer;billing=Invoice;"
+        );
+    }
+
+    #[test]
+    fn test_sort_by_keeps_ties_in_order_both_ways() {
+        let render = |template| render_with(template, with_entities);
+        assert_eq!(
+            render(r#"{{ entities | sort_by("module") | map("name") | join(",") }}"#),
+            "Invoice,Order,Customer"
+        );
+        assert_eq!(
+            render(r#"{{ entities | sort_by("module", true) | map("name") | join(",") }}"#),
+            "Order,Customer,Invoice"
+        );
+    }
+
+    #[test]
+    fn test_batch_zip_enumerate_flatten() {
+        let render = |template| {
+            render_with(template, |lua, ctx| {
+                let items: Table = lua.load(r#"{ "a", "b", "c", "d", "e" }"#).eval().unwrap();
+                ctx.set("items", items).unwrap();
+            })
+        };
+        assert_eq!(
+            render(r#"{% for _, b in ipairs(batch(items, 2, "-")) do %}[{{ b | join(",") }}]{% end %}"#),
+            "[a,b][c,d][e,-]"
+        );
+        assert_eq!(render(r#"{{ batch(items, 2) | last | length }}"#), "1");
+        assert_eq!(
+            render(r#"{% for _, p in ipairs(zip(items, { 1, 2 })) do %}{{ p[1] }}{{ p[2] }} {% end %}"#),
+            "a1 b2 "
+        );
+        assert_eq!(
+            render(r#"{% for _, e in ipairs(enumerate(items, 0)) do %}{{ e.index }}{{ e.value }}{% end %}"#),
+            "0a1b2c3d4e"
+        );
+        assert_eq!(render(r#"{{ flatten({ 1, { 2, { 3, { 4 } } }, { x = 5 } }) | length }}"#), "5");
+        assert_eq!(render(r#"{{ flatten({ 1, { 2, { 3, { 4 } } } }, 1) | length }}"#), "3");
+    }
+
+    #[test]
+    fn test_keys_values_items_are_ordered() {
+        let render = |template| {
+            render_with(template, |lua, ctx| {
+                let ports: Table = lua.load("{ web = 8080, db = 5432, cache = 6379 }").eval().unwrap();
+                ctx.set("ports", ports).unwrap();
+            })
+        };
+        assert_eq!(render(r#"{{ ports | keys | join(",") }}"#), "cache,db,web");
+        assert_eq!(render(r#"{{ ports | values | join(",") }}"#), "6379,5432,8080");
+        assert_eq!(
+            render(r#"{% for _, p in ipairs(items(ports)) do %}{{ p.key }}:{{ p.value }} {% end %}"#),
+            "cache:6379 db:5432 web:8080 "
+        );
+    }
+
+    #[test]
+    fn test_context_names_win_over_collection_functions() {
+        let out = render_with(r#"{{ items }}|{{ ports | items | map("key") | join(",") }}"#, |lua, ctx| {
+            ctx.set("items", "from the context").unwrap();
+            let ports: Table = lua.load("{ web = 8080, db = 5432 }").eval().unwrap();
+            ctx.set("ports", ports).unwrap();
+        });
+        assert_eq!(out, "from the context|db,web");
+    }
+
+    #[test]
+    fn test_sum_min_max() {
+        let render = |template| render_with(template, with_entities);
+        assert_eq!(render(r#"{{ entities | sum("fields") }}"#), "13");
+        assert_eq!(render(r#"{{ sum({ 1, 2.5 }) }}"#), "3.5");
+        assert_eq!(render(r#"{{ min(entities, "fields").name }}"#), "Customer");
+        assert_eq!(render(r#"{{ max(entities, "name").name }}"#), "Order");
+        assert_eq!(render(r#"{{ max({ 3, 10, 7 }) }}|{{ min({}) }}"#), "10|");
+    }
+
+    // ---------- layout ----------
+
+    #[test]
+    fn test_wrap_keeps_indentation_and_blank_lines() {
+        let out = render_with("{{ text | wrap(16) }}", |_, ctx| {
+            ctx.set("text", "A generator for services\n\n  - indented items wrap under themselves\n")
+                .unwrap();
+        });
+        assert_eq!(
+            out,
+            "A generator for\nservices\n\n  - indented\n  items wrap\n  under\n  themselves\n"
+        );
+        assert_eq!(render_no_ctx(r#"{{ wrap("a supercalifragilistic b", 5) }}"#), "a\nsupercalifragilistic\nb");
+    }
+
+    #[test]
+    fn test_dedent() {
+        let out = render_with("{{ text | dedent }}", |_, ctx| {
+            ctx.set("text", "    fn main() {\n        run();\n  \n    }\n").unwrap();
+        });
+        assert_eq!(out, "fn main() {\n    run();\n\n}\n");
+    }
+
+    #[test]
+    fn test_padding_is_display_width_aware() {
+        assert_eq!(render_no_ctx(r#"[{{ "ab" | ljust(5) }}][{{ "ab" | rjust(5, ".") }}]"#), "[ab   ][...ab]");
+        assert_eq!(render_no_ctx(r#"[{{ center("ab", 7, "*") }}]"#), "[**ab***]");
+        assert_eq!(render_no_ctx(r#"[{{ "日本" | ljust(6) }}]"#), "[日本  ]");
+        assert_eq!(render_no_ctx(r#"[{{ "toolong" | ljust(3) }}]"#), "[toolong]");
+    }
+
+    #[test]
+    fn test_columns() {
+        let out = render_with(r#"{{ columns(rows, { align = "lr" }) }}"#, |lua, ctx| {
+            let rows: Table = lua
+                .load(r#"{ { "NAME", "PORT", "NOTE" }, { "web", 8080 }, { "データ", 5432, "primary" } }"#)
+                .eval()
+                .unwrap();
+            ctx.set("rows", rows).unwrap();
+        });
+        assert_eq!(out, "NAME    PORT  NOTE\nweb     8080\nデータ  5432  primary");
+        assert_eq!(
+            render_no_ctx(r#"{{ columns({ { "a", "b" }, { "ccc", "d" } }, { sep = " | " }) }}"#),
+            "a   | b\nccc | d"
+        );
+    }
+
+    // ---------- license / comment_block ----------
+
+    #[test]
+    fn test_license_and_comment_block() {
+        let year = chrono::Datelike::year(&chrono::Local::now());
+        let header = render_no_ctx(r#"{{ license("mit", "Acme") | comment_block("rust") }}"#);
+        assert!(header.starts_with(&format!("// Copyright (c) {} Acme\n//\n// Permission", year)));
+        assert_eq!(render_no_ctx(r#"{{ "a\n\nb" | comment_block("css") }}"#), "/*\n * a\n *\n * b\n */");
+        assert_eq!(render_no_ctx(r#"{{ comment_block("a", ".py") }}"#), "# a");
+    }
+
     // ---------- datetime ----------
 
     #[test]
@@ -2003,6 +2695,23 @@ mod tests {
         assert_eq!(out, "00000000-0000-0000-0000-000000000000");
     }
 
+    #[test]
+    fn test_reproducible_pins_dates_and_uuids() {
+        let render = |seed| {
+            render_with(
+                "{{ now() }} {{ year() }} {{ timestamp() }} {{ uuid_v4() }} {{ uuid_v7() }}",
+                |lua, _| crate::reproducible::Reproducible::new(1_700_000_000, seed).install(lua).unwrap(),
+            )
+        };
+        let out = render(7);
+        assert!(out.starts_with("2023-11-14T22:13:20+00:00 2023 1700000000 "), "{}", out);
+        let uuids: Vec<&str> = out.split(' ').skip(3).collect();
+        assert_eq!(&uuids[0][14..15], "4", "{}", out);
+        assert!(uuids[1].starts_with("018bcfe5-6800-7"), "{}", out);
+        assert_eq!(out, render(7));
+        assert_ne!(out, render(8));
+    }
+
     // ---------- paths ----------
 
     #[test]
@@ -2073,4 +2782,69 @@ mod tests {
         });
         assert_eq!(out, "/a/c");
     }
+
+    // ---------- escaping ----------
+
+    #[test]
+    fn test_escape_filters() {
+        let render = |filter: &str| {
+            render_with(&format!("{{{{ v | {} }}}}", filter), |_, ctx| {
+                ctx.set("v", "it's \"$x\" <&>").unwrap();
+            })
+        };
+        assert_eq!(render("escape_json"), r#"it's \"$x\" <&>"#);
+        assert_eq!(render("escape_yaml"), r#"it's \"$x\" <&>"#);
+        assert_eq!(render("escape_toml"), r#"it's \"$x\" <&>"#);
+        assert_eq!(render("escape_shell"), r#"it's \"\$x\" <&>"#);
+        assert_eq!(render("escape_xml"), "it&#39;s &quot;$x&quot; &lt;&amp;&gt;");
+        assert_eq!(render("escape_html"), render("escape_xml"));
+    }
+
+    // ---------- text: regex, digests, encodings ----------
+
+    #[test]
+    fn test_regex_filters() {
+        let render = |template: &str| {
+            render_with(template, |_, ctx| {
+                ctx.set("v", "orders-service-v2").unwrap();
+            })
+        };
+        assert_eq!(render(r#"{{ v | regex_match("-v[0-9]+$") }}"#), "true");
+        assert_eq!(render(r#"{{ regex_match(v, "^v") }}"#), "false");
+        assert_eq!(render(r#"{{ v | regex_replace("-(service)", "_$1") }}"#), "orders_service-v2");
+        let captures = concat!(
+            r#"{% local c = regex_captures(v, "^(?P<name>[a-z]+)-.*-v([0-9])(x)?$") %}"#,
+            "{{ c[0] }}|{{ c.name }}|{{ c[2] }}|{{ c[3] }}"
+        );
+        assert_eq!(render(captures), "orders-service-v2|orders|2|");
+        assert_eq!(render(r#"{{ regex_captures(v, "^x") == nil }}"#), "true");
+    }
+
+    #[test]
+    fn test_invalid_regex_names_the_filter() {
+        let compiled = TemplateCompiler::compile(r#"{{ "x" | regex_match("(") }}"#, "test").unwrap();
+        let lua = Lua::new();
+        let func: mlua::Function = lua.load(&compiled.source).eval().unwrap();
+        let filters = create_builtin_filters(&lua).unwrap();
+        let err = func.call::<String>((lua.create_table().unwrap(), filters)).unwrap_err();
+        assert!(err.to_string().contains("filter `regex_match`: invalid pattern `(`"), "{}", err);
+    }
+
+    #[test]
+    fn test_digest_and_encoding_filters() {
+        assert_eq!(
+            render_no_ctx(r#"{{ "abc" | sha256 }}"#),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(render_no_ctx(r#"{{ sha1("abc") }}"#), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(render_no_ctx(r#"{{ "abc" | md5 }}"#), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(render_no_ctx(r#"{{ "123456789" | crc32 }}"#), "3421780262");
+        // A stable port derived from a service name.
+        assert_eq!(render_no_ctx(r#"{{ 20000 + crc32("orders") % 10000 }}"#), "27662");
+        assert_eq!(render_no_ctx(r#"{{ "hi there?" | base64_encode }}"#), "aGkgdGhlcmU/");
+        assert_eq!(render_no_ctx(r#"{{ base64_decode("aGkgdGhlcmU/") }}"#), "hi there?");
+        assert_eq!(render_no_ctx(r#"{{ "\0\255" | base64_encode | base64_decode | hex }}"#), "00ff");
+        assert_eq!(render_no_ctx(r#"{{ "Hi" | hex }}|{{ hex(255) }}"#), "4869|ff");
+        assert_eq!(render_no_ctx(r#"{{ "a b&c=d/é~_.-" | url_encode }}"#), "a%20b%26c%3Dd%2F%C3%A9~_.-");
+    }
 }
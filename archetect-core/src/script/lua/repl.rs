@@ -0,0 +1,279 @@
+//! The Lua state behind `archetect repl`: an archetype's runtime, held open
+//! between inputs.
+//!
+//! The state is the one a render would give the archetype's script —
+//! `Context`, `catalog`, `directory`, `file`, `template`, `format`, its
+//! staged libraries — built by the same `create_lua`, so what works here
+//! works in the script. A `context` global holds a fresh `Context` for
+//! trying prompts and templates against. The terminal side (line editing,
+//! history, dry-run) is the CLI's.
+
+use mlua::{Lua, MultiValue, Value};
+
+use crate::archetype::archetype::Archetype;
+use crate::archetype::render_context::RenderContext;
+use crate::errors::ArchetypeError;
+use crate::help;
+
+use super::{create_lua, is_clean_exit};
+
+/// What an input came to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Evaluation {
+    /// The values an expression or `return` produced, displayed — tables
+    /// and Contexts as YAML. Empty for a statement.
+    Values(Vec<String>),
+    /// The input stops mid-statement; more lines should follow.
+    Incomplete,
+    /// `exit()` was called.
+    Exit,
+}
+
+pub struct Repl {
+    lua: Lua,
+    /// Everything introspection documents, completed as typed: `template.render`,
+    /// `Context:prompt_text`, `Cases`.
+    documented: Vec<String>,
+}
+
+impl Repl {
+    pub fn new(archetype: &Archetype, render_context: RenderContext) -> Result<Repl, ArchetypeError> {
+        let archetect = archetype.archetect();
+        archetype.manifest().requires().check_capabilities(archetect)?;
+        archetect.set_destination(render_context.destination());
+        let lua = create_lua(archetype, archetect, &render_context)?;
+        lua.load("context = Context.new()")
+            .exec()
+            .map_err(|_| ArchetypeError::ScriptAbortError)?;
+        let mut documented: Vec<String> = help::core_entries().into_iter().map(|entry| entry.name).collect();
+        documented.sort();
+        documented.dedup();
+        Ok(Repl { lua, documented })
+    }
+
+    /// Whether `input` stops mid-statement, so more lines should follow
+    /// before it runs.
+    pub fn is_incomplete(&self, input: &str) -> bool {
+        matches!(
+            self.compile(input),
+            Err(mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            })
+        )
+    }
+
+    /// Run one input. An expression shows its value, as in `lua -i`;
+    /// anything else runs as statements.
+    pub fn eval(&self, input: &str) -> Result<Evaluation, String> {
+        let chunk = match self.compile(input) {
+            Ok(chunk) => chunk,
+            Err(mlua::Error::SyntaxError {
+                incomplete_input: true, ..
+            }) => return Ok(Evaluation::Incomplete),
+            Err(err) => return Err(message(&err)),
+        };
+        match chunk.call::<MultiValue>(()) {
+            Ok(values) => Ok(Evaluation::Values(
+                values.iter().map(|value| self.display(value)).collect(),
+            )),
+            Err(err) if is_clean_exit(&err) => Ok(Evaluation::Exit),
+            Err(err) => Err(message(&err)),
+        }
+    }
+
+    /// `input` as an expression to return, if it is one, or as statements.
+    fn compile(&self, input: &str) -> mlua::Result<mlua::Function> {
+        self.lua
+            .load(format!("return {}", input))
+            .set_name("=repl")
+            .into_function()
+            .or_else(|_| self.lua.load(input).set_name("=repl").into_function())
+    }
+
+    /// Render an ATL template against the `context` global.
+    pub fn render(&self, template: &str) -> Result<String, String> {
+        let render: mlua::Function = self
+            .lua
+            .load("function(template_source) return template.render(template_source, context) end")
+            .set_name("=:render")
+            .eval()
+            .map_err(|err| message(&err))?;
+        render.call::<String>(template).map_err(|err| match err {
+            mlua::Error::CallbackError { cause, .. } => message(&cause),
+            err => message(&err),
+        })
+    }
+
+    /// The ways to finish `line`'s last word: what introspection documents,
+    /// then whatever else the state holds by that name. Each is the whole
+    /// line, completed.
+    pub fn completions(&self, line: &str) -> Vec<String> {
+        let start = line
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == ':'))
+            .map_or(0, |i| i + 1);
+        let (head, word) = line.split_at(start);
+        if word.is_empty() {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<String> = Vec::new();
+        match word.rsplit_once(':') {
+            // A method, on whatever the receiver is: `context:pro` finds
+            // `Context:prompt_text`.
+            Some((receiver, method)) => {
+                for name in &self.documented {
+                    if let Some((_, documented)) = name.split_once(':') {
+                        if documented.starts_with(method) {
+                            candidates.push(format!("{}:{}", receiver, documented));
+                        }
+                    }
+                }
+            }
+            None => {
+                candidates.extend(
+                    self.documented
+                        .iter()
+                        .filter(|name| name.starts_with(word) && !name.contains(':'))
+                        .cloned(),
+                );
+                let (table, prefix) = match word.rsplit_once('.') {
+                    Some((table, prefix)) => (Some(table), prefix),
+                    None => (None, word),
+                };
+                for key in self.keys(table) {
+                    if key.starts_with(prefix) {
+                        candidates.push(match table {
+                            Some(table) => format!("{}.{}", table, key),
+                            None => key,
+                        });
+                    }
+                }
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+            .into_iter()
+            .map(|candidate| format!("{}{}", head, candidate))
+            .collect()
+    }
+
+    /// The string keys of the table at the dotted `path` — the globals when
+    /// there's no path.
+    fn keys(&self, path: Option<&str>) -> Vec<String> {
+        let mut table = self.lua.globals();
+        for segment in path.into_iter().flat_map(|path| path.split('.')) {
+            match table.raw_get::<Value>(segment) {
+                Ok(Value::Table(next)) => table = next,
+                _ => return Vec::new(),
+            }
+        }
+        table
+            .pairs::<Value, Value>()
+            .filter_map(|pair| match pair {
+                Ok((Value::String(key), _)) => key.to_str().ok().map(|key| key.to_string()),
+                _ => None,
+            })
+            .filter(|key| !key.starts_with("__"))
+            .collect()
+    }
+
+    fn display(&self, value: &Value) -> String {
+        if matches!(value, Value::Table(_) | Value::UserData(_)) {
+            let yaml = self
+                .lua
+                .globals()
+                .get::<mlua::Table>("format")
+                .and_then(|format| format.get::<mlua::Function>("to_yaml"))
+                .and_then(|to_yaml| to_yaml.call::<String>(value.clone()));
+            if let Ok(yaml) = yaml {
+                return yaml.trim_end().to_string();
+            }
+        }
+        match value {
+            Value::String(string) => format!("{:?}", string.to_string_lossy()),
+            other => other.to_string().unwrap_or_else(|_| format!("{:?}", other)),
+        }
+    }
+}
+
+/// An error as the REPL shows it: the message, without the traceback of a
+/// stack that is only ever the prompt's.
+fn message(err: &mlua::Error) -> String {
+    let message = err.to_string();
+    match message.split_once("\nstack traceback:") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::Configuration;
+    use crate::Archetect;
+    use camino::Utf8PathBuf;
+    use tempfile::TempDir;
+
+    fn repl(root: &TempDir) -> Repl {
+        std::fs::write(root.path().join("archetype.yaml"), "description: repl test\n").unwrap();
+        let archetect = Archetect::builder()
+            .with_configuration(Configuration::default().with_headless(true).with_dry_run(true))
+            .with_temp_layout()
+            .unwrap()
+            .build()
+            .unwrap();
+        let archetype = archetect.new_archetype(root.path().to_str().unwrap()).unwrap();
+        let destination = Utf8PathBuf::from_path_buf(root.path().join("out")).unwrap();
+        Repl::new(&archetype, RenderContext::new(destination, Default::default())).unwrap()
+    }
+
+    #[test]
+    fn expressions_show_their_values_and_statements_run() {
+        let root = TempDir::new().unwrap();
+        let repl = repl(&root);
+        assert_eq!(
+            repl.eval("1 + 2, 'three'"),
+            Ok(Evaluation::Values(vec!["3".into(), "\"three\"".into()]))
+        );
+        assert_eq!(repl.eval("x = { a = 1 }"), Ok(Evaluation::Values(vec![])));
+        assert_eq!(repl.eval("x"), Ok(Evaluation::Values(vec!["a: 1".into()])));
+        assert_eq!(repl.eval("for i = 1, 3 do"), Ok(Evaluation::Incomplete));
+        assert!(repl.is_incomplete("function f()\n  return 1"));
+        assert!(!repl.is_incomplete("function f()\n  return 1\nend"));
+        assert_eq!(
+            repl.eval("error('nope')"),
+            Err("runtime error: repl:1: nope".to_string())
+        );
+        assert_eq!(repl.eval("exit()"), Ok(Evaluation::Exit));
+    }
+
+    #[test]
+    fn templates_render_against_the_context_global() {
+        let root = TempDir::new().unwrap();
+        let repl = repl(&root);
+        repl.eval("context:set('name', 'World')").unwrap();
+        assert_eq!(
+            repl.render("Hello, {{ name | upper }}!"),
+            Ok("Hello, WORLD!".to_string())
+        );
+        assert!(repl.render("{% if %}").is_err());
+    }
+
+    #[test]
+    fn completion_draws_on_introspection_and_the_live_state() {
+        let root = TempDir::new().unwrap();
+        let repl = repl(&root);
+        assert!(repl.completions("template.re").contains(&"template.render".to_string()));
+        assert!(repl
+            .completions("x = Cases.pro")
+            .contains(&"x = Cases.programming".to_string()));
+        assert!(repl
+            .completions("context:prompt_t")
+            .contains(&"context:prompt_text".to_string()));
+        repl.eval("my_helper = 1").unwrap();
+        assert_eq!(repl.completions("my_h"), vec!["my_helper".to_string()]);
+        assert!(repl.completions("").is_empty());
+    }
+}
@@ -9,7 +9,7 @@ use archetect_api::ScriptMessage;
 /// In dry-run mode, send a `[dry-run] <action>` Display message and return
 /// `true`. Callers short-circuit when `true` is returned so no actual side
 /// effect (subprocess, network call, file mutation) occurs.
-fn dry_run_skip(archetect: &crate::Archetect, action: &str) -> bool {
+pub(super) fn dry_run_skip(archetect: &crate::Archetect, action: &str) -> bool {
     if archetect.is_dry_run() {
         let _ = archetect.request(ScriptMessage::Display(format!("[dry-run] {}", action)));
         true
@@ -71,7 +71,7 @@ fn run_captured(
 }
 
 /// Back-compat alias: shell.run-style "show me everything" behavior.
-fn run_logged(archetect: &Archetect, cmd: &mut Command, label: &str) -> LuaResult<std::process::ExitStatus> {
+pub(super) fn run_logged(archetect: &Archetect, cmd: &mut Command, label: &str) -> LuaResult<std::process::ExitStatus> {
     run_captured(archetect, cmd, label, OutputVerbosity::Info)
 }
 
@@ -81,12 +81,14 @@ fn run_git(archetect: &Archetect, cmd: &mut Command, label: &str) -> LuaResult<s
     run_captured(archetect, cmd, label, OutputVerbosity::Quiet)
 }
 
+use crate::archetype::archetype::Archetype;
 use crate::archetype::render_context::RenderContext;
 use crate::Archetect;
 
 /// Register archetect.* modules available via require()
 pub fn register_require_modules(
     lua: &Lua,
+    archetype: &Archetype,
     archetect: &Archetect,
     render_context: &RenderContext,
 ) -> LuaResult<()> {
@@ -134,6 +136,19 @@ pub fn register_require_modules(
         )?;
     }
 
+    // archetect.fs — read-only, so ungated
+    {
+        let arc = archetect.clone();
+        let archetype_root = archetype.root().to_owned();
+        let destination = render_context.destination().to_owned();
+        preload.set(
+            "archetect.fs",
+            lua.create_function(move |lua, ()| {
+                super::filesystem::create_fs_module(lua, &arc, &archetype_root, &destination)
+            })?,
+        )?;
+    }
+
     // archetect.model
     {
         preload.set(
@@ -338,7 +353,7 @@ fn create_shell_module(lua: &Lua, archetect: &Archetect, render_context: &Render
 /// - `Allowed` → proceed without prompting.
 /// - `Prompt` → ask the user via the IO channel, showing the exact command.
 ///   In headless mode, the prompt fails and the call is denied.
-fn authorize_shell_exec(
+pub(super) fn authorize_shell_exec(
     archetect: &Archetect,
     program: &str,
     args: &[String],
@@ -841,10 +856,12 @@ fn emit_archive(
         )));
     }
 
+    // A reproducible render stamps members with its pinned clock.
+    let mtime = archetect.reproducible().map(|reproducible| reproducible.epoch());
     let contents = match format {
-        ArchiveFormat::Zip => crate::archive::build_zip(root, &entries),
-        ArchiveFormat::Tar => crate::archive::build_tar(root, &entries, false),
-        ArchiveFormat::TarGz => crate::archive::build_tar(root, &entries, true),
+        ArchiveFormat::Zip => crate::archive::build_zip(root, &entries, mtime),
+        ArchiveFormat::Tar => crate::archive::build_tar(root, &entries, false, mtime),
+        ArchiveFormat::TarGz => crate::archive::build_tar(root, &entries, true, mtime),
     }
     .map_err(|e| LuaError::RuntimeError(format!("{} error: {}", format.label(), e)))?;
 
@@ -853,11 +870,13 @@ fn emit_archive(
             destination: output_path.to_string(),
             contents,
             existing_file_policy: archetect_api::ExistingFilePolicy::Overwrite,
+            shadow: None,
+            mode: None,
         }))
         .map_err(|e| LuaError::RuntimeError(format!("{} write failed: {}", format.label(), e)))?;
 
     match archetect.response() {
-        Ok(archetect_api::ClientMessage::Ack) => {}
+        Ok(archetect_api::ClientMessage::Ack | archetect_api::ClientMessage::Written(_)) => {}
         Ok(archetect_api::ClientMessage::Error(msg)) => {
             return Err(LuaError::RuntimeError(format!(
                 "{} write failed: {}",
@@ -0,0 +1,610 @@
+//! The sandboxed Lua runtime, for sessions that run archetypes on someone
+//! else's behalf — `archetect server` and `archetect mcp` turn it on unless
+//! the configuration's `security.sandbox` says otherwise.
+//!
+//! `Lua::new()` already leaves out `debug` and C modules. The sandbox closes
+//! the standard library's remaining ways out, which would otherwise step
+//! around the checks `archetect.shell` and `archetect.github` make:
+//!
+//! - `os.execute` and `io.popen` pass the same `ShellExecPolicy` gate as
+//!   `archetect.shell`, and honor `--dry-run` the same way.
+//! - `io.open`, `io.lines`, `os.remove`, `os.rename`, `loadfile`, and
+//!   `dofile` may read the archetype and read or write the destination;
+//!   any other path takes the `filesystem` capability, as does
+//!   `os.tmpname`. Symlinks are followed before the check, so a link can't
+//!   lead out.
+//! - `os.getenv` takes the `environment` capability: a server's environment
+//!   holds the server's own credentials.
+//! - The process's standard streams are the session's, not the script's —
+//!   under MCP, stdout carries the protocol. `print`, `io.write`, and
+//!   `io.stdout` print through the IO channel as `output.print` does;
+//!   `io.read`, `io.stdin`, `io.input`, and `io.output` are removed, as
+//!   are the forms of `io.lines`, `loadfile`, and `dofile` that read stdin.
+//! - `require` and `package.searchpath` find files as `io.open` reads
+//!   them, except along the `package.path` entries archetect itself set
+//!   up for the archetype's libraries; Lua's default entries don't count.
+//! - `os.exit`, `package.loadlib`, and `package.cpath` are removed.
+//!
+//! A capability counts only if the session named it: one that enumerated
+//! no grants — MCP, or a local render with the sandbox configured on — is
+//! granted none, though outside the sandbox it would be granted all.
+
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use camino::Utf8Path;
+use mlua::{Error as LuaError, Function, IntoLuaMulti, Lua, MultiValue, Result as LuaResult, Table, Value};
+
+use super::require_modules::{authorize_shell_exec, dry_run_skip, run_logged};
+use crate::Archetect;
+
+/// Reaching the filesystem beyond the archetype and the destination.
+pub const FILESYSTEM: &str = "filesystem";
+/// Reading the process environment.
+pub const ENVIRONMENT: &str = "environment";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// What the sandboxed functions check against.
+#[derive(Clone)]
+struct Guard {
+    archetect: Archetect,
+    archetype: PathBuf,
+    destination: PathBuf,
+}
+
+impl Guard {
+    fn new(archetect: &Archetect, archetype_root: &Utf8Path, destination: &Utf8Path) -> Guard {
+        Guard {
+            archetect: archetect.clone(),
+            archetype: real_path(archetype_root.as_std_path()),
+            destination: real_path(destination.as_std_path()),
+        }
+    }
+
+    fn permits(&self, path: &str, access: Access) -> LuaResult<bool> {
+        let cwd = std::env::current_dir().map_err(LuaError::external)?;
+        let real = real_path(&cwd.join(path));
+        let allowed = real.starts_with(&self.destination)
+            || (access == Access::Read && real.starts_with(&self.archetype));
+        Ok(allowed || self.archetect.grants_explicitly(FILESYSTEM))
+    }
+
+    fn check_path(&self, call: &str, path: &str, access: Access) -> LuaResult<()> {
+        if self.permits(path, access)? {
+            return Ok(());
+        }
+        let reach = match access {
+            Access::Read => "reads outside the archetype and the destination",
+            Access::Write => "writes outside the destination",
+        };
+        Err(denied(&format!("{}(\"{}\") {}", call, path, reach), FILESYSTEM))
+    }
+
+    fn check_capability(&self, call: &str, capability: &str) -> LuaResult<()> {
+        if self.archetect.grants_explicitly(capability) {
+            Ok(())
+        } else {
+            Err(denied(call, capability))
+        }
+    }
+}
+
+fn denied(what: &str, capability: &str) -> LuaError {
+    LuaError::RuntimeError(format!(
+        "{} in the sandbox, which requires the `{}` capability this session did not grant. \
+         Declare it in the manifest (requires.capabilities) and grant it with `--allow {}`.",
+        what, capability, capability
+    ))
+}
+
+/// Check a read by one of archetect's own modules, of a path it has already
+/// resolved, as `io.open` is checked: beyond the archetype and the
+/// destination — under `Location.Cwd`, or through a symlink — a sandboxed
+/// session needs the `filesystem` capability. Outside the sandbox anything
+/// goes.
+pub(crate) fn check_read(
+    archetect: &Archetect,
+    archetype_root: &Utf8Path,
+    destination: &Utf8Path,
+    call: &str,
+    path: &Utf8Path,
+) -> LuaResult<()> {
+    if !archetect.configuration().sandboxed() {
+        return Ok(());
+    }
+    Guard::new(archetect, archetype_root, destination).check_path(call, path.as_str(), Access::Read)
+}
+
+/// Sandbox `lua`. Installed last, after archetect's own modules are in
+/// place, so only archetype code sees it.
+pub(crate) fn install(
+    lua: &Lua,
+    archetect: &Archetect,
+    archetype_root: &Utf8Path,
+    destination: &Utf8Path,
+) -> LuaResult<()> {
+    let guard = Guard::new(archetect, archetype_root, destination);
+    let globals = lua.globals();
+    let os: Table = globals.get("os")?;
+    let io: Table = globals.get("io")?;
+    let package: Table = globals.get("package")?;
+
+    // Processes
+    let arc = archetect.clone();
+    os.set(
+        "execute",
+        lua.create_function(move |lua, command: Option<String>| {
+            // Without a command, Lua asks whether a shell is available.
+            let Some(command) = command else {
+                return false.into_lua_multi(lua);
+            };
+            let cwd = current_dir();
+            if dry_run_skip(&arc, &format!("os.execute {} (in {})", command, cwd)) {
+                return (true, "exit", 0).into_lua_multi(lua);
+            }
+            let args = vec!["-c".to_string(), command.clone()];
+            authorize_shell_exec(&arc, "sh", &args, &cwd)?;
+            let status = run_logged(&arc, Command::new("sh").args(&args), &format!("Failed to run '{}'", command))?;
+            match status.code() {
+                Some(code) => (status.success().then_some(true), "exit", code).into_lua_multi(lua),
+                None => (Value::Nil, "signal").into_lua_multi(lua),
+            }
+        })?,
+    )?;
+    let arc = archetect.clone();
+    let popen: Function = io.get("popen")?;
+    io.set(
+        "popen",
+        lua.create_function(move |lua, (command, mode): (String, Option<String>)| {
+            let cwd = current_dir();
+            if dry_run_skip(&arc, &format!("io.popen {} (in {})", command, cwd)) {
+                return (Value::Nil, "io.popen: not run under --dry-run").into_lua_multi(lua);
+            }
+            authorize_shell_exec(&arc, "sh", &["-c".to_string(), command.clone()], &cwd)?;
+            popen.call::<MultiValue>((command, mode))
+        })?,
+    )?;
+
+    // Modules, before `loadfile` is wrapped: the searcher loads what
+    // `searchpath` has already let through.
+    install_searcher(lua, &package, &globals.get("loadfile")?, &guard)?;
+
+    // Files
+    wrap(lua, &io, "open", &guard, |guard, args| {
+        let mode = string_arg(args, 1).unwrap_or_default();
+        let access = if mode.contains(['w', 'a', '+']) { Access::Write } else { Access::Read };
+        path_arg("io.open", args, 0)?.map_or(Ok(()), |path| guard.check_path("io.open", &path, access))
+    })?;
+    for (table, name, call, access) in [
+        (&os, "remove", "os.remove", Access::Write),
+        (&globals, "loadfile", "loadfile", Access::Read),
+        (&globals, "dofile", "dofile", Access::Read),
+    ] {
+        // Without a path, `loadfile` and `dofile` read standard input.
+        wrap(lua, table, name, &guard, move |guard, args| match path_arg(call, args, 0)? {
+            Some(path) => guard.check_path(call, &path, access),
+            None => Err(unavailable(&format!("{}() without a path", call), "name a file")),
+        })?;
+    }
+    wrap(lua, &io, "lines", &guard, |guard, args| match path_arg("io.lines", args, 0)? {
+        Some(path) => guard.check_path("io.lines", &path, Access::Read),
+        None => Err(unavailable("io.lines() without a path", "name a file")),
+    })?;
+    wrap(lua, &os, "rename", &guard, |guard, args| {
+        for path in [path_arg("os.rename", args, 0)?, path_arg("os.rename", args, 1)?].into_iter().flatten() {
+            guard.check_path("os.rename", &path, Access::Write)?;
+        }
+        Ok(())
+    })?;
+    wrap(lua, &os, "tmpname", &guard, |guard, _| {
+        guard.check_capability("os.tmpname() creates a file outside the destination", FILESYSTEM)
+    })?;
+
+    // The environment
+    wrap(lua, &os, "getenv", &guard, |guard, args| {
+        let name = string_arg(args, 0).unwrap_or_default();
+        guard.check_capability(&format!("os.getenv(\"{}\") reads the environment", name), ENVIRONMENT)
+    })?;
+
+    // Standard streams
+    install_stdio(lua, &globals, &io, archetect)?;
+
+    os.set(
+        "exit",
+        lua.create_function(|_, _: MultiValue| -> LuaResult<()> {
+            Err(unavailable("os.exit", "call exit() to end the script early"))
+        })?,
+    )?;
+    package.set("loadlib", Value::Nil)?;
+    package.set("cpath", "")?;
+    Ok(())
+}
+
+fn unavailable(what: &str, instead: &str) -> LuaError {
+    LuaError::RuntimeError(format!("{} is not available in the sandbox; {}", what, instead))
+}
+
+/// Point `print`, `io.write`, and `io.stdout` at the IO channel, and take
+/// away standard input.
+fn install_stdio(lua: &Lua, globals: &Table, io: &Table, archetect: &Archetect) -> LuaResult<()> {
+    let arc = archetect.clone();
+    let tostring: Function = globals.get("tostring")?;
+    globals.set(
+        "print",
+        lua.create_function(move |_, args: MultiValue| {
+            let parts = args
+                .into_iter()
+                .map(|arg| tostring.call::<String>(arg))
+                .collect::<LuaResult<Vec<_>>>()?;
+            let _ = arc.request(archetect_api::ScriptMessage::Print(parts.join("\t")));
+            Ok(())
+        })?,
+    )?;
+
+    // `io.write(...)` is `io.stdout:write(...)`; each call prints what it
+    // was given, less the newline the receiving end adds back.
+    let stdout = lua.create_table()?;
+    let arc = archetect.clone();
+    let write = lua.create_function(move |_, (stdout, args): (Table, MultiValue)| {
+        let mut text = String::new();
+        for arg in args {
+            match arg {
+                Value::String(value) => text.push_str(&value.to_string_lossy()),
+                Value::Integer(value) => text.push_str(&value.to_string()),
+                Value::Number(value) => text.push_str(&value.to_string()),
+                other => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "bad argument to 'write' (string expected, got {})",
+                        other.type_name()
+                    )))
+                }
+            }
+        }
+        let text = text.strip_suffix('\n').unwrap_or(&text).to_string();
+        let _ = arc.request(archetect_api::ScriptMessage::Print(text));
+        Ok(stdout)
+    })?;
+    stdout.set("write", write.clone())?;
+    stdout.set("flush", lua.create_function(|_, stdout: Table| Ok(stdout))?)?;
+    stdout.set("setvbuf", lua.create_function(|_, _: MultiValue| Ok(true))?)?;
+    let handle = stdout.clone();
+    io.set(
+        "write",
+        lua.create_function(move |_, args: MultiValue| write.call::<Table>((handle.clone(), args)))?,
+    )?;
+    io.set("stdout", stdout)?;
+
+    let stdin = lua.create_table()?;
+    for name in ["read", "lines", "close"] {
+        let what = format!("io.stdin:{}", name);
+        stdin.set(
+            name,
+            lua.create_function(move |_, _: MultiValue| -> LuaResult<()> {
+                Err(unavailable(&what, "ask with a prompt instead"))
+            })?,
+        )?;
+    }
+    io.set("stdin", stdin)?;
+    io.set(
+        "read",
+        lua.create_function(|_, _: MultiValue| -> LuaResult<()> {
+            Err(unavailable("io.read", "ask with a prompt instead"))
+        })?,
+    )?;
+    for name in ["input", "output"] {
+        let what = format!("io.{}", name);
+        io.set(
+            name,
+            lua.create_function(move |_, _: MultiValue| -> LuaResult<()> {
+                Err(unavailable(&what, "use io.open and the file it returns"))
+            })?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Replace `package.searchpath` and the Lua file searcher, which opens
+/// files without going through `loadfile`, with ones that only find what
+/// the sandbox lets the script read.
+fn install_searcher(lua: &Lua, package: &Table, loadfile: &Function, guard: &Guard) -> LuaResult<()> {
+    // Archetect's own entries — the archetype's, and its staged libraries',
+    // which live outside it — are what `package.path` holds beyond Lua's
+    // defaults.
+    let defaults = Lua::new().globals().get::<Table>("package")?.get::<String>("path")?;
+    let trusted: Vec<String> = package
+        .get::<String>("path")?
+        .split(';')
+        .filter(|template| !template.is_empty() && !defaults.split(';').any(|default| default == *template))
+        .map(str::to_string)
+        .collect();
+    let search = {
+        let guard = guard.clone();
+        move |name: &str, path: &str, separator: &str, replacement: &str| -> LuaResult<Result<String, String>> {
+            let name = if separator.is_empty() { name.to_string() } else { name.replace(separator, replacement) };
+            let mut tried = String::new();
+            for template in path.split(';').filter(|template| !template.is_empty()) {
+                let candidate = template.replace('?', &name);
+                // A file the script may not read is one it can't find.
+                let readable = trusted.iter().any(|trusted| trusted == template)
+                    || guard.permits(&candidate, Access::Read)?;
+                if readable && std::fs::File::open(&candidate).is_ok() {
+                    return Ok(Ok(candidate));
+                }
+                tried.push_str(&format!("\n\tno file '{}'", candidate));
+            }
+            Ok(Err(tried))
+        }
+    };
+    let search = std::rc::Rc::new(search);
+
+    let searchpath = search.clone();
+    package.set(
+        "searchpath",
+        lua.create_function(
+            move |lua, (name, path, separator, replacement): (String, String, Option<String>, Option<String>)| {
+                let separator = separator.unwrap_or_else(|| ".".into());
+                let replacement = replacement.unwrap_or_else(|| "/".into());
+                match searchpath(&name, &path, &separator, &replacement)? {
+                    Ok(found) => found.into_lua_multi(lua),
+                    Err(tried) => (Value::Nil, tried).into_lua_multi(lua),
+                }
+            },
+        )?,
+    )?;
+
+    let loadfile = loadfile.clone();
+    let package_table = package.clone();
+    let searchers: Table = package.get("searchers")?;
+    searchers.set(
+        2,
+        lua.create_function(move |lua, name: String| {
+            let Value::String(path) = package_table.get::<Value>("path")? else {
+                return Err(LuaError::RuntimeError("'package.path' must be a string".into()));
+            };
+            match search(&name, &path.to_string_lossy(), ".", "/")? {
+                Ok(filename) => match loadfile.call::<(Value, Value)>(filename.as_str())? {
+                    (Value::Function(loader), _) => (loader, filename).into_lua_multi(lua),
+                    (_, message) => Err(LuaError::RuntimeError(format!(
+                        "error loading module '{}' from file '{}':\n\t{}",
+                        name,
+                        filename,
+                        message.to_string()?
+                    ))),
+                },
+                Err(tried) => tried.into_lua_multi(lua),
+            }
+        })?,
+    )
+}
+
+/// Replace `table[name]` with a function that runs `check` on the
+/// arguments before handing them to the original.
+fn wrap(
+    lua: &Lua,
+    table: &Table,
+    name: &str,
+    guard: &Guard,
+    check: impl Fn(&Guard, &MultiValue) -> LuaResult<()> + 'static,
+) -> LuaResult<()> {
+    let original: Function = table.get(name)?;
+    let guard = guard.clone();
+    table.set(
+        name,
+        lua.create_function(move |_, args: MultiValue| {
+            check(&guard, &args)?;
+            original.call::<MultiValue>(args)
+        })?,
+    )
+}
+
+/// A string argument. File handles and `nil` aren't paths, so a
+/// `None` here leaves nothing to check.
+fn string_arg(args: &MultiValue, index: usize) -> Option<String> {
+    match args.get(index) {
+        Some(Value::String(value)) => Some(value.to_string_lossy()),
+        _ => None,
+    }
+}
+
+/// The file name at `index`, if one was given. Lua would take a number as
+/// a file name too, which would slip past the guard's checks, so anything
+/// but a string is refused.
+fn path_arg(call: &str, args: &MultiValue, index: usize) -> LuaResult<Option<String>> {
+    match args.get(index) {
+        Some(Value::String(value)) => Ok(Some(value.to_string_lossy())),
+        None | Some(Value::Nil) => Ok(None),
+        Some(other) => Err(LuaError::RuntimeError(format!(
+            "{}: expected a file name, got {}",
+            call,
+            other.type_name()
+        ))),
+    }
+}
+
+fn current_dir() -> String {
+    std::env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default()
+}
+
+/// `path` with every symlink along it resolved and `..` applied after
+/// each, as the OS would. Components that don't exist yet are kept as
+/// written.
+pub(super) fn real_path(path: &Path) -> PathBuf {
+    let mut real = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                real.pop();
+            }
+            Component::CurDir => {}
+            other => {
+                real.push(other);
+                if let Ok(target) = real.canonicalize() {
+                    real = target;
+                }
+            }
+        }
+    }
+    real
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::{Configuration, ShellExecPolicy};
+    use tempfile::TempDir;
+
+    struct Sandbox {
+        lua: Lua,
+        root: TempDir,
+    }
+
+    impl Sandbox {
+        fn new(capabilities: &[&str]) -> Sandbox {
+            Sandbox::build(Some(capabilities))
+        }
+
+        /// A session that enumerated no grants, as MCP's is.
+        fn unrestricted() -> Sandbox {
+            Sandbox::build(None)
+        }
+
+        fn build(capabilities: Option<&[&str]>) -> Sandbox {
+            let root = TempDir::new().unwrap();
+            std::fs::create_dir_all(root.path().join("archetype")).unwrap();
+            std::fs::create_dir_all(root.path().join("destination")).unwrap();
+            std::fs::write(root.path().join("archetype/notes.txt"), "from the archetype").unwrap();
+            std::fs::write(root.path().join("outside.txt"), "secret").unwrap();
+            std::fs::write(root.path().join("archetype/helper.lua"), "return 'helped'").unwrap();
+            std::fs::create_dir_all(root.path().join("staged")).unwrap();
+            std::fs::write(root.path().join("staged/shared.lua"), "return 'shared'").unwrap();
+            let configuration = Configuration::default().with_shell_exec_policy(ShellExecPolicy::Forbidden);
+            let mut builder = Archetect::builder().with_configuration(configuration).with_temp_layout().unwrap();
+            if let Some(capabilities) = capabilities {
+                builder = builder.with_capabilities(capabilities.iter().map(|capability| capability.to_string()));
+            }
+            let archetect = builder.build().unwrap();
+            let lua = Lua::new();
+            let path = |name: &str| Utf8Path::from_path(&root.path().join(name)).unwrap().to_owned();
+            // As the archetype's staged libraries are, outside it.
+            let package: Table = lua.globals().get("package").unwrap();
+            let staged = format!("{}/?.lua;{}", path("staged"), package.get::<String>("path").unwrap());
+            package.set("path", staged).unwrap();
+            install(&lua, &archetect, &path("archetype"), &path("destination")).unwrap();
+            lua.globals().set("root", root.path().to_str().unwrap()).unwrap();
+            Sandbox { lua, root }
+        }
+
+        fn run(&self, code: &str) -> Result<String, String> {
+            self.lua.load(code).eval::<String>().map_err(|err| err.to_string())
+        }
+    }
+
+    #[test]
+    fn file_names_must_be_strings() {
+        let sandbox = Sandbox::new(&[]);
+        let err = sandbox.run(r#"io.open(123, "w"); return "" "#).unwrap_err();
+        assert!(err.contains("io.open: expected a file name, got integer"), "{}", err);
+        let err = sandbox.run(r#"os.rename(1, 2); return "" "#).unwrap_err();
+        assert!(err.contains("os.rename: expected a file name"), "{}", err);
+        assert!(sandbox.run(r#"loadfile(1.5); return "" "#).is_err());
+    }
+
+    #[test]
+    fn files_inside_the_archetype_and_destination_are_open() {
+        let sandbox = Sandbox::new(&[]);
+        let read = sandbox.run(r#"local f = io.open(root .. "/archetype/notes.txt"); return f:read("a")"#);
+        assert_eq!(read.unwrap(), "from the archetype");
+        sandbox
+            .run(r#"local f = io.open(root .. "/destination/out.txt", "w"); f:write("ok"); f:close(); return "" "#)
+            .unwrap();
+        assert!(sandbox.root.path().join("destination/out.txt").exists());
+    }
+
+    #[test]
+    fn files_elsewhere_need_the_filesystem_capability() {
+        let sandbox = Sandbox::new(&[]);
+        let err = sandbox.run(r#"return io.open(root .. "/outside.txt"):read("a")"#).unwrap_err();
+        assert!(err.contains("reads outside the archetype and the destination"), "{}", err);
+        assert!(err.contains("`filesystem` capability") && err.contains("--allow filesystem"), "{}", err);
+
+        let err = sandbox.run(r#"io.open(root .. "/archetype/notes.txt", "a"); return """#).unwrap_err();
+        assert!(err.contains("writes outside the destination"), "{}", err);
+        let err = sandbox.run(r#"os.remove(root .. "/destination/../outside.txt"); return """#).unwrap_err();
+        assert!(err.contains("os.remove"), "{}", err);
+
+        let granted = Sandbox::new(&[FILESYSTEM]);
+        assert_eq!(granted.run(r#"return io.open(root .. "/outside.txt"):read("a")"#).unwrap(), "secret");
+    }
+
+    #[test]
+    fn a_session_without_grants_is_granted_nothing() {
+        let sandbox = Sandbox::unrestricted();
+        let err = sandbox.run(r#"io.open(root .. "/outside.txt", "w"); return """#).unwrap_err();
+        assert!(err.contains("`filesystem` capability"), "{}", err);
+        let err = sandbox.run(r#"return os.getenv("PATH")"#).unwrap_err();
+        assert!(err.contains("`environment` capability"), "{}", err);
+        assert_eq!(std::fs::read_to_string(sandbox.root.path().join("outside.txt")).unwrap(), "secret");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_do_not_lead_out() {
+        let sandbox = Sandbox::new(&[]);
+        let destination = sandbox.root.path().join("destination");
+        std::os::unix::fs::symlink(sandbox.root.path(), destination.join("up")).unwrap();
+        let err = sandbox.run(r#"return io.open(root .. "/destination/up/outside.txt"):read("a")"#).unwrap_err();
+        assert!(err.contains("`filesystem` capability"), "{}", err);
+    }
+
+    #[test]
+    fn processes_go_through_the_shell_policy() {
+        let sandbox = Sandbox::new(&[]);
+        let err = sandbox.run(r#"os.execute("true"); return """#).unwrap_err();
+        assert!(err.contains("Shell execution is forbidden"), "{}", err);
+        let err = sandbox.run(r#"io.popen("ls"); return """#).unwrap_err();
+        assert!(err.contains("Shell execution is forbidden"), "{}", err);
+        let err = sandbox.run(r#"os.exit(1); return """#).unwrap_err();
+        assert!(err.contains("call exit()"), "{}", err);
+        assert_eq!(sandbox.run("return tostring(package.loadlib)").unwrap(), "nil");
+    }
+
+    #[test]
+    fn modules_are_found_where_files_may_be_read() {
+        let sandbox = Sandbox::new(&[]);
+        assert_eq!(sandbox.run(r#"return require("shared")"#).unwrap(), "shared");
+        let helper = r#"package.path = root .. "/archetype/?.lua;" .. package.path; return require("helper")"#;
+        assert_eq!(sandbox.run(helper).unwrap(), "helped");
+
+        let err = sandbox.run(r#"package.path = root .. "/?.txt"; return require("outside")"#).unwrap_err();
+        assert!(err.contains("module 'outside' not found"), "{}", err);
+        assert!(!err.contains("secret"), "{}", err);
+    }
+
+    #[test]
+    fn searchpath_does_not_probe_elsewhere() {
+        let sandbox = Sandbox::new(&[]);
+        let found = sandbox.run(r#"return package.searchpath("notes", root .. "/archetype/?.txt")"#);
+        assert!(found.unwrap().ends_with("archetype/notes.txt"));
+        let missing = sandbox.run(r#"return tostring(package.searchpath("outside", root .. "/?.txt"))"#);
+        assert_eq!(missing.unwrap(), "nil");
+
+        let granted = Sandbox::new(&[FILESYSTEM]);
+        let found = granted.run(r#"return package.searchpath("outside", root .. "/?.txt")"#);
+        assert!(found.unwrap().ends_with("outside.txt"));
+    }
+
+    #[test]
+    fn the_environment_needs_its_capability() {
+        let err = Sandbox::new(&[]).run(r#"return os.getenv("PATH")"#).unwrap_err();
+        assert!(err.contains("os.getenv(\"PATH\")") && err.contains("`environment` capability"), "{}", err);
+        let granted = Sandbox::new(&[ENVIRONMENT]);
+        assert_eq!(granted.run(r#"return tostring(os.getenv("PATH") ~= nil)"#).unwrap(), "true");
+    }
+}
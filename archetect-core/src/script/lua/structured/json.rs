@@ -0,0 +1,201 @@
+//! JSON has no comments to keep, so preserving it means keeping key order,
+//! indentation, and the trailing newline. The document is held as a tree
+//! whose objects remember insertion order and written back with the indent
+//! it was read with.
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::ser::{CompactFormatter, PrettyFormatter};
+use serde_json::Value;
+
+use super::{Backend, Segment};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Node {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    String(String),
+    Array(Vec<Node>),
+    Object(LinkedHashMap<String, Node>),
+}
+
+pub(super) struct JsonDocument {
+    root: Node,
+    /// `None` for a document written on one line.
+    indent: Option<String>,
+    trailing_newline: bool,
+}
+
+impl JsonDocument {
+    pub fn parse(text: &str) -> Result<JsonDocument, String> {
+        let root = if text.trim().is_empty() {
+            Node::Object(LinkedHashMap::new())
+        } else {
+            serde_json::from_str(text).map_err(|error| error.to_string())?
+        };
+        let body = text.trim();
+        let indent = if body.contains('\n') || body.is_empty() {
+            let detected = body
+                .lines()
+                .skip(1)
+                .map(|line| &line[..line.len() - line.trim_start().len()])
+                .find(|indent| !indent.is_empty())
+                .unwrap_or("  ");
+            Some(detected.to_string())
+        } else {
+            None
+        };
+        Ok(JsonDocument {
+            root,
+            indent,
+            trailing_newline: text.ends_with('\n') || text.trim().is_empty(),
+        })
+    }
+}
+
+impl Backend for JsonDocument {
+    fn value(&self) -> Value {
+        Value::from(&self.root)
+    }
+
+    fn set(&mut self, path: &[Segment], value: &Value) -> Result<(), String> {
+        let Some((last, parents)) = path.split_last() else {
+            self.root = Node::from(value);
+            return Ok(());
+        };
+        let mut current = &mut self.root;
+        for segment in parents {
+            if matches!(current, Node::Null) {
+                *current = Node::Object(LinkedHashMap::new());
+            }
+            current = match (current, segment) {
+                (Node::Object(entries), Segment::Key(key)) => {
+                    entries.entry(key.clone()).or_insert_with(|| Node::Object(LinkedHashMap::new()))
+                }
+                (Node::Array(items), Segment::Index(index)) if *index < items.len() => &mut items[*index],
+                _ => return Err("path runs through a value that isn't a table".to_string()),
+            };
+        }
+        if matches!(current, Node::Null) {
+            *current = Node::Object(LinkedHashMap::new());
+        }
+        match (current, last) {
+            (Node::Object(entries), Segment::Key(key)) => {
+                match entries.get_mut(key) {
+                    Some(existing) => *existing = Node::from(value),
+                    None => {
+                        entries.insert(key.clone(), Node::from(value));
+                    }
+                }
+                Ok(())
+            }
+            (Node::Array(items), Segment::Index(index)) if *index == items.len() => {
+                items.push(Node::from(value));
+                Ok(())
+            }
+            (Node::Array(items), Segment::Index(index)) => {
+                items[*index] = Node::from(value);
+                Ok(())
+            }
+            _ => Err("path runs through a value that isn't a table".to_string()),
+        }
+    }
+
+    fn remove(&mut self, path: &[Segment]) -> Result<(), String> {
+        let Some((last, parents)) = path.split_last() else {
+            return Ok(());
+        };
+        let parent = parents.iter().try_fold(&mut self.root, |node, segment| match (node, segment) {
+            (Node::Object(entries), Segment::Key(key)) => entries.get_mut(key),
+            (Node::Array(items), Segment::Index(index)) => items.get_mut(*index),
+            _ => None,
+        });
+        match (parent, last) {
+            (Some(Node::Object(entries)), Segment::Key(key)) => {
+                entries.remove(key);
+            }
+            (Some(Node::Array(items)), Segment::Index(index)) if *index < items.len() => {
+                items.remove(*index);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        let written = match &self.indent {
+            Some(indent) => {
+                let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+                self.root.serialize(&mut serde_json::Serializer::with_formatter(&mut buffer, formatter))
+            }
+            None => self
+                .root
+                .serialize(&mut serde_json::Serializer::with_formatter(&mut buffer, CompactFormatter)),
+        };
+        written.expect("JSON values always serialize");
+        let mut rendered = String::from_utf8(buffer).expect("serde_json writes UTF-8");
+        if self.trailing_newline {
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
+
+impl From<&Node> for Value {
+    fn from(node: &Node) -> Value {
+        match node {
+            Node::Null => Value::Null,
+            Node::Bool(value) => Value::Bool(*value),
+            Node::Number(value) => Value::Number(value.clone()),
+            Node::String(value) => Value::String(value.clone()),
+            Node::Array(items) => Value::Array(items.iter().map(Value::from).collect()),
+            Node::Object(entries) => {
+                Value::Object(entries.iter().map(|(key, node)| (key.clone(), Value::from(node))).collect())
+            }
+        }
+    }
+}
+
+impl From<&Value> for Node {
+    fn from(value: &Value) -> Node {
+        match value {
+            Value::Null => Node::Null,
+            Value::Bool(value) => Node::Bool(*value),
+            Value::Number(value) => Node::Number(value.clone()),
+            Value::String(value) => Node::String(value.clone()),
+            Value::Array(items) => Node::Array(items.iter().map(Node::from).collect()),
+            Value::Object(entries) => {
+                Node::Object(entries.iter().map(|(key, value)| (key.clone(), Node::from(value))).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::super::{parse_path, Document, Format};
+
+    #[test]
+    fn test_keeps_key_order_and_indent() {
+        let text = "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"zod\": \"^3\"\n    }\n}\n";
+        let mut document = Document::parse(Format::Json, text).unwrap();
+        document.set(&parse_path("dependencies.axios"), json!("^1")).unwrap();
+        document.set(&parse_path("version"), json!("1.0.0")).unwrap();
+        assert_eq!(
+            document.render(),
+            "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"zod\": \"^3\",\n        \"axios\": \"^1\"\n    },\n    \"version\": \"1.0.0\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_unchanged_document_renders_identically() {
+        let text = "{\n  \"b\": 1,\n  \"a\": [\n    true,\n    null\n  ]\n}";
+        let document = Document::parse(Format::Json, text).unwrap();
+        assert_eq!(document.render(), text);
+    }
+}
@@ -0,0 +1,339 @@
+//! Format-preserving edits of TOML, JSON, and YAML files.
+//!
+//! `file.edit_structured` loads a destination file into a [`Document`],
+//! hands it to a Lua function to change, and writes back only what changed:
+//! comments, key order, and layout elsewhere in the file are left as they
+//! were. Each format has its own backend — `toml_edit` for TOML, an
+//! order-keeping tree for JSON, and a line-level editor for block-style
+//! YAML — behind one set of operations addressed by [`path`](parse_path)s.
+//!
+//! Values cross in and out as `serde_json::Value`, the same currency the
+//! rest of the Lua bindings use.
+
+use serde_json::Value;
+
+mod json;
+mod toml;
+mod yaml;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// The format named by `name` (`"toml"`, `"json"`, `"yaml"`/`"yml"`).
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+
+    /// The format implied by a file name's extension.
+    pub fn from_path(path: &str) -> Option<Format> {
+        let extension = path.rsplit_once('.').map(|(_, extension)| extension)?;
+        Format::from_name(extension)
+    }
+}
+
+/// One step of a path: a key into a table, or a zero-based index into an
+/// array. An index one past the end addresses the slot an append fills.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// What each format's backend provides. Paths arrive resolved against the
+/// document's current value, so a backend never has to guess whether `"1"`
+/// is a key or an index.
+trait Backend {
+    /// The whole document as a plain value.
+    fn value(&self) -> Value;
+    /// Set the value at `path`, creating missing tables along the way. An
+    /// index one past the end of an array appends.
+    fn set(&mut self, path: &[Segment], value: &Value) -> Result<(), String>;
+    /// Remove the value at `path`, which exists.
+    fn remove(&mut self, path: &[Segment]) -> Result<(), String>;
+    fn render(&self) -> String;
+}
+
+pub(crate) struct Document {
+    backend: Box<dyn Backend>,
+}
+
+impl Document {
+    pub fn parse(format: Format, text: &str) -> Result<Document, String> {
+        let backend: Box<dyn Backend> = match format {
+            Format::Toml => Box::new(toml::TomlDocument::parse(text)?),
+            Format::Json => Box::new(json::JsonDocument::parse(text)?),
+            Format::Yaml => Box::new(yaml::YamlDocument::parse(text)?),
+        };
+        Ok(Document { backend })
+    }
+
+    pub fn render(&self) -> String {
+        self.backend.render()
+    }
+
+    pub fn get(&self, path: &[String]) -> Option<Value> {
+        let root = self.backend.value();
+        let resolved = resolve(&root, path).ok()?;
+        lookup(&root, &resolved).cloned()
+    }
+
+    /// Set `path` to `value`. Setting a value to what it already is leaves
+    /// the file untouched.
+    pub fn set(&mut self, path: &[String], value: Value) -> Result<(), String> {
+        if path.is_empty() {
+            return Err("can't replace the whole document; use merge".to_string());
+        }
+        let root = self.backend.value();
+        let resolved = resolve(&root, path)?;
+        if lookup(&root, &resolved) == Some(&value) {
+            return Ok(());
+        }
+        self.backend.set(&resolved, &value)
+    }
+
+    /// Remove `path`. Returns whether there was anything there.
+    pub fn remove(&mut self, path: &[String]) -> Result<bool, String> {
+        if path.is_empty() {
+            return Err("can't remove the whole document".to_string());
+        }
+        let root = self.backend.value();
+        let resolved = resolve(&root, path)?;
+        if lookup(&root, &resolved).is_none() {
+            return Ok(false);
+        }
+        self.backend.remove(&resolved)?;
+        Ok(true)
+    }
+
+    /// Append `value` to the array at `path` unless an equal element is
+    /// already there, creating the array if need be. Returns whether it was
+    /// added.
+    pub fn add(&mut self, path: &[String], value: Value) -> Result<bool, String> {
+        match self.get(path) {
+            None => {
+                self.set(path, Value::Array(vec![value]))?;
+                Ok(true)
+            }
+            Some(Value::Array(items)) if items.contains(&value) => Ok(false),
+            Some(Value::Array(items)) => {
+                let mut appended = path.to_vec();
+                appended.push((items.len() + 1).to_string());
+                self.set(&appended, value)?;
+                Ok(true)
+            }
+            Some(_) => Err(format!("{} is not an array", display_path(path))),
+        }
+    }
+
+    /// Deep-merge `value` into `path`: tables merge key by key, anything
+    /// else is set outright.
+    pub fn merge(&mut self, path: &[String], value: Value) -> Result<(), String> {
+        match value {
+            Value::Object(entries) if path.is_empty() || matches!(self.get(path), Some(Value::Object(_))) => {
+                for (key, entry) in entries {
+                    let mut child = path.to_vec();
+                    child.push(key);
+                    self.merge(&child, entry)?;
+                }
+                Ok(())
+            }
+            value if path.is_empty() => Err(format!("can't merge {} into the whole document", type_name(&value))),
+            value => self.set(path, value),
+        }
+    }
+}
+
+/// Split a dotted path string into segments. Empty segments are dropped, so
+/// `""` addresses the document itself.
+pub(crate) fn parse_path(path: &str) -> Vec<String> {
+    path.split('.').filter(|segment| !segment.is_empty()).map(str::to_string).collect()
+}
+
+pub(crate) fn display_path(path: &[String]) -> String {
+    if path.is_empty() {
+        "the document".to_string()
+    } else {
+        format!("'{}'", path.join("."))
+    }
+}
+
+/// Turn user-facing segments into [`Segment`]s, reading a segment as a
+/// one-based index wherever the value it steps into is an array.
+fn resolve(root: &Value, path: &[String]) -> Result<Vec<Segment>, String> {
+    let mut resolved = Vec::with_capacity(path.len());
+    let mut current = Some(root);
+    for (depth, segment) in path.iter().enumerate() {
+        let step = match current {
+            Some(Value::Array(items)) => {
+                let index = segment
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| (1..=items.len() + 1).contains(index))
+                    .ok_or_else(|| {
+                        format!(
+                            "{} is an array of {}; '{}' is not an index into it",
+                            display_path(&path[..depth]),
+                            items.len(),
+                            segment
+                        )
+                    })?;
+                Segment::Index(index - 1)
+            }
+            Some(Value::Object(_)) | Some(Value::Null) | None => Segment::Key(segment.clone()),
+            Some(other) if depth == 0 => return Err(format!("the document is {}, not a table", type_name(other))),
+            Some(other) => {
+                return Err(format!(
+                    "{} is {}, not a table",
+                    display_path(&path[..depth]),
+                    type_name(other)
+                ))
+            }
+        };
+        current = current.and_then(|value| lookup(value, std::slice::from_ref(&step)));
+        resolved.push(step);
+    }
+    Ok(resolved)
+}
+
+fn lookup<'a>(root: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    path.iter().try_fold(root, |value, segment| match (value, segment) {
+        (Value::Object(entries), Segment::Key(key)) => entries.get(key),
+        (Value::Array(items), Segment::Index(index)) => items.get(*index),
+        _ => None,
+    })
+}
+
+/// `value` wrapped in a table for each key of `path`, innermost last: the
+/// value to insert where a path first leaves the document.
+fn nest(path: &[Segment], value: &Value) -> Value {
+    path.iter().rev().fold(value.clone(), |inner, segment| {
+        let mut entries = serde_json::Map::new();
+        let key = match segment {
+            Segment::Key(key) => key.clone(),
+            Segment::Index(index) => (index + 1).to_string(),
+        };
+        entries.insert(key, inner);
+        Value::Object(entries)
+    })
+}
+
+/// Apply a [`Backend::set`] to a plain value, for backends that check their
+/// work against it.
+fn set_value(root: &mut Value, path: &[Segment], value: &Value) {
+    let Some((last, parents)) = path.split_last() else {
+        *root = value.clone();
+        return;
+    };
+    let mut current = root;
+    for segment in parents {
+        if current.is_null() {
+            *current = Value::Object(serde_json::Map::new());
+        }
+        current = match (current, segment) {
+            (Value::Object(entries), Segment::Key(key)) => {
+                entries.entry(key.clone()).or_insert_with(|| Value::Object(serde_json::Map::new()))
+            }
+            (Value::Array(items), Segment::Index(index)) => &mut items[*index],
+            _ => return,
+        };
+    }
+    if current.is_null() {
+        *current = Value::Object(serde_json::Map::new());
+    }
+    match (current, last) {
+        (Value::Object(entries), Segment::Key(key)) => {
+            entries.insert(key.clone(), value.clone());
+        }
+        (Value::Array(items), Segment::Index(index)) if *index == items.len() => items.push(value.clone()),
+        (Value::Array(items), Segment::Index(index)) => items[*index] = value.clone(),
+        _ => {}
+    }
+}
+
+fn remove_value(root: &mut Value, path: &[Segment]) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let parent = parents.iter().try_fold(root, |value, segment| match (value, segment) {
+        (Value::Object(entries), Segment::Key(key)) => entries.get_mut(key),
+        (Value::Array(items), Segment::Index(index)) => items.get_mut(*index),
+        _ => None,
+    });
+    match (parent, last) {
+        (Some(Value::Object(entries)), Segment::Key(key)) => {
+            entries.remove(key);
+        }
+        (Some(Value::Array(items)), Segment::Index(index)) if *index < items.len() => {
+            items.remove(*index);
+        }
+        _ => {}
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "a table",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn path(path: &str) -> Vec<String> {
+        parse_path(path)
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path("Cargo.toml"), Some(Format::Toml));
+        assert_eq!(Format::from_path("config/app.yml"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("package.json"), Some(Format::Json));
+        assert_eq!(Format::from_path("Makefile"), None);
+    }
+
+    #[test]
+    fn test_array_segments_are_one_based() {
+        let root = json!({ "items": ["a", "b"] });
+        assert_eq!(
+            resolve(&root, &path("items.1")).unwrap(),
+            vec![Segment::Key("items".into()), Segment::Index(0)]
+        );
+        assert_eq!(resolve(&root, &path("items.3")).unwrap()[1], Segment::Index(2));
+        assert!(resolve(&root, &path("items.0")).is_err());
+        assert!(resolve(&root, &path("items.name")).is_err());
+    }
+
+    #[test]
+    fn test_add_skips_equal_elements() {
+        let mut document = Document::parse(Format::Json, "{\"tags\": [\"a\"]}").unwrap();
+        assert!(!document.add(&path("tags"), json!("a")).unwrap());
+        assert!(document.add(&path("tags"), json!("b")).unwrap());
+        assert!(document.add(&path("more"), json!(1)).unwrap());
+        assert_eq!(document.get(&[]).unwrap(), json!({ "tags": ["a", "b"], "more": [1] }));
+    }
+
+    #[test]
+    fn test_merge_is_deep() {
+        let mut document = Document::parse(Format::Json, r#"{"a": {"b": 1, "c": 2}}"#).unwrap();
+        document.merge(&[], json!({ "a": { "c": 3, "d": 4 } })).unwrap();
+        assert_eq!(document.get(&[]).unwrap(), json!({ "a": { "b": 1, "c": 3, "d": 4 } }));
+    }
+}
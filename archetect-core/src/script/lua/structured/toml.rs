@@ -0,0 +1,295 @@
+//! TOML edits through `toml_edit`, which keeps comments and whitespace
+//! attached to the items around them. A replaced value keeps the decor of
+//! the one it replaces; an appended array element copies its neighbour's, so
+//! a one-per-line list stays one per line.
+
+use serde_json::Value as Json;
+use toml_edit::{Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, Value};
+
+use super::{Backend, Segment};
+
+pub(super) struct TomlDocument {
+    document: DocumentMut,
+}
+
+impl TomlDocument {
+    pub fn parse(text: &str) -> Result<TomlDocument, String> {
+        let document = text.parse::<DocumentMut>().map_err(|error| error.to_string().trim_end().to_string())?;
+        Ok(TomlDocument { document })
+    }
+}
+
+impl Backend for TomlDocument {
+    fn value(&self) -> Json {
+        item_to_json(self.document.as_item()).unwrap_or(Json::Null)
+    }
+
+    fn set(&mut self, path: &[Segment], value: &Json) -> Result<(), String> {
+        let Some((last, parents)) = path.split_last() else {
+            return Err("can't replace the whole document".to_string());
+        };
+        let mut current = self.document.as_item_mut();
+        for segment in parents {
+            current = child_or_insert(current, segment)?;
+        }
+        match last {
+            Segment::Key(key) => {
+                let standard = current.is_table();
+                let slot = current.get_mut(key.as_str()).ok_or_else(|| not_a_table(key))?;
+                let mut replacement = match (&*slot, value) {
+                    (Item::Table(_), Json::Object(_)) => Item::Table(to_table(value)?),
+                    (Item::ArrayOfTables(_), Json::Array(items)) if items.iter().all(Json::is_object) => {
+                        Item::ArrayOfTables(to_array_of_tables(items)?)
+                    }
+                    (Item::None, Json::Object(_)) if standard && parents.is_empty() => Item::Table(to_table(value)?),
+                    _ => Item::Value(to_value(value)?),
+                };
+                if let (Item::Value(old), Item::Value(new)) = (&*slot, &mut replacement) {
+                    *new.decor_mut() = old.decor().clone();
+                }
+                *slot = replacement;
+            }
+            Segment::Index(index) => {
+                if let Some(tables) = current.as_array_of_tables_mut() {
+                    let table = to_table(value)?;
+                    if *index == tables.len() {
+                        tables.push(table);
+                    } else {
+                        tables.replace(*index, table);
+                    }
+                } else if let Some(array) = current.as_array_mut() {
+                    let mut element = to_value(value)?;
+                    if *index == array.len() {
+                        if let Some(previous) = array.get(array.len().saturating_sub(1)) {
+                            let prefix = previous.decor().prefix().and_then(|prefix| prefix.as_str()).unwrap_or("");
+                            if array.len() > 1 || prefix.contains('\n') {
+                                *element.decor_mut() = previous.decor().clone();
+                            }
+                        }
+                        array.push_formatted(element);
+                    } else {
+                        *element.decor_mut() = array.get(*index).map(|old| old.decor().clone()).unwrap_or_default();
+                        array.replace_formatted(*index, element);
+                    }
+                } else {
+                    return Err("path runs through a value that isn't an array".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &[Segment]) -> Result<(), String> {
+        let Some((last, parents)) = path.split_last() else {
+            return Ok(());
+        };
+        let mut current = self.document.as_item_mut();
+        for segment in parents {
+            current = match segment {
+                Segment::Key(key) => current.get_mut(key.as_str()),
+                Segment::Index(index) => current.get_mut(*index),
+            }
+            .ok_or_else(|| "path not found".to_string())?;
+        }
+        match last {
+            Segment::Key(key) => {
+                if let Some(table) = current.as_table_like_mut() {
+                    table.remove(key);
+                }
+            }
+            Segment::Index(index) => {
+                if let Some(tables) = current.as_array_of_tables_mut() {
+                    tables.remove(*index);
+                } else if let Some(array) = current.as_array_mut() {
+                    let removed = array.remove(*index);
+                    // The new first element takes over the old one's
+                    // leading whitespace, so `["a", "b"]` becomes `["b"]`.
+                    if *index == 0 {
+                        if let Some(first) = array.get_mut(0) {
+                            first.decor_mut().set_prefix(removed.decor().prefix().cloned().unwrap_or_default());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self) -> String {
+        self.document.to_string()
+    }
+}
+
+/// The child of `item` at `segment`, created if it is a missing key: an
+/// implicit table under a standard table, so `[a.b]` appears only once it
+/// has keys of its own, or an inline table under an inline one.
+fn child_or_insert<'a>(item: &'a mut Item, segment: &Segment) -> Result<&'a mut Item, String> {
+    match segment {
+        Segment::Key(key) => {
+            let standard = item.is_table();
+            let child = item.get_mut(key.as_str()).ok_or_else(|| not_a_table(key))?;
+            if child.is_none() {
+                *child = if standard {
+                    let mut table = Table::new();
+                    table.set_implicit(true);
+                    Item::Table(table)
+                } else {
+                    Item::Value(Value::InlineTable(InlineTable::new()))
+                };
+            }
+            Ok(child)
+        }
+        Segment::Index(index) => item
+            .get_mut(*index)
+            .ok_or_else(|| format!("no element {} to descend into", index + 1)),
+    }
+}
+
+fn not_a_table(key: &str) -> String {
+    format!("can't set '{}': its parent isn't a table", key)
+}
+
+fn to_value(value: &Json) -> Result<Value, String> {
+    Ok(match value {
+        Json::Null => return Err("TOML has no null; use remove to delete a key".to_string()),
+        Json::Bool(value) => Value::from(*value),
+        Json::Number(number) => match (number.as_i64(), number.as_f64()) {
+            (Some(integer), _) => Value::from(integer),
+            (None, Some(float)) if !number.is_u64() => Value::from(float),
+            _ => return Err(format!("{} doesn't fit in a TOML integer", number)),
+        },
+        Json::String(value) => Value::from(value.as_str()),
+        Json::Array(items) => {
+            let mut array = Array::new();
+            for item in items {
+                array.push(to_value(item)?);
+            }
+            Value::Array(array)
+        }
+        Json::Object(entries) => {
+            let mut table = InlineTable::new();
+            for (key, entry) in entries {
+                table.insert(key.as_str(), to_value(entry)?);
+            }
+            Value::InlineTable(table)
+        }
+    })
+}
+
+fn to_table(value: &Json) -> Result<Table, String> {
+    let Json::Object(entries) = value else {
+        return Err("expected a table".to_string());
+    };
+    let mut table = Table::new();
+    for (key, entry) in entries {
+        table.insert(key, Item::Value(to_value(entry)?));
+    }
+    Ok(table)
+}
+
+fn to_array_of_tables(items: &[Json]) -> Result<ArrayOfTables, String> {
+    let mut tables = ArrayOfTables::new();
+    for item in items {
+        tables.push(to_table(item)?);
+    }
+    Ok(tables)
+}
+
+fn item_to_json(item: &Item) -> Option<Json> {
+    match item {
+        Item::None => None,
+        Item::Value(value) => Some(value_to_json(value)),
+        Item::Table(_) => item.as_table_like().map(table_to_json),
+        Item::ArrayOfTables(tables) => Some(Json::Array(
+            tables.iter().map(|table| table_to_json(table)).collect(),
+        )),
+    }
+}
+
+fn table_to_json(table: &dyn toml_edit::TableLike) -> Json {
+    Json::Object(
+        table
+            .iter()
+            .filter_map(|(key, item)| item_to_json(item).map(|value| (key.to_string(), value)))
+            .collect(),
+    )
+}
+
+fn value_to_json(value: &Value) -> Json {
+    match value {
+        Value::String(value) => Json::String(value.value().clone()),
+        Value::Integer(value) => Json::from(*value.value()),
+        Value::Float(value) => serde_json::Number::from_f64(*value.value()).map(Json::Number).unwrap_or(Json::Null),
+        Value::Boolean(value) => Json::Bool(*value.value()),
+        Value::Datetime(value) => Json::String(value.value().to_string()),
+        Value::Array(array) => Json::Array(array.iter().map(value_to_json).collect()),
+        Value::InlineTable(table) => table_to_json(table),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::super::{parse_path, Document, Format};
+
+    const CARGO: &str = r#"[package]
+name = "app" # the crate name
+version = "0.1.0"
+
+# Runtime dependencies
+[dependencies]
+serde = "1"   # keep in step with serde_json
+
+[features]
+default = [
+    "std",
+]
+"#;
+
+    #[test]
+    fn test_adds_a_dependency_keeping_comments() {
+        let mut document = Document::parse(Format::Toml, CARGO).unwrap();
+        document
+            .set(&parse_path("dependencies.tokio"), json!({ "features": ["full"], "version": "1" }))
+            .unwrap();
+        document.set(&parse_path("package.version"), json!("0.2.0")).unwrap();
+        assert!(document.add(&parse_path("features.default"), json!("serde")).unwrap());
+        assert_eq!(
+            document.render(),
+            r#"[package]
+name = "app" # the crate name
+version = "0.2.0"
+
+# Runtime dependencies
+[dependencies]
+serde = "1"   # keep in step with serde_json
+tokio = { features = ["full"], version = "1" }
+
+[features]
+default = [
+    "std",
+    "serde",
+]
+"#
+        );
+    }
+
+    #[test]
+    fn test_new_top_level_table_is_a_section() {
+        let mut document = Document::parse(Format::Toml, "[package]\nname = \"app\"\n").unwrap();
+        document.set(&parse_path("lints.rust.unsafe_code"), json!("forbid")).unwrap();
+        assert_eq!(
+            document.render(),
+            "[package]\nname = \"app\"\n\n[lints.rust]\nunsafe_code = \"forbid\"\n"
+        );
+    }
+
+    #[test]
+    fn test_remove_keeps_the_rest() {
+        let mut document = Document::parse(Format::Toml, CARGO).unwrap();
+        assert!(document.remove(&parse_path("dependencies.serde")).unwrap());
+        assert!(!document.remove(&parse_path("dependencies.serde")).unwrap());
+        assert!(document.render().contains("# Runtime dependencies\n[dependencies]\n\n[features]"));
+    }
+}
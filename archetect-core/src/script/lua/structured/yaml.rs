@@ -0,0 +1,585 @@
+//! YAML edits made line by line, so that comments, blank lines, quoting, and
+//! the rest of the file's layout survive them.
+//!
+//! Block-style YAML — the kind people write by hand — is laid out by
+//! indentation: an entry owns every following line indented deeper than it.
+//! That is enough to find the lines behind any path and to replace, insert,
+//! or delete them. A scalar is replaced in place, keeping its trailing
+//! comment; anything else is re-rendered from the entry down, in the
+//! document's own indent.
+//!
+//! Every edit is checked by parsing the result and comparing it with what
+//! the edit should have produced. Anchors, flow-style documents, and the
+//! like that this reading doesn't cover fail that check, and the edit is
+//! refused rather than risk the file.
+
+use serde_json::Value;
+
+use super::{lookup, nest, remove_value, set_value, Backend, Segment};
+
+pub(super) struct YamlDocument {
+    lines: Vec<String>,
+    newline: &'static str,
+    trailing_newline: bool,
+}
+
+/// Where a block collection's entries sit: the first at `col` on `line`
+/// (which may be partway along it, as in `- name: x`), the rest on lines
+/// indented to `col`, up to `end`.
+#[derive(Clone, Copy, Debug)]
+struct Region {
+    line: usize,
+    col: usize,
+    end: usize,
+}
+
+/// One entry of a block collection: a `key:` of a mapping, or a `-` item of
+/// a sequence.
+#[derive(Clone, Debug)]
+struct Node {
+    line: usize,
+    col: usize,
+    /// The line after the entry's last; trailing blank and comment lines are
+    /// left to whatever follows.
+    end: usize,
+    key: Option<String>,
+    /// Where the entry's value starts on its first line.
+    value_col: usize,
+}
+
+impl YamlDocument {
+    pub fn parse(text: &str) -> Result<YamlDocument, String> {
+        serde_yaml::from_str::<Value>(text).map_err(|error| error.to_string())?;
+        Ok(YamlDocument {
+            lines: text.lines().map(str::to_string).collect(),
+            newline: if text.contains("\r\n") { "\r\n" } else { "\n" },
+            trailing_newline: text.ends_with('\n') || text.is_empty(),
+        })
+    }
+
+    fn apply(&mut self, expected: &Value, edit: impl FnOnce(&mut YamlDocument) -> Result<(), String>) -> Result<(), String> {
+        let original = self.lines.clone();
+        let result = edit(self).and_then(|()| {
+            let mut actual = self.value();
+            if actual.is_null() && expected.as_object().is_some_and(serde_json::Map::is_empty) {
+                actual = expected.clone();
+            }
+            if &actual == expected {
+                Ok(())
+            } else {
+                Err("this part of the YAML can't be edited without reformatting it".to_string())
+            }
+        });
+        if result.is_err() {
+            self.lines = original;
+        }
+        result
+    }
+
+    fn set_lines(&mut self, path: &[Segment], value: &Value, expected: &Value) -> Result<(), String> {
+        let Some(mut region) = self.root_region() else {
+            let rendered = render_block(expected, self.step());
+            self.lines.extend(rendered);
+            return Ok(());
+        };
+        for (depth, segment) in path.iter().enumerate() {
+            let nodes = self.entries(region)?;
+            let sequence = nodes.first().is_some_and(|node| node.key.is_none());
+            let found = match segment {
+                Segment::Key(key) if !sequence => nodes.iter().find(|node| node.key.as_ref() == Some(key)),
+                Segment::Index(index) if sequence => nodes.get(*index),
+                _ => return Err(unsupported()),
+            };
+            let Some(node) = found else {
+                let entry = match segment {
+                    Segment::Key(key) => render_entry(Some(key), &nest(&path[depth + 1..], value), self.step()),
+                    Segment::Index(_) => render_entry(None, &nest(&path[depth + 1..], value), self.step()),
+                };
+                let at = nodes.last().map_or(region.end, |last| last.end);
+                let indent = " ".repeat(region.col);
+                self.lines.splice(at..at, entry.into_iter().map(|line| format!("{}{}", indent, line)));
+                return Ok(());
+            };
+            if depth + 1 == path.len() {
+                return self.replace(node, value);
+            }
+            match self.block_region(node) {
+                Some(children) => region = children,
+                None => {
+                    let replacement = lookup(expected, &path[..=depth]).ok_or_else(unsupported)?;
+                    return self.rewrite(node, replacement);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_lines(&mut self, path: &[Segment], expected: &Value) -> Result<(), String> {
+        let mut region = self.root_region().ok_or_else(unsupported)?;
+        let mut owner: Option<(Node, usize)> = None;
+        for (depth, segment) in path.iter().enumerate() {
+            let nodes = self.entries(region)?;
+            let found = match segment {
+                Segment::Key(key) => nodes.iter().find(|node| node.key.as_ref() == Some(key)),
+                Segment::Index(index) => nodes.get(*index).filter(|node| node.key.is_none()),
+            }
+            .ok_or_else(unsupported)?;
+            if depth + 1 < path.len() {
+                match self.block_region(found) {
+                    Some(children) => {
+                        owner = Some((found.clone(), depth));
+                        region = children;
+                        continue;
+                    }
+                    None => {
+                        let replacement = lookup(expected, &path[..=depth]).ok_or_else(unsupported)?;
+                        return self.rewrite(found, replacement);
+                    }
+                }
+            }
+            // The last entry of a collection, or the first key of a `- key:`
+            // item, can't just be cut out: re-render what holds it instead.
+            let shares_line = found.col > indent_of(&self.lines[found.line]);
+            if nodes.len() == 1 || shares_line {
+                if let Some((owner, owner_depth)) = owner {
+                    let replacement = lookup(expected, &path[..=owner_depth]).ok_or_else(unsupported)?;
+                    return self.rewrite(&owner, replacement);
+                }
+            }
+            self.lines.drain(found.line..found.end);
+            return Ok(());
+        }
+        Ok(())
+    }
+
+    /// Give `node` a new value: in place when a scalar (or a one-line flow
+    /// collection) replaces another, keeping any trailing comment.
+    fn replace(&mut self, node: &Node, value: &Value) -> Result<(), String> {
+        let line = &self.lines[node.line];
+        let rest = &line[node.value_col..];
+        let value_end = comment_start(rest).unwrap_or(rest.len());
+        let inline = rest[..value_end].trim_end();
+        let in_place = node.end == node.line + 1
+            && !inline.is_empty()
+            && !inline.starts_with(['|', '>', '&', '!', '*'])
+            && (!is_collection(value) || inline.starts_with(['[', '{']));
+        if !in_place {
+            return self.rewrite(node, value);
+        }
+        let rendered = if is_collection(value) {
+            serde_json::to_string(value).map_err(|error| error.to_string())?
+        } else {
+            render_inline(value)
+        };
+        let replaced = format!(
+            "{}{}{}",
+            &line[..node.value_col],
+            rendered,
+            &rest[inline.len()..]
+        );
+        self.lines[node.line] = replaced;
+        Ok(())
+    }
+
+    /// Replace every line of `node` with a fresh rendering of it.
+    fn rewrite(&mut self, node: &Node, value: &Value) -> Result<(), String> {
+        let line = &self.lines[node.line];
+        let prefix = line[..node.col].to_string();
+        let entry = render_entry(node.key.as_deref(), value, self.step());
+        let indent = " ".repeat(node.col);
+        let rendered: Vec<String> = entry
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| if index == 0 { format!("{}{}", prefix, text) } else { format!("{}{}", indent, text) })
+            .collect();
+        self.lines.splice(node.line..node.end, rendered);
+        Ok(())
+    }
+
+    fn root_region(&self) -> Option<Region> {
+        let line = self.lines.iter().position(|line| is_content(line))?;
+        Some(Region {
+            line,
+            col: indent_of(&self.lines[line]),
+            end: self.lines.len(),
+        })
+    }
+
+    /// The entries of the collection at `region`.
+    fn entries(&self, region: Region) -> Result<Vec<Node>, String> {
+        let sequence = is_item(&self.lines[region.line][region.col..]);
+        let mut starts = vec![(region.line, region.col)];
+        let mut end = region.end;
+        for index in region.line + 1..region.end {
+            let line = &self.lines[index];
+            if !is_content(line) {
+                continue;
+            }
+            let indent = indent_of(line);
+            if indent < region.col || (indent == region.col && sequence && !is_item(&line[indent..])) {
+                end = index;
+                break;
+            }
+            // A sequence may sit at its key's own indent; its items belong
+            // to that key, not to the mapping.
+            if indent == region.col && (sequence || !is_item(&line[indent..])) {
+                starts.push((index, indent));
+            }
+        }
+        let mut nodes = Vec::with_capacity(starts.len());
+        for (position, &(line, col)) in starts.iter().enumerate() {
+            let mut node_end = starts.get(position + 1).map_or(end, |&(next, _)| next);
+            while node_end > line + 1 && !is_content(&self.lines[node_end - 1]) {
+                node_end -= 1;
+            }
+            nodes.push(self.parse_entry(line, col, node_end)?);
+        }
+        Ok(nodes)
+    }
+
+    fn parse_entry(&self, line: usize, col: usize, end: usize) -> Result<Node, String> {
+        let text = &self.lines[line][col..];
+        if is_item(text) {
+            let after = &text[1..];
+            let value_col = col + 1 + (after.len() - after.trim_start().len());
+            return Ok(Node {
+                line,
+                col,
+                end,
+                key: None,
+                value_col,
+            });
+        }
+        let (key, colon) = parse_key(text).ok_or_else(unsupported)?;
+        let after = &text[colon + 1..];
+        Ok(Node {
+            line,
+            col,
+            end,
+            key: Some(key),
+            value_col: col + colon + 1 + (after.len() - after.trim_start().len()),
+        })
+    }
+
+    /// Where `node`'s value lays out its own entries, if it is a block
+    /// collection.
+    fn block_region(&self, node: &Node) -> Option<Region> {
+        let rest = &self.lines[node.line][node.value_col..];
+        let inline = &rest[..comment_start(rest).unwrap_or(rest.len())];
+        if !inline.trim().is_empty() {
+            let nested = node.key.is_none() && (is_item(inline) || parse_key(inline).is_some());
+            return nested.then_some(Region {
+                line: node.line,
+                col: node.value_col,
+                end: node.end,
+            });
+        }
+        let first = (node.line + 1..node.end).find(|&index| is_content(&self.lines[index]))?;
+        Some(Region {
+            line: first,
+            col: indent_of(&self.lines[first]),
+            end: node.end,
+        })
+    }
+
+    /// The indent the document nests mappings by, for rendering new ones.
+    fn step(&self) -> usize {
+        let content: Vec<&String> = self.lines.iter().filter(|line| is_content(line)).collect();
+        content
+            .windows(2)
+            .find_map(|pair| {
+                let (parent, child) = (indent_of(pair[0]), indent_of(pair[1]));
+                (pair[0].trim_end().ends_with(':') && child > parent).then(|| child - parent)
+            })
+            .unwrap_or(2)
+    }
+}
+
+impl Backend for YamlDocument {
+    fn value(&self) -> Value {
+        serde_yaml::from_str(&self.render()).unwrap_or(Value::Null)
+    }
+
+    fn set(&mut self, path: &[Segment], value: &Value) -> Result<(), String> {
+        let mut expected = self.value();
+        set_value(&mut expected, path, value);
+        self.apply(&expected, |document| document.set_lines(path, value, &expected))
+    }
+
+    fn remove(&mut self, path: &[Segment]) -> Result<(), String> {
+        let mut expected = self.value();
+        remove_value(&mut expected, path);
+        self.apply(&expected, |document| document.remove_lines(path, &expected))
+    }
+
+    fn render(&self) -> String {
+        let mut rendered = self.lines.join(self.newline);
+        if self.trailing_newline && !self.lines.is_empty() {
+            rendered.push_str(self.newline);
+        }
+        rendered
+    }
+}
+
+fn unsupported() -> String {
+    "this part of the YAML isn't in a block layout that can be edited in place".to_string()
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Whether a line carries part of the document, rather than being blank, a
+/// comment, or a document marker.
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !(trimmed.is_empty()
+        || trimmed.starts_with('#')
+        || trimmed.starts_with('%')
+        || trimmed == "---"
+        || trimmed == "...")
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Split `key: ...` into its key and the offset of the `:`.
+fn parse_key(text: &str) -> Option<(String, usize)> {
+    if text.starts_with(['"', '\'']) {
+        let quote = text.as_bytes()[0];
+        let mut index = 1;
+        let bytes = text.as_bytes();
+        while index < bytes.len() {
+            if quote == b'"' && bytes[index] == b'\\' {
+                index += 2;
+                continue;
+            }
+            if bytes[index] == quote {
+                if quote == b'\'' && bytes.get(index + 1) == Some(&b'\'') {
+                    index += 2;
+                    continue;
+                }
+                break;
+            }
+            index += 1;
+        }
+        let colon = index + 1;
+        if text[colon..].starts_with(':') && is_separator(&text[colon + 1..]) {
+            let key = serde_yaml::from_str::<String>(&text[..colon]).ok()?;
+            return Some((key, colon));
+        }
+        return None;
+    }
+    if text.starts_with(['{', '[', '?', '&', '*', '!', '|', '>', '#']) {
+        return None;
+    }
+    let colon = text.match_indices(':').map(|(index, _)| index).find(|&index| is_separator(&text[index + 1..]))?;
+    Some((text[..colon].trim_end().to_string(), colon))
+}
+
+fn is_separator(after_colon: &str) -> bool {
+    after_colon.is_empty() || after_colon.starts_with([' ', '\t'])
+}
+
+/// Where a trailing `# comment` begins, skipping `#`s inside quotes.
+fn comment_start(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut quote: Option<u8> = None;
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        match quote {
+            Some(b'"') if byte == b'\\' => index += 1,
+            Some(open) if byte == open => quote = None,
+            Some(_) => {}
+            None if (byte == b'"' || byte == b'\'')
+                && (index == 0 || matches!(bytes[index - 1], b' ' | b'[' | b'{' | b',' | b':')) =>
+            {
+                quote = Some(byte)
+            }
+            None if byte == b'#' && (index == 0 || matches!(bytes[index - 1], b' ' | b'\t')) => {
+                return Some(index)
+            }
+            None => {}
+        }
+        index += 1;
+    }
+    None
+}
+
+fn is_collection(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(entries) => !entries.is_empty(),
+        _ => false,
+    }
+}
+
+/// An entry for `value`, its first line starting at the entry's column and
+/// the rest relative to it.
+fn render_entry(key: Option<&str>, value: &Value, step: usize) -> Vec<String> {
+    let head = match key {
+        Some(key) => format!("{}:", render_key(key)),
+        None => "-".to_string(),
+    };
+    if !is_collection(value) {
+        return vec![format!("{} {}", head, render_inline(value))];
+    }
+    let body = render_block(value, step);
+    match key {
+        Some(_) => std::iter::once(head)
+            .chain(body.into_iter().map(|line| format!("{}{}", " ".repeat(step), line)))
+            .collect(),
+        None => body
+            .into_iter()
+            .enumerate()
+            .map(|(index, line)| if index == 0 { format!("- {}", line) } else { format!("  {}", line) })
+            .collect(),
+    }
+}
+
+fn render_block(value: &Value, step: usize) -> Vec<String> {
+    let rendered = serde_yaml::to_string(value).unwrap_or_default();
+    rendered
+        .lines()
+        .map(|line| {
+            // serde_yaml nests by two; re-indent to the document's step.
+            let indent = indent_of(line);
+            format!("{}{}", " ".repeat(indent / 2 * step + indent % 2), &line[indent..])
+        })
+        .collect()
+}
+
+fn render_inline(value: &Value) -> String {
+    match value {
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        _ => {
+            let rendered = serde_yaml::to_string(value).unwrap_or_default();
+            let rendered = rendered.trim_end();
+            if rendered.contains('\n') {
+                serde_json::to_string(value).unwrap_or_default()
+            } else {
+                rendered.to_string()
+            }
+        }
+    }
+}
+
+fn render_key(key: &str) -> String {
+    let plain = !key.is_empty()
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || "_-./".contains(c))
+        && !key.starts_with(['-', '.'])
+        && serde_yaml::from_str::<Value>(key).ok() == Some(Value::String(key.to_string()));
+    if plain {
+        key.to_string()
+    } else {
+        serde_json::to_string(key).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::super::{parse_path, Document, Format};
+
+    const COMPOSE: &str = "\
+# Local development stack
+services:
+  web:
+    image: nginx:1.25   # pinned
+    ports:
+      - \"8080:80\"
+
+  db:
+    image: postgres
+volumes: {}
+";
+
+    fn edit(text: &str, change: impl FnOnce(&mut Document)) -> String {
+        let mut document = Document::parse(Format::Yaml, text).unwrap();
+        change(&mut document);
+        document.render()
+    }
+
+    #[test]
+    fn test_scalar_replaced_in_place() {
+        let edited = edit(COMPOSE, |document| {
+            document.set(&parse_path("services.web.image"), json!("nginx:1.27")).unwrap();
+        });
+        assert_eq!(edited, COMPOSE.replace("nginx:1.25   # pinned", "nginx:1.27   # pinned"));
+    }
+
+    #[test]
+    fn test_new_keys_and_items_keep_layout() {
+        let edited = edit(COMPOSE, |document| {
+            document
+                .set(&parse_path("services.db.environment"), json!({ "POSTGRES_DB": "app" }))
+                .unwrap();
+            assert!(document.add(&parse_path("services.web.ports"), json!("8443:443")).unwrap());
+            document.set(&parse_path("services.cache"), json!({ "image": "redis" })).unwrap();
+        });
+        assert_eq!(
+            edited,
+            "\
+# Local development stack
+services:
+  web:
+    image: nginx:1.25   # pinned
+    ports:
+      - \"8080:80\"
+      - 8443:443
+
+  db:
+    image: postgres
+    environment:
+      POSTGRES_DB: app
+  cache:
+    image: redis
+volumes: {}
+"
+        );
+    }
+
+    #[test]
+    fn test_remove_and_rewrite_empty_mapping() {
+        let edited = edit(COMPOSE, |document| {
+            assert!(document.remove(&parse_path("services.web.ports")).unwrap());
+            document.set(&parse_path("volumes.data"), json!({})).unwrap();
+        });
+        assert_eq!(
+            edited,
+            "\
+# Local development stack
+services:
+  web:
+    image: nginx:1.25   # pinned
+
+  db:
+    image: postgres
+volumes:
+  data: {}
+"
+        );
+    }
+
+    #[test]
+    fn test_items_with_mappings() {
+        let text = "steps:\n  - name: build\n    run: make\n  - name: test\n";
+        let edited = edit(text, |document| {
+            document.set(&parse_path("steps.2.run"), json!("make test")).unwrap();
+            document.set(&parse_path("steps.1.name"), json!("compile")).unwrap();
+        });
+        assert_eq!(edited, "steps:\n  - name: compile\n    run: make\n  - name: test\n    run: make test\n");
+    }
+
+    #[test]
+    fn test_anchors_are_refused() {
+        let text = "base: &base\n  image: x\nweb:\n  <<: *base\n";
+        let mut document = Document::parse(Format::Yaml, text).unwrap();
+        assert!(document.set(&parse_path("base.image"), json!("y")).is_err());
+        assert_eq!(document.render(), text);
+    }
+}
@@ -4,7 +4,8 @@ use std::time::Duration;
 use archetect_api::ContextMap;
 use linked_hash_map::LinkedHashMap;
 use tokio::sync::mpsc;
-use tokio::time::sleep;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout_at};
 use tokio_stream::{Stream, StreamExt};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status, Streaming};
@@ -19,8 +20,9 @@ use crate::manifest::CatalogEntry;
 use archetect_api::ScriptMessage;
 
 use crate::archetype::render_context::RenderContext;
-use crate::errors::ArchetectError;
+use crate::errors::{ArchetectError, ArchetypeError};
 use crate::io::AsyncScriptIoHandle;
+use crate::limits::LimitExceeded;
 use crate::proto::grpc;
 use crate::proto::grpc::archetect_service_server::ArchetectService;
 use crate::Archetect;
@@ -102,11 +104,13 @@ impl ArchetectService for ArchetectServiceCore {
         let (client_tx, client_rx) = mpsc::channel(10);
         let (script_tx, script_rx) = mpsc::channel(10);
         let client_failure_tx = client_tx.clone();
+        let status_tx = script_tx.clone();
 
         let script_handle = AsyncScriptIoHandle::from_channels(script_tx, client_rx);
         let archetect = Archetect::builder()
             .with_configuration(self.prototype().configuration().clone())
             .with_driver(script_handle)
+            .with_limits(self.prototype().limits())
             .build()
             .map_err(|e| Status::internal(format!("Failed to initialize Archetect: {}", e)))?;
 
@@ -120,9 +124,13 @@ impl ArchetectService for ArchetectServiceCore {
                 match message {
                     Ok(message) => {
                         if !initialized {
+                            let session = archetect.clone();
+                            let timeout_tx = status_tx.clone();
+                            let abort_tx = client_failure_tx.clone();
                             let archetect = archetect.clone();
                             let default_action = default_action.clone();
-                            archetect_handle = Some(tokio::task::spawn_blocking(move || {
+                            let status_tx = status_tx.clone();
+                            let render = tokio::task::spawn_blocking(move || {
                                 if let grpc::ClientMessage {
                                     message:
                                         Some(grpc::client_message::Message::Initialize(initialize)),
@@ -195,6 +203,20 @@ impl ArchetectService for ArchetectServiceCore {
                                                         ),
                                                     );
                                                 }
+                                                // A runaway script ends the stream with
+                                                // RESOURCE_EXHAUSTED, so clients can tell
+                                                // it from an archetype that failed.
+                                                Err(ArchetectError::ArchetypeError(
+                                                    ArchetypeError::ResourceLimitExceeded(limit),
+                                                )) => {
+                                                    warn!("Render of '{}' stopped: {}", label, limit);
+                                                    let _ = status_tx.blocking_send(Err(
+                                                        Status::resource_exhausted(format!(
+                                                            "Resource limit exceeded: {}",
+                                                            limit
+                                                        )),
+                                                    ));
+                                                }
                                                 Err(err) => {
                                                     // The client cannot see this log — it is
                                                     // on the other end of a wire. Without a
@@ -222,7 +244,9 @@ impl ArchetectService for ArchetectServiceCore {
                                         "Improper Initialization Message".to_string(),
                                     ));
                                 }
-                            }));
+                            });
+                            archetect_handle =
+                                Some(tokio::spawn(await_render(session, render, timeout_tx, abort_tx)));
 
                             initialized = true;
                         } else {
@@ -253,7 +277,7 @@ impl ArchetectService for ArchetectServiceCore {
             info!("Client disconnected");
         }.instrument(task_span));
 
-        let out_stream = ReceiverStream::new(script_rx).map(Ok);
+        let out_stream = ReceiverStream::new(script_rx);
 
         Ok(Response::new(
             Box::pin(out_stream) as Self::StreamingApiStream
@@ -368,6 +392,34 @@ impl ArchetectService for ArchetectServiceCore {
     }
 }
 
+/// Wait out a render, for no longer than its deadline. The script's own
+/// check runs between Lua instructions, so a render blocked in Rust — on a
+/// prompt nobody answers, in a long `shell` call — is stopped here instead:
+/// the stream ends with RESOURCE_EXHAUSTED and the render is told to abort.
+async fn await_render(
+    archetect: Archetect,
+    render: JoinHandle<()>,
+    status_tx: mpsc::Sender<Result<grpc::ScriptMessage, Status>>,
+    abort_tx: mpsc::Sender<grpc::ClientMessage>,
+) {
+    let (Some(deadline), Some(timeout)) = (archetect.deadline(), archetect.limits().timeout()) else {
+        let _ = render.await;
+        return;
+    };
+    if timeout_at(deadline.into(), render).await.is_err() {
+        let limit = LimitExceeded::Timeout(timeout.as_secs());
+        warn!("Render stopped: {}", limit);
+        let _ = status_tx
+            .send(Err(Status::resource_exhausted(format!("Resource limit exceeded: {}", limit))))
+            .await;
+        let _ = abort_tx
+            .send(grpc::ClientMessage {
+                message: Some(grpc::client_message::Message::Abort(())),
+            })
+            .await;
+    }
+}
+
 /// Convert an `IndexEntry` (with its full subtree) into the proto wire
 /// format. Children are included verbatim so clients get a browsable tree
 /// from one RPC.
@@ -1,6 +1,24 @@
 //! Collection (array) built-in filters and functions.
+//!
+//! The higher-order filters — `map`, `select`, `reject`, `sort_by`,
+//! `group_by`, `sum`, `min`, `max` — take a key: a dotted attribute path
+//! into each element, or a function of it.
+//!
+//!   `{{ entities | map("name.pascal") | join(", ") }}`
+//!   `{% for _, f in ipairs(select(fields, "required")) do %}`
 
-use mlua::{Error as LuaError, Lua, Result as LuaResult, Table, Value};
+use std::cmp::Ordering;
+
+use mlua::{Error as LuaError, Lua, MultiValue, Result as LuaResult, Table, Value, Variadic};
+
+/// Filters a context value of the same name shadows in function form:
+/// answers and models name their own data `items` or `keys` often enough
+/// that a builtin must not hide them. `{{ x | items }}` still reaches the
+/// filter.
+pub const CONTEXT_FIRST: &[&str] = &[
+    "map", "select", "reject", "sort_by", "group_by", "batch", "zip", "enumerate", "keys", "values", "items", "sum",
+    "min", "max", "flatten",
+];
 
 pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
     // join(arr, sep) — concatenate array elements with separator
@@ -149,6 +167,248 @@ pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
         })?,
     )?;
 
+    // map(arr, key) — each element's attribute (a dotted path like
+    // `"type.name"`) or the result of calling a function on it. Elements
+    // the key yields nil for are dropped; a Lua array cannot hold a nil.
+    filters.set(
+        "map",
+        lua.create_function(|lua, (arr, key): (Table, Value)| {
+            let mut out = Vec::new();
+            for item in elements(&arr)? {
+                let v = key_of("map", &item, &key)?;
+                if !v.is_nil() {
+                    out.push(v);
+                }
+            }
+            lua.create_sequence_from(out)
+        })?,
+    )?;
+
+    // select(arr[, key[, value]]) — the elements whose key equals `value`,
+    // or is truthy when no value is given; with no key, the truthy
+    // elements. reject(...) keeps the rest.
+    //
+    // `select` is also Lua's `select(n, ...)`; a call whose first argument
+    // is a number or "#" still behaves that way, so template code written
+    // against the stdlib keeps working.
+    filters.set(
+        "select",
+        lua.create_function(|lua, mut args: MultiValue| {
+            if matches!(args.front(), Some(Value::Integer(_) | Value::Number(_)))
+                || matches!(args.front(), Some(Value::String(s)) if s.as_bytes().as_ref() == b"#")
+            {
+                return lua_select(args);
+            }
+            let arr = match args.pop_front() {
+                Some(Value::Table(t)) => t,
+                other => return Err(expected_array("select", other.as_ref())),
+            };
+            filter_by(lua, "select", &arr, args, true).map(|t| MultiValue::from(vec![Value::Table(t)]))
+        })?,
+    )?;
+    filters.set(
+        "reject",
+        lua.create_function(|lua, (arr, args): (Table, MultiValue)| filter_by(lua, "reject", &arr, args, false))?,
+    )?;
+
+    // sort_by(arr, key[, descending]) — a stably sorted copy, ordered by
+    // each element's key. Numbers compare numerically, strings bytewise.
+    filters.set(
+        "sort_by",
+        lua.create_function(|lua, (arr, key, descending): (Table, Value, Option<bool>)| {
+            let mut keyed = Vec::new();
+            for item in elements(&arr)? {
+                keyed.push((key_of("sort_by", &item, &key)?, item));
+            }
+            // Flipping the comparison, rather than reversing the result,
+            // keeps elements with equal keys in their original order.
+            if descending.unwrap_or(false) {
+                keyed.sort_by(|(a, _), (b, _)| compare(b, a));
+            } else {
+                keyed.sort_by(|(a, _), (b, _)| compare(a, b));
+            }
+            lua.create_sequence_from(keyed.into_iter().map(|(_, item)| item))
+        })?,
+    )?;
+
+    // group_by(arr, key) — `{ key = k, items = {...} }` per distinct key,
+    // in the order each key first appears. Sort first for sorted groups.
+    filters.set(
+        "group_by",
+        lua.create_function(|lua, (arr, key): (Table, Value)| {
+            let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
+            for item in elements(&arr)? {
+                let k = key_of("group_by", &item, &key)?;
+                match groups.iter_mut().find(|(existing, _)| *existing == k) {
+                    Some((_, members)) => members.push(item),
+                    None => groups.push((k, vec![item])),
+                }
+            }
+            let out = lua.create_table()?;
+            for (k, members) in groups {
+                let group = lua.create_table()?;
+                group.set("key", k)?;
+                group.set("items", lua.create_sequence_from(members)?)?;
+                out.raw_push(group)?;
+            }
+            Ok(out)
+        })?,
+    )?;
+
+    // batch(arr, n[, fill]) — split into arrays of n elements; `fill` pads
+    // the last one out to n
+    filters.set(
+        "batch",
+        lua.create_function(|lua, (arr, size, fill): (Table, i64, Value)| {
+            if size < 1 {
+                return Err(LuaError::RuntimeError(format!(
+                    "filter `batch`: batch size must be at least 1, got {}",
+                    size
+                )));
+            }
+            let out = lua.create_table()?;
+            for chunk in elements(&arr)?.chunks(size as usize) {
+                let batch = lua.create_sequence_from(chunk.iter().cloned())?;
+                if !fill.is_nil() {
+                    for _ in chunk.len()..size as usize {
+                        batch.raw_push(fill.clone())?;
+                    }
+                }
+                out.raw_push(batch)?;
+            }
+            Ok(out)
+        })?,
+    )?;
+
+    // zip(a, b, ...) — `{ a[i], b[i], ... }` for each index, stopping at the
+    // shortest array
+    filters.set(
+        "zip",
+        lua.create_function(|lua, arrays: Variadic<Table>| {
+            let len = arrays.iter().map(|a| a.raw_len()).min().unwrap_or(0);
+            let out = lua.create_table()?;
+            for i in 1..=len {
+                let row = lua.create_table()?;
+                for a in arrays.iter() {
+                    row.raw_push(a.raw_get::<Value>(i)?)?;
+                }
+                out.raw_push(row)?;
+            }
+            Ok(out)
+        })?,
+    )?;
+
+    // enumerate(arr[, start]) — `{ index = i, value = v }` per element,
+    // counting from `start` (default 1)
+    filters.set(
+        "enumerate",
+        lua.create_function(|lua, (arr, start): (Table, Option<i64>)| {
+            let out = lua.create_table()?;
+            for (i, item) in elements(&arr)?.into_iter().enumerate() {
+                let entry = lua.create_table()?;
+                entry.set("index", start.unwrap_or(1) + i as i64)?;
+                entry.set("value", item)?;
+                out.raw_push(entry)?;
+            }
+            Ok(out)
+        })?,
+    )?;
+
+    // keys(t), values(t), items(t) — a table's keys, its values, and
+    // `{ key = k, value = v }` pairs, all in key order rather than Lua's
+    // unspecified `pairs` order, so generated output is stable
+    filters.set(
+        "keys",
+        lua.create_function(|lua, t: Table| lua.create_sequence_from(sorted_pairs(&t)?.into_iter().map(|(k, _)| k)))?,
+    )?;
+    filters.set(
+        "values",
+        lua.create_function(|lua, t: Table| lua.create_sequence_from(sorted_pairs(&t)?.into_iter().map(|(_, v)| v)))?,
+    )?;
+    filters.set(
+        "items",
+        lua.create_function(|lua, t: Table| {
+            let out = lua.create_table()?;
+            for (k, v) in sorted_pairs(&t)? {
+                let entry = lua.create_table()?;
+                entry.set("key", k)?;
+                entry.set("value", v)?;
+                out.raw_push(entry)?;
+            }
+            Ok(out)
+        })?,
+    )?;
+
+    // sum(arr[, key]) — the total of the elements, or of their keys. Stays
+    // an integer while every term is one.
+    filters.set(
+        "sum",
+        lua.create_function(|_, (arr, key): (Table, Option<Value>)| {
+            let mut total = Value::Integer(0);
+            for item in elements(&arr)? {
+                let term = match &key {
+                    Some(key) => key_of("sum", &item, key)?,
+                    None => item,
+                };
+                if term.is_nil() {
+                    continue;
+                }
+                total = match (total, term) {
+                    (Value::Integer(a), Value::Integer(b)) => match a.checked_add(b) {
+                        Some(n) => Value::Integer(n),
+                        None => Value::Number(a as f64 + b as f64),
+                    },
+                    (a, b) => match (as_number(&a), as_number(&b)) {
+                        (Some(a), Some(b)) => Value::Number(a + b),
+                        (_, _) => {
+                            return Err(LuaError::RuntimeError(format!(
+                                "filter `sum`: cannot add a {} value",
+                                b.type_name()
+                            )))
+                        }
+                    },
+                };
+            }
+            Ok(total)
+        })?,
+    )?;
+
+    // min(arr[, key]), max(arr[, key]) — the smallest / largest element,
+    // compared by key when one is given. nil for an empty array.
+    for (name, wanted) in [("min", Ordering::Less), ("max", Ordering::Greater)] {
+        filters.set(
+            name,
+            lua.create_function(move |_, (arr, key): (Table, Option<Value>)| {
+                let mut best: Option<(Value, Value)> = None;
+                for item in elements(&arr)? {
+                    let k = match &key {
+                        Some(key) => key_of(name, &item, key)?,
+                        None => item.clone(),
+                    };
+                    if best
+                        .as_ref()
+                        .is_none_or(|(best_key, _)| compare(&k, best_key) == wanted)
+                    {
+                        best = Some((k, item));
+                    }
+                }
+                Ok(best.map(|(_, item)| item).unwrap_or(Value::Nil))
+            })?,
+        )?;
+    }
+
+    // flatten(arr[, depth]) — splice nested arrays into one, all the way
+    // down or `depth` levels. Tables with keys other than 1..n are
+    // elements, not arrays, and are kept whole.
+    filters.set(
+        "flatten",
+        lua.create_function(|lua, (arr, depth): (Table, Option<i64>)| {
+            let out = lua.create_table()?;
+            flatten_into(&out, &arr, depth.unwrap_or(i64::MAX))?;
+            Ok(out)
+        })?,
+    )?;
+
     Ok(())
 }
 
@@ -164,3 +424,151 @@ fn sort_key(value: &Value) -> String {
         other => format!("?:{}", other.type_name()),
     }
 }
+
+/// An array's elements, 1..n.
+fn elements(arr: &Table) -> LuaResult<Vec<Value>> {
+    let len = arr.raw_len();
+    let mut out = Vec::with_capacity(len);
+    for i in 1..=len {
+        out.push(arr.raw_get(i)?);
+    }
+    Ok(out)
+}
+
+/// What a higher-order filter's `key` argument picks out of an element: a
+/// dotted attribute path (`"type.name"`, `"fields.1"`), or a function's
+/// result. A path through a missing or non-table value is nil.
+fn key_of(filter: &str, item: &Value, key: &Value) -> LuaResult<Value> {
+    match key {
+        Value::String(path) => {
+            let mut current = item.clone();
+            for segment in path.to_str()?.split('.') {
+                let Value::Table(t) = current else {
+                    return Ok(Value::Nil);
+                };
+                current = match segment.parse::<i64>() {
+                    Ok(i) => t.get(i)?,
+                    Err(_) => t.get(segment)?,
+                };
+            }
+            Ok(current)
+        }
+        Value::Function(f) => f.call(item.clone()),
+        other => Err(LuaError::RuntimeError(format!(
+            "filter `{}`: expected an attribute path or a function, got {}",
+            filter,
+            other.type_name()
+        ))),
+    }
+}
+
+/// Shared body of `select` and `reject`: `args` is `key[, value]`.
+fn filter_by(lua: &Lua, filter: &str, arr: &Table, mut args: MultiValue, keep: bool) -> LuaResult<Table> {
+    let key = args.pop_front().unwrap_or(Value::Nil);
+    let wanted = args.pop_front();
+    let out = lua.create_table()?;
+    for item in elements(arr)? {
+        let v = match key {
+            Value::Nil => item.clone(),
+            _ => key_of(filter, &item, &key)?,
+        };
+        let matches = match &wanted {
+            Some(wanted) => v == *wanted,
+            None => !matches!(v, Value::Nil | Value::Boolean(false)),
+        };
+        if matches == keep {
+            out.raw_push(item)?;
+        }
+    }
+    Ok(out)
+}
+
+/// Lua's own `select(n, ...)` / `select("#", ...)`, for calls that mean it.
+fn lua_select(mut args: MultiValue) -> LuaResult<MultiValue> {
+    let Some(n) = args.pop_front() else {
+        return Err(LuaError::RuntimeError(
+            "bad argument #1 to 'select' (number expected, got no value)".into(),
+        ));
+    };
+    let count = args.len() as i64;
+    let n = match n {
+        Value::String(_) => return Ok(MultiValue::from(vec![Value::Integer(count)])),
+        n => n.as_i64().unwrap_or_else(|| n.as_f64().unwrap_or_default() as i64),
+    };
+    let skip = match n {
+        n if n > 0 => n - 1,
+        n if n < 0 && -n <= count => count + n,
+        _ => {
+            return Err(LuaError::RuntimeError(
+                "bad argument #1 to 'select' (index out of range)".into(),
+            ))
+        }
+    };
+    Ok(args.into_iter().skip(skip as usize).collect())
+}
+
+fn expected_array(filter: &str, got: Option<&Value>) -> LuaError {
+    LuaError::RuntimeError(format!(
+        "filter `{}`: expected an array, got {}",
+        filter,
+        got.map_or("no value", |v| v.type_name())
+    ))
+}
+
+/// A table's entries, ordered by key.
+fn sorted_pairs(t: &Table) -> LuaResult<Vec<(Value, Value)>> {
+    let mut entries = t.pairs::<Value, Value>().collect::<LuaResult<Vec<_>>>()?;
+    entries.sort_by(|(a, _), (b, _)| compare(a, b));
+    Ok(entries)
+}
+
+/// Total order over the values templates sort by: numbers numerically,
+/// then strings bytewise, then booleans, with everything else — and nil —
+/// after them.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Integer(_) | Value::Number(_) => 0,
+            Value::String(_) => 1,
+            Value::Boolean(_) => 2,
+            Value::Nil => 4,
+            _ => 3,
+        }
+    }
+    match (a, b) {
+        (Value::Integer(x), Value::Integer(y)) => x.cmp(y),
+        (Value::String(x), Value::String(y)) => x.as_bytes().cmp(&y.as_bytes()),
+        (Value::Boolean(x), Value::Boolean(y)) => x.cmp(y),
+        _ => match (as_number(a), as_number(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            _ => rank(a).cmp(&rank(b)),
+        },
+    }
+}
+
+fn as_number(v: &Value) -> Option<f64> {
+    match v {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn flatten_into(out: &Table, arr: &Table, depth: i64) -> LuaResult<()> {
+    for item in elements(arr)? {
+        match item {
+            Value::Table(inner) if depth > 0 && is_array(&inner)? => flatten_into(out, &inner, depth - 1)?,
+            item => out.raw_push(item)?,
+        }
+    }
+    Ok(())
+}
+
+fn is_array(t: &Table) -> LuaResult<bool> {
+    let mut count = 0;
+    for pair in t.pairs::<Value, Value>() {
+        pair?;
+        count += 1;
+    }
+    Ok(count == t.raw_len())
+}
@@ -8,37 +8,41 @@
 //!   `{{ year() }}`       — current local year as integer
 //!   `{{ timestamp() }}`  — current Unix timestamp as integer
 //!
+//! In a reproducible session the clock is pinned, and "local" is UTC.
+//!
 //! Filter form:
 //!
 //!   `{{ value | date(format) }}` — strftime-style formatting of an RFC3339 input
 
-use chrono::{Datelike, Local, NaiveDate, TimeZone, Utc};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, TimeZone, Utc};
 use mlua::{Error as LuaError, Lua, Result as LuaResult, Table};
 
+use crate::reproducible::Reproducible;
+
 pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
     filters.set(
         "now",
-        lua.create_function(|_, ()| Ok(Local::now().to_rfc3339()))?,
+        lua.create_function(|lua, ()| Ok(local_now(lua).to_rfc3339()))?,
     )?;
 
     filters.set(
         "now_utc",
-        lua.create_function(|_, ()| Ok(Utc::now().to_rfc3339()))?,
+        lua.create_function(|lua, ()| Ok(utc_now(lua).to_rfc3339()))?,
     )?;
 
     filters.set(
         "today",
-        lua.create_function(|_, ()| Ok(Local::now().date_naive().format("%Y-%m-%d").to_string()))?,
+        lua.create_function(|lua, ()| Ok(local_now(lua).date_naive().format("%Y-%m-%d").to_string()))?,
     )?;
 
     filters.set(
         "year",
-        lua.create_function(|_, ()| Ok(Local::now().year() as i64))?,
+        lua.create_function(|lua, ()| Ok(local_now(lua).year() as i64))?,
     )?;
 
     filters.set(
         "timestamp",
-        lua.create_function(|_, ()| Ok(Utc::now().timestamp()))?,
+        lua.create_function(|lua, ()| Ok(utc_now(lua).timestamp()))?,
     )?;
 
     // date(value, format) — format an RFC3339 datetime string OR a YYYY-MM-DD
@@ -65,3 +69,20 @@ pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
 
     Ok(())
 }
+
+/// The current time — or the pinned one, in a reproducible session.
+pub fn utc_now(lua: &Lua) -> DateTime<Utc> {
+    match Reproducible::of(lua) {
+        Some(reproducible) => reproducible.now(),
+        None => Utc::now(),
+    }
+}
+
+/// The current local time. A reproducible session has no "local": its
+/// pinned clock reads the same on every machine, in UTC.
+pub fn local_now(lua: &Lua) -> DateTime<FixedOffset> {
+    match Reproducible::of(lua) {
+        Some(reproducible) => reproducible.now().fixed_offset(),
+        None => Local::now().fixed_offset(),
+    }
+}
@@ -0,0 +1,25 @@
+//! Output escaping filters: `escape_json`, `escape_yaml`, `escape_xml`,
+//! `escape_html`, `escape_shell`, and `escape_toml`.
+//!
+//! The same escapers `{% autoescape %}` and the manifest's
+//! `templating.autoescape` apply to every interpolation; these reach one
+//! value at a time. See [`Escaper`] for what each one escapes.
+
+use mlua::{Lua, Result as LuaResult, Table};
+
+use crate::templating::atl::Escaper;
+
+pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
+    for escaper in Escaper::ALL {
+        filters.set(
+            escaper.filter_name(),
+            lua.create_function(move |_, s: String| Ok(escaper.escape(&s)))?,
+        )?;
+    }
+    // `escape_html` reads better in an HTML template; it is the XML escaper.
+    filters.set(
+        "escape_html",
+        filters.get::<mlua::Function>(Escaper::Xml.filter_name())?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,233 @@
+//! Text layout built-in filters: wrapping, dedenting, padding, and columns.
+//!
+//! Widths are display widths, not bytes or code points, so a CJK character
+//! or an emoji counts as the two columns a terminal gives it and aligned
+//! output stays aligned.
+//!
+//!   `{{ description | wrap(72) }}`
+//!   `{{ name | ljust(20) }}{{ value }}`
+//!   `{{ columns({ { "NAME", "PORT" }, { "web", 8080 } }, { align = "lr" }) }}`
+
+use mlua::{Error as LuaError, Lua, Result as LuaResult, Table, Value};
+use unicode_width::UnicodeWidthStr;
+
+pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
+    // wrap(s, width) — re-flow each line to at most `width` columns,
+    // breaking between words. A line's indentation carries over to the
+    // lines it wraps onto; blank lines are kept, and a word longer than
+    // the width gets a line of its own rather than being split.
+    filters.set(
+        "wrap",
+        lua.create_function(|_, (s, width): (String, i64)| Ok(wrap(&s, positive("wrap", width)?)))?,
+    )?;
+
+    // dedent(s) — remove the leading whitespace every non-blank line shares
+    filters.set("dedent", lua.create_function(|_, s: String| Ok(dedent(&s)))?)?;
+
+    // ljust(s, width, fill?), rjust(...), center(...) — pad to `width`
+    // columns with `fill` (default a space) on the right, the left, or
+    // both. Text already that wide is returned as is.
+    for (name, align) in [
+        ("ljust", Align::Left),
+        ("rjust", Align::Right),
+        ("center", Align::Center),
+    ] {
+        filters.set(
+            name,
+            lua.create_function(move |_, (s, width, fill): (String, i64, Option<String>)| {
+                let width = positive(name, width)?;
+                let fill = fill_char(name, fill.as_deref())?;
+                Ok(pad(&s, width, fill, align))
+            })?,
+        )?;
+    }
+
+    // columns(rows, options?) — lay an array of rows (arrays of cells) out
+    // as aligned columns, one line per row. Options: `sep`, the text
+    // between columns (default two spaces), and `align`, a letter per
+    // column — `l`, `r`, or `c` (default all `l`).
+    filters.set(
+        "columns",
+        lua.create_function(|_, (rows, options): (Table, Option<Table>)| {
+            let (sep, align) = match options {
+                Some(options) => (
+                    options.get::<Option<String>>("sep")?,
+                    options.get::<Option<String>>("align")?,
+                ),
+                None => (None, None),
+            };
+            let mut cells: Vec<Vec<String>> = Vec::new();
+            for row in rows.sequence_values::<Table>() {
+                let mut line = Vec::new();
+                for cell in row?.sequence_values::<Value>() {
+                    line.push(cell_text(cell?)?);
+                }
+                cells.push(line);
+            }
+            let aligns = match align {
+                Some(align) => align.chars().map(Align::from_letter).collect::<LuaResult<Vec<_>>>()?,
+                None => Vec::new(),
+            };
+            Ok(columns(&cells, sep.as_deref().unwrap_or("  "), &aligns))
+        })?,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+impl Align {
+    fn from_letter(letter: char) -> LuaResult<Align> {
+        match letter {
+            'l' | 'L' => Ok(Align::Left),
+            'r' | 'R' => Ok(Align::Right),
+            'c' | 'C' => Ok(Align::Center),
+            other => Err(LuaError::RuntimeError(format!(
+                "filter `columns`: `align` takes `l`, `r`, or `c` per column, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+fn positive(filter: &str, width: i64) -> LuaResult<usize> {
+    if width < 1 {
+        return Err(LuaError::RuntimeError(format!(
+            "filter `{}`: width must be at least 1, got {}",
+            filter, width
+        )));
+    }
+    Ok(width as usize)
+}
+
+fn fill_char(filter: &str, fill: Option<&str>) -> LuaResult<char> {
+    let Some(fill) = fill else {
+        return Ok(' ');
+    };
+    let mut chars = fill.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if fill.width() == 1 => Ok(c),
+        _ => Err(LuaError::RuntimeError(format!(
+            "filter `{}`: fill must be a single one-column character, got `{}`",
+            filter, fill
+        ))),
+    }
+}
+
+/// A cell as text; `nil` is an empty cell.
+fn cell_text(cell: Value) -> LuaResult<String> {
+    match cell {
+        Value::Nil => Ok(String::new()),
+        Value::String(_) | Value::Integer(_) | Value::Number(_) | Value::Boolean(_) => cell.to_string(),
+        other => Err(LuaError::RuntimeError(format!(
+            "filter `columns`: cells must be scalars, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+fn wrap(s: &str, width: usize) -> String {
+    let mut out = Vec::new();
+    for line in s.split('\n') {
+        let body = line.trim_start();
+        if body.trim_end().is_empty() {
+            out.push(String::new());
+            continue;
+        }
+        let indent = &line[..line.len() - body.len()];
+        let room = width.saturating_sub(indent.width()).max(1);
+        let mut current = String::new();
+        for word in body.split_whitespace() {
+            if !current.is_empty() && current.width() + 1 + word.width() > room {
+                out.push(format!("{}{}", indent, current));
+                current.clear();
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        out.push(format!("{}{}", indent, current));
+    }
+    out.join("\n")
+}
+
+fn dedent(s: &str) -> String {
+    let mut margin: Option<&str> = None;
+    for line in s.lines().filter(|line| !line.trim().is_empty()) {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        margin = Some(match margin {
+            None => indent,
+            Some(margin) => {
+                let shared = margin
+                    .char_indices()
+                    .zip(indent.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(margin.len().min(indent.len()), |((i, _), _)| i);
+                &margin[..shared]
+            }
+        });
+    }
+    let margin = margin.unwrap_or("");
+    s.split('\n')
+        .map(|line| match line.strip_prefix(margin) {
+            _ if line.trim().is_empty() => "",
+            Some(rest) => rest,
+            None => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn pad(s: &str, width: usize, fill: char, align: Align) -> String {
+    let missing = width.saturating_sub(s.width());
+    let (left, right) = match align {
+        Align::Left => (0, missing),
+        Align::Right => (missing, 0),
+        Align::Center => (missing / 2, missing - missing / 2),
+    };
+    let mut out = String::with_capacity(s.len() + missing);
+    out.extend(std::iter::repeat_n(fill, left));
+    out.push_str(s);
+    out.extend(std::iter::repeat_n(fill, right));
+    out
+}
+
+/// Rows padded into columns. The last cell of a row isn't padded on the
+/// right, so lines carry no trailing whitespace.
+fn columns(rows: &[Vec<String>], sep: &str, aligns: &[Align]) -> String {
+    let count = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..count)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.width())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut lines = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            if i > 0 {
+                line.push_str(sep);
+            }
+            let align = aligns.get(i).copied().unwrap_or(Align::Left);
+            let last = i + 1 == row.len();
+            match align {
+                Align::Left if last => line.push_str(cell),
+                Align::Center if last => line.push_str(pad(cell, widths[i], ' ', align).trim_end()),
+                _ => line.push_str(&pad(cell, widths[i], ' ', align)),
+            }
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,237 @@
+//! License headers, and the comment syntax that carries them.
+//!
+//!   `{{ license("Apache-2.0", { owner = org_name }) | comment_block(destination) }}`
+//!
+//! `license(id, owner_or_options)` returns one of the embedded SPDX texts
+//! with `{year}` and `{owner}` filled in. `comment_block(text, style)` turns
+//! any text into a comment; the style is a language (`"rust"`), an
+//! extension (`"rs"`, `".rs"`), a path (`"src/main.rs"` — its extension, by
+//! `extname`), or the delimiter itself (`"//"`, `"#"`, `"--"`, `"/*"`,
+//! `"<!--"`). Passing `destination` picks the style of the file being
+//! rendered.
+
+use chrono::Datelike;
+use mlua::{Error as LuaError, Lua, Result as LuaResult, Table, Value};
+
+use super::datetime::local_now;
+use super::paths::extname;
+
+/// Embedded license texts, by SPDX identifier. For the licenses meant to
+/// be applied per file, the text is the license's standard header notice
+/// rather than the whole license.
+const LICENSES: &[(&str, &str)] = &[
+    ("MIT", include_str!("licenses/MIT.txt")),
+    ("Apache-2.0", include_str!("licenses/Apache-2.0.txt")),
+    ("BSD-3-Clause", include_str!("licenses/BSD-3-Clause.txt")),
+    ("MPL-2.0", include_str!("licenses/MPL-2.0.txt")),
+    ("proprietary", include_str!("licenses/proprietary.txt")),
+];
+
+/// How a comment is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CommentStyle {
+    /// Every line starts with the marker: `//`, `#`, `--`.
+    Line(&'static str),
+    /// `/* ... */`, with ` * ` down the side.
+    CBlock,
+    /// `<!-- ... -->`.
+    Markup,
+}
+
+/// Languages, extensions, and file names, to the comment style they use.
+const STYLES: &[(&str, CommentStyle)] = &[
+    (
+        "rust rs c h cpp cc cxx hpp java kotlin kt kts scala go javascript js mjs cjs jsx typescript ts tsx \
+         csharp cs swift dart groovy gradle proto protobuf zig php jsonc",
+        CommentStyle::Line("//"),
+    ),
+    (
+        "python py ruby rb shell sh bash zsh fish powershell ps1 perl pl r yaml yml toml terraform tf hcl \
+         properties ini conf cfg env elixir ex exs julia jl nim nix cmake dockerfile containerfile makefile mk \
+         gitignore dockerignore editorconfig",
+        CommentStyle::Line("#"),
+    ),
+    ("sql lua haskell hs elm ada adb ads", CommentStyle::Line("--")),
+    ("css less", CommentStyle::CBlock),
+    (
+        "html htm xhtml xml svg xsd xsl vue markdown md csproj plist",
+        CommentStyle::Markup,
+    ),
+];
+
+pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
+    // license(id, owner) / license(id, { owner = ..., year = ... }) — an
+    // embedded license text with `{owner}` and `{year}` (default: this
+    // year) filled in
+    filters.set(
+        "license",
+        lua.create_function(|lua, (id, options): (String, Value)| {
+            let (owner, year) = match options {
+                Value::String(owner) => (Some(owner.to_str()?.to_string()), None),
+                Value::Table(options) => (
+                    options.get::<Option<String>>("owner")?,
+                    options.get::<Option<i64>>("year")?,
+                ),
+                Value::Nil => (None, None),
+                other => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "filter `license`: expected an owner or an options table, got {}",
+                        other.type_name()
+                    )));
+                }
+            };
+            license(
+                &id,
+                owner.as_deref(),
+                year.unwrap_or_else(|| local_now(lua).year() as i64),
+            )
+            .map_err(LuaError::RuntimeError)
+        })?,
+    )?;
+
+    // comment_block(text, style) — the text as a comment in the given
+    // language, extension, path, or delimiter's syntax
+    filters.set(
+        "comment_block",
+        lua.create_function(|_, (text, style): (String, Option<String>)| {
+            let Some(style) = style else {
+                return Err(LuaError::RuntimeError(
+                    "filter `comment_block`: needs a language, an extension, or a path — `destination` is nil \
+                     outside a file render"
+                        .into(),
+                ));
+            };
+            let resolved = comment_style(&style).ok_or_else(|| {
+                LuaError::RuntimeError(format!(
+                    "filter `comment_block`: no comment style known for `{}`; pass a language, an extension, or \
+                     one of `//` `#` `--` `/*` `<!--`",
+                    style
+                ))
+            })?;
+            Ok(comment_block(&text, resolved))
+        })?,
+    )?;
+
+    Ok(())
+}
+
+fn license(id: &str, owner: Option<&str>, year: i64) -> Result<String, String> {
+    let Some((name, text)) = LICENSES.iter().find(|(name, _)| name.eq_ignore_ascii_case(id)) else {
+        let known: Vec<&str> = LICENSES.iter().map(|(name, _)| *name).collect();
+        return Err(format!(
+            "filter `license`: unknown license `{}`; known: {}",
+            id,
+            known.join(", ")
+        ));
+    };
+    let Some(owner) = owner else {
+        return Err(format!("filter `license`: `{}` needs an owner", name));
+    };
+    Ok(text
+        .trim_end()
+        .replace("{year}", &year.to_string())
+        .replace("{owner}", owner))
+}
+
+fn comment_style(spec: &str) -> Option<CommentStyle> {
+    let spec = spec.trim();
+    match spec {
+        "//" => return Some(CommentStyle::Line("//")),
+        "#" => return Some(CommentStyle::Line("#")),
+        "--" => return Some(CommentStyle::Line("--")),
+        "/*" | "/* */" => return Some(CommentStyle::CBlock),
+        "<!--" | "<!-- -->" => return Some(CommentStyle::Markup),
+        _ => {}
+    }
+    let lookup = |name: &str| {
+        let name = name.to_ascii_lowercase();
+        STYLES
+            .iter()
+            .find(|(names, _)| names.split_whitespace().any(|known| known == name))
+            .map(|(_, style)| *style)
+    };
+    let base = spec.rsplit('/').next().unwrap_or(spec);
+    // A bare name is a language or an extension; failing that, a path is
+    // known by its extension or, like `Dockerfile`, its name.
+    lookup(spec)
+        .or_else(|| lookup(extname(spec).trim_start_matches('.')))
+        .or_else(|| lookup(base.trim_start_matches('.')))
+}
+
+fn comment_block(text: &str, style: CommentStyle) -> String {
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    let mut out = String::new();
+    match style {
+        CommentStyle::Line(marker) => {
+            for (i, line) in lines.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                out.push_str(marker);
+                if !line.is_empty() {
+                    out.push(' ');
+                    out.push_str(line);
+                }
+            }
+        }
+        CommentStyle::CBlock => {
+            out.push_str("/*\n");
+            for line in &lines {
+                out.push_str(" *");
+                if !line.is_empty() {
+                    out.push(' ');
+                    out.push_str(line);
+                }
+                out.push('\n');
+            }
+            out.push_str(" */");
+        }
+        CommentStyle::Markup => {
+            out.push_str("<!--\n");
+            for line in &lines {
+                if !line.is_empty() {
+                    out.push_str("  ");
+                    out.push_str(line);
+                }
+                out.push('\n');
+            }
+            out.push_str("-->");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn styles_resolve_from_languages_extensions_and_paths() {
+        assert_eq!(comment_style("rust"), Some(CommentStyle::Line("//")));
+        assert_eq!(comment_style(".rs"), Some(CommentStyle::Line("//")));
+        assert_eq!(comment_style("/work/svc/src/main.RS"), Some(CommentStyle::Line("//")));
+        assert_eq!(comment_style("deploy/Dockerfile"), Some(CommentStyle::Line("#")));
+        assert_eq!(comment_style(".gitignore"), Some(CommentStyle::Line("#")));
+        assert_eq!(comment_style("schema.sql"), Some(CommentStyle::Line("--")));
+        assert_eq!(comment_style("#"), Some(CommentStyle::Line("#")));
+        assert_eq!(comment_style("/*"), Some(CommentStyle::CBlock));
+        assert_eq!(comment_style("pom.xml"), Some(CommentStyle::Markup));
+        assert_eq!(comment_style("notes.txt"), None);
+    }
+
+    #[test]
+    fn every_license_fills_in_year_and_owner() {
+        for (id, _) in LICENSES {
+            let text = license(id, Some("Acme Corp"), 2031).unwrap();
+            assert!(text.contains("2031") && text.contains("Acme Corp"), "{}", id);
+            assert!(!text.contains('{'), "{}", id);
+        }
+        assert!(license("apache-2.0", Some("Acme"), 2031)
+            .unwrap()
+            .starts_with("Copyright 2031 Acme"));
+        assert!(license("GPL-3.0", Some("Acme"), 2031)
+            .unwrap_err()
+            .contains("known: MIT, Apache-2.0"));
+        assert!(license("MIT", None, 2031).unwrap_err().contains("needs an owner"));
+    }
+}
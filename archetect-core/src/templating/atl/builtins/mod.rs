@@ -16,21 +16,32 @@
 //!
 //! Filters take precedence over context keys with the same name, so authors
 //! cannot accidentally shadow a builtin by calling `ctx:set("now", ...)`.
+//! The higher-order collection filters are the exception: their names
+//! (`items`, `keys`, `sum`, ...) are common in answers and models, so a
+//! context key wins over them — see `collections::CONTEXT_FIRST`.
 
 use mlua::{Lua, Result as LuaResult, Table};
 
 pub mod collections;
 pub mod datetime;
+pub mod escape;
+pub mod layout;
+pub mod license;
 pub mod paths;
 pub mod strings;
+pub mod text;
 pub mod uuid;
 
 /// Register every built-in module into the shared filter table.
 pub fn register_all(lua: &Lua, filters: &Table) -> LuaResult<()> {
     strings::register(lua, filters)?;
+    layout::register(lua, filters)?;
     collections::register(lua, filters)?;
     datetime::register(lua, filters)?;
     uuid::register(lua, filters)?;
     paths::register(lua, filters)?;
+    escape::register(lua, filters)?;
+    text::register(lua, filters)?;
+    license::register(lua, filters)?;
     Ok(())
 }
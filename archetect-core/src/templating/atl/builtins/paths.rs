@@ -65,22 +65,7 @@ pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
     // extname(p) — file extension including the leading dot, or empty string
     filters.set(
         "extname",
-        lua.create_function(|_, p: String| {
-            let base = match p.rfind('/') {
-                Some(idx) => &p[idx + 1..],
-                None => p.as_str(),
-            };
-            // Leading dots (e.g. ".gitignore") are not extensions
-            let dot = base
-                .char_indices()
-                .skip(1)
-                .filter(|(_, c)| *c == '.')
-                .last();
-            Ok(match dot {
-                Some((idx, _)) => base[idx..].to_string(),
-                None => String::new(),
-            })
-        })?,
+        lua.create_function(|_, p: String| Ok(extname(&p).to_string()))?,
     )?;
 
     // path_normalize(p) — collapse `.` and `..` segments, deduplicate
@@ -118,3 +103,22 @@ pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
 
     Ok(())
 }
+
+/// A path's extension, including the leading dot, or `""`. Shared with the
+/// filters that pick a behaviour by file type, like `comment_block`.
+pub fn extname(p: &str) -> &str {
+    let base = match p.rfind('/') {
+        Some(idx) => &p[idx + 1..],
+        None => p,
+    };
+    // Leading dots (e.g. ".gitignore") are not extensions
+    let dot = base
+        .char_indices()
+        .skip(1)
+        .filter(|(_, c)| *c == '.')
+        .last();
+    match dot {
+        Some((idx, _)) => &base[idx..],
+        None => "",
+    }
+}
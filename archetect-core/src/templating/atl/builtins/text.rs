@@ -0,0 +1,162 @@
+//! Regex, digest, and encoding built-in filters.
+//!
+//! Every entry registered here is reachable in templates as both:
+//!   `{{ s | foo(...) }}`   — pipe form
+//!   `{{ foo(s, ...) }}`    — function form
+//!
+//! Digests and encodings work on a string's bytes, so they round-trip
+//! anything `base64_decode` produces. Digests render as lowercase hex, and
+//! `crc32` as a number — handy for deriving stable values from a name:
+//!
+//!   `port: {{ 20000 + crc32(service_name) % 10000 }}`
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use base64::Engine;
+use md5::Md5;
+use mlua::{Error as LuaError, Lua, Result as LuaResult, String as LuaString, Table, Value};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use regex::Regex;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// What `url_encode` leaves alone: RFC 3986's unreserved characters.
+const URL_UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
+    // Patterns are compiled once per render, however many times a loop
+    // applies them.
+    let patterns = Patterns::default();
+
+    // regex_match(s, pattern) — whether the pattern matches anywhere in s
+    let cache = patterns.clone();
+    filters.set(
+        "regex_match",
+        lua.create_function(move |_, (s, pattern): (String, String)| {
+            Ok(cache.get("regex_match", &pattern)?.is_match(&s))
+        })?,
+    )?;
+
+    // regex_replace(s, pattern, replacement) — replace every match;
+    // `$1` and `${name}` in the replacement insert capture groups
+    let cache = patterns.clone();
+    filters.set(
+        "regex_replace",
+        lua.create_function(move |_, (s, pattern, replacement): (String, String, String)| {
+            Ok(cache
+                .get("regex_replace", &pattern)?
+                .replace_all(&s, replacement.as_str())
+                .into_owned())
+        })?,
+    )?;
+
+    // regex_captures(s, pattern) — the first match's groups: `[0]` is the
+    // whole match, `[1]`... the numbered groups, and named groups are also
+    // under their names. nil when nothing matches; a group that took no
+    // part in the match is nil too.
+    let cache = patterns;
+    filters.set(
+        "regex_captures",
+        lua.create_function(move |lua, (s, pattern): (String, String)| {
+            let regex = cache.get("regex_captures", &pattern)?;
+            let Some(captures) = regex.captures(&s) else {
+                return Ok(Value::Nil);
+            };
+            let table = lua.create_table()?;
+            for (index, group) in captures.iter().enumerate() {
+                if let Some(group) = group {
+                    table.set(index, group.as_str())?;
+                }
+            }
+            for name in regex.capture_names().flatten() {
+                if let Some(group) = captures.name(name) {
+                    table.set(name, group.as_str())?;
+                }
+            }
+            Ok(Value::Table(table))
+        })?,
+    )?;
+
+    // sha256(s), sha1(s), md5(s) — hex digests of s's bytes
+    filters.set(
+        "sha256",
+        lua.create_function(|_, s: LuaString| Ok(to_hex(&Sha256::digest(s.as_bytes()))))?,
+    )?;
+    filters.set(
+        "sha1",
+        lua.create_function(|_, s: LuaString| Ok(to_hex(&Sha1::digest(s.as_bytes()))))?,
+    )?;
+    filters.set(
+        "md5",
+        lua.create_function(|_, s: LuaString| Ok(to_hex(&Md5::digest(s.as_bytes()))))?,
+    )?;
+
+    // crc32(s) — the CRC-32 (IEEE) checksum of s's bytes, as a number
+    filters.set(
+        "crc32",
+        lua.create_function(|_, s: LuaString| Ok(i64::from(crc32fast::hash(&s.as_bytes()))))?,
+    )?;
+
+    // base64_encode(s) / base64_decode(s) — standard alphabet, padded
+    filters.set(
+        "base64_encode",
+        lua.create_function(|_, s: LuaString| Ok(base64::engine::general_purpose::STANDARD.encode(s.as_bytes())))?,
+    )?;
+    filters.set(
+        "base64_decode",
+        lua.create_function(|lua, s: String| {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(s.trim())
+                .map_err(|err| LuaError::RuntimeError(format!("filter `base64_decode`: invalid base64: {}", err)))?;
+            lua.create_string(bytes)
+        })?,
+    )?;
+
+    // hex(v) — lowercase hex of a string's bytes, or of an integer's value
+    filters.set(
+        "hex",
+        lua.create_function(|_, value: Value| match value {
+            Value::String(s) => Ok(to_hex(&s.as_bytes())),
+            Value::Integer(n) if n >= 0 => Ok(format!("{:x}", n)),
+            Value::Number(n) if n >= 0.0 && n.fract() == 0.0 && n <= i64::MAX as f64 => Ok(format!("{:x}", n as i64)),
+            other => Err(LuaError::RuntimeError(format!(
+                "filter `hex`: expected a string or a non-negative integer, got {}",
+                match other {
+                    Value::Integer(_) | Value::Number(_) => "a negative or fractional number",
+                    other => other.type_name(),
+                }
+            ))),
+        })?,
+    )?;
+
+    // url_encode(s) — percent-encode all but RFC 3986's unreserved characters
+    filters.set(
+        "url_encode",
+        lua.create_function(|_, s: String| Ok(utf8_percent_encode(&s, URL_UNRESERVED).to_string()))?,
+    )?;
+
+    Ok(())
+}
+
+/// Compiled patterns, shared by the regex filters.
+#[derive(Clone, Default)]
+struct Patterns(Rc<RefCell<HashMap<String, Regex>>>);
+
+impl Patterns {
+    fn get(&self, filter: &str, pattern: &str) -> LuaResult<Regex> {
+        if let Some(regex) = self.0.borrow().get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(pattern).map_err(|err| {
+            LuaError::RuntimeError(format!("filter `{}`: invalid pattern `{}`: {}", filter, pattern, err))
+        })?;
+        self.0.borrow_mut().insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -4,24 +4,29 @@
 //!   `{{ uuid_v4() }}`     — random v4 UUID
 //!   `{{ uuid_v7() }}`     — time-ordered v7 UUID (sortable)
 //!   `{{ uuid_nil() }}`    — `00000000-0000-0000-0000-000000000000`
+//!
+//! In a reproducible session, the random bits come from the seeded
+//! generator and v7's timestamp from the pinned clock.
 
 use mlua::{Lua, Result as LuaResult, Table};
-use uuid::Uuid;
+use uuid::{Builder, Uuid};
+
+use crate::reproducible::Reproducible;
 
 pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
     filters.set(
         "uuid",
-        lua.create_function(|_, ()| Ok(Uuid::new_v4().to_string()))?,
+        lua.create_function(|lua, ()| Ok(uuid_v4(lua).to_string()))?,
     )?;
 
     filters.set(
         "uuid_v4",
-        lua.create_function(|_, ()| Ok(Uuid::new_v4().to_string()))?,
+        lua.create_function(|lua, ()| Ok(uuid_v4(lua).to_string()))?,
     )?;
 
     filters.set(
         "uuid_v7",
-        lua.create_function(|_, ()| Ok(Uuid::now_v7().to_string()))?,
+        lua.create_function(|lua, ()| Ok(uuid_v7(lua).to_string()))?,
     )?;
 
     filters.set(
@@ -31,3 +36,22 @@ pub fn register(lua: &Lua, filters: &Table) -> LuaResult<()> {
 
     Ok(())
 }
+
+fn uuid_v4(lua: &Lua) -> Uuid {
+    let Some(reproducible) = Reproducible::of(lua) else {
+        return Uuid::new_v4();
+    };
+    let mut bytes = [0u8; 16];
+    reproducible.fill_bytes(&mut bytes);
+    Builder::from_random_bytes(bytes).into_uuid()
+}
+
+fn uuid_v7(lua: &Lua) -> Uuid {
+    let Some(reproducible) = Reproducible::of(lua) else {
+        return Uuid::now_v7();
+    };
+    let mut bytes = [0u8; 10];
+    reproducible.fill_bytes(&mut bytes);
+    let millis = reproducible.now().timestamp_millis().max(0) as u64;
+    Builder::from_unix_timestamp_millis(millis, &bytes).into_uuid()
+}
@@ -1,5 +1,10 @@
+use camino::Utf8PathBuf;
+
+use super::builtins::collections::CONTEXT_FIRST;
 use super::error::TemplateCompileError;
+use super::escape::Escaper;
 use super::include_resolver::IncludeResolver;
+use super::source_map::{Chunk, SourceMap};
 use super::tokenizer::{Filter, Token, Tokenizer};
 
 /// Compile-time options that influence the generated Lua source.
@@ -19,6 +24,10 @@ pub struct CompileOptions {
     /// Strip leading whitespace on lines that contain only a block tag.
     /// Maps to `templating.lstrip_blocks` in the manifest.
     pub lstrip_blocks: bool,
+    /// Escape every `{{ }}` interpolation for this format unless it is
+    /// marked `safe`. Picked per file from `templating.autoescape` in the
+    /// manifest, by extension; `{% autoescape %}` overrides it for a region.
+    pub autoescape: Option<Escaper>,
 }
 
 pub struct Compiler;
@@ -35,16 +44,32 @@ impl Compiler {
     /// Phase 4: when a `Token::Include` is encountered, the resolver reads
     /// the included file and the tokens are spliced inline. The included
     /// body shares `__ctx`, `__filters`, `__out`, and `__w` with the outer
-    /// template. Cycle detection is handled by the resolver.
+    /// template. Cycle detection is handled by the resolver. An include
+    /// `with` arguments is spliced into a scope whose context is just them.
+    ///
+    /// An include whose path is an expression can't be read until the path
+    /// is known. It calls the optional third argument, `__include(path,
+    /// escaper, ctx, args)`, which the renderer supplies to find, compile,
+    /// and run the partial, returning its output. The fourth, `__with`, is
+    /// true when the partial runs that way with arguments for its context.
     ///
     /// Phase 6: `opts.strict` installs a metatable on `__ctx` so that any
     /// undefined-variable access raises a render-time error.
+    ///
+    /// `{% block %}`s become functions in a `__blocks` table, defined before
+    /// the body runs. A template that `{% extends %}` a parent contributes
+    /// only its blocks; the body that runs is the root ancestor's, and each
+    /// override can reach the definition it replaced as `super()`.
+    ///
+    /// Alongside the source comes a [`SourceMap`] from the lines of Lua each
+    /// expression and logic block produced back to the template, so a
+    /// render-time error can be reported where the author wrote it.
     pub fn compile(
         tokens: &[Token],
         resolver: &mut IncludeResolver,
         opts: CompileOptions,
-    ) -> Result<String, TemplateCompileError> {
-        let mut lua = String::with_capacity(1024);
+    ) -> Result<(String, SourceMap), TemplateCompileError> {
+        let mut lua = Chunk::default();
 
         // Function preamble — set up output buffer and _ENV for context resolution.
         //
@@ -62,32 +87,76 @@ impl Compiler {
         //
         // Filters take precedence over context. An author who calls
         // `ctx:set("now", ...)` will not shadow the `now` builtin.
-        lua.push_str("return function(__ctx, __filters)\n");
+        // `__destination` is where the file being rendered will land, when
+        // there is one: templates see it as `destination`.
+        lua.push_str("return function(__ctx, __filters, __include, __with, __destination)\n");
         lua.push_str("    local __out = {}\n");
         // nil is dropped silently — emitting the literal "nil" into a generated source
         // file is far worse than an empty interpolation. Strict mode (Phase 6) will
         // offer fail-on-undefined as an opt-in.
         lua.push_str("    local __w = function(s) if s ~= nil then __out[#__out+1] = tostring(s) end end\n");
-        // Strict mode: install a metatable on __ctx that errors when a key
-        // is missing. The lookup chain `_ENV → __filters → __ctx → metatable`
-        // means undefined bare names like `{{ name }}` reach the metatable
-        // function and raise a Lua RuntimeError that surfaces as
-        // `RenderError::LuaTemplateRuntimeError` with a clear message.
+        // Autoescaping: `__e` runs a value through an escaper unless it was
+        // marked safe — by `| safe` or `safe(x)`, or by being what a macro
+        // rendered, which was escaped as it was written. The mark is on the
+        // value, not its text: a safe value is its string wrapped in a
+        // `__Safe` table, which prints, concatenates and takes string
+        // methods like the string, and which filters get unwrapped. The
+        // same text arriving some other way is still escaped.
+        lua.push_str("    local __Safe = {}\n");
+        lua.push_str("    __Safe.__tostring = function(v) return v[1] end\n");
+        lua.push_str("    __Safe.__concat = function(a, b) return tostring(a) .. tostring(b) end\n");
+        lua.push_str("    __Safe.__len = function(v) return #v[1] end\n");
+        lua.push_str("    __Safe.__eq = function(a, b) return a[1] == b[1] end\n");
+        lua.push_str(
+            "    __Safe.__index = function(v, k) local f = string[k] if f then return function(self, ...) return f(self[1], ...) end end end\n",
+        );
+        lua.push_str(
+            "    local __plain = function(v) if getmetatable(v) == __Safe then return v[1] end return v end\n",
+        );
+        lua.push_str(
+            "    local __mark_safe = function(s) if s == nil or getmetatable(s) == __Safe then return s end return setmetatable({ tostring(s) }, __Safe) end\n",
+        );
+        lua.push_str(
+            "    local __e = function(v, escape) if v == nil then return nil end if getmetatable(v) == __Safe then return v[1] end return escape(tostring(v)) end\n",
+        );
+        // Filters are Rust functions that take strings: hand them the bare
+        // string of a safe value. What they return is a new value, and is
+        // escaped like any other. The shared table is left as it is.
+        lua.push_str("    local __builtins = __filters\n");
+        lua.push_str("    __filters = setmetatable({}, { __index = function(t, name)\n");
+        lua.push_str("        local f = __builtins[name]\n");
+        lua.push_str("        if type(f) ~= \"function\" then return f end\n");
+        lua.push_str("        local g = function(...)\n");
+        lua.push_str("            local args = table.pack(...)\n");
+        lua.push_str("            for i = 1, args.n do args[i] = __plain(args[i]) end\n");
+        lua.push_str("            return f(table.unpack(args, 1, args.n))\n");
+        lua.push_str("        end\n");
+        lua.push_str("        rawset(t, name, g)\n");
+        lua.push_str("        return g\n");
+        lua.push_str("    end })\n");
+        // Includes with a path computed at render time need the renderer;
+        // rendering a string or a file name has none to offer.
+        lua.push_str(
+            "    __include = __include or function() error(\"dynamic includes are only available to template files\", 2) end\n",
+        );
+        // `__scope(ctx, with)` builds the `_ENV` a template body runs in:
+        // the stdlib subset below, then filters, then `ctx`. The root body
+        // runs in `__scope(__ctx)`; an include `with` arguments runs in a
+        // scope of its own, with the arguments as its whole context. Those
+        // were passed deliberately, so they shadow the builtins — `with {
+        // type = f.type }` gives the partial a `type`.
         //
-        // Note: an EXPLICIT nil (e.g., `ctx:set("x", nil)`) is still
-        // resolvable — rawget on __ctx returns nil for both "absent" and
-        // "explicitly nil". This matches the spec: undefined-vs-nil is a
-        // distinction we don't preserve at this layer because Lua tables
-        // can't distinguish them.
-        if opts.strict {
-            lua.push_str(
-                "    setmetatable(__ctx, {__index = function(_, k) error(\"undefined template variable: \" .. tostring(k), 2) end})\n",
-            );
+        // Names fall through to `__filters` and the context by function
+        // rather than by chaining metatables onto them, since both tables
+        // are shared — with the partials a render includes, and across
+        // renders.
+        lua.push_str("    local __context_first = {");
+        for name in CONTEXT_FIRST {
+            lua.push_str(&format!(" {} = true,", name));
         }
-        // Chain __filters → __ctx so that bare names in `{{ }}` resolve through
-        // both. The setmetatable call is per-render but idempotent.
-        lua.push_str("    setmetatable(__filters, {__index = __ctx})\n");
-        lua.push_str("    local _ENV = setmetatable({\n");
+        lua.push_str(" }\n");
+        lua.push_str("    local __scope = function(__ctx, with)\n");
+        lua.push_str("        local env = setmetatable({\n");
         lua.push_str("        __ctx = __ctx,\n");
         lua.push_str("        __filters = __filters,\n");
         lua.push_str("        __out = __out,\n");
@@ -104,13 +173,15 @@ impl Compiler {
         lua.push_str("        print = print,\n");
         lua.push_str("        error = error,\n");
         lua.push_str("        pcall = pcall,\n");
-        lua.push_str("        select = select,\n");
+        // No `select` here: the collection filter of that name answers
+        // Lua's `select(n, ...)` calls too.
         lua.push_str("        unpack = table.unpack or unpack,\n");
         lua.push_str("        next = next,\n");
         lua.push_str("        rawget = rawget,\n");
         lua.push_str("        rawset = rawset,\n");
         lua.push_str("        setmetatable = setmetatable,\n");
         lua.push_str("        getmetatable = getmetatable,\n");
+        lua.push_str("        safe = __mark_safe,\n");
         // Read-only deterministic data exposed to templates so authors can
         // branch on switches / platform / process flags directly inside
         // `{% if %}` blocks without having to mirror values into the
@@ -134,15 +205,320 @@ impl Compiler {
         lua.push_str("        RIGHT_EXPR = \"}}\", RE = \"}}\",\n");
         lua.push_str("        LEFT_STMT = \"{%\", LS = \"{%\",\n");
         lua.push_str("        RIGHT_STMT = \"%}\", RS = \"%}\",\n");
-        lua.push_str("    }, {__index = __filters})\n");
+        lua.push_str("        }, {__index = function(_, k)\n");
+        // Filters take precedence over context, so `ctx:set("now", ...)`
+        // does not shadow the `now` builtin — except the collection names
+        // answers and models commonly use themselves (`items`, `keys`, ...),
+        // where the context wins. The pipe form reaches the filter either way.
+        lua.push_str("            local v\n");
+        lua.push_str("            if __context_first[k] then v = __ctx[k] if v == nil then v = __filters[k] end\n");
+        lua.push_str("            else v = __filters[k] if v == nil then v = __ctx[k] end end\n");
+        // `destination` comes last, so a context key of that name keeps
+        // meaning what it always did.
+        lua.push_str("            if v == nil and k == \"destination\" then v = __destination end\n");
+        // Strict mode: a name neither defines is an error rather than nil.
+        // Level 2 blames the template code doing the lookup, so the error
+        // maps back to the expression that named it.
+        //
+        // Note: an EXPLICIT nil (e.g., `ctx:set("x", nil)`) is still
+        // undefined — Lua tables can't distinguish "absent" from
+        // "explicitly nil", so neither does this.
+        if opts.strict {
+            lua.push_str(
+                "            if v == nil then error(\"undefined template variable: \" .. tostring(k), 2) end\n",
+            );
+        }
+        lua.push_str("            return v\n");
+        lua.push_str("        end})\n");
+        lua.push_str("        if with then for k, v in next, __ctx do env[k] = v end end\n");
+        lua.push_str("        return env\n");
+        lua.push_str("    end\n");
+        lua.push_str("    local _ENV = __scope(__ctx, __with)\n");
         lua.push_str("\n");
 
-        compile_body(tokens, resolver, opts, &mut lua)?;
+        compile_layouts(tokens, resolver, opts, &mut lua)?;
 
         lua.push_str("\n    return table.concat(__out)\n");
         lua.push_str("end\n");
 
-        Ok(lua)
+        Ok(lua.finish())
+    }
+}
+
+/// A template's tokens with its `{% block %}`s lifted out. Each block's
+/// tokens keep their opening and closing tags at either end, so whitespace
+/// control sees the same neighbours it would in place; in `body` (or an
+/// enclosing block) a `BlockCall` marks where the block renders.
+///
+/// The body of a template that extends another never runs, but its blocks
+/// still call the macros it defines and imports at the top level; those
+/// move to `prelude`.
+struct Layout {
+    extends: Option<(String, usize)>,
+    body: Vec<Token>,
+    blocks: Vec<(String, Vec<Token>)>,
+    prelude: Vec<Token>,
+}
+
+impl Layout {
+    fn split(tokens: &[Token]) -> Result<Layout, TemplateCompileError> {
+        let mut layout = Layout {
+            extends: None,
+            body: Vec::new(),
+            blocks: Vec::new(),
+            prelude: Vec::new(),
+        };
+        // Blocks still open, innermost last, each with its tokens so far.
+        let mut open: Vec<(String, usize, Vec<Token>)> = Vec::new();
+        // Macros still open. A block can't live in one: it would render
+        // into the page rather than into the macro's result.
+        let mut macros = 0usize;
+        // Autoescape regions still open. A block lifted out of one takes
+        // the innermost with it.
+        let mut escaping: Vec<&Token> = Vec::new();
+        let mut enclosing_escapes: Vec<Option<&Token>> = Vec::new();
+        for token in tokens {
+            match token {
+                Token::Macro { .. } => macros += 1,
+                Token::EndMacro { .. } => macros = macros.saturating_sub(1),
+                Token::Autoescape { .. } => escaping.push(token),
+                Token::EndAutoescape { .. } => {
+                    escaping.pop();
+                }
+                _ => {}
+            }
+            match token {
+                Token::Extends { path, line, .. } => {
+                    let detail = if !open.is_empty() {
+                        "`{% extends %}` cannot appear inside a block"
+                    } else if layout.extends.is_some() {
+                        "a template can extend only one parent"
+                    } else {
+                        layout.extends = Some((path.clone(), *line));
+                        continue;
+                    };
+                    return Err(TemplateCompileError::InvalidExtends {
+                        line: *line,
+                        detail: detail.to_string(),
+                    });
+                }
+                Token::Block { line, .. } if macros > 0 => {
+                    return Err(TemplateCompileError::InvalidBlock {
+                        line: *line,
+                        detail: "a block cannot be defined inside a macro".to_string(),
+                    });
+                }
+                Token::Block { name, line, .. } => {
+                    let taken = layout.blocks.iter().map(|(taken, _)| taken);
+                    if taken.chain(open.iter().map(|(taken, _, _)| taken)).any(|taken| taken == name) {
+                        return Err(TemplateCompileError::InvalidBlock {
+                            line: *line,
+                            detail: format!("block `{}` is defined more than once", name),
+                        });
+                    }
+                    open.push((name.clone(), *line, vec![token.clone()]));
+                    enclosing_escapes.push(escaping.last().copied());
+                }
+                Token::EndBlock {
+                    name: closing,
+                    line,
+                    trim_right,
+                    ..
+                } => {
+                    let Some((name, _, mut block)) = open.pop() else {
+                        return Err(TemplateCompileError::InvalidBlock {
+                            line: *line,
+                            detail: "`{% endblock %}` without an open block".to_string(),
+                        });
+                    };
+                    if closing.as_ref().is_some_and(|closing| *closing != name) {
+                        return Err(TemplateCompileError::InvalidBlock {
+                            line: *line,
+                            detail: format!(
+                                "`{{% endblock {} %}}` closes block `{}`",
+                                closing.as_deref().unwrap_or_default(),
+                                name
+                            ),
+                        });
+                    }
+                    let call = Token::BlockCall {
+                        name: name.clone(),
+                        trim_left: has_trim_left(&block[0]),
+                        trim_right: *trim_right,
+                    };
+                    block.push(token.clone());
+                    if let Some(escape) = enclosing_escapes.pop().flatten() {
+                        block.insert(0, escape.clone());
+                        block.push(Token::EndAutoescape {
+                            line: *line,
+                            trim_left: false,
+                            trim_right: false,
+                        });
+                    }
+                    layout.blocks.push((name, block));
+                    match open.last_mut() {
+                        Some((_, _, enclosing)) => enclosing.push(call),
+                        None => layout.body.push(call),
+                    }
+                }
+                other => match open.last_mut() {
+                    Some((_, _, block)) => block.push(other.clone()),
+                    None => layout.body.push(other.clone()),
+                },
+            }
+        }
+        if let Some((name, line, _)) = open.pop() {
+            return Err(TemplateCompileError::UnterminatedBlock { name, line });
+        }
+        if layout.extends.is_some() {
+            layout.prelude = definitions(&layout.body);
+        }
+        Ok(layout)
+    }
+}
+
+/// The imports and macro definitions among `tokens`, in order.
+fn definitions(tokens: &[Token]) -> Vec<Token> {
+    let mut definitions = Vec::new();
+    let mut depth = 0usize;
+    for token in tokens {
+        match token {
+            Token::Macro { .. } => depth += 1,
+            Token::EndMacro { .. } if depth > 0 => {
+                definitions.push(token.clone());
+                depth -= 1;
+                continue;
+            }
+            Token::Import { .. } => {}
+            _ if depth == 0 => continue,
+            _ => {}
+        }
+        definitions.push(token.clone());
+    }
+    definitions
+}
+
+/// Emit a template and the ancestors it `{% extends %}` into `lua`.
+///
+/// Parents are read through `resolver`, so they come from the same include
+/// directories as partials (staged libraries included) and a template that
+/// extends itself, directly or not, is caught as a cycle. Block definitions
+/// run root first, so each override captures the one before it.
+fn compile_layouts(
+    tokens: &[Token],
+    resolver: &mut IncludeResolver,
+    opts: CompileOptions,
+    lua: &mut Chunk,
+) -> Result<(), TemplateCompileError> {
+    let mut levels = vec![(None, None, Layout::split(tokens)?)];
+    let result = read_ancestors(&mut levels, resolver).and_then(|()| emit_levels(&levels, resolver, opts, lua));
+    // The child itself was never pushed onto the resolver's stack.
+    for _ in 1..levels.len() {
+        resolver.pop();
+    }
+    result
+}
+
+/// Follow `extends` from the last of `levels` up to a template that extends
+/// nothing, appending each parent with the path it was named by and its text.
+fn read_ancestors(levels: &mut Vec<Level>, resolver: &mut IncludeResolver) -> Result<(), TemplateCompileError> {
+    while let Some((path, line)) = levels.last().and_then(|(_, _, layout)| layout.extends.clone()) {
+        let named_by = levels.last().and_then(|(named_by, _, _)| named_by.clone());
+        let (contents, resolved) = resolver.read(&path, line).map_err(|err| in_parent(named_by.as_deref(), err))?;
+        let layout = Tokenizer::tokenize(&contents)
+            .and_then(|tokens| Layout::split(&tokens))
+            .map_err(|err| in_parent(Some(&path), err))?;
+        levels.push((Some(path), Some((contents, resolved)), layout));
+    }
+    Ok(())
+}
+
+/// A template in an `extends` chain: the path it was named by, and its text
+/// and the file it resolved to (neither for the template being compiled),
+/// and its layout.
+type Level = (Option<String>, Option<(String, Utf8PathBuf)>, Layout);
+
+fn emit_levels(
+    levels: &[Level],
+    resolver: &mut IncludeResolver,
+    opts: CompileOptions,
+    lua: &mut Chunk,
+) -> Result<(), TemplateCompileError> {
+    if levels.iter().any(|(_, _, layout)| !layout.blocks.is_empty()) {
+        lua.push_str("    local __blocks = {}\n");
+    }
+    let root = levels.len() - 1;
+    let mut preludes = lua.sibling();
+    let mut body = lua.sibling();
+    for (depth, (path, contents, layout)) in levels.iter().enumerate().rev() {
+        let mut level = lua.sibling();
+        if let (Some(path), Some((contents, resolved))) = (path, contents) {
+            level.enter(path, resolved, contents);
+        }
+        for (name, block) in &layout.blocks {
+            emit_block(name, block, resolver, opts, &mut level).map_err(|err| in_parent(path.as_deref(), err))?;
+        }
+        let mut prelude = level.sibling();
+        compile_body(&layout.prelude, resolver, opts, &mut prelude).map_err(|err| in_parent(path.as_deref(), err))?;
+        let mut root_body = level.sibling();
+        if depth == root {
+            compile_body(&layout.body, resolver, opts, &mut root_body)
+                .map_err(|err| in_parent(path.as_deref(), err))?;
+        }
+        // The merged function is checked as a whole once it is complete, but
+        // under the child's name. Check each parent's share on its own first,
+        // so a bad logic block is reported against the template it is in.
+        if let Some(path) = path {
+            let standalone = format!(
+                "return function(__ctx, __filters)\n    local __blocks = {{}}\n{}{}{}end\n",
+                level.as_str(),
+                prelude.as_str(),
+                root_body.as_str()
+            );
+            super::validate_lua_syntax(&standalone, path).map_err(|err| in_parent(Some(path), err))?;
+        }
+        lua.append(&level);
+        preludes.append(&prelude);
+        body.append(&root_body);
+    }
+    // Every definition is in place, down to the child's, before the root's
+    // body calls any of them. Blocks find macros through `_ENV` when they
+    // run, so the preludes only have to come first too.
+    lua.append(&preludes);
+    lua.append(&body);
+    Ok(())
+}
+
+/// Define `__blocks[name]` as a function rendering `block`, with `super`
+/// bound to whatever definition it replaces.
+fn emit_block(
+    name: &str,
+    block: &[Token],
+    resolver: &mut IncludeResolver,
+    opts: CompileOptions,
+    lua: &mut Chunk,
+) -> Result<(), TemplateCompileError> {
+    lua.push_str("    do\n");
+    lua.push_str(&format!("    local __super = __blocks[\"{}\"]\n", name));
+    lua.push_str(&format!("    __blocks[\"{}\"] = function()\n", name));
+    lua.push_str(&format!(
+        "    local super = __super or function() error(\"super(): block `{}` has no parent to render\", 2) end\n",
+        name
+    ));
+    compile_body(block, resolver, opts, lua)?;
+    lua.push_str("    end\n");
+    lua.push_str("    end\n");
+    Ok(())
+}
+
+/// Attribute `err` to the parent template at `path`, if it came from one.
+fn in_parent(path: Option<&str>, err: TemplateCompileError) -> TemplateCompileError {
+    match path {
+        Some(path) => TemplateCompileError::ExtendsChain {
+            parent_path: path.to_string(),
+            source: Box::new(err),
+        },
+        None => err,
     }
 }
 
@@ -152,9 +528,12 @@ impl Compiler {
 fn compile_body(
     tokens: &[Token],
     resolver: &mut IncludeResolver,
-    opts: CompileOptions,
-    lua: &mut String,
+    mut opts: CompileOptions,
+    lua: &mut Chunk,
 ) -> Result<(), TemplateCompileError> {
+    check_macros_balance(tokens)?;
+    // The escaper each open `{% autoescape %}` replaced, with its line.
+    let mut escaping: Vec<(Option<Escaper>, usize)> = Vec::new();
     let token_count = tokens.len();
     for (i, token) in tokens.iter().enumerate() {
         match token {
@@ -207,14 +586,21 @@ fn compile_body(
                     lua.push_str("\")\n");
                 }
             }
-            Token::Expression { expr, filters, .. } => {
+            Token::Expression {
+                expr,
+                filters,
+                line,
+                column,
+                ..
+            } => {
                 let safe_expr = make_lua_safe(expr);
-                let value_expr = apply_filters(&safe_expr, filters);
-                lua.push_str("    __w(");
-                lua.push_str(&value_expr);
-                lua.push_str(")\n");
+                let mut value_expr = apply_filters(&safe_expr, filters);
+                if let Some(escaper) = opts.autoescape {
+                    value_expr = format!("__e({}, __filters.{})", value_expr, escaper.filter_name());
+                }
+                lua.push_from(*line, *column, &format!("    __w({})\n", value_expr));
             }
-            Token::Logic { code, .. } => {
+            Token::Logic { code, line, column, .. } => {
                 // Pre-pass: expand `?.` optional chains in the code before
                 // sugar rewrites, so `{% if a?.b?.c %}` works.
                 let code = expand_optional_chains_in_code(code);
@@ -222,11 +608,19 @@ fn compile_body(
                 // ergonomic Lua. If no sugar pattern matches, the body
                 // passes through verbatim as raw Lua.
                 let rewritten = rewrite_sugar(&code);
-                lua.push_str("    ");
-                lua.push_str(rewritten.as_deref().unwrap_or(&code));
-                lua.push('\n');
+                lua.push_from(
+                    *line,
+                    *column,
+                    &format!("    {}\n", rewritten.as_deref().unwrap_or(&code)),
+                );
             }
-            Token::Include { path, line, .. } => {
+            Token::Include {
+                path,
+                args,
+                line,
+                column,
+                ..
+            } => {
                 // Resolve and read the included file via the resolver
                 // (which checks the cycle stack and the includes-dir
                 // sandbox), then recursively splice its body into the
@@ -240,14 +634,29 @@ fn compile_body(
                 // the partial template's body (tokenize + recursive
                 // compile), so the user can tell which partial actually
                 // contains the malformed content.
-                let (contents, _resolved) = resolver.read(path, *line)?;
+                let (contents, resolved) = resolver.read(path, *line)?;
                 let wrap = |source| TemplateCompileError::IncludeChain {
                     include_path: path.clone(),
                     source: Box::new(source),
                 };
-                let nested_tokens = Tokenizer::tokenize(&contents).map_err(wrap)?;
+                let nested_tokens = Tokenizer::tokenize(&contents)
+                    .and_then(|tokens| reject_inheritance(&tokens).map(|()| tokens))
+                    .map_err(wrap)?;
+                // With arguments, the partial sees them and the builtins,
+                // and nothing else: not the context, and not the names the
+                // includer defined.
+                if let Some(args) = args {
+                    lua.push_str("    do\n");
+                    lua.push_from(*line, *column, &format!("    local __ctx = {}\n", args));
+                    lua.push_str("    local _ENV = __scope(__ctx, true)\n");
+                }
+                let outer = lua.enter(path, &resolved, &contents);
                 let result = compile_body(&nested_tokens, resolver, opts, lua);
+                lua.leave(outer);
                 resolver.pop();
+                if args.is_some() {
+                    lua.push_str("    end\n");
+                }
                 result.map_err(|e| match e {
                     // Already chained — leave as-is so the chain reads
                     // outermost-first without re-wrapping.
@@ -263,11 +672,152 @@ fn compile_body(
                     },
                 })?;
             }
+            Token::DynamicInclude {
+                path,
+                args,
+                line,
+                column,
+                ..
+            } => {
+                // Found and compiled once the path is known. Without
+                // arguments the partial sees the same context; either way,
+                // its output is escaped as it is written, for whatever
+                // region this include sits in, so it isn't escaped again.
+                let escaper = match opts.autoescape {
+                    Some(escaper) => format!("\"{}\"", escaper.name()),
+                    None => "nil".to_string(),
+                };
+                lua.push_from(
+                    *line,
+                    *column,
+                    &format!(
+                        "    __w(__include({}, {}, __ctx, {}))\n",
+                        path,
+                        escaper,
+                        args.as_deref().unwrap_or("nil")
+                    ),
+                );
+            }
+            Token::Macro { name, params, .. } => {
+                // A macro is a function in `_ENV`, so blocks and imports
+                // find it by name however it was defined. It renders into
+                // a buffer of its own and returns the text.
+                let names = params.iter().map(|(param, _)| param.as_str()).collect::<Vec<_>>();
+                lua.push_str(&format!("    {} = function({})\n", name, names.join(", ")));
+                lua.push_str("    local __out = {}\n");
+                lua.push_str("    local __w = function(s) if s ~= nil then __out[#__out+1] = tostring(s) end end\n");
+                for (param, default) in params {
+                    if let Some(default) = default {
+                        lua.push_str(&format!("    if {0} == nil then {0} = {1} end\n", param, default));
+                    }
+                }
+            }
+            Token::EndMacro { .. } => {
+                lua.push_str("    return __mark_safe(table.concat(__out))\n");
+                lua.push_str("    end\n");
+            }
+            Token::Import { path, alias, line, .. } => {
+                // The imported template runs once, with its output thrown
+                // away, against an `_ENV` of its own that falls back to
+                // ours. Whatever it defines there — its macros — becomes
+                // the module bound to `alias`.
+                let (contents, resolved) = resolver.read(path, *line)?;
+                let wrap = |source| TemplateCompileError::ImportChain {
+                    import_path: path.clone(),
+                    source: Box::new(source),
+                };
+                let nested_tokens = Tokenizer::tokenize(&contents)
+                    .and_then(|tokens| reject_inheritance(&tokens).map(|()| tokens))
+                    .map_err(wrap)?;
+                lua.push_str(&format!("    {} = (function()\n", alias));
+                lua.push_str("    local __w = function() end\n");
+                lua.push_str("    local _ENV = setmetatable({}, {__index = _ENV})\n");
+                let outer = lua.enter(path, &resolved, &contents);
+                let result = compile_body(&nested_tokens, resolver, opts, lua);
+                lua.leave(outer);
+                resolver.pop();
+                result.map_err(wrap)?;
+                lua.push_str("    local __module = {}\n");
+                lua.push_str("    for k, v in next, _ENV do __module[k] = v end\n");
+                lua.push_str("    return __module\n");
+                lua.push_str("    end)()\n");
+            }
+            Token::Autoescape { escaper, line, .. } => {
+                escaping.push((opts.autoescape, *line));
+                opts.autoescape = *escaper;
+            }
+            Token::EndAutoescape { line, .. } => {
+                let Some((outer, _)) = escaping.pop() else {
+                    return Err(TemplateCompileError::InvalidAutoescape {
+                        line: *line,
+                        detail: "`{% endautoescape %}` without an open autoescape".to_string(),
+                    });
+                };
+                opts.autoescape = outer;
+            }
+            Token::BlockCall { name, .. } => {
+                lua.push_str(&format!("    __blocks[\"{}\"]()\n", name));
+            }
+            Token::Block { .. } | Token::EndBlock { .. } | Token::Extends { .. } => {
+                // The tags at either end of a lifted block's tokens. They
+                // only steer whitespace control — emit nothing.
+            }
             Token::Comment => {
                 // Comments are stripped — emit nothing
             }
         }
     }
+    if let Some((_, line)) = escaping.pop() {
+        return Err(TemplateCompileError::UnterminatedAutoescape { line });
+    }
+    Ok(())
+}
+
+/// Every `{% macro %}` in `tokens` must close within them. An `endmacro`
+/// emits a bare Lua `end`, so a stray one could otherwise close an `if`.
+fn check_macros_balance(tokens: &[Token]) -> Result<(), TemplateCompileError> {
+    let mut open = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Macro { name, line, .. } => open.push((name, *line)),
+            Token::EndMacro { line, .. } if open.pop().is_none() => {
+                return Err(TemplateCompileError::InvalidMacro {
+                    line: *line,
+                    detail: "`{% endmacro %}` without an open macro".to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+    match open.pop() {
+        Some((name, line)) => Err(TemplateCompileError::UnterminatedMacro {
+            name: name.clone(),
+            line,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Included partials are spliced inline, so they have no body of their own
+/// for blocks to live in or a parent to fill in.
+fn reject_inheritance(tokens: &[Token]) -> Result<(), TemplateCompileError> {
+    for token in tokens {
+        match token {
+            Token::Extends { line, .. } => {
+                return Err(TemplateCompileError::InvalidExtends {
+                    line: *line,
+                    detail: "an included template cannot extend another".to_string(),
+                })
+            }
+            Token::Block { line, .. } | Token::EndBlock { line, .. } => {
+                return Err(TemplateCompileError::InvalidBlock {
+                    line: *line,
+                    detail: "an included template cannot define blocks".to_string(),
+                })
+            }
+            _ => {}
+        }
+    }
     Ok(())
 }
 
@@ -767,7 +1317,10 @@ fn apply_filters(expr: &str, filters: &[Filter]) -> String {
 
     let mut result = expr.to_string();
     for filter in filters {
-        if filter.args.is_empty() {
+        if filter.name == "safe" {
+            // Not a filter at all: it exempts the value from autoescaping.
+            result = format!("__mark_safe({})", result);
+        } else if filter.args.is_empty() {
             result = format!("__filters.{}({})", filter.name, result);
         } else {
             result = format!(
@@ -803,6 +1356,16 @@ fn has_trim_right(token: &Token) -> bool {
         Token::Expression { trim_right, .. } => *trim_right,
         Token::Logic { trim_right, .. } => *trim_right,
         Token::Include { trim_right, .. } => *trim_right,
+        Token::DynamicInclude { trim_right, .. } => *trim_right,
+        Token::Extends { trim_right, .. } => *trim_right,
+        Token::Block { trim_right, .. } => *trim_right,
+        Token::EndBlock { trim_right, .. } => *trim_right,
+        Token::BlockCall { trim_right, .. } => *trim_right,
+        Token::Macro { trim_right, .. } => *trim_right,
+        Token::EndMacro { trim_right, .. } => *trim_right,
+        Token::Import { trim_right, .. } => *trim_right,
+        Token::Autoescape { trim_right, .. } => *trim_right,
+        Token::EndAutoescape { trim_right, .. } => *trim_right,
         _ => false,
     }
 }
@@ -812,15 +1375,39 @@ fn has_trim_left(token: &Token) -> bool {
         Token::Expression { trim_left, .. } => *trim_left,
         Token::Logic { trim_left, .. } => *trim_left,
         Token::Include { trim_left, .. } => *trim_left,
+        Token::DynamicInclude { trim_left, .. } => *trim_left,
+        Token::Extends { trim_left, .. } => *trim_left,
+        Token::Block { trim_left, .. } => *trim_left,
+        Token::EndBlock { trim_left, .. } => *trim_left,
+        Token::BlockCall { trim_left, .. } => *trim_left,
+        Token::Macro { trim_left, .. } => *trim_left,
+        Token::EndMacro { trim_left, .. } => *trim_left,
+        Token::Import { trim_left, .. } => *trim_left,
+        Token::Autoescape { trim_left, .. } => *trim_left,
+        Token::EndAutoescape { trim_left, .. } => *trim_left,
         _ => false,
     }
 }
 
-/// True if the token is a `{% ... %}` block tag — Logic or Include.
-/// `trim_blocks` and `lstrip_blocks` only fire around block tags, not
-/// around `{{ ... }}` expressions.
+/// True if the token is a `{% ... %}` block tag — Logic, Include, or one of
+/// the inheritance, macro, import, or autoescape tags. `trim_blocks` and `lstrip_blocks` only fire around
+/// block tags, not around `{{ ... }}` expressions.
 fn is_block_token(token: &Token) -> bool {
-    matches!(token, Token::Logic { .. } | Token::Include { .. })
+    matches!(
+        token,
+        Token::Logic { .. }
+            | Token::Include { .. }
+            | Token::DynamicInclude { .. }
+            | Token::Extends { .. }
+            | Token::Block { .. }
+            | Token::EndBlock { .. }
+            | Token::BlockCall { .. }
+            | Token::Macro { .. }
+            | Token::EndMacro { .. }
+            | Token::Import { .. }
+            | Token::Autoescape { .. }
+            | Token::EndAutoescape { .. }
+    )
 }
 
 /// `lstrip_blocks` companion: if the trailing portion of `text` (the part
@@ -881,7 +1468,7 @@ mod tests {
     /// exercised in mod.rs.
     fn compile(tokens: &[Token]) -> String {
         let mut resolver = IncludeResolver::disabled();
-        Compiler::compile(tokens, &mut resolver, CompileOptions::default()).unwrap()
+        Compiler::compile(tokens, &mut resolver, CompileOptions::default()).unwrap().0
     }
 
     #[test]
@@ -1048,7 +1635,7 @@ message {{ entity.name.pascal }} {
         let tokens = Tokenizer::tokenize("hello").unwrap();
         let lua = compile(&tokens);
 
-        assert!(lua.starts_with("return function(__ctx, __filters)"));
+        assert!(lua.starts_with("return function(__ctx, __filters, __include, __with, __destination)"));
         assert!(lua.contains("local __out = {}"));
         assert!(lua.contains("local __w = function(s) if s ~= nil then __out[#__out+1] = tostring(s) end end"));
         assert!(lua.contains("return table.concat(__out)"));
@@ -0,0 +1,207 @@
+//! Which entries `directory.render` skips.
+//!
+//! Two sources of globs feed the decision: the `exclude`/`include` lists in
+//! the `directory.render` options, rooted at the directory being rendered,
+//! and `.archetectignore` files, each rooted at the directory that holds it
+//! and applying to everything beneath. Every glob is tried against both the
+//! entry's source path and its rendered destination path, so an author can
+//! name a file by whichever they find easier to write down.
+//!
+//! A glob without a `/` matches an entry's name at any depth (`.DS_Store`,
+//! `*~`); a glob with one matches the path relative to its root, with `*`
+//! stopping at separators and `**` crossing them (`docs/**`,
+//! `src/*/generated.rs`).
+
+use std::fs;
+use std::sync::Arc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::errors::RenderError;
+
+/// File name of the per-directory ignore list. Blank lines and lines starting
+/// with `#` are skipped; every other line is a glob.
+pub const IGNORE_FILE: &str = ".archetectignore";
+
+/// The exclude and include globs in force at one point of a directory walk.
+/// Cheap to clone: descending into a directory shares its parent's layers.
+#[derive(Clone, Debug, Default)]
+pub struct EntryFilter {
+    excludes: Vec<Arc<GlobLayer>>,
+    include: Option<Arc<GlobLayer>>,
+}
+
+/// Globs rooted at a source directory and the destination it renders to.
+#[derive(Debug)]
+struct GlobLayer {
+    source: Utf8PathBuf,
+    destination: Utf8PathBuf,
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl EntryFilter {
+    /// The filter for a `directory.render` of `source` into `destination`.
+    /// An empty `include` list includes everything.
+    pub fn new(
+        source: &Utf8Path,
+        destination: &Utf8Path,
+        exclude: &[String],
+        include: &[String],
+    ) -> Result<EntryFilter, RenderError> {
+        let mut filter = EntryFilter::default();
+        if !exclude.is_empty() {
+            filter.excludes.push(Arc::new(GlobLayer::new(source, destination, exclude)?));
+        }
+        if !include.is_empty() {
+            filter.include = Some(Arc::new(GlobLayer::new(source, destination, include)?));
+        }
+        Ok(filter)
+    }
+
+    /// The filter for the entries of `source`: this one, plus whatever the
+    /// directory's own `.archetectignore` adds.
+    pub fn descend(&self, source: &Utf8Path, destination: &Utf8Path) -> Result<EntryFilter, RenderError> {
+        let ignore_file = source.join(IGNORE_FILE);
+        if !ignore_file.is_file() {
+            return Ok(self.clone());
+        }
+        let contents = fs::read_to_string(&ignore_file).map_err(|err| RenderError::FileReadError {
+            path: ignore_file.clone(),
+            source: err,
+        })?;
+        let patterns: Vec<String> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+
+        let mut filter = self.clone();
+        if !patterns.is_empty() {
+            filter.excludes.push(Arc::new(GlobLayer::new(source, destination, &patterns)?));
+        }
+        Ok(filter)
+    }
+
+    /// Whether the entry at `source`, rendering to `destination`, is skipped.
+    /// The include list narrows files only: a directory is always descended
+    /// unless excluded, since `src/**/*.rs` can only match beneath `src`.
+    pub fn skips(&self, source: &Utf8Path, destination: &Utf8Path, is_dir: bool) -> bool {
+        if self.excludes.iter().any(|layer| layer.matches(source, destination)) {
+            return true;
+        }
+        match &self.include {
+            Some(layer) if !is_dir => !layer.matches(source, destination),
+            _ => false,
+        }
+    }
+}
+
+impl GlobLayer {
+    fn new(source: &Utf8Path, destination: &Utf8Path, patterns: &[String]) -> Result<GlobLayer, RenderError> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in patterns {
+            let trimmed = pattern.trim_matches('/');
+            let glob = GlobBuilder::new(trimmed)
+                .literal_separator(true)
+                .build()
+                .map_err(|source| RenderError::InvalidGlob {
+                    pattern: pattern.clone(),
+                    source,
+                })?;
+            if trimmed.contains('/') {
+                paths.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+        let build = |builder: GlobSetBuilder| {
+            builder.build().map_err(|source| RenderError::InvalidGlob {
+                pattern: patterns.join(", "),
+                source,
+            })
+        };
+        Ok(GlobLayer {
+            source: source.to_owned(),
+            destination: destination.to_owned(),
+            names: build(names)?,
+            paths: build(paths)?,
+        })
+    }
+
+    fn matches(&self, source: &Utf8Path, destination: &Utf8Path) -> bool {
+        [(source, &self.source), (destination, &self.destination)]
+            .into_iter()
+            .filter_map(|(path, root)| path.strip_prefix(root).ok())
+            .any(|relative| {
+                let relative = relative.as_str().replace('\\', "/");
+                let name = relative.rsplit('/').next().unwrap_or(&relative);
+                self.names.is_match(name) || self.paths.is_match(&relative)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(exclude: &[&str], include: &[&str]) -> EntryFilter {
+        let owned = |globs: &[&str]| globs.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        EntryFilter::new(
+            Utf8Path::new("/arch/content"),
+            Utf8Path::new("/out"),
+            &owned(exclude),
+            &owned(include),
+        )
+        .unwrap()
+    }
+
+    fn skips(filter: &EntryFilter, source: &str, destination: &str) -> bool {
+        filter.skips(Utf8Path::new(source), Utf8Path::new(destination), false)
+    }
+
+    #[test]
+    fn test_name_glob_matches_at_any_depth() {
+        let filter = filter(&[".DS_Store", "*~"], &[]);
+        assert!(skips(&filter, "/arch/content/.DS_Store", "/out/.DS_Store"));
+        assert!(skips(&filter, "/arch/content/a/b/.DS_Store", "/out/a/b/.DS_Store"));
+        assert!(skips(&filter, "/arch/content/src/main.rs~", "/out/src/main.rs~"));
+        assert!(!skips(&filter, "/arch/content/src/main.rs", "/out/src/main.rs"));
+    }
+
+    #[test]
+    fn test_path_glob_is_rooted() {
+        let filter = filter(&["docs/**"], &[]);
+        assert!(skips(&filter, "/arch/content/docs/guide.md", "/out/docs/guide.md"));
+        assert!(!skips(&filter, "/arch/content/src/docs/guide.md", "/out/src/docs/guide.md"));
+    }
+
+    #[test]
+    fn test_glob_matches_rendered_destination() {
+        let filter = filter(&["my-app/README.md"], &[]);
+        assert!(skips(&filter, "/arch/content/{{ name }}/README.md", "/out/my-app/README.md"));
+    }
+
+    #[test]
+    fn test_include_narrows_files_but_not_directories() {
+        let filter = filter(&[], &["*.md"]);
+        assert!(!skips(&filter, "/arch/content/README.md", "/out/README.md"));
+        assert!(skips(&filter, "/arch/content/main.rs", "/out/main.rs"));
+        assert!(!filter.skips(Utf8Path::new("/arch/content/src"), Utf8Path::new("/out/src"), true));
+    }
+
+    #[test]
+    fn test_exclude_wins_over_include() {
+        let filter = filter(&["CHANGELOG.md"], &["*.md"]);
+        assert!(skips(&filter, "/arch/content/CHANGELOG.md", "/out/CHANGELOG.md"));
+    }
+
+    #[test]
+    fn test_invalid_glob_is_an_error() {
+        let result = EntryFilter::new(Utf8Path::new("/a"), Utf8Path::new("/b"), &["[unclosed".to_string()], &[]);
+        assert!(matches!(result, Err(RenderError::InvalidGlob { .. })));
+    }
+}
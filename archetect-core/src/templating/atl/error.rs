@@ -47,6 +47,39 @@ pub enum TemplateCompileError {
     },
     /// `{% raw %}` was opened but no matching `{% endraw %}` was found.
     UnterminatedRaw { line: usize },
+    /// `{% extends "..." %}` was malformed, repeated, or placed inside a block.
+    InvalidExtends { line: usize, detail: String },
+    /// A `{% block %}` or `{% endblock %}` tag was malformed, unmatched, or
+    /// reused a name already taken in the same template.
+    InvalidBlock { line: usize, detail: String },
+    /// `{% block name %}` was opened but never closed.
+    UnterminatedBlock { name: String, line: usize },
+    /// `{% import "..." as name %}` was malformed.
+    InvalidImport { line: usize, detail: String },
+    /// A `{% macro %}` tag was malformed, or an `{% endmacro %}` had no
+    /// macro to close.
+    InvalidMacro { line: usize, detail: String },
+    /// `{% macro name %}` was opened but never closed.
+    UnterminatedMacro { name: String, line: usize },
+    /// An `{% autoescape %}` tag named no known escaper, or an
+    /// `{% endautoescape %}` had no region to close.
+    InvalidAutoescape { line: usize, detail: String },
+    /// `{% autoescape %}` was opened but never closed.
+    UnterminatedAutoescape { line: usize },
+    /// An error that originated inside an imported template. Wraps the
+    /// underlying error with the import path, like `IncludeChain`.
+    ImportChain {
+        import_path: String,
+        source: Box<TemplateCompileError>,
+    },
+    /// An error that originated in a parent template named by
+    /// `{% extends %}`. Wraps the underlying error with the parent's path,
+    /// the way `IncludeChain` does for partials, so the chain reads from the
+    /// child outwards.
+    ExtendsChain {
+        parent_path: String,
+        source: Box<TemplateCompileError>,
+    },
 }
 
 impl fmt::Display for TemplateCompileError {
@@ -112,6 +145,36 @@ impl fmt::Display for TemplateCompileError {
             Self::UnterminatedRaw { line } => {
                 write!(f, "Unterminated '{{% raw %}}' block at line {}", line)
             }
+            Self::InvalidExtends { line, detail } => {
+                write!(f, "Invalid extends at line {}: {}", line, detail)
+            }
+            Self::InvalidBlock { line, detail } => {
+                write!(f, "Invalid block at line {}: {}", line, detail)
+            }
+            Self::UnterminatedBlock { name, line } => {
+                write!(f, "Unterminated '{{% block {} %}}' at line {}", name, line)
+            }
+            Self::InvalidImport { line, detail } => {
+                write!(f, "Invalid import at line {}: {}", line, detail)
+            }
+            Self::InvalidMacro { line, detail } => {
+                write!(f, "Invalid macro at line {}: {}", line, detail)
+            }
+            Self::UnterminatedMacro { name, line } => {
+                write!(f, "Unterminated '{{% macro {} %}}' at line {}", name, line)
+            }
+            Self::InvalidAutoescape { line, detail } => {
+                write!(f, "Invalid autoescape at line {}: {}", line, detail)
+            }
+            Self::UnterminatedAutoescape { line } => {
+                write!(f, "Unterminated '{{% autoescape %}}' at line {}", line)
+            }
+            Self::ImportChain { import_path, source } => {
+                write!(f, "while compiling import `{}`: {}", import_path, source)
+            }
+            Self::ExtendsChain { parent_path, source } => {
+                write!(f, "while compiling parent template `{}`: {}", parent_path, source)
+            }
         }
     }
 }
@@ -119,15 +182,18 @@ impl fmt::Display for TemplateCompileError {
 impl std::error::Error for TemplateCompileError {}
 
 impl TemplateCompileError {
-    /// Walk past any `IncludeChain` or `InTemplate` wrappers to the
-    /// underlying error. Useful for callers (and tests) that want to
-    /// inspect the leaf variant without caring about the wrapping chain.
+    /// Walk past any `IncludeChain`, `ImportChain`, `ExtendsChain`, or
+    /// `InTemplate` wrappers to the underlying error. Useful for callers
+    /// (and tests) that want to inspect the leaf variant without caring
+    /// about the wrapping chain.
     #[allow(dead_code)] // exposed for test introspection and future API consumers
     pub fn root_cause(&self) -> &TemplateCompileError {
         let mut cur = self;
         loop {
             match cur {
                 TemplateCompileError::IncludeChain { source, .. }
+                | TemplateCompileError::ExtendsChain { source, .. }
+                | TemplateCompileError::ImportChain { source, .. }
                 | TemplateCompileError::InTemplate { source, .. } => cur = source,
                 _ => return cur,
             }
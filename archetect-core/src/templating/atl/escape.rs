@@ -0,0 +1,201 @@
+//! Output escaping for the formats archetypes generate.
+//!
+//! An answer with a quote, a backslash, or a `$` in it turns into broken
+//! JSON or a shell script that runs something else once it lands inside a
+//! string of the generated file. Each [`Escaper`] makes a value safe inside
+//! a double-quoted string of its format — for XML and HTML, inside text or
+//! an attribute — and leaves the quotes themselves to the template:
+//!
+//! ```text
+//! {% autoescape "json" %}
+//! { "description": "{{ description }}" }
+//! {% endautoescape %}
+//! ```
+//!
+//! Every escaper is also a filter, `escape_json` through `escape_toml`, for
+//! a single interpolation in a file that isn't escaped as a whole.
+
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+/// An output format interpolated values can be escaped for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Escaper {
+    Json,
+    Yaml,
+    Xml,
+    Shell,
+    Toml,
+}
+
+impl Escaper {
+    pub const ALL: [Escaper; 5] = [
+        Escaper::Json,
+        Escaper::Yaml,
+        Escaper::Xml,
+        Escaper::Shell,
+        Escaper::Toml,
+    ];
+
+    /// The escaper called `name`, accepting the usual aliases (`yml`,
+    /// `html`, `sh`, `bash`).
+    pub fn from_name(name: &str) -> Option<Escaper> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Escaper::Json),
+            "yaml" | "yml" => Some(Escaper::Yaml),
+            "xml" | "html" => Some(Escaper::Xml),
+            "shell" | "sh" | "bash" => Some(Escaper::Shell),
+            "toml" => Some(Escaper::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Escaper::Json => "json",
+            Escaper::Yaml => "yaml",
+            Escaper::Xml => "xml",
+            Escaper::Shell => "shell",
+            Escaper::Toml => "toml",
+        }
+    }
+
+    /// The filter that applies this escaper, e.g. `escape_json`.
+    pub fn filter_name(&self) -> String {
+        format!("escape_{}", self.name())
+    }
+
+    pub fn escape(&self, value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len() + 8);
+        for c in value.chars() {
+            match self {
+                Escaper::Json => match c {
+                    '"' => escaped.push_str("\\\""),
+                    '\\' => escaped.push_str("\\\\"),
+                    '\n' => escaped.push_str("\\n"),
+                    '\r' => escaped.push_str("\\r"),
+                    '\t' => escaped.push_str("\\t"),
+                    '\u{8}' => escaped.push_str("\\b"),
+                    '\u{c}' => escaped.push_str("\\f"),
+                    c if c < ' ' => write_unicode_escape(&mut escaped, c),
+                    c => escaped.push(c),
+                },
+                Escaper::Yaml => match c {
+                    '"' => escaped.push_str("\\\""),
+                    '\\' => escaped.push_str("\\\\"),
+                    '\n' => escaped.push_str("\\n"),
+                    '\r' => escaped.push_str("\\r"),
+                    '\t' => escaped.push_str("\\t"),
+                    '\0' => escaped.push_str("\\0"),
+                    c if c < ' ' || c == '\u{7f}' => {
+                        let _ = write!(escaped, "\\x{:02X}", c as u32);
+                    }
+                    c => escaped.push(c),
+                },
+                Escaper::Xml => match c {
+                    '&' => escaped.push_str("&amp;"),
+                    '<' => escaped.push_str("&lt;"),
+                    '>' => escaped.push_str("&gt;"),
+                    '"' => escaped.push_str("&quot;"),
+                    '\'' => escaped.push_str("&#39;"),
+                    c => escaped.push(c),
+                },
+                Escaper::Shell => {
+                    if matches!(c, '\\' | '"' | '$' | '`') {
+                        escaped.push('\\');
+                    }
+                    escaped.push(c);
+                }
+                Escaper::Toml => match c {
+                    '"' => escaped.push_str("\\\""),
+                    '\\' => escaped.push_str("\\\\"),
+                    '\n' => escaped.push_str("\\n"),
+                    '\r' => escaped.push_str("\\r"),
+                    '\t' => escaped.push_str("\\t"),
+                    '\u{8}' => escaped.push_str("\\b"),
+                    '\u{c}' => escaped.push_str("\\f"),
+                    c if c < ' ' || c == '\u{7f}' => write_unicode_escape(&mut escaped, c),
+                    c => escaped.push(c),
+                },
+            }
+        }
+        escaped
+    }
+}
+
+fn write_unicode_escape(out: &mut String, c: char) {
+    let _ = write!(out, "\\u{:04x}", c as u32);
+}
+
+impl TryFrom<String> for Escaper {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Escaper, String> {
+        Escaper::from_name(&name).ok_or_else(|| {
+            format!(
+                "unknown escaper `{}`; expected one of json, yaml, xml, html, shell, toml",
+                name
+            )
+        })
+    }
+}
+
+impl From<Escaper> for String {
+    fn from(escaper: Escaper) -> String {
+        escaper.name().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_escaper_neutralizes_its_format() {
+        let value = "say \"hi\" \\ $HOME `id` <b> & 'x'\n\tend\u{1}";
+        assert_eq!(
+            Escaper::Json.escape(value),
+            "say \\\"hi\\\" \\\\ $HOME `id` <b> & 'x'\\n\\tend\\u0001"
+        );
+        assert_eq!(
+            Escaper::Yaml.escape(value),
+            "say \\\"hi\\\" \\\\ $HOME `id` <b> & 'x'\\n\\tend\\x01"
+        );
+        assert_eq!(
+            Escaper::Xml.escape(value),
+            "say &quot;hi&quot; \\ $HOME `id` &lt;b&gt; &amp; &#39;x&#39;\n\tend\u{1}"
+        );
+        assert_eq!(
+            Escaper::Shell.escape(value),
+            "say \\\"hi\\\" \\\\ \\$HOME \\`id\\` <b> & 'x'\n\tend\u{1}"
+        );
+        assert_eq!(
+            Escaper::Toml.escape(value),
+            "say \\\"hi\\\" \\\\ $HOME `id` <b> & 'x'\\n\\tend\\u0001"
+        );
+    }
+
+    #[test]
+    fn test_escaped_strings_parse_back_to_the_value() {
+        let value = "quote \" slash \\ line\nbell\u{7}";
+        let document = format!("\"{}\"", Escaper::Json.escape(value));
+        assert_eq!(serde_json::from_str::<String>(&document).unwrap(), value);
+        let document = format!("\"{}\"", Escaper::Yaml.escape(value));
+        assert_eq!(serde_yaml::from_str::<String>(&document).unwrap(), value);
+        let document = format!("v = \"{}\"", Escaper::Toml.escape(value));
+        let table = toml::from_str::<toml::Table>(&document).unwrap();
+        assert_eq!(table["v"].as_str(), Some(value));
+    }
+
+    #[test]
+    fn test_names_and_aliases() {
+        assert_eq!(Escaper::from_name("HTML"), Some(Escaper::Xml));
+        assert_eq!(Escaper::from_name("yml"), Some(Escaper::Yaml));
+        assert_eq!(Escaper::from_name("bash"), Some(Escaper::Shell));
+        assert_eq!(Escaper::from_name("csv"), None);
+        assert!(serde_yaml::from_str::<Escaper>("nope").is_err());
+        assert_eq!(serde_yaml::from_str::<Escaper>("sh").unwrap(), Escaper::Shell);
+    }
+}
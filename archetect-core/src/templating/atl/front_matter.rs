@@ -0,0 +1,171 @@
+//! Per-file rendering directives carried at the top of a template.
+//!
+//! A template may open with a YAML block between two `---` lines:
+//!
+//! ```text
+//! ---
+//! when: use_docker
+//! if_exists: overwrite
+//! destination: "{{ service_name }}.Dockerfile"
+//! verbatim: false
+//! ---
+//! FROM rust:1.80
+//! ```
+//!
+//! The block is stripped before the template is compiled, so it never reaches
+//! the rendered file. Plenty of YAML files open with `---` on their own, so a
+//! block only counts as front matter when it is a mapping that names at least
+//! one directive; anything else is left as template text. Once a block does
+//! count, an unknown key is an error rather than a silently rendered typo.
+
+use serde_yaml::Value;
+
+use crate::archetype::archetype::OverwritePolicy;
+
+const DELIMITER: &str = "---";
+const KEYS: [&str; 4] = ["when", "if_exists", "destination", "verbatim"];
+
+/// The directives a template's front matter sets. Each is optional.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrontMatter {
+    /// A template expression, evaluated against the context; the file is
+    /// skipped unless it is truthy.
+    pub when: Option<String>,
+    /// Overrides the policy the render was called with, for this file only.
+    pub if_exists: Option<OverwritePolicy>,
+    /// Replaces the file's rendered name. Rendered like a name, and relative
+    /// to the directory the file would otherwise have landed in.
+    pub destination: Option<String>,
+    /// Write the body as-is rather than rendering it as a template.
+    pub verbatim: bool,
+}
+
+/// Split `text` into its front matter and the body after it, or `None` when
+/// it doesn't open with a directive block.
+pub fn split(text: &str) -> Result<Option<(FrontMatter, &str)>, String> {
+    let Some((block, body)) = delimited(text) else {
+        return Ok(None);
+    };
+    let Ok(Value::Mapping(mapping)) = serde_yaml::from_str::<Value>(block) else {
+        return Ok(None);
+    };
+    if !mapping
+        .keys()
+        .any(|key| key.as_str().is_some_and(|key| KEYS.contains(&key)))
+    {
+        return Ok(None);
+    }
+
+    let mut front_matter = FrontMatter::default();
+    for (key, value) in mapping {
+        let key = key.as_str().map(str::to_owned).unwrap_or_else(|| format!("{:?}", key));
+        match key.as_str() {
+            "when" => {
+                front_matter.when = Some(match value {
+                    Value::String(expression) => expression,
+                    Value::Bool(flag) => flag.to_string(),
+                    _ => return Err("'when' must be an expression such as `use_docker`".to_owned()),
+                })
+            }
+            "if_exists" => {
+                let policy = value.as_str().and_then(policy_from_name).ok_or_else(|| {
+                    "'if_exists' must be one of overwrite, preserve, prompt, error, or merge".to_owned()
+                })?;
+                front_matter.if_exists = Some(policy);
+            }
+            "destination" => match value {
+                Value::String(destination) if !destination.trim().is_empty() => {
+                    front_matter.destination = Some(destination)
+                }
+                _ => return Err("'destination' must be a non-empty path".to_owned()),
+            },
+            "verbatim" => {
+                front_matter.verbatim = value
+                    .as_bool()
+                    .ok_or_else(|| "'verbatim' must be true or false".to_owned())?
+            }
+            other => return Err(format!("unknown key '{}'; expected one of {}", other, KEYS.join(", "))),
+        }
+    }
+    Ok(Some((front_matter, body)))
+}
+
+/// The template text with any front matter removed.
+pub fn strip(text: &str) -> Result<&str, String> {
+    Ok(split(text)?.map_or(text, |(_, body)| body))
+}
+
+/// The text between an opening `---` line and the next `---` line, and
+/// everything after the closing one.
+fn delimited(text: &str) -> Option<(&str, &str)> {
+    let rest = text
+        .strip_prefix(DELIMITER)
+        .and_then(|rest| rest.strip_prefix('\n').or_else(|| rest.strip_prefix("\r\n")))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == DELIMITER {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn policy_from_name(name: &str) -> Option<OverwritePolicy> {
+    match name.to_ascii_lowercase().as_str() {
+        "overwrite" => Some(OverwritePolicy::Overwrite),
+        "preserve" => Some(OverwritePolicy::Preserve),
+        "prompt" => Some(OverwritePolicy::Prompt),
+        "error" => Some(OverwritePolicy::Error),
+        "merge" => Some(OverwritePolicy::Merge),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directives_are_parsed_and_stripped() {
+        let text = "---\nwhen: use_docker and not minimal\nif_exists: Overwrite\ndestination: \"{{ name }}.txt\"\nverbatim: true\n---\nbody {{ x }}\n";
+        let (front_matter, body) = split(text).unwrap().unwrap();
+        assert_eq!(
+            front_matter,
+            FrontMatter {
+                when: Some("use_docker and not minimal".to_owned()),
+                if_exists: Some(OverwritePolicy::Overwrite),
+                destination: Some("{{ name }}.txt".to_owned()),
+                verbatim: true,
+            }
+        );
+        assert_eq!(body, "body {{ x }}\n");
+    }
+
+    #[test]
+    fn test_yaml_documents_are_not_front_matter() {
+        // A multi-document YAML file opens with `---` but names no directive.
+        let text = "---\napiVersion: v1\nkind: Service\n---\napiVersion: v1\n";
+        assert_eq!(split(text).unwrap(), None);
+        assert_eq!(strip(text).unwrap(), text);
+        assert_eq!(split("---\n- a\n- b\n---\n").unwrap(), None);
+        assert_eq!(split("---\nwhen: x\n").unwrap(), None);
+        assert_eq!(split("text\n---\nwhen: x\n---\n").unwrap(), None);
+    }
+
+    #[test]
+    fn test_unknown_keys_and_bad_values_are_errors() {
+        assert!(split("---\nwhen: x\nif_exist: overwrite\n---\n")
+            .unwrap_err()
+            .contains("if_exist"));
+        assert!(split("---\nif_exists: clobber\n---\n").is_err());
+        assert!(split("---\nverbatim: yes please\n---\n").is_err());
+    }
+
+    #[test]
+    fn test_crlf_delimiters() {
+        let (front_matter, body) = split("---\r\nwhen: false\r\n---\r\nbody\r\n").unwrap().unwrap();
+        assert_eq!(front_matter.when.as_deref(), Some("false"));
+        assert_eq!(body, "body\r\n");
+    }
+}
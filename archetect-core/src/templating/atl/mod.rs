@@ -1,13 +1,19 @@
 pub mod builtins;
 mod compiler;
+pub mod entry_filter;
 mod error;
+mod escape;
+pub mod front_matter;
 pub mod include_resolver;
 pub mod render;
+mod source_map;
 mod tokenizer;
 
 pub use compiler::{CompileOptions, Compiler};
 pub use error::TemplateCompileError;
+pub use escape::Escaper;
 pub use include_resolver::IncludeResolver;
+pub use source_map::{Location, SourceMap, CHUNK_NAME};
 use tokenizer::Tokenizer;
 
 /// A compiled template: Lua source code ready to be loaded into an mlua VM.
@@ -15,6 +21,9 @@ use tokenizer::Tokenizer;
 pub struct CompiledTemplate {
     /// The Lua source code (a function definition).
     pub source: String,
+    /// Where each line of `source` came from in the template. Load
+    /// `source` under [`CHUNK_NAME`] for it to apply to the errors it raises.
+    pub source_map: SourceMap,
 }
 
 /// Compiles Archetect Template Language (ATL) templates into Lua functions.
@@ -69,9 +78,10 @@ impl TemplateCompiler {
         opts: CompileOptions,
     ) -> Result<CompiledTemplate, TemplateCompileError> {
         let tokens = Tokenizer::tokenize(template)?;
-        let source = Compiler::compile(&tokens, resolver, opts)?;
+        let (source, mut source_map) = Compiler::compile(&tokens, resolver, opts)?;
         validate_lua_syntax(&source, name)?;
-        Ok(CompiledTemplate { source })
+        source_map.place_root(name, template, 0);
+        Ok(CompiledTemplate { source, source_map })
     }
 }
 
@@ -861,6 +871,46 @@ service {{ entity.name.pascal }}Service {
         );
     }
 
+    #[test]
+    fn test_include_with_args_sees_only_its_arguments() {
+        // The partial renders once per field with that field's values, and
+        // can't see the context or the includer's names — not even one it
+        // shares a name with.
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "field.atl", "[{{ name }}:{{ ty }}{{ secret }}{{ label }}]");
+        let template = r#"{% label = "L" %}{% for _, f in ipairs(fields) do %}{% include "field.atl" with { name = f.name, ty = f.ty } %}{% end %}{{ label }}{{ name }}"#;
+        let result = render_with_includes(template, dir, |lua, ctx| {
+            let fields = lua.create_table().unwrap();
+            for (i, (name, ty)) in [("id", "Uuid"), ("note", "String")].into_iter().enumerate() {
+                let field = lua.create_table().unwrap();
+                field.set("name", name).unwrap();
+                field.set("ty", ty).unwrap();
+                fields.set(i + 1, field).unwrap();
+            }
+            ctx.set("fields", fields).unwrap();
+            ctx.set("secret", "!").unwrap();
+            ctx.set("name", "outer").unwrap();
+        })
+        .unwrap();
+        assert_eq!(result, "[id:Uuid][note:String]Louter");
+    }
+
+    #[test]
+    fn test_dynamic_include_needs_a_template_file() {
+        // Compiling a string leaves no renderer to find the partial with.
+        let compiled = TemplateCompiler::compile(r#"{% include "ci/" .. "github.atl" %}"#, "test").unwrap();
+        let lua = mlua::Lua::new();
+        let func: mlua::Function = lua.load(&compiled.source).eval().unwrap();
+        let err = func
+            .call::<String>((lua.create_table().unwrap(), lua.create_table().unwrap()))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("dynamic includes are only available to template files"),
+            "got {}",
+            err
+        );
+    }
+
     // ---------- Phase 7: sugar — end-to-end render ----------
 
     fn render_simple(template: &str, ctx_setup: impl FnOnce(&mlua::Lua, &mlua::Table)) -> String {
@@ -1390,4 +1440,391 @@ service {{ entity.name.pascal }}Service {
         );
         assert_eq!(result, "{{ project }}");
     }
+
+    // ---------- Template inheritance ----------
+
+    fn render_extending(
+        template: &str,
+        dir: camino::Utf8PathBuf,
+        opts: CompileOptions,
+    ) -> Result<String, TemplateCompileError> {
+        let mut resolver = IncludeResolver::single(dir);
+        let compiled = TemplateCompiler::compile_with(template, "child.atl", &mut resolver, opts)?;
+        let lua = mlua::Lua::new();
+        let func: mlua::Function = lua.load(&compiled.source).eval().unwrap();
+        let ctx = lua.create_table().unwrap();
+        ctx.set("name", "demo").unwrap();
+        let filters = lua.create_table().unwrap();
+        Ok(func.call::<String>((ctx, filters)).unwrap())
+    }
+
+    #[test]
+    fn test_runtime_errors_map_to_the_template_they_came_from() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "base.atl", "# {{ name }}\n{% block body %}\n{{ owner.email }}\n{% endblock %}");
+        let render = |template: &str| {
+            let mut resolver = IncludeResolver::single(dir.clone());
+            let compiled =
+                TemplateCompiler::compile_with(template, "child.atl", &mut resolver, CompileOptions::default())
+                    .unwrap();
+            let lua = mlua::Lua::new();
+            let func: mlua::Function = lua.load(&compiled.source).set_name(CHUNK_NAME).eval().unwrap();
+            let ctx = lua.create_table().unwrap();
+            let filters = lua.create_table().unwrap();
+            let err = func.call::<String>((ctx, filters)).unwrap_err();
+            compiled.source_map.annotate(&err.to_string()).unwrap()
+        };
+
+        // The parent's default block fails in the parent.
+        let annotated = render(r#"{% extends "base.atl" %}"#);
+        assert!(annotated.contains("--> base.atl:3:4"), "{}", annotated);
+        assert!(annotated.contains("3 | {{ owner.email }}\n  |    ^"), "{}", annotated);
+
+        // An override fails in the child, even on a later line of a tag.
+        let annotated = render("{% extends \"base.atl\" %}\n{% block body %}{%\n  local n = #items\n%}{% endblock %}");
+        assert!(annotated.contains("attempt to get length of a nil value (global 'items')"), "{}", annotated);
+        assert!(annotated.contains("--> child.atl:3:3"), "{}", annotated);
+    }
+
+    #[test]
+    fn test_extends_overrides_blocks_and_keeps_the_rest() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(
+            &dir,
+            "base.atl",
+            "<{% block title %}Base{% endblock %}|{% block body %}default{% endblock %}>",
+        );
+
+        let result = render_extending(
+            r#"{% extends "base.atl" %}ignored{% block body %}{{ name }}{% endblock %}"#,
+            dir,
+            CompileOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "<Base|demo>");
+    }
+
+    #[test]
+    fn test_super_renders_each_replaced_definition() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "base.atl", "[{% block steps %}build{% endblock %}]");
+        write_include(
+            &dir,
+            "middle.atl",
+            r#"{% extends "base.atl" %}{% block steps %}{{ super() }},test{% endblock %}"#,
+        );
+
+        let result = render_extending(
+            r#"{% extends "middle.atl" %}{% block steps %}{{ super() }},deploy {{ name }}{% endblock %}"#,
+            dir,
+            CompileOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "[build,test,deploy demo]");
+    }
+
+    #[test]
+    fn test_nested_blocks_override_independently() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(
+            &dir,
+            "base.atl",
+            "{% block outer %}({% block inner %}a{% endblock %}){% endblock %}",
+        );
+
+        let result = render_extending(
+            r#"{% extends "base.atl" %}{% block inner %}b{% endblock %}"#,
+            dir,
+            CompileOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "(b)");
+    }
+
+    #[test]
+    fn test_blocks_without_extends_render_in_place() {
+        let result = render_simple("a{% block b %}{{ x }}{% endblock %}c", |_, ctx| {
+            ctx.set("x", "B").unwrap();
+        });
+        assert_eq!(result, "aBc");
+    }
+
+    #[test]
+    fn test_trim_blocks_applies_to_block_tags() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "base.atl", "FROM rust\n{% block run %}\nRUN build\n{% endblock %}\nEND\n");
+        let opts = CompileOptions {
+            trim_blocks: true,
+            ..CompileOptions::default()
+        };
+
+        let result = render_extending(
+            "{% extends \"base.atl\" %}\n{% block run %}\nRUN {{ name }}\n{% endblock %}\n",
+            dir,
+            opts,
+        )
+        .unwrap();
+        assert_eq!(result, "FROM rust\nRUN demo\nEND\n");
+    }
+
+    #[test]
+    fn test_extends_error_names_the_parent() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "base.atl", "{% block body %}{% if then %}{% end %}{% endblock %}");
+
+        let err = render_extending(
+            r#"{% extends "base.atl" %}{% block body %}x{% endblock %}"#,
+            dir.clone(),
+            CompileOptions::default(),
+        )
+        .unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("child.atl") && msg.contains("parent template `base.atl`"), "{}", msg);
+        assert!(matches!(err.root_cause(), TemplateCompileError::InvalidLuaSyntax { .. }));
+
+        write_include(&dir, "unclosed.atl", "\n{% block body %}");
+        let err = render_extending(r#"{% extends "unclosed.atl" %}"#, dir, CompileOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("unclosed.atl"), "{}", err);
+        assert!(matches!(
+            err.root_cause(),
+            TemplateCompileError::UnterminatedBlock { line: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn test_extends_cycle_detected() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "a.atl", r#"{% extends "b.atl" %}"#);
+        write_include(&dir, "b.atl", r#"{% extends "a.atl" %}"#);
+
+        let err = render_extending(r#"{% extends "a.atl" %}"#, dir, CompileOptions::default()).unwrap_err();
+        assert!(matches!(err.root_cause(), TemplateCompileError::IncludeCycle { .. }));
+    }
+
+    #[test]
+    fn test_malformed_block_structure_rejected() {
+        for template in [
+            "{% block a %}{% block a %}{% endblock %}{% endblock %}",
+            "{% block a %}{% endblock b %}",
+            "{% endblock %}",
+            r#"{% block a %}{% extends "base.atl" %}{% endblock %}"#,
+        ] {
+            let err = TemplateCompiler::compile(template, "test").unwrap_err();
+            assert!(
+                matches!(
+                    err.root_cause(),
+                    TemplateCompileError::InvalidBlock { .. } | TemplateCompileError::InvalidExtends { .. }
+                ),
+                "{}: {:?}",
+                template,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_included_partial_cannot_define_blocks() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "partial.atl", "{% block a %}{% endblock %}");
+
+        let err = render_with_includes(r#"{% include "partial.atl" %}"#, dir, |_, _| {}).unwrap_err();
+        assert!(matches!(err.root_cause(), TemplateCompileError::InvalidBlock { .. }));
+    }
+
+    // ---------- Macros and imports ----------
+
+    #[test]
+    fn test_macro_renders_with_arguments_and_defaults() {
+        let result = render_simple(
+            r#"{% macro field(name, ty, optional=false) %}{{ name }}: {% if optional %}Option<{{ ty }}>{% else %}{{ ty }}{% end %}{% endmacro %}{{ field("id", "Uuid") }}; {{ field("note", "String", true) }}"#,
+            |_, _| {},
+        );
+        assert_eq!(result, "id: Uuid; note: Option<String>");
+    }
+
+    #[test]
+    fn test_macro_output_stays_out_of_the_page_until_called() {
+        let opts = CompileOptions {
+            trim_blocks: true,
+            ..CompileOptions::default()
+        };
+        let mut resolver = IncludeResolver::disabled();
+        let compiled = TemplateCompiler::compile_with(
+            "{% macro env(key, value) %}\n- name: {{ key }}\n  value: {{ value }}\n{% endmacro %}\nenv:\n{{ env(\"A\", name) }}",
+            "test",
+            &mut resolver,
+            opts,
+        )
+        .unwrap();
+        let lua = mlua::Lua::new();
+        let func: mlua::Function = lua.load(&compiled.source).eval().unwrap();
+        let ctx = lua.create_table().unwrap();
+        ctx.set("name", "demo").unwrap();
+        let result = func.call::<String>((ctx, lua.create_table().unwrap())).unwrap();
+        assert_eq!(result, "env:\n- name: A\n  value: demo\n");
+    }
+
+    #[test]
+    fn test_import_binds_the_templates_macros() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(
+            &dir,
+            "macros.atl",
+            "ignored output\n{% macro ty(t) %}<{{ t }}>{% endmacro %}{% macro field(n, t) %}{{ n }}: {{ ty(t) }}{% endmacro %}",
+        );
+
+        let result = render_with_includes(
+            r#"{% import "macros.atl" as m %}{{ m.field("id", "Uuid") }} {{ m.field(name, "String") }}"#,
+            dir,
+            |_, ctx| {
+                ctx.set("name", "title").unwrap();
+            },
+        )
+        .unwrap();
+        assert_eq!(result, "id: <Uuid> title: <String>");
+    }
+
+    #[test]
+    fn test_extending_template_keeps_its_imports_and_macros() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "macros.atl", "{% macro shout(s) %}{{ s }}!{% endmacro %}");
+        write_include(&dir, "base.atl", "[{% block body %}{% endblock %}]");
+
+        let result = render_extending(
+            r#"{% extends "base.atl" %}{% import "macros.atl" as m %}{% macro twice(s) %}{{ s }}{{ s }}{% endmacro %}{% block body %}{{ m.shout(twice(name)) }}{% endblock %}"#,
+            dir,
+            CompileOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "[demodemo!]");
+    }
+
+    #[test]
+    fn test_import_errors_name_the_imported_template() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "broken.atl", "{% macro f() %}{{ oops");
+
+        let err = render_with_includes(r#"{% import "broken.atl" as m %}"#, dir.clone(), |_, _| {}).unwrap_err();
+        assert!(err.to_string().contains("import `broken.atl`"), "{}", err);
+
+        let err = render_with_includes(r#"{% import "missing.atl" as m %}"#, dir, |_, _| {}).unwrap_err();
+        assert!(matches!(err.root_cause(), TemplateCompileError::IncludeNotFound { .. }));
+    }
+
+    #[test]
+    fn test_unbalanced_macros_rejected() {
+        let err = TemplateCompiler::compile("{% if x %}{% endmacro %}", "test").unwrap_err();
+        assert!(matches!(err.root_cause(), TemplateCompileError::InvalidMacro { .. }));
+
+        let err = TemplateCompiler::compile("\n{% macro f() %}body", "test").unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            TemplateCompileError::UnterminatedMacro { line: 2, .. }
+        ));
+
+        let err = TemplateCompiler::compile("{% macro f() %}{% block b %}{% endblock %}{% endmacro %}", "test")
+            .unwrap_err();
+        assert!(matches!(err.root_cause(), TemplateCompileError::InvalidBlock { .. }));
+    }
+
+    // ---------- Autoescaping ----------
+
+    fn render_escaped(template: &str, dir: camino::Utf8PathBuf, autoescape: Option<Escaper>) -> String {
+        let mut resolver = IncludeResolver::single(dir);
+        let opts = CompileOptions {
+            autoescape,
+            ..CompileOptions::default()
+        };
+        let compiled = TemplateCompiler::compile_with(template, "test", &mut resolver, opts).unwrap();
+        let lua = mlua::Lua::new();
+        let func: mlua::Function = lua.load(&compiled.source).eval().unwrap();
+        let ctx = lua.create_table().unwrap();
+        ctx.set("name", "a \"b\" $c").unwrap();
+        let filters = lua.create_table().unwrap();
+        builtins::register_all(&lua, &filters).unwrap();
+        func.call::<String>((ctx, filters)).unwrap()
+    }
+
+    #[test]
+    fn test_autoescape_applies_to_interpolations_only() {
+        let (_tmp, dir) = temp_includes_dir();
+        let result = render_escaped(
+            r#""{{ name }}" "{{ name | replace("$", "%") }}" "{{ missing }}" {{ 1 + 6 }} {{ name | safe }} {{ safe(name) }}"#,
+            dir.clone(),
+            Some(Escaper::Json),
+        );
+        assert_eq!(result, r#""a \"b\" $c" "a \"b\" %c" "" 7 a "b" $c a "b" $c"#);
+
+        let result = render_escaped(r#""{{ name }}""#, dir, None);
+        assert_eq!(result, r#""a "b" $c""#);
+    }
+
+    #[test]
+    fn test_autoescape_marks_the_value_not_its_text() {
+        let (_tmp, dir) = temp_includes_dir();
+        let result = render_escaped(
+            r#"{{ name | safe }} {{ name }} {{ safe(name) }} {{ name }}"#,
+            dir.clone(),
+            Some(Escaper::Xml),
+        );
+        assert_eq!(result, r#"a "b" $c a &quot;b&quot; $c a "b" $c a &quot;b&quot; $c"#);
+
+        // A safe value still reads as a string; what a filter makes of it is
+        // escaped afresh.
+        let result = render_escaped(
+            r#"{% local s = safe(name) %}{{ s }} {{ s .. "!" }} {{ s:upper() }} {{ s | replace("a", "@") }} {{ #s }}"#,
+            dir,
+            Some(Escaper::Xml),
+        );
+        assert_eq!(result, r#"a "b" $c a &quot;b&quot; $c! A &quot;B&quot; $C @ &quot;b&quot; $c 8"#);
+    }
+
+    #[test]
+    fn test_autoescape_regions_nest_and_reach_includes() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "arg.atl", r#"--name "{{ name }}""#);
+
+        let result = render_escaped(
+            r#"{{ name }}|{% autoescape "shell" %}{% include "arg.atl" %}|{% autoescape false %}{{ name }}{% endautoescape %}|{{ name }}{% endautoescape %}|{{ name }}"#,
+            dir,
+            Some(Escaper::Xml),
+        );
+        assert_eq!(
+            result,
+            r#"a &quot;b&quot; $c|--name "a \"b\" \$c"|a "b" $c|a \"b\" \$c|a &quot;b&quot; $c"#
+        );
+    }
+
+    #[test]
+    fn test_autoescape_does_not_escape_macro_output_twice() {
+        let (_tmp, dir) = temp_includes_dir();
+        write_include(&dir, "base.atl", "<{% block body %}{% endblock %}>");
+
+        let result = render_escaped(
+            r#"{% macro quoted(s) %}"{{ s }}"{% endmacro %}{{ quoted(name) }}"#,
+            dir.clone(),
+            Some(Escaper::Xml),
+        );
+        assert_eq!(result, "\"a &quot;b&quot; $c\"");
+
+        // A block lifted out of a region stays escaped.
+        let result = render_escaped(
+            r#"{% extends "base.atl" %}{% autoescape "xml" %}{% block body %}{{ name }}{% endblock %}{% endautoescape %}"#,
+            dir,
+            None,
+        );
+        assert_eq!(result, "<a &quot;b&quot; $c>");
+    }
+
+    #[test]
+    fn test_unbalanced_autoescape_rejected() {
+        let err = TemplateCompiler::compile("{% endautoescape %}", "test").unwrap_err();
+        assert!(matches!(err.root_cause(), TemplateCompileError::InvalidAutoescape { .. }));
+
+        let err = TemplateCompiler::compile("\n{% autoescape \"json\" %}{{ x }}", "test").unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            TemplateCompileError::UnterminatedAutoescape { line: 2 }
+        ));
+    }
 }
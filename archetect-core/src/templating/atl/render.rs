@@ -1,19 +1,26 @@
-use std::collections::HashMap;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::rc::Rc;
 
-use camino::{Utf8Path, Utf8PathBuf};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use content_inspector::ContentType;
-use mlua::{Function, Lua, Table};
+use mlua::{Function, Lua, Table, Value};
 
-use archetect_api::{ScriptMessage, WriteDirectoryInfo, WriteFileInfo};
+use archetect_api::{
+    Artifact, ExistingFilePolicy, FileOperation, ScriptMessage, WriteDirectoryInfo, WriteFileInfo, WriteSymlinkInfo,
+};
 
 use crate::archetype::archetype::OverwritePolicy;
+use crate::debugger;
 use crate::errors::RenderError;
 use crate::Archetect;
 
 pub use super::include_resolver::IncludeTrust;
+use super::entry_filter::{EntryFilter, IGNORE_FILE};
 use super::error::TemplateCompileError;
-use super::{CompileOptions, IncludeResolver, TemplateCompiler};
+use super::front_matter::{self, FrontMatter};
+use super::{CompileOptions, CompiledTemplate, Escaper, IncludeResolver, TemplateCompiler, CHUNK_NAME};
 
 /// Unwrap an `InTemplate` wrapper if present. Used at the render layer
 /// where the template path is already reported separately, so the wrapper
@@ -35,8 +42,11 @@ fn strip_in_template(err: TemplateCompileError) -> TemplateCompileError {
 /// Archetypes are finite trees of files on disk, so an unbounded `HashMap`
 /// is appropriate here — adding eviction would only add complexity for a
 /// scenario that cannot occur in practice.
+///
+/// Entries are shared, so a render takes a pointer to its template rather
+/// than a copy of the compiled source.
 pub struct TemplateCache {
-    cache: HashMap<String, String>,
+    cache: HashMap<String, Rc<CompiledTemplate>>,
     /// Ordered list of include search directories with their trust level.
     /// The consumer's own `<root>/includes` is conventionally first
     /// (trust: User), followed by any library staging dirs (trust:
@@ -46,6 +56,9 @@ pub struct TemplateCache {
     /// (strict, trim_blocks, lstrip_blocks). Applied to every template
     /// compiled through this cache.
     options: CompileOptions,
+    /// The escaper for each file extension, from `templating.autoescape`,
+    /// keyed without the leading dot.
+    autoescape: BTreeMap<String, Escaper>,
 }
 
 impl TemplateCache {
@@ -54,6 +67,7 @@ impl TemplateCache {
             cache: HashMap::new(),
             includes_dirs: Vec::new(),
             options: CompileOptions::default(),
+            autoescape: BTreeMap::new(),
         }
     }
 
@@ -77,11 +91,39 @@ impl TemplateCache {
         self
     }
 
+    /// Escape the interpolations of templates whose file extension is a key
+    /// of `autoescape` (with or without its dot) with that escaper.
+    pub fn with_autoescape(mut self, autoescape: BTreeMap<String, Escaper>) -> Self {
+        self.autoescape = autoescape
+            .into_iter()
+            .map(|(extension, escaper)| (extension.trim_start_matches('.').to_ascii_lowercase(), escaper))
+            .collect();
+        self
+    }
+
+    /// The options to compile the template at `path` with.
+    fn options_for(&self, path: &Utf8Path) -> CompileOptions {
+        let extension = path.extension().map(str::to_ascii_lowercase);
+        let escaper = extension.and_then(|extension| self.autoescape.get(&extension).copied());
+        CompileOptions {
+            autoescape: escaper.or(self.options.autoescape),
+            ..self.options
+        }
+    }
+
     /// Build a fresh include resolver for a single compile. The resolver's
     /// active stack starts empty for each top-level template, since cycles
-    /// are only meaningful within one compile chain.
-    fn make_resolver(&self) -> IncludeResolver {
-        IncludeResolver::new(self.includes_dirs.clone())
+    /// are only meaningful within one compile chain. `extra_dir`, if any,
+    /// is searched first.
+    fn make_resolver(&self, extra_dir: Option<&Utf8Path>) -> IncludeResolver {
+        match extra_dir {
+            Some(dir) => {
+                let mut dirs = vec![(dir.to_owned(), IncludeTrust::System)];
+                dirs.extend_from_slice(&self.includes_dirs);
+                IncludeResolver::new(dirs)
+            }
+            None => IncludeResolver::new(self.includes_dirs.clone()),
+        }
     }
 
     /// Locate `relative` in the configured include search directories and
@@ -89,11 +131,20 @@ impl TemplateCache {
     /// (e.g. `file.render`) that need a real path before they can open a file
     /// but want the same search semantics as `{% include %}`.
     pub fn find_include(&self, relative: &str) -> Option<Utf8PathBuf> {
-        self.make_resolver().find(relative)
+        self.make_resolver(None).find(relative)
     }
 
-    /// Get or compile a template, returning the Lua source code.
-    pub fn get_or_compile(&mut self, path: &Utf8Path) -> Result<&str, RenderError> {
+    /// Like `find_include`, searching `extra_dir` first — the directory a
+    /// template that was itself found on the include path came from.
+    fn find_include_from(&self, relative: &str, extra_dir: Option<&Utf8Path>) -> Option<Utf8PathBuf> {
+        match extra_dir {
+            Some(_) => self.make_resolver(extra_dir).find(relative),
+            None => self.find_include(relative),
+        }
+    }
+
+    /// Get or compile a template, returning its Lua source and source map.
+    pub fn get_or_compile(&mut self, path: &Utf8Path) -> Result<Rc<CompiledTemplate>, RenderError> {
         self.get_or_compile_with_extra_dir(path, None)
     }
 
@@ -109,11 +160,47 @@ impl TemplateCache {
         &mut self,
         path: &Utf8Path,
         extra_dir: Option<&Utf8Path>,
-    ) -> Result<&str, RenderError> {
+    ) -> Result<Rc<CompiledTemplate>, RenderError> {
         let key = match extra_dir {
             Some(dir) => format!("{}|extra:{}", path, dir),
             None => path.to_string(),
         };
+        let options = self.options_for(path);
+        self.compile_cached(key, path, path.as_str(), extra_dir, options)
+    }
+
+    /// Get or compile the partial at `path`, included as `name`, for a
+    /// dynamic `{% include %}`. Its interpolations are escaped with
+    /// `escaper`, the escaper of the region the include sits in, rather
+    /// than one picked by extension.
+    pub fn get_or_compile_include(
+        &mut self,
+        path: &Utf8Path,
+        name: &str,
+        extra_dir: Option<&Utf8Path>,
+        escaper: Option<Escaper>,
+    ) -> Result<Rc<CompiledTemplate>, RenderError> {
+        let mut key = format!("{}|include:{}", path, escaper.map_or("none", |escaper| escaper.name()));
+        if let Some(dir) = extra_dir {
+            key.push_str(&format!("|extra:{}", dir));
+        }
+        let options = CompileOptions {
+            autoescape: escaper,
+            ..self.options
+        };
+        self.compile_cached(key, path, name, extra_dir, options)
+    }
+
+    /// Compile the template at `path` under `key`, unless it already was.
+    /// Its errors name it as `name`.
+    fn compile_cached(
+        &mut self,
+        key: String,
+        path: &Utf8Path,
+        name: &str,
+        extra_dir: Option<&Utf8Path>,
+        options: CompileOptions,
+    ) -> Result<Rc<CompiledTemplate>, RenderError> {
         if !self.cache.contains_key(&key) {
             let template_text = fs::read_to_string(path).map_err(|err| {
                 RenderError::FileRenderIOError {
@@ -121,37 +208,49 @@ impl TemplateCache {
                     source: err,
                 }
             })?;
-            let mut resolver = if let Some(dir) = extra_dir {
-                let mut dirs = vec![(dir.to_owned(), IncludeTrust::System)];
-                dirs.extend_from_slice(&self.includes_dirs);
-                IncludeResolver::new(dirs)
-            } else {
-                self.make_resolver()
-            };
-            let compiled = TemplateCompiler::compile_with(
-                &template_text,
-                path.as_str(),
-                &mut resolver,
-                self.options,
-            )
-            .map_err(|err| RenderError::LuaTemplateCompileError {
-                path: path.to_owned(),
-                // Strip the InTemplate wrapper since `path` already
-                // identifies the template at this layer; including the
-                // template name in `message` would duplicate it.
-                message: strip_in_template(err).to_string(),
+            let body = front_matter::strip(&template_text).map_err(|message| {
+                RenderError::InvalidFrontMatter {
+                    path: path.to_owned(),
+                    message,
+                }
             })?;
-            self.cache.insert(key.clone(), compiled.source);
+            let mut resolver = self.make_resolver(extra_dir);
+            let mut compiled = TemplateCompiler::compile_with(body, path.as_str(), &mut resolver, options)
+                .map_err(|err| RenderError::LuaTemplateCompileError {
+                    path: path.to_owned(),
+                    // Strip the InTemplate wrapper since `path` already
+                    // identifies the template at this layer; including the
+                    // template name in `message` would duplicate it.
+                    message: strip_in_template(err).to_string(),
+                })?;
+            // Errors should name lines of the file, front matter and all.
+            let front_matter_lines = template_text[..template_text.len() - body.len()].matches('\n').count();
+            compiled
+                .source_map
+                .place_root(name, &template_text, front_matter_lines);
+            self.cache.insert(key.clone(), Rc::new(compiled));
         }
-        Ok(&self.cache[&key])
+        Ok(Rc::clone(&self.cache[&key]))
     }
 }
 
+/// How deep dynamic includes may nest. A partial may include itself — to
+/// render a tree, say — but one that never stops fails here rather than
+/// overflowing the stack.
+const MAX_INCLUDE_DEPTH: usize = 64;
+
 /// Render a template file using the Lua template engine.
 ///
 /// `extra_include_dir` — when the template was resolved via `find_include`
 /// (i.e. from a staged library), pass the resolved file's parent directory
 /// here so sibling fragments are reachable as plain `{% include "sibling.atl" %}`.
+///
+/// `destination` — where the rendered file will be written, if anywhere;
+/// the template sees it as `destination`.
+///
+/// An error raised while the template runs names the template file, line,
+/// and column it came from — a partial's own, for code that was included —
+/// and shows that line, rather than a line of the compiled Lua.
 pub fn lua_render_contents(
     lua: &Lua,
     path: &Utf8Path,
@@ -159,24 +258,129 @@ pub fn lua_render_contents(
     filters_table: &Table,
     cache: &mut TemplateCache,
     extra_include_dir: Option<&Utf8Path>,
+    destination: Option<&Utf8Path>,
 ) -> Result<String, RenderError> {
-    let lua_source = cache.get_or_compile_with_extra_dir(path, extra_include_dir)?;
+    let compiled = cache.get_or_compile_with_extra_dir(path, extra_include_dir)?;
+    let destination = destination.map(Utf8Path::as_str);
+
+    // Dynamic includes call back here, through a function that lives only
+    // as long as this render. It hands itself on to the partials it runs,
+    // so theirs work too.
+    let cache = RefCell::new(cache);
+    let depth = Cell::new(0);
+    let include_fn: OnceCell<Function> = OnceCell::new();
+    let rendered = lua.scope(|scope| {
+        let include = scope.create_function(|lua, (partial, escaper, ctx, args): IncludeArgs| {
+            if depth.get() >= MAX_INCLUDE_DEPTH {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "include `{}` nested more than {} deep",
+                    partial, MAX_INCLUDE_DEPTH
+                )));
+            }
+            let resolved = cache
+                .borrow()
+                .find_include_from(&partial, extra_include_dir)
+                .ok_or_else(|| {
+                    mlua::Error::RuntimeError(format!("include `{}` not found in the includes directories", partial))
+                })?;
+            let escaper = escaper.as_deref().and_then(Escaper::from_name);
+            let compiled = cache
+                .borrow_mut()
+                .get_or_compile_include(&resolved, &partial, extra_include_dir, escaper)
+                .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+            depth.set(depth.get() + 1);
+            let with = args.is_some();
+            let ctx = args.unwrap_or(ctx);
+            let result = run_compiled(
+                lua,
+                &resolved,
+                &compiled,
+                ctx,
+                with,
+                filters_table,
+                include_fn.get(),
+                destination,
+            );
+            depth.set(depth.get() - 1);
+            result.map_err(|message| match message {
+                RunError::Load(err) => mlua::Error::RuntimeError(format!("Failed to load `{}`: {}", resolved, err)),
+                // Already a complete message; passed on as is, so it isn't
+                // prefixed with "runtime error:" a second time.
+                RunError::Render(message) => mlua::Error::external(message),
+            })
+        })?;
+        let _ = include_fn.set(include);
+        Ok(run_compiled(
+            lua,
+            path,
+            &compiled,
+            ctx_table.clone(),
+            false,
+            filters_table,
+            include_fn.get(),
+            destination,
+        ))
+    });
 
-    let func: Function = lua.load(lua_source).eval().map_err(|err| {
-        RenderError::LuaTemplateRuntimeError {
+    match rendered {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(RunError::Load(err))) => Err(RenderError::LuaTemplateRuntimeError {
             path: path.to_owned(),
             message: format!("Failed to load compiled template: {}", err),
+        }),
+        Ok(Err(RunError::Render(message))) | Err(mlua::Error::RuntimeError(message)) => {
+            Err(RenderError::LuaTemplateRuntimeError {
+                path: path.to_owned(),
+                message,
+            })
         }
-    })?;
-
-    let result: String = func
-        .call::<String>((ctx_table.clone(), filters_table.clone()))
-        .map_err(|err| RenderError::LuaTemplateRuntimeError {
+        Err(err) => Err(RenderError::LuaTemplateRuntimeError {
             path: path.to_owned(),
-            message: format!("{}", err),
-        })?;
+            message: err.to_string(),
+        }),
+    }
+}
 
-    Ok(result)
+/// What a dynamic include passes: the path, the escaper of the region it
+/// sits in, the context it sits in, and its arguments, if any.
+type IncludeArgs = (String, Option<String>, Table, Option<Table>);
+
+/// Why running a compiled template failed.
+enum RunError {
+    /// The compiled source didn't load.
+    Load(mlua::Error),
+    /// It raised an error while rendering; the message points at the
+    /// template where the source map allows.
+    Render(String),
+}
+
+/// Load and run `compiled`, the template at `path`, against `ctx_table` —
+/// the arguments of an include, if `with` — with `include` for the dynamic
+/// includes in it, and `destination` for the file being rendered, if it has
+/// one.
+#[allow(clippy::too_many_arguments)]
+fn run_compiled(
+    lua: &Lua,
+    path: &Utf8Path,
+    compiled: &CompiledTemplate,
+    ctx_table: Table,
+    with: bool,
+    filters_table: &Table,
+    include: Option<&Function>,
+    destination: Option<&str>,
+) -> Result<String, RunError> {
+    let func: Function = lua
+        .load(&compiled.source)
+        .set_name(CHUNK_NAME)
+        .eval()
+        .map_err(RunError::Load)?;
+    let include = include.map_or(Value::Nil, |include| Value::Function(include.clone()));
+    let _running = debugger::enter_template(lua, path, &compiled.source_map, &func);
+    func.call::<String>((ctx_table, filters_table.clone(), include, with, destination))
+        .map_err(|err| {
+            let message = err.to_string();
+            RunError::Render(compiled.source_map.annotate(&message).unwrap_or(message))
+        })
 }
 
 /// Render a file/directory name using the Lua template engine.
@@ -214,7 +418,12 @@ pub fn lua_render_path(
     Ok(result)
 }
 
-/// Render a directory tree using the Lua template engine.
+/// Render a directory tree using the Lua template engine, skipping the
+/// entries `filter` rules out, and the files whose front matter does.
+///
+/// Executable bits on source files carry over to the rendered files, and
+/// symlinks are recreated as symlinks rather than followed. A link's target
+/// is rendered like any name, and must resolve inside `destination`.
 pub fn lua_render_directory(
     lua: &Lua,
     archetect: &Archetect,
@@ -223,57 +432,138 @@ pub fn lua_render_directory(
     source: Utf8PathBuf,
     destination: Utf8PathBuf,
     overwrite_policy: OverwritePolicy,
+    filter: &EntryFilter,
     cache: &mut TemplateCache,
 ) -> Result<(), RenderError> {
-    send_write_directory(archetect, &destination)?;
-
-    for entry in fs::read_dir(&source).map_err(|err| RenderError::DirectoryListError {
-        path: source.to_path_buf(),
-        source: err,
-    })? {
-        let entry = entry.map_err(|err| RenderError::DirectoryReadError {
-            path: source.clone(),
-            source: err,
-        })?;
-        let path = Utf8PathBuf::from_path_buf(entry.path())
-            .map_err(|bad| RenderError::InvalidUtf8Path { path: bad })?;
+    let walk = DirectoryWalk {
+        lua,
+        archetect,
+        ctx_table,
+        filters_table,
+        overwrite_policy,
+        root: &destination,
+    };
+    walk.render(source, destination.clone(), filter, cache)
+}
 
-        if path.is_dir() {
-            let dest = lua_render_destination(lua, ctx_table, filters_table, &destination, &path)?;
-            send_write_directory(archetect, &dest)?;
-            lua_render_directory(
-                lua,
-                archetect,
-                ctx_table,
-                filters_table,
-                path,
-                dest,
-                overwrite_policy,
-                cache,
-            )?;
-        } else if path.is_file() {
-            let contents = fs::read(&path).map_err(|err| RenderError::FileReadError {
-                path: path.to_path_buf(),
+/// What stays fixed while a `directory.render` descends.
+struct DirectoryWalk<'a> {
+    lua: &'a Lua,
+    archetect: &'a Archetect,
+    ctx_table: &'a Table,
+    filters_table: &'a Table,
+    overwrite_policy: OverwritePolicy,
+    root: &'a Utf8Path,
+}
+
+impl DirectoryWalk<'_> {
+    fn render(
+        &self,
+        source: Utf8PathBuf,
+        destination: Utf8PathBuf,
+        filter: &EntryFilter,
+        cache: &mut TemplateCache,
+    ) -> Result<(), RenderError> {
+        send_write_directory(self.archetect, &destination)?;
+        let filter = filter.descend(&source, &destination)?;
+
+        for entry in fs::read_dir(&source).map_err(|err| RenderError::DirectoryListError {
+            path: source.to_path_buf(),
+            source: err,
+        })? {
+            let entry = entry.map_err(|err| RenderError::DirectoryReadError {
+                path: source.clone(),
+                source: err,
+            })?;
+            let path = Utf8PathBuf::from_path_buf(entry.path())
+                .map_err(|bad| RenderError::InvalidUtf8Path { path: bad })?;
+            // Unlike `Path::is_dir`, the entry's own type does not follow links.
+            let file_type = entry.file_type().map_err(|err| RenderError::DirectoryReadError {
+                path: source.clone(),
                 source: err,
             })?;
 
-            let is_binary = matches!(
-                content_inspector::inspect(contents.as_slice()),
-                ContentType::BINARY
-            );
-
-            let dest = lua_render_destination(lua, ctx_table, filters_table, &destination, &path)?;
+            if path.file_name() == Some(IGNORE_FILE) {
+                continue;
+            }
+            let dest = lua_render_destination(self.lua, self.ctx_table, self.filters_table, &destination, &path)?;
+            if filter.skips(&path, &dest, file_type.is_dir()) {
+                report_skipped(self.archetect, &dest);
+                continue;
+            }
 
-            if is_binary {
-                send_write_file(archetect, &dest, contents, overwrite_policy)?;
-            } else {
-                let rendered = lua_render_contents(lua, &path, ctx_table, filters_table, cache, None)?;
-                send_write_file(archetect, &dest, rendered.into_bytes(), overwrite_policy)?;
+            if file_type.is_symlink() {
+                self.render_symlink(&path, &dest)?;
+            } else if file_type.is_dir() {
+                self.render(path, dest, &filter, cache)?;
+            } else if file_type.is_file() {
+                let template = TemplateFile {
+                    source: &path,
+                    destination: &dest,
+                    overwrite_policy: self.overwrite_policy,
+                    mode: None,
+                    extra_include_dir: None,
+                };
+                template.render(self.lua, self.archetect, self.ctx_table, self.filters_table, cache)?;
             }
         }
+
+        Ok(())
     }
 
-    Ok(())
+    fn render_symlink(&self, path: &Utf8Path, destination: &Utf8Path) -> Result<(), RenderError> {
+        let target = fs::read_link(path).map_err(|err| RenderError::FileReadError {
+            path: path.to_path_buf(),
+            source: err,
+        })?;
+        let target = Utf8PathBuf::from_path_buf(target).map_err(|bad| RenderError::InvalidUtf8Path { path: bad })?;
+        let target = lua_render_path(self.lua, target.as_str(), self.ctx_table, self.filters_table)?;
+        if !link_stays_within(self.root, destination, Utf8Path::new(&target)) {
+            return Err(RenderError::SymlinkEscape {
+                path: destination.to_path_buf(),
+                target,
+            });
+        }
+        send_write_symlink(self.archetect, destination, &target, self.overwrite_policy)
+    }
+}
+
+/// Whether a link at `link` pointing at `target` resolves, lexically, to
+/// somewhere beneath `root`. A rendered tree must not carry a way out of
+/// itself: later writes through such a link would land outside it.
+fn link_stays_within(root: &Utf8Path, link: &Utf8Path, target: &Utf8Path) -> bool {
+    if target.has_root() {
+        return false;
+    }
+    let Some(Ok(parent)) = link.parent().map(|parent| parent.strip_prefix(root)) else {
+        return false;
+    };
+    let mut depth = parent.components().count();
+    for component in target.components() {
+        match component {
+            Utf8Component::ParentDir => match depth.checked_sub(1) {
+                Some(shallower) => depth = shallower,
+                None => return false,
+            },
+            Utf8Component::Normal(_) => depth += 1,
+            _ => {}
+        }
+    }
+    true
+}
+
+/// The source file's permission bits, if any execute bit is set. Anything
+/// else is left to the client's umask.
+#[cfg(unix)]
+fn executable_mode(path: &Utf8Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path).ok()?.permissions().mode() & 0o777;
+    (mode & 0o111 != 0).then_some(mode)
+}
+
+#[cfg(not(unix))]
+fn executable_mode(_path: &Utf8Path) -> Option<u32> {
+    None
 }
 
 fn lua_render_destination(
@@ -290,14 +580,19 @@ fn lua_render_destination(
     Ok(destination)
 }
 
-fn send_write_directory(archetect: &Archetect, path: &Utf8Path) -> Result<(), RenderError> {
+/// A dry run lists what the filter left out, so an author can check their
+/// globs without rendering for real. A real render skips silently.
+fn report_skipped(archetect: &Archetect, path: &Utf8Path) {
+    if archetect.is_dry_run() {
+        let _ = archetect.request(ScriptMessage::Display(format!("[dry-run] skip    {}", path)));
+    }
+}
+
+pub(crate) fn send_write_directory(archetect: &Archetect, path: &Utf8Path) -> Result<(), RenderError> {
     if archetect.is_dry_run() {
         // Display goes to stderr via the IO driver — visible alongside other
         // diagnostic output regardless of how stdout is being consumed.
-        let _ = archetect.request(ScriptMessage::Display(format!(
-            "[dry-run] mkdir   {}",
-            path
-        )));
+        archetect.report_dry_run(format!("[dry-run] mkdir   {}", path));
         return Ok(());
     }
     archetect.request(ScriptMessage::WriteDirectory(WriteDirectoryInfo {
@@ -313,54 +608,175 @@ fn send_write_directory(archetect: &Archetect, path: &Utf8Path) -> Result<(), Re
     }
 }
 
-fn send_write_file(
+pub(crate) fn send_write_file(
     archetect: &Archetect,
     destination: &Utf8Path,
     contents: Vec<u8>,
     overwrite_policy: OverwritePolicy,
+    mode: Option<u32>,
 ) -> Result<(), RenderError> {
     if archetect.is_dry_run() {
-        let exists_marker = if destination.exists() {
-            match overwrite_policy {
-                OverwritePolicy::Overwrite => " (overwrite)",
-                OverwritePolicy::Preserve => " (skip — already exists)",
-                OverwritePolicy::Prompt => " (would prompt — exists)",
-                OverwritePolicy::Error => " (would error — exists)",
-            }
-        } else {
-            ""
-        };
-        let _ = archetect.request(ScriptMessage::Display(format!(
-            "[dry-run] write   {} ({} bytes){}",
+        let mode_marker = mode.map(|mode| format!(" (mode {:o})", mode)).unwrap_or_default();
+        archetect.report_dry_run(format!(
+            "[dry-run] write   {} ({} bytes){}{}",
             destination,
             contents.len(),
-            exists_marker
-        )));
+            mode_marker,
+            exists_marker(destination, &overwrite_policy.into())
+        ));
+        archetect.capture(ScriptMessage::WriteFile(WriteFileInfo {
+            destination: destination.to_string(),
+            contents,
+            existing_file_policy: overwrite_policy.into(),
+            shadow: None,
+            mode,
+        }));
         return Ok(());
     }
     archetect.request(ScriptMessage::WriteFile(WriteFileInfo {
         destination: destination.to_string(),
-        contents,
+        contents: contents.clone(),
+        existing_file_policy: overwrite_policy.into(),
+        shadow: None,
+        mode,
+    }))?;
+    let outcome = match archetect.response()? {
+        archetect_api::ClientMessage::Ack => None,
+        archetect_api::ClientMessage::Written(outcome) => Some(outcome),
+        archetect_api::ClientMessage::Error(msg) => {
+            return Err(RenderError::WriteError {
+                path: destination.to_path_buf(),
+                source: std::io::Error::new(std::io::ErrorKind::Other, msg),
+            })
+        }
+        other => return Err(RenderError::UnexpectedResponse(format!("{:?}", other))),
+    };
+    archetect.record_written(destination, &contents, outcome);
+    Ok(())
+}
+
+/// What a file operation leaves in the destination, as artifacts.
+fn operation_artifacts(archetect: &Archetect, operation: &FileOperation) -> Vec<Artifact> {
+    let path = |path: &str| archetect.artifact_path(Utf8Path::new(path));
+    match operation {
+        FileOperation::Append { path: appended, .. } => vec![Artifact::file(path(appended))],
+        FileOperation::Delete { path: deleted } => vec![Artifact::removed(path(deleted))],
+        FileOperation::Move { source, destination, .. } => {
+            vec![Artifact::removed(path(source)), Artifact::file(path(destination))]
+        }
+        FileOperation::Copy { destination, .. } => vec![Artifact::file(path(destination))],
+    }
+}
+
+fn send_write_symlink(
+    archetect: &Archetect,
+    path: &Utf8Path,
+    target: &str,
+    overwrite_policy: OverwritePolicy,
+) -> Result<(), RenderError> {
+    if archetect.is_dry_run() {
+        archetect.report_dry_run(format!(
+            "[dry-run] link    {} -> {}{}",
+            path,
+            target,
+            exists_marker(path, &overwrite_policy.into())
+        ));
+        archetect.capture(ScriptMessage::WriteSymlink(WriteSymlinkInfo {
+            path: path.to_string(),
+            target: target.to_string(),
+            existing_file_policy: overwrite_policy.into(),
+        }));
+        return Ok(());
+    }
+    archetect.request(ScriptMessage::WriteSymlink(WriteSymlinkInfo {
+        path: path.to_string(),
+        target: target.to_string(),
         existing_file_policy: overwrite_policy.into(),
     }))?;
     match archetect.response()? {
         archetect_api::ClientMessage::Ack => Ok(()),
         archetect_api::ClientMessage::Error(msg) => Err(RenderError::WriteError {
-            path: destination.to_path_buf(),
+            path: path.to_path_buf(),
             source: std::io::Error::new(std::io::ErrorKind::Other, msg),
         }),
         other => Err(RenderError::UnexpectedResponse(format!("{:?}", other))),
     }
 }
 
+pub(crate) fn send_file_operation(archetect: &Archetect, operation: FileOperation) -> Result<(), RenderError> {
+    if archetect.is_dry_run() {
+        let description = match &operation {
+            FileOperation::Append { path, contents } => format!("append  {} ({} bytes)", path, contents.len()),
+            FileOperation::Delete { path } => format!("delete  {}", path),
+            FileOperation::Move {
+                source,
+                destination,
+                existing_file_policy,
+            } => format!(
+                "move    {} -> {}{}",
+                source,
+                destination,
+                exists_marker(Utf8Path::new(destination), existing_file_policy)
+            ),
+            FileOperation::Copy {
+                source,
+                destination,
+                existing_file_policy,
+            } => format!(
+                "copy    {} -> {}{}",
+                source,
+                destination,
+                exists_marker(Utf8Path::new(destination), existing_file_policy)
+            ),
+        };
+        archetect.report_dry_run(format!("[dry-run] {}", description));
+        archetect.capture(ScriptMessage::FileOperation(operation));
+        return Ok(());
+    }
+    let path = Utf8PathBuf::from(operation.path());
+    let artifacts = operation_artifacts(archetect, &operation);
+    archetect.request(ScriptMessage::FileOperation(operation))?;
+    match archetect.response()? {
+        archetect_api::ClientMessage::Ack => {
+            artifacts.into_iter().for_each(|artifact| archetect.record_artifact(artifact));
+            Ok(())
+        }
+        archetect_api::ClientMessage::Error(msg) => Err(RenderError::WriteError {
+            path,
+            source: std::io::Error::new(std::io::ErrorKind::Other, msg),
+        }),
+        other => Err(RenderError::UnexpectedResponse(format!("{:?}", other))),
+    }
+}
+
+/// How a dry run describes a write that lands on something already there.
+/// `symlink_metadata`, so that a dangling link still counts.
+fn exists_marker(destination: &Utf8Path, policy: &ExistingFilePolicy) -> &'static str {
+    if destination.symlink_metadata().is_err() {
+        return "";
+    }
+    match policy {
+        ExistingFilePolicy::Overwrite => " (overwrite)",
+        ExistingFilePolicy::Preserve => " (skip — already exists)",
+        ExistingFilePolicy::Prompt => " (would prompt — exists)",
+        ExistingFilePolicy::Error => " (would error — exists)",
+        ExistingFilePolicy::Merge => " (would merge — exists)",
+    }
+}
+
 /// Render a single template file from `source` and write it to `destination`.
 ///
 /// Binary files are passed through untouched; text files are compiled via
-/// the Lua template engine with `ctx_table` + `filters_table` in scope.
+/// the Lua template engine with `ctx_table` + `filters_table` in scope, after
+/// any front matter (see [`front_matter`]) has had its say.
 /// Used by `file.render(...)` in Lua — the single-file analogue of
 /// `directory.render(...)`.
 ///
 /// `extra_include_dir` — see `lua_render_contents`.
+///
+/// `mode` sets the rendered file's permission bits; without it, an
+/// executable source stays executable, as with `directory.render`.
+#[allow(clippy::too_many_arguments)]
 pub fn lua_render_file(
     lua: &Lua,
     archetect: &Archetect,
@@ -369,6 +785,7 @@ pub fn lua_render_file(
     source: &Utf8Path,
     destination: &Utf8Path,
     overwrite_policy: OverwritePolicy,
+    mode: Option<u32>,
     cache: &mut TemplateCache,
     extra_include_dir: Option<&Utf8Path>,
 ) -> Result<(), RenderError> {
@@ -381,21 +798,139 @@ pub fn lua_render_file(
         }
     }
 
-    let contents = fs::read(source).map_err(|err| RenderError::FileReadError {
-        path: source.to_path_buf(),
-        source: err,
-    })?;
+    let template = TemplateFile {
+        source,
+        destination,
+        overwrite_policy,
+        mode,
+        extra_include_dir,
+    };
+    template.render(lua, archetect, ctx_table, filters_table, cache)
+}
+
+/// One regular file on its way from a template tree to the destination.
+struct TemplateFile<'a> {
+    source: &'a Utf8Path,
+    destination: &'a Utf8Path,
+    overwrite_policy: OverwritePolicy,
+    mode: Option<u32>,
+    extra_include_dir: Option<&'a Utf8Path>,
+}
+
+impl TemplateFile<'_> {
+    /// Binary files are written untouched. A text file is rendered, unless
+    /// its front matter skips it or asks for it verbatim; the front matter
+    /// can also move it and override the policy for an existing file.
+    fn render(
+        &self,
+        lua: &Lua,
+        archetect: &Archetect,
+        ctx_table: &Table,
+        filters_table: &Table,
+        cache: &mut TemplateCache,
+    ) -> Result<(), RenderError> {
+        let contents = fs::read(self.source).map_err(|err| RenderError::FileReadError {
+            path: self.source.to_path_buf(),
+            source: err,
+        })?;
+        let mode = self.mode.or_else(|| executable_mode(self.source));
+
+        if matches!(content_inspector::inspect(contents.as_slice()), ContentType::BINARY) {
+            return send_write_file(archetect, self.destination, contents, self.overwrite_policy, mode);
+        }
+
+        let front_matter = match std::str::from_utf8(&contents).map(front_matter::split) {
+            Ok(Ok(Some((front_matter, body)))) => Some((front_matter, body.as_bytes().to_vec())),
+            Ok(Err(message)) => return Err(self.invalid_front_matter(message)),
+            _ => None,
+        };
+        let Some((front_matter, body)) = front_matter else {
+            let rendered = lua_render_contents(
+                lua,
+                self.source,
+                ctx_table,
+                filters_table,
+                cache,
+                self.extra_include_dir,
+                Some(self.destination),
+            )?;
+            return send_write_file(archetect, self.destination, rendered.into_bytes(), self.overwrite_policy, mode);
+        };
+
+        if let Some(condition) = &front_matter.when {
+            if !self.holds(lua, condition, ctx_table, filters_table)? {
+                report_skipped(archetect, self.destination);
+                return Ok(());
+            }
+        }
+        let destination = self.relocate(lua, archetect, &front_matter, ctx_table, filters_table)?;
+        let overwrite_policy = front_matter.if_exists.unwrap_or(self.overwrite_policy);
+        let contents = if front_matter.verbatim {
+            body
+        } else {
+            lua_render_contents(
+                lua,
+                self.source,
+                ctx_table,
+                filters_table,
+                cache,
+                self.extra_include_dir,
+                Some(&destination),
+            )?
+            .into_bytes()
+        };
+        send_write_file(archetect, &destination, contents, overwrite_policy, mode)
+    }
 
-    let is_binary = matches!(
-        content_inspector::inspect(contents.as_slice()),
-        ContentType::BINARY
-    );
+    /// Evaluate a `when:` condition the way `{% if %}` would.
+    fn holds(&self, lua: &Lua, condition: &str, ctx_table: &Table, filters_table: &Table) -> Result<bool, RenderError> {
+        let template = format!("{{% if {} %}}1{{% endif %}}", condition);
+        let compiled = TemplateCompiler::compile(&template, self.source.as_str())
+            .map_err(|err| self.invalid_front_matter(format!("'when': {}", strip_in_template(err))))?;
+        let result = lua
+            .load(&compiled.source)
+            .eval::<Function>()
+            .and_then(|func| func.call::<String>((ctx_table.clone(), filters_table.clone())))
+            .map_err(|err| self.invalid_front_matter(format!("'when': {}", err)))?;
+        Ok(!result.is_empty())
+    }
 
-    if is_binary {
-        send_write_file(archetect, destination, contents, overwrite_policy)?;
-    } else {
-        let rendered = lua_render_contents(lua, source, ctx_table, filters_table, cache, extra_include_dir)?;
-        send_write_file(archetect, destination, rendered.into_bytes(), overwrite_policy)?;
+    /// Where the file lands: its own rendered name, or the front matter's
+    /// `destination:`, which must stay beneath the same directory.
+    fn relocate(
+        &self,
+        lua: &Lua,
+        archetect: &Archetect,
+        front_matter: &FrontMatter,
+        ctx_table: &Table,
+        filters_table: &Table,
+    ) -> Result<Utf8PathBuf, RenderError> {
+        let Some(destination) = &front_matter.destination else {
+            return Ok(self.destination.to_path_buf());
+        };
+        let rendered = lua_render_path(lua, destination, ctx_table, filters_table)?;
+        let relative = Utf8Path::new(&rendered);
+        let beneath = relative
+            .components()
+            .all(|component| matches!(component, Utf8Component::Normal(_) | Utf8Component::CurDir));
+        if relative.file_name().is_none() || !beneath {
+            return Err(self.invalid_front_matter(format!(
+                "'destination' must be a relative path without `..`, got `{}`",
+                rendered
+            )));
+        }
+        let directory = self.destination.parent().unwrap_or(Utf8Path::new(""));
+        let relocated = directory.join(relative);
+        if let Some(parent) = relocated.parent().filter(|parent| *parent != directory) {
+            send_write_directory(archetect, parent)?;
+        }
+        Ok(relocated)
+    }
+
+    fn invalid_front_matter(&self, message: String) -> RenderError {
+        RenderError::InvalidFrontMatter {
+            path: self.source.to_path_buf(),
+            message,
+        }
     }
-    Ok(())
 }
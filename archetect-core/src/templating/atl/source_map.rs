@@ -0,0 +1,371 @@
+//! Where the lines of a compiled template's Lua came from.
+//!
+//! A template fails at render time inside the Lua it was compiled to, so
+//! mlua reports a line of a chunk nobody wrote. While it emits that chunk,
+//! the compiler records the template line and column behind each line of
+//! Lua an expression or logic block produced — a partial under its own
+//! name — and [`SourceMap::annotate`] turns a runtime error back into a
+//! position in the template, with the offending line underneath it. The
+//! debugger reads the same map to put a template's breakpoints and stack
+//! frames on the template's own lines.
+
+use std::rc::Rc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// The name compiled templates are loaded under. The leading `=` has Lua
+/// use the rest verbatim in messages and tracebacks (`atl:12: ...`), which
+/// is what [`SourceMap::annotate`] looks for.
+pub const CHUNK_NAME: &str = "=atl";
+
+const CHUNK_PREFIX: &str = "atl:";
+const TRACEBACK: &str = "\nstack traceback:";
+/// How [`SourceMap::annotate`] introduces a location.
+const LOCATION: &str = "\n  --> ";
+
+/// A position in a template: its file, line, and column, counting from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Origin {
+    /// The partial or parent the code came from, as it was named; `None`
+    /// for the template being compiled.
+    file: Option<Rc<str>>,
+    line: usize,
+    column: usize,
+}
+
+/// Maps lines of a compiled template's Lua back to the template.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    root: (String, String),
+    /// Lines the template's own text starts below the top of its file: a
+    /// file's front matter is stripped before it compiles.
+    root_offset: usize,
+    /// The text of each partial and parent, by the name it was included as.
+    files: Vec<(Rc<str>, String)>,
+    /// The file each partial and parent resolved to, by the same name.
+    paths: Vec<(Rc<str>, Utf8PathBuf)>,
+    /// `(lua line, origin)`, ordered by line.
+    origins: Vec<(usize, Origin)>,
+}
+
+impl SourceMap {
+    /// Name the template the map's unattributed lines belong to. Its
+    /// compiled text starts `offset` lines into `text`.
+    pub fn place_root(&mut self, name: &str, text: &str, offset: usize) {
+        self.root = (name.to_owned(), text.to_owned());
+        self.root_offset = offset;
+    }
+
+    /// The template position the Lua at `lua_line` was compiled from.
+    pub fn locate(&self, lua_line: usize) -> Option<Location> {
+        let index = self.origins.binary_search_by_key(&lua_line, |(line, _)| *line).ok()?;
+        let origin = &self.origins[index].1;
+        Some(match &origin.file {
+            Some(file) => Location {
+                file: file.to_string(),
+                line: origin.line,
+                column: origin.column,
+            },
+            None => Location {
+                file: self.root.0.clone(),
+                line: origin.line + self.root_offset,
+                column: origin.column,
+            },
+        })
+    }
+
+    /// The file the partial or parent included as `file` resolved to. The
+    /// template being compiled isn't one; its caller knows where it is.
+    pub fn path(&self, file: &str) -> Option<&Utf8Path> {
+        self.paths
+            .iter()
+            .find(|(name, _)| **name == *file)
+            .map(|(_, path)| path.as_path())
+    }
+
+    /// Rewrite `message`, an error raised while running the compiled chunk,
+    /// to name the template position it came from and show that line with
+    /// a caret under the column. The Lua traceback is dropped; its lines
+    /// are the chunk's, not the template's. `None` when the error doesn't
+    /// point into the chunk.
+    ///
+    /// An error from a partial this template included at render time
+    /// already shows the partial's line; the include that ran it is only
+    /// named, and not again if a recursive include already named it.
+    pub fn annotate(&self, message: &str) -> Option<String> {
+        let location = self.locate(first_chunk_line(message)?)?;
+        let summary = message.split(TRACEBACK).next().unwrap_or(message);
+        let summary = strip_chunk_positions(summary);
+
+        let pointer = format!("\n  --> {}:{}:{}", location.file, location.line, location.column);
+        if summary.contains(LOCATION) {
+            if summary.contains(&pointer) {
+                return Some(summary);
+            }
+            return Some(summary + &pointer);
+        }
+        let mut annotated = summary + &pointer;
+        if let Some(source_line) = self.source_line(&location) {
+            let gutter = location.line.to_string();
+            let pad = " ".repeat(gutter.len());
+            let indent = source_line
+                .chars()
+                .take(location.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            annotated.push_str(&format!(
+                "\n{} |\n{} | {}\n{} | {}^",
+                pad, gutter, source_line, pad, indent
+            ));
+        }
+        Some(annotated)
+    }
+
+    fn source_line(&self, location: &Location) -> Option<&str> {
+        let text = self
+            .files
+            .iter()
+            .find(|(name, _)| **name == *location.file)
+            .map_or(self.root.1.as_str(), |(_, text)| text.as_str());
+        text.lines()
+            .nth(location.line.checked_sub(1)?)
+            .map(|line| line.trim_end_matches('\r'))
+    }
+}
+
+/// The first line of the chunk a Lua error message mentions: where it was
+/// raised, or for an error out of a Rust function, the innermost frame of
+/// its traceback that is template code.
+fn first_chunk_line(message: &str) -> Option<usize> {
+    message.match_indices(CHUNK_PREFIX).find_map(|(at, _)| {
+        if !starts_a_name(message, at) {
+            return None;
+        }
+        let after = &message[at + CHUNK_PREFIX.len()..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        after[digits..]
+            .starts_with(':')
+            .then(|| after[..digits].parse().ok())
+            .flatten()
+    })
+}
+
+/// `message` without the `atl:12: ` positions Lua prefixes errors with.
+fn strip_chunk_positions(message: &str) -> String {
+    let mut stripped = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(at) = rest.find(CHUNK_PREFIX) {
+        let after = &rest[at + CHUNK_PREFIX.len()..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        stripped.push_str(&rest[..at]);
+        match after[digits..].strip_prefix(':') {
+            Some(tail) if digits > 0 && starts_a_name(rest, at) => rest = tail.strip_prefix(' ').unwrap_or(tail),
+            _ => {
+                stripped.push_str(CHUNK_PREFIX);
+                rest = after;
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Whether the text at `at` starts a name of its own, rather than ending a
+/// file name like `footer.atl` that an annotated message already names.
+fn starts_a_name(message: &str, at: usize) -> bool {
+    message[..at]
+        .chars()
+        .next_back()
+        .is_none_or(|c| !(c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '/' | '\\')))
+}
+
+/// Lua source under construction, recording the template position of each
+/// line it is told about. Pushed text with no position — the preamble,
+/// literal text — simply has none.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    lua: String,
+    /// Newlines pushed so far; the line being written is one past this.
+    newlines: usize,
+    /// The file being compiled from, as it was named.
+    file: Option<Rc<str>>,
+    files: Vec<(Rc<str>, String)>,
+    paths: Vec<(Rc<str>, Utf8PathBuf)>,
+    origins: Vec<(usize, Origin)>,
+}
+
+impl Chunk {
+    pub fn push_str(&mut self, lua: &str) {
+        self.newlines += lua.matches('\n').count();
+        self.lua.push_str(lua);
+    }
+
+    /// Push `lua`, compiled from the template text at `line` and `column`.
+    /// Its later lines are taken to follow the template's line for line,
+    /// as code copied out of a multi-line tag does.
+    pub fn push_from(&mut self, line: usize, column: usize, lua: &str) {
+        let lines = lua.strip_suffix('\n').unwrap_or(lua);
+        for (offset, lua_line) in lines.split('\n').enumerate() {
+            let column = match offset {
+                0 => column,
+                _ => 1 + lua_line.len() - lua_line.trim_start().len(),
+            };
+            self.origins.push((
+                self.newlines + 1 + offset,
+                Origin {
+                    file: self.file.clone(),
+                    line: line + offset,
+                    column,
+                },
+            ));
+        }
+        self.push_str(lua);
+    }
+
+    /// Attribute what is pushed from here on to the partial `name`, whose
+    /// text is `text`, read from `path`. Returns the file to hand back to
+    /// [`Chunk::leave`].
+    pub fn enter(&mut self, name: &str, path: &Utf8Path, text: &str) -> Option<Rc<str>> {
+        let file = match self.files.iter().find(|(known, _)| **known == *name) {
+            Some((known, _)) => known.clone(),
+            None => {
+                let file: Rc<str> = Rc::from(name);
+                self.files.push((file.clone(), text.to_owned()));
+                self.paths.push((file.clone(), path.to_owned()));
+                file
+            }
+        };
+        self.file.replace(file)
+    }
+
+    pub fn leave(&mut self, outer: Option<Rc<str>>) {
+        self.file = outer;
+    }
+
+    /// A new, empty chunk that attributes what is pushed to the same file
+    /// this one does.
+    pub fn sibling(&self) -> Chunk {
+        Chunk {
+            file: self.file.clone(),
+            ..Chunk::default()
+        }
+    }
+
+    /// Push the whole of `other`, keeping its positions.
+    pub fn append(&mut self, other: &Chunk) {
+        let base = self.newlines;
+        self.origins
+            .extend(other.origins.iter().map(|(line, origin)| (line + base, origin.clone())));
+        for (name, text) in &other.files {
+            if !self.files.iter().any(|(known, _)| known == name) {
+                self.files.push((name.clone(), text.clone()));
+            }
+        }
+        for (name, path) in &other.paths {
+            if !self.paths.iter().any(|(known, _)| known == name) {
+                self.paths.push((name.clone(), path.clone()));
+            }
+        }
+        self.push_str(&other.lua);
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.lua
+    }
+
+    pub fn finish(self) -> (String, SourceMap) {
+        let map = SourceMap {
+            files: self.files,
+            paths: self.paths,
+            origins: self.origins,
+            ..SourceMap::default()
+        };
+        (self.lua, map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_map_back_to_their_files() {
+        let mut chunk = Chunk::default();
+        chunk.push_str("return function()\n");
+        chunk.push_from(2, 4, "    __w(a.b)\n");
+        let outer = chunk.enter("header.atl", Utf8Path::new("/includes/header.atl"), "x\n{% if y.z %}\n");
+        chunk.push_from(2, 4, "    if y.z\n    then\n");
+        chunk.leave(outer);
+        let mut tail = chunk.sibling();
+        tail.push_from(5, 1, "    __w(c)\n");
+        chunk.append(&tail);
+        let (lua, mut map) = chunk.finish();
+        map.place_root("page.txt", "one\ntwo\n", 1);
+
+        assert_eq!(lua.lines().count(), 5);
+        assert_eq!(map.locate(1), None);
+        let at = |file: &str, line, column| {
+            Some(Location {
+                file: file.to_owned(),
+                line,
+                column,
+            })
+        };
+        assert_eq!(map.locate(2), at("page.txt", 3, 4));
+        assert_eq!(map.locate(3), at("header.atl", 2, 4));
+        assert_eq!(map.locate(4), at("header.atl", 3, 5));
+        assert_eq!(map.locate(5), at("page.txt", 6, 1));
+        assert_eq!(map.path("header.atl"), Some(Utf8Path::new("/includes/header.atl")));
+        assert_eq!(map.path("page.txt"), None);
+    }
+
+    #[test]
+    fn test_annotate_points_at_the_template() {
+        let mut chunk = Chunk::default();
+        chunk.push_str("return function()\n");
+        chunk.push_from(2, 11, "    __w(user.name)\n");
+        let (_, mut map) = chunk.finish();
+        map.place_root("greeting.txt", "Hi,\nHello, {{ user.name }}!\n", 0);
+
+        let message = "runtime error: atl:2: attempt to index a nil value (global 'user')\n\
+                       stack traceback:\n\t[C]: in metamethod 'index'\n\tatl:2: in function <atl:1>";
+        let expected = [
+            "runtime error: attempt to index a nil value (global 'user')",
+            "  --> greeting.txt:2:11",
+            "  |",
+            "2 | Hello, {{ user.name }}!",
+            "  |           ^",
+        ];
+        assert_eq!(map.annotate(message).unwrap(), expected.join("\n"));
+        assert_eq!(map.annotate("runtime error: boom"), None);
+        // Raised in a partial it included, and already mapped there.
+        let nested = "boom\n  --> footer.atl:1:4\n  |\n1 | {{ x.y }}\n  |    ^\n\
+                      stack traceback:\n\t[C]: in local '__include'\n\tatl:2: in function <atl:1>";
+        let named = map.annotate(nested).unwrap();
+        assert!(named.ends_with("  |    ^\n  --> greeting.txt:2:11"), "{}", named);
+        assert_eq!(map.annotate(&format!("{}\nstack traceback:\n\tatl:2:", named)).unwrap(), named);
+        assert_eq!(map.annotate("runtime error: atl:7: elsewhere"), None);
+    }
+
+    #[test]
+    fn test_callback_errors_use_the_innermost_template_frame() {
+        assert_eq!(
+            first_chunk_line(
+                "runtime error: boom\nstack traceback:\n\t[C]: in function 'boom'\n\tatl:3: in function <atl:1>"
+            ),
+            Some(3)
+        );
+        assert_eq!(strip_chunk_positions("atl:3: bad atl:x"), "bad atl:x");
+        // An error already mapped to a partial names it, not the chunk.
+        let nested = "boom\n  --> footer.atl:2:11\nstack traceback:\n\tatl:5: in function <atl:1>";
+        assert_eq!(first_chunk_line(nested), Some(5));
+        assert_eq!(strip_chunk_positions("at footer.atl:2: x"), "at footer.atl:2: x");
+    }
+}
@@ -1,4 +1,5 @@
 use super::error::TemplateCompileError;
+use super::escape::Escaper;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Filter {
@@ -16,23 +17,117 @@ pub enum Token {
     /// Literal text emitted as-is.
     Text(String),
     /// `{{ expr | filter1 | filter2 }}` — expression with optional filter chain.
+    /// `line` and `column` are where the expression itself starts.
     Expression {
         expr: String,
         filters: Vec<Filter>,
+        line: usize,
+        column: usize,
         trim_left: bool,
         trim_right: bool,
     },
-    /// `{% lua_code %}` — raw Lua code block.
+    /// `{% lua_code %}` — raw Lua code block. `line` and `column` are where
+    /// the code starts.
     Logic {
         code: String,
+        line: usize,
+        column: usize,
         trim_left: bool,
         trim_right: bool,
     },
     /// `{% include "path/to/file.atl" %}` — special-form logic block. The
     /// path is recorded as parsed (relative to the configured includes
     /// directory) and resolved at compile time by an `IncludeResolver`.
+    /// With `with { name = f.name }`, `args` is that Lua expression, whose
+    /// value becomes the partial's whole context. `line` and `column` are
+    /// where the tag's contents start.
     Include {
         path: String,
+        args: Option<String>,
+        line: usize,
+        column: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% include "ci/" .. provider .. ".atl" %}` — an include whose path
+    /// is a Lua expression, found and compiled at render time. `args` is as
+    /// for [`Token::Include`].
+    DynamicInclude {
+        path: String,
+        args: Option<String>,
+        line: usize,
+        column: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% extends "base.atl" %}` — the template fills in the blocks of the
+    /// named parent instead of standing on its own. The parent is found the
+    /// same way an include is.
+    Extends {
+        path: String,
+        line: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% block name %}` — opens a block a child template can override.
+    Block {
+        name: String,
+        line: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% endblock %}` or `{% endblock name %}` — closes the innermost block.
+    EndBlock {
+        name: Option<String>,
+        line: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// Where a block renders. Never produced by the tokenizer: the compiler
+    /// leaves one in place of each `{% block %}...{% endblock %}` it lifts
+    /// out, carrying the outer trim markers of the two tags.
+    BlockCall {
+        name: String,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% macro field(name, type, optional=false) %}` — opens a macro: a
+    /// function that renders its body and returns the text. A parameter's
+    /// default is a raw Lua expression, used when the caller passes nil.
+    Macro {
+        name: String,
+        params: Vec<MacroParam>,
+        line: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% endmacro %}` — closes the innermost macro.
+    EndMacro {
+        line: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% import "macros.atl" as m %}` — binds `m` to the macros (and any
+    /// other names) the template at `path` defines. The path is resolved
+    /// the same way an include's is.
+    Import {
+        path: String,
+        alias: String,
+        line: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% autoescape "json" %}` — escape the interpolations up to the
+    /// matching `{% endautoescape %}` for a format; `{% autoescape false %}`
+    /// turns escaping off instead.
+    Autoescape {
+        escaper: Option<Escaper>,
+        line: usize,
+        trim_left: bool,
+        trim_right: bool,
+    },
+    /// `{% endautoescape %}` — closes the innermost autoescape region.
+    EndAutoescape {
         line: usize,
         trim_left: bool,
         trim_right: bool,
@@ -91,7 +186,15 @@ impl Tokenizer {
                                 return Err(TemplateCompileError::EmptyExpression { line: start_line });
                             }
                             let (expr, filters) = parse_expression(raw, start_line)?;
-                            tokens.push(Token::Expression { expr, filters, trim_left, trim_right });
+                            let (line, column) = position_of(template, raw, next_brace, start_line);
+                            tokens.push(Token::Expression {
+                                expr,
+                                filters,
+                                line,
+                                column,
+                                trim_left,
+                                trim_right,
+                            });
                             pos = content_end + 2;
                         }
                         None => {
@@ -124,8 +227,43 @@ impl Tokenizer {
                                 // Must be followed by whitespace, otherwise
                                 // it could be a Lua identifier like `include_xxx`.
                                 if rest.starts_with(|c: char| c.is_whitespace()) {
-                                    let path = parse_include_path(rest.trim(), start_line)?;
-                                    tokens.push(Token::Include {
+                                    let (path, args) = parse_include(rest.trim(), start_line)?;
+                                    let (line, column) = position_of(template, raw, next_brace, start_line);
+                                    tokens.push(match path {
+                                        IncludePath::Static(path) => Token::Include {
+                                            path,
+                                            args,
+                                            line,
+                                            column,
+                                            trim_left,
+                                            trim_right,
+                                        },
+                                        IncludePath::Dynamic(path) => Token::DynamicInclude {
+                                            path,
+                                            args,
+                                            line,
+                                            column,
+                                            trim_left,
+                                            trim_right,
+                                        },
+                                    });
+                                    pos = content_end + 2;
+                                    continue;
+                                }
+                            }
+
+                            // Special-forms for template inheritance:
+                            // `extends "path"`, `block name`, and `endblock`.
+                            // The compiler pairs the block tags up and
+                            // resolves the parent. `block = ...` and the like
+                            // stay Lua assignments to a variable that happens
+                            // to be called `block`.
+                            if let Some(rest) = raw.strip_prefix("extends") {
+                                if rest.starts_with(|c: char| c.is_whitespace()) {
+                                    let path = parse_quoted_path(rest.trim(), "extends").map_err(|detail| {
+                                        TemplateCompileError::InvalidExtends { line: start_line, detail }
+                                    })?;
+                                    tokens.push(Token::Extends {
                                         path,
                                         line: start_line,
                                         trim_left,
@@ -135,6 +273,86 @@ impl Tokenizer {
                                     continue;
                                 }
                             }
+                            if let Some(rest) = raw.strip_prefix("import") {
+                                if rest.starts_with(|c: char| c.is_whitespace()) {
+                                    let (path, alias) = parse_import(rest.trim(), start_line)?;
+                                    tokens.push(Token::Import {
+                                        path,
+                                        alias,
+                                        line: start_line,
+                                        trim_left,
+                                        trim_right,
+                                    });
+                                    pos = content_end + 2;
+                                    continue;
+                                }
+                            }
+                            if let Some(signature) = block_tag_name(raw, "macro") {
+                                let (name, params) = parse_macro_signature(signature, start_line)?;
+                                tokens.push(Token::Macro {
+                                    name,
+                                    params,
+                                    line: start_line,
+                                    trim_left,
+                                    trim_right,
+                                });
+                                pos = content_end + 2;
+                                continue;
+                            }
+                            if let Some(mode) = block_tag_name(raw, "autoescape") {
+                                tokens.push(Token::Autoescape {
+                                    escaper: parse_autoescape_mode(mode, start_line)?,
+                                    line: start_line,
+                                    trim_left,
+                                    trim_right,
+                                });
+                                pos = content_end + 2;
+                                continue;
+                            }
+                            if raw == "endautoescape" {
+                                tokens.push(Token::EndAutoescape {
+                                    line: start_line,
+                                    trim_left,
+                                    trim_right,
+                                });
+                                pos = content_end + 2;
+                                continue;
+                            }
+                            if raw == "endmacro" {
+                                tokens.push(Token::EndMacro {
+                                    line: start_line,
+                                    trim_left,
+                                    trim_right,
+                                });
+                                pos = content_end + 2;
+                                continue;
+                            }
+                            if let Some(name) = block_tag_name(raw, "block") {
+                                let name = parse_block_name(name, start_line)?;
+                                tokens.push(Token::Block {
+                                    name,
+                                    line: start_line,
+                                    trim_left,
+                                    trim_right,
+                                });
+                                pos = content_end + 2;
+                                continue;
+                            }
+                            if let Some(name) = block_tag_name(raw, "endblock") {
+                                let name = if name.is_empty() {
+                                    None
+                                } else {
+                                    Some(parse_block_name(name, start_line)?)
+                                };
+                                tokens.push(Token::EndBlock {
+                                    name,
+                                    line: start_line,
+                                    trim_left,
+                                    trim_right,
+                                });
+                                pos = content_end + 2;
+                                continue;
+                            }
 
                             // Special-form: `raw` / `endraw`. A `{% raw %}`
                             // block emits everything between it and the
@@ -191,8 +409,11 @@ impl Tokenizer {
                                 continue;
                             }
 
+                            let (line, column) = position_of(template, raw, next_brace, start_line);
                             tokens.push(Token::Logic {
                                 code: raw.to_string(),
+                                line,
+                                column,
                                 trim_left,
                                 trim_right,
                             });
@@ -236,6 +457,16 @@ impl Tokenizer {
     }
 }
 
+/// The line and column, counting from 1, at which `inner` — a slice of
+/// `template` — starts, given that the tag opening at byte `tag_start` is on
+/// `tag_line`.
+fn position_of(template: &str, inner: &str, tag_start: usize, tag_line: usize) -> (usize, usize) {
+    let offset = inner.as_ptr() as usize - template.as_ptr() as usize;
+    let line = tag_line + template[tag_start..offset].matches('\n').count();
+    let line_start = template[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+    (line, template[line_start..offset].chars().count() + 1)
+}
+
 /// Find the closing delimiter (e.g., `}}`, `%}`, `#}`) starting from `start`.
 /// Updates `line` to track newlines within the content.
 /// Returns the byte position of the closing delimiter start, or None.
@@ -373,41 +604,253 @@ fn skip_long_string(bytes: &[u8], pos: usize, level: usize, line: &mut usize) ->
     bytes.len() // unterminated — consume to end
 }
 
-/// Parse an `{% include "path" %}` body into the bare path string.
+/// Parse the body of an `{% include "path" %}` or `{% extends "path" %}`
+/// tag into the bare path string, or describe what is wrong with it.
 ///
 /// Accepts double-quoted (`"path"`) and single-quoted (`'path'`) forms.
 /// The path content itself is returned verbatim — the resolver layer
 /// validates that it stays inside the configured includes directory.
-fn parse_include_path(body: &str, line: usize) -> Result<String, TemplateCompileError> {
+fn parse_quoted_path(body: &str, tag: &str) -> Result<String, String> {
     let body = body.trim();
     if body.is_empty() {
-        return Err(TemplateCompileError::InvalidInclude {
-            line,
-            detail: "missing path; expected `{% include \"path\" %}`".to_string(),
-        });
+        return Err(format!("missing path; expected `{{% {} \"path\" %}}`", tag));
     }
     let bytes = body.as_bytes();
     let quote = bytes[0];
     if quote != b'"' && quote != b'\'' {
-        return Err(TemplateCompileError::InvalidInclude {
-            line,
-            detail: format!("expected quoted path, got `{}`", body),
-        });
+        return Err(format!("expected quoted path, got `{}`", body));
     }
     if bytes.len() < 2 || bytes[bytes.len() - 1] != quote {
-        return Err(TemplateCompileError::InvalidInclude {
-            line,
-            detail: "unterminated quoted path".to_string(),
-        });
+        return Err("unterminated quoted path".to_string());
     }
     let path = &body[1..body.len() - 1];
     if path.is_empty() {
-        return Err(TemplateCompileError::InvalidInclude {
+        return Err(format!("{} path cannot be empty", tag));
+    }
+    Ok(path.to_string())
+}
+
+/// Where an `{% include %}` finds its template.
+enum IncludePath {
+    /// A quoted path, inlined at compile time.
+    Static(String),
+    /// A Lua expression evaluating to the path, resolved at render time.
+    Dynamic(String),
+}
+
+/// Parse the body of an `{% include %}` tag: its path, and the expression
+/// after `with`, if any.
+///
+/// A path that is a single quoted string is static. Anything else is a Lua
+/// expression — a name, a `..` concatenation, a call, or a parenthesized
+/// lookup like `(ci.partial)` — except an unquoted `header.atl` or
+/// `partials/header.atl`, which is far more likely a path missing its
+/// quotes than a field lookup or a division.
+fn parse_include(body: &str, line: usize) -> Result<(IncludePath, Option<String>), TemplateCompileError> {
+    let invalid = |detail: String| TemplateCompileError::InvalidInclude { line, detail };
+    let (path, args) = match find_top_level_keyword(body, "with") {
+        Some(at) => {
+            let args = body[at + "with".len()..].trim();
+            if args.is_empty() {
+                return Err(invalid(
+                    "missing arguments after `with`; expected `{% include \"path\" with { name = value } %}`"
+                        .to_string(),
+                ));
+            }
+            (body[..at].trim(), Some(args.to_string()))
+        }
+        None => (body.trim(), None),
+    };
+    if is_single_quoted_string(path) {
+        let path = parse_quoted_path(path, "include").map_err(invalid)?;
+        return Ok((IncludePath::Static(path), args));
+    }
+    if path.is_empty() {
+        return Err(invalid("missing path; expected `{% include \"path\" %}`".to_string()));
+    }
+    let looks_like_a_path = path.contains(['.', '/'])
+        && path
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'));
+    if looks_like_a_path {
+        return Err(invalid(format!(
+            "expected quoted path, got `{}`; wrap an expression that builds one in parentheses",
+            path
+        )));
+    }
+    Ok((IncludePath::Dynamic(path.to_string()), args))
+}
+
+/// Whether `s` is exactly one string literal, rather than an expression
+/// that starts and ends with one, like `"ci/" .. name .. ".atl"`.
+fn is_single_quoted_string(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let Some(&quote) = bytes.first() else {
+        return false;
+    };
+    if quote != b'"' && quote != b'\'' {
+        return false;
+    }
+    match bytes[1..].iter().position(|&b| b == quote) {
+        Some(end) => end + 2 == bytes.len(),
+        // Unterminated; let `parse_quoted_path` say so.
+        None => true,
+    }
+}
+
+/// The byte offset of `keyword` as a word of its own in `s`, outside string
+/// literals and brackets.
+fn find_top_level_keyword(s: &str, keyword: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut depth = 0i32;
+    let mut in_string: Option<u8> = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if let Some(quote) = in_string {
+            if b == b'\\' {
+                i += 1;
+            } else if b == quote {
+                in_string = None;
+            }
+        } else {
+            match b {
+                b'"' | b'\'' => in_string = Some(b),
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' => depth -= 1,
+                _ if depth == 0
+                    && s[i..].starts_with(keyword)
+                    && (i == 0 || !is_word(bytes[i - 1]))
+                    && bytes.get(i + keyword.len()).is_none_or(|&after| !is_word(after)) =>
+                {
+                    return Some(i);
+                }
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+/// If `raw` is a `tag` tag (`block header`, `endblock`), the text after the
+/// keyword. `None` for anything else, including Lua that merely starts with
+/// the same word, like `block = 1` or `blocks[1] = x`.
+fn block_tag_name<'a>(raw: &'a str, tag: &str) -> Option<&'a str> {
+    let rest = raw.strip_prefix(tag)?;
+    if !rest.is_empty() && !rest.starts_with(|c: char| c.is_whitespace()) {
+        return None;
+    }
+    let rest = rest.trim();
+    if rest.starts_with('=') {
+        return None;
+    }
+    Some(rest)
+}
+
+/// `"json"` (or any escaper's name, quoted) selects an escaper; `false`
+/// turns escaping off.
+fn parse_autoescape_mode(mode: &str, line: usize) -> Result<Option<Escaper>, TemplateCompileError> {
+    if mode == "false" {
+        return Ok(None);
+    }
+    let name = ['"', '\'']
+        .iter()
+        .find_map(|quote| mode.strip_prefix(*quote)?.strip_suffix(*quote))
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| TemplateCompileError::InvalidAutoescape {
+            line,
+            detail: format!("expected a quoted escaper such as \"json\", or false; got `{}`", mode),
+        })?;
+    Escaper::from_name(name).map(Some).ok_or_else(|| TemplateCompileError::InvalidAutoescape {
+        line,
+        detail: format!("unknown escaper \"{}\"; expected json, yaml, xml, html, shell, or toml", name),
+    })
+}
+
+/// A block name must be a plain identifier: it names a slot, not a value.
+fn parse_block_name(name: &str, line: usize) -> Result<String, TemplateCompileError> {
+    if !is_identifier(name) {
+        return Err(TemplateCompileError::InvalidBlock {
             line,
-            detail: "include path cannot be empty".to_string(),
+            detail: if name.is_empty() {
+                "missing name; expected `{% block name %}`".to_string()
+            } else {
+                format!("expected a name made of letters, digits, and `_`, got `{}`", name)
+            },
         });
     }
-    Ok(path.to_string())
+    Ok(name.to_string())
+}
+
+/// Parse the body of `{% import "path" as alias %}`.
+fn parse_import(body: &str, line: usize) -> Result<(String, String), TemplateCompileError> {
+    let invalid = |detail: String| TemplateCompileError::InvalidImport { line, detail };
+    let Some(as_pos) = body.rfind(" as ") else {
+        return Err(invalid(format!(
+            "expected `{{% import \"path\" as name %}}`, got `{}`",
+            body
+        )));
+    };
+    let path = parse_quoted_path(&body[..as_pos], "import").map_err(invalid)?;
+    let alias = body[as_pos + 4..].trim();
+    if !is_identifier(alias) {
+        return Err(invalid(format!(
+            "expected a name made of letters, digits, and `_` after `as`, got `{}`",
+            alias
+        )));
+    }
+    Ok((path, alias.to_string()))
+}
+
+/// A macro parameter's name and its default, as a raw Lua expression.
+pub type MacroParam = (String, Option<String>);
+
+/// Parse `name(param, param=default)` — the parentheses are optional for a
+/// macro that takes nothing.
+fn parse_macro_signature(
+    signature: &str,
+    line: usize,
+) -> Result<(String, Vec<MacroParam>), TemplateCompileError> {
+    let invalid = |detail: String| TemplateCompileError::InvalidMacro { line, detail };
+    let (name, params) = match signature.find('(') {
+        Some(open) => {
+            let Some(params) = signature[open + 1..].trim_end().strip_suffix(')') else {
+                return Err(invalid(format!("unbalanced parentheses in `{}`", signature)));
+            };
+            (signature[..open].trim(), params)
+        }
+        None => (signature, ""),
+    };
+    if !is_identifier(name) {
+        return Err(invalid(if name.is_empty() {
+            "missing name; expected `{% macro name(params) %}`".to_string()
+        } else {
+            format!("expected a name made of letters, digits, and `_`, got `{}`", name)
+        }));
+    }
+    let mut parsed = Vec::new();
+    for param in split_filter_args(params) {
+        let (param, default) = match param.split_once('=') {
+            Some((param, default)) => (param.trim(), Some(default.trim().to_string())),
+            None => (param.trim(), None),
+        };
+        if !is_identifier(param) || default.as_deref() == Some("") {
+            return Err(invalid(format!("invalid parameter `{}` in macro `{}`", param, name)));
+        }
+        if parsed.iter().any(|(taken, _)| taken == param) {
+            return Err(invalid(format!("parameter `{}` appears twice in macro `{}`", param, name)));
+        }
+        parsed.push((param.to_string(), default));
+    }
+    Ok((name.to_string(), parsed))
+}
+
+/// Letters, digits, and `_`, not starting with a digit.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
 }
 
 /// Parse an expression string into the base expression and filter chain.
@@ -610,6 +1053,8 @@ mod tests {
             Token::Expression {
                 expr: "name".to_string(),
                 filters: vec![],
+                line: 1,
+                column: 10,
                 trim_left: false,
                 trim_right: false,
             },
@@ -624,6 +1069,8 @@ mod tests {
             Token::Expression {
                 expr: "name".to_string(),
                 filters: vec![Filter { name: "snake_case".to_string(), args: vec![] }],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -640,6 +1087,8 @@ mod tests {
                     Filter { name: "snake_case".to_string(), args: vec![] },
                     Filter { name: "upper".to_string(), args: vec![] },
                 ],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -656,6 +1105,8 @@ mod tests {
                     name: "truncate".to_string(),
                     args: vec!["40".to_string()],
                 }],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -672,6 +1123,8 @@ mod tests {
                     name: "replace".to_string(),
                     args: vec![r#""a""#.to_string(), r#""b""#.to_string()],
                 }],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -689,6 +1142,8 @@ mod tests {
                     name: "f".to_string(),
                     args: vec!["g(y)".to_string()],
                 }],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -706,6 +1161,8 @@ mod tests {
                     name: "join".to_string(),
                     args: vec![r#"", ""#.to_string()],
                 }],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -723,6 +1180,8 @@ mod tests {
                     name: "upper_case".to_string(),
                     args: vec![],
                 }],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -740,6 +1199,8 @@ mod tests {
                     Filter { name: "truncate".to_string(), args: vec!["10".to_string()] },
                     Filter { name: "upper_case".to_string(), args: vec![] },
                 ],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -803,6 +1264,8 @@ mod tests {
             Token::Expression {
                 expr: "entity.name.pascal".to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -815,6 +1278,8 @@ mod tests {
         assert_eq!(tokens, vec![
             Token::Logic {
                 code: "for i, x in ipairs(t) do".to_string(),
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -838,6 +1303,8 @@ mod tests {
             Token::Expression {
                 expr: "name".to_string(),
                 filters: vec![],
+                line: 1,
+                column: 5,
                 trim_left: true,
                 trim_right: true,
             },
@@ -850,12 +1317,28 @@ mod tests {
         assert_eq!(tokens, vec![
             Token::Logic {
                 code: "end".to_string(),
+                line: 1,
+                column: 5,
                 trim_left: true,
                 trim_right: true,
             },
         ]);
     }
 
+    #[test]
+    fn test_positions_point_at_the_tag_contents() {
+        let template = "a\n  x {{- user.name }}\n{%\n  if y then %}{% end %}";
+        let positions = Tokenizer::tokenize(template)
+            .unwrap()
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Expression { line, column, .. } | Token::Logic { line, column, .. } => Some((line, column)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![(2, 9), (4, 3), (4, 18)]);
+    }
+
     #[test]
     fn test_mixed_template() {
         let template = "Hello {{ name }}!\n{% if show then %}\nWelcome\n{% end %}";
@@ -984,6 +1467,8 @@ mod tests {
             Token::Expression {
                 expr: r#""}}""#.to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -997,6 +1482,8 @@ mod tests {
             Token::Expression {
                 expr: "'}}'" .to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -1011,6 +1498,8 @@ mod tests {
             Token::Expression {
                 expr: r#""hello \"}}\" world""#.to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -1024,6 +1513,8 @@ mod tests {
             Token::Expression {
                 expr: "[[ }} ]]".to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -1037,6 +1528,8 @@ mod tests {
             Token::Expression {
                 expr: "[=[ }} ]=]".to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -1051,6 +1544,8 @@ mod tests {
             Token::Expression {
                 expr: "1 + 1 -- }}".to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -1064,6 +1559,8 @@ mod tests {
             Token::Expression {
                 expr: "1 --[[ }} ]]".to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -1077,6 +1574,8 @@ mod tests {
         assert_eq!(tokens, vec![
             Token::Logic {
                 code: r#"local x = "%}""#.to_string(),
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
@@ -1091,9 +1590,207 @@ mod tests {
             Token::Expression {
                 expr: r#""{{ var }}""#.to_string(),
                 filters: vec![],
+                line: 1,
+                column: 4,
                 trim_left: false,
                 trim_right: false,
             },
         ]);
     }
+
+    #[test]
+    fn test_inheritance_tags() {
+        let tokens = Tokenizer::tokenize(r#"{% extends "base.atl" %}{%- block body %}x{% endblock body -%}"#).unwrap();
+        assert_eq!(tokens, vec![
+            Token::Extends {
+                path: "base.atl".to_string(),
+                line: 1,
+                trim_left: false,
+                trim_right: false,
+            },
+            Token::Block {
+                name: "body".to_string(),
+                line: 1,
+                trim_left: true,
+                trim_right: false,
+            },
+            Token::Text("x".to_string()),
+            Token::EndBlock {
+                name: Some("body".to_string()),
+                line: 1,
+                trim_left: false,
+                trim_right: true,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_block_named_lua_stays_lua() {
+        let tokens = Tokenizer::tokenize("{% block = 1 %}{% blocks[1] = 2 %}").unwrap();
+        assert!(tokens.iter().all(|token| matches!(token, Token::Logic { .. })));
+    }
+
+    #[test]
+    fn test_block_name_must_be_an_identifier() {
+        assert!(matches!(
+            Tokenizer::tokenize("{% block %}"),
+            Err(TemplateCompileError::InvalidBlock { line: 1, .. })
+        ));
+        assert!(matches!(
+            Tokenizer::tokenize("\n{% block my-block %}"),
+            Err(TemplateCompileError::InvalidBlock { line: 2, .. })
+        ));
+        assert!(matches!(
+            Tokenizer::tokenize("{% extends base %}"),
+            Err(TemplateCompileError::InvalidExtends { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_macro_and_import_tags() {
+        let tokens =
+            Tokenizer::tokenize(r#"{% import "macros.atl" as m %}{% macro field(name, ty, optional=false) %}x{% endmacro %}"#)
+                .unwrap();
+        assert_eq!(tokens, vec![
+            Token::Import {
+                path: "macros.atl".to_string(),
+                alias: "m".to_string(),
+                line: 1,
+                trim_left: false,
+                trim_right: false,
+            },
+            Token::Macro {
+                name: "field".to_string(),
+                params: vec![
+                    ("name".to_string(), None),
+                    ("ty".to_string(), None),
+                    ("optional".to_string(), Some("false".to_string())),
+                ],
+                line: 1,
+                trim_left: false,
+                trim_right: false,
+            },
+            Token::Text("x".to_string()),
+            Token::EndMacro {
+                line: 1,
+                trim_left: false,
+                trim_right: false,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_malformed_macro_and_import_tags() {
+        for template in [
+            "{% macro (a) %}",
+            "{% macro f(a %}",
+            "{% macro f(a, a) %}",
+            "{% macro f(1a) %}",
+            "{% macro f(a=) %}",
+        ] {
+            assert!(
+                matches!(Tokenizer::tokenize(template), Err(TemplateCompileError::InvalidMacro { .. })),
+                "{}",
+                template
+            );
+        }
+        for template in [r#"{% import "m.atl" %}"#, r#"{% import m.atl as m %}"#, r#"{% import "m.atl" as m-1 %}"#] {
+            assert!(
+                matches!(Tokenizer::tokenize(template), Err(TemplateCompileError::InvalidImport { .. })),
+                "{}",
+                template
+            );
+        }
+        // Lua that happens to use the words stays Lua.
+        let tokens = Tokenizer::tokenize("{% macro = 1 %}{% import_all() %}").unwrap();
+        assert!(tokens.iter().all(|token| matches!(token, Token::Logic { .. })));
+    }
+
+    #[test]
+    fn test_autoescape_tags() {
+        let tokens = Tokenizer::tokenize(r#"{% autoescape "HTML" %}{%- autoescape false %}{% endautoescape -%}"#).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Autoescape {
+                    escaper: Some(Escaper::Xml),
+                    line: 1,
+                    trim_left: false,
+                    trim_right: false,
+                },
+                Token::Autoescape {
+                    escaper: None,
+                    line: 1,
+                    trim_left: true,
+                    trim_right: false,
+                },
+                Token::EndAutoescape {
+                    line: 1,
+                    trim_left: false,
+                    trim_right: true,
+                },
+            ]
+        );
+        for template in [r#"{% autoescape "csv" %}"#, "{% autoescape json %}", "{% autoescape %}", r#"{% autoescape "" %}"#] {
+            assert!(
+                matches!(Tokenizer::tokenize(template), Err(TemplateCompileError::InvalidAutoescape { .. })),
+                "{}",
+                template
+            );
+        }
+        let tokens = Tokenizer::tokenize("{% autoescape = true %}").unwrap();
+        assert!(matches!(tokens[0], Token::Logic { .. }));
+    }
+
+    #[test]
+    fn test_include_with_args_and_dynamic_paths() {
+        let template = "{% include \"field.atl\" with { name = f.name, note = \"a with b\" } %}\n\
+                        {%- include \"ci/\" .. provider .. \".atl\" %}{% include (ci.partial) with ci %}";
+        assert_eq!(
+            Tokenizer::tokenize(template).unwrap(),
+            vec![
+                Token::Include {
+                    path: "field.atl".to_string(),
+                    args: Some("{ name = f.name, note = \"a with b\" }".to_string()),
+                    line: 1,
+                    column: 4,
+                    trim_left: false,
+                    trim_right: false,
+                },
+                Token::Text("\n".to_string()),
+                Token::DynamicInclude {
+                    path: "\"ci/\" .. provider .. \".atl\"".to_string(),
+                    args: None,
+                    line: 2,
+                    column: 5,
+                    trim_left: true,
+                    trim_right: false,
+                },
+                Token::DynamicInclude {
+                    path: "(ci.partial)".to_string(),
+                    args: Some("ci".to_string()),
+                    line: 2,
+                    column: 46,
+                    trim_left: false,
+                    trim_right: false,
+                },
+            ]
+        );
+        for template in [
+            "{% include header.atl %}",
+            "{% include partials/header.atl %}",
+            "{% include \"header.atl\" with %}",
+            "{% include with { a = 1 } %}",
+        ] {
+            assert!(
+                matches!(Tokenizer::tokenize(template), Err(TemplateCompileError::InvalidInclude { .. })),
+                "{}",
+                template
+            );
+        }
+        // `with` inside a name or a string is not the keyword.
+        let tokens = Tokenizer::tokenize("{% include withheld %}{% include \"with x\" %}").unwrap();
+        assert!(matches!(&tokens[0], Token::DynamicInclude { path, args: None, .. } if path == "withheld"));
+        assert!(matches!(&tokens[1], Token::Include { path, args: None, .. } if path == "with x"));
+    }
 }
@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use camino::Utf8PathBuf;
+use serde_json::{json, Value};
+
+use archetect_core::archetype::render_context::RenderContext;
+use archetect_core::configuration::Configuration;
+use archetect_core::debugger::Debugger;
+use archetect_core::interface::ProbeDriver;
+use archetect_core::Archetect;
+
+use crate::test_utils::get_archetype_path;
+
+/// One end of an in-memory pipe.
+struct PipeReader {
+    chunks: Receiver<Vec<u8>>,
+    buffer: VecDeque<u8>,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.buffer.is_empty() {
+            match self.chunks.recv_timeout(Duration::from_secs(10)) {
+                Ok(chunk) => self.buffer.extend(chunk),
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(0),
+                Err(mpsc::RecvTimeoutError::Timeout) => panic!("nothing arrived for 10 seconds"),
+            }
+        }
+        let n = buf.len().min(self.buffer.len());
+        for (slot, byte) in buf.iter_mut().zip(self.buffer.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+struct PipeWriter(Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let _ = self.0.send(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn pipe() -> (PipeWriter, PipeReader) {
+    let (tx, rx) = mpsc::channel();
+    (
+        PipeWriter(tx),
+        PipeReader {
+            chunks: rx,
+            buffer: VecDeque::new(),
+        },
+    )
+}
+
+/// The editor's side of a debug session.
+struct Client {
+    requests: PipeWriter,
+    messages: BufReader<PipeReader>,
+    seq: i64,
+    /// Events read while waiting for a response, not yet expected.
+    events: VecDeque<Value>,
+    /// Everything the script printed, whenever it arrived.
+    outputs: Vec<Value>,
+    render: Option<JoinHandle<()>>,
+}
+
+impl Client {
+    /// Start debugging the archetype next to `test_file`, answering its
+    /// prompts with the probe driver.
+    fn start(test_file: &str) -> Client {
+        let (requests, adapter_input) = pipe();
+        let (adapter_output, messages) = pipe();
+        let debugger = Debugger::new(adapter_output);
+        let overrides = BTreeMap::from([("name".to_string(), json!("World"))]);
+        let archetect = Archetect::builder()
+            .with_driver(debugger.driver(ProbeDriver::new(10, overrides)))
+            .with_debugger(debugger.clone())
+            .with_configuration(Configuration::default())
+            .with_temp_layout()
+            .unwrap()
+            .build()
+            .unwrap();
+        let archetype = archetect.new_archetype(get_archetype_path(test_file).as_str()).unwrap();
+        let destination = Utf8PathBuf::from_path_buf(tempfile::tempdir().unwrap().keep()).unwrap();
+        let render = std::thread::spawn(move || {
+            let launch = debugger.listen(adapter_input).expect("the client should launch");
+            let rendered = archetype.render(RenderContext::new(destination, launch.answers));
+            debugger.finish(rendered.map(|_| ()).map_err(|err| err.to_string()));
+        });
+        Client {
+            requests,
+            messages: BufReader::new(messages),
+            seq: 0,
+            events: VecDeque::new(),
+            outputs: Vec::new(),
+            render: Some(render),
+        }
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let body =
+            json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments }).to_string();
+        self.requests
+            .write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes())
+            .unwrap();
+        loop {
+            let message = self.read();
+            if message["type"] == "response" && message["request_seq"] == self.seq {
+                assert_eq!(message["success"], true, "{} failed: {}", command, message);
+                return message["body"].clone();
+            }
+            self.events.push_back(message);
+        }
+    }
+
+    fn expect_event(&mut self, event: &str) -> Value {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => self.read(),
+            };
+            if message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn read(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.messages.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length: ") {
+                length = value.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.messages.read_exact(&mut body).unwrap();
+        let message: Value = serde_json::from_slice(&body).unwrap();
+        if message["event"] == "output" {
+            self.outputs.push(message["body"].clone());
+        }
+        message
+    }
+
+    /// The stopped render's frames, innermost first.
+    fn frames(&mut self) -> Vec<Value> {
+        let trace = self.request("stackTrace", json!({ "threadId": 1 }));
+        trace["stackFrames"].as_array().unwrap().clone()
+    }
+
+    /// A frame's locals, or the entries behind a variables reference, by name.
+    fn variables(&mut self, reference: &Value) -> BTreeMap<String, Value> {
+        let variables = self.request("variables", json!({ "variablesReference": reference }));
+        variables["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variable| (variable["name"].as_str().unwrap().to_string(), variable.clone()))
+            .collect()
+    }
+
+    fn locals(&mut self, frame: &Value) -> BTreeMap<String, Value> {
+        let scopes = self.request("scopes", json!({ "frameId": frame["id"] }));
+        let locals = scopes["scopes"][0]["variablesReference"].clone();
+        self.variables(&locals)
+    }
+
+    fn finish(mut self) {
+        self.request("disconnect", json!({}));
+        self.render.take().unwrap().join().unwrap();
+    }
+}
+
+fn fixture(relative: &str) -> String {
+    let path = get_archetype_path(file!()).join(relative);
+    path.canonicalize_utf8().unwrap().to_string()
+}
+
+/// Where a frame is: its file's name and line.
+fn at(frame: &Value) -> (String, u64) {
+    (
+        frame["source"]["name"].as_str().unwrap_or_default().to_string(),
+        frame["line"].as_u64().unwrap(),
+    )
+}
+
+#[test]
+fn test_breakpoints_steps_and_variables() {
+    let mut client = Client::start(file!());
+    let capabilities = client.request("initialize", json!({ "adapterID": "archetect" }));
+    assert_eq!(capabilities["supportsConfigurationDoneRequest"], true);
+    client.expect_event("initialized");
+
+    let set = client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": fixture("archetype.lua") }, "breakpoints": [{ "line": 6 }] }),
+    );
+    assert_eq!(set["breakpoints"][0]["verified"], true);
+    client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": fixture("contents/greeting.txt") }, "breakpoints": [{ "line": 2 }] }),
+    );
+    client.request("launch", json!({}));
+    client.request("configurationDone", json!({}));
+
+    // The script, stopped after its prompt went to the probe driver.
+    assert_eq!(client.expect_event("stopped")["reason"], "breakpoint");
+    let frames = client.frames();
+    assert_eq!(at(&frames[0]), ("archetype.lua".to_string(), 6));
+    let locals = client.locals(&frames[0]);
+    assert_eq!(locals["greet"]["type"], "table");
+    assert_eq!(locals["context"]["value"], "Context {1 keys}");
+    let context = client.variables(&locals["context"]["variablesReference"]);
+    assert_eq!(context["name"]["value"], "\"World\"");
+
+    // Into the library module, where the evaluation sees its locals.
+    client.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("stopped")["reason"], "step");
+    let frames = client.frames();
+    assert_eq!(at(&frames[0]), ("greet.lua".to_string(), 4));
+    assert_eq!(frames[0]["name"], "hello");
+    assert_eq!(at(&frames[1]), ("archetype.lua".to_string(), 6));
+    let evaluated = client.request(
+        "evaluate",
+        json!({ "expression": "name .. '?'", "frameId": frames[0]["id"], "context": "watch" }),
+    );
+    assert_eq!(evaluated["result"], "\"World?\"");
+
+    client.request("stepOut", json!({ "threadId": 1 }));
+    client.expect_event("stopped");
+    let frames = client.frames();
+    assert_eq!(frames[0]["source"]["name"], "archetype.lua");
+    assert_eq!(frames.len(), 1);
+
+    // On to the template, shown at its own line, above the script that
+    // rendered it.
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("stopped")["reason"], "breakpoint");
+    let frames = client.frames();
+    assert_eq!(at(&frames[0]), ("greeting.txt".to_string(), 2));
+    let script = frames.last().unwrap();
+    assert_eq!(at(script), ("archetype.lua".to_string(), 8));
+
+    client.request("continue", json!({ "threadId": 1 }));
+    client.expect_event("terminated");
+    assert_eq!(
+        client.outputs,
+        vec![json!({ "category": "stdout", "output": "Hello, World!\n" })]
+    );
+    assert_eq!(client.expect_event("exited")["exitCode"], 0);
+    client.finish();
+}
+
+#[test]
+fn test_stop_on_entry_and_next() {
+    let mut client = Client::start(file!());
+    client.request("initialize", json!({ "adapterID": "archetect" }));
+    client.request("launch", json!({ "stopOnEntry": true }));
+    client.request("configurationDone", json!({}));
+
+    assert_eq!(client.expect_event("stopped")["reason"], "entry");
+    assert_eq!(at(&client.frames()[0]), ("archetype.lua".to_string(), 2));
+    // Over the `require`, without stopping in the module it loads.
+    client.request("next", json!({ "threadId": 1 }));
+    client.expect_event("stopped");
+    assert_eq!(at(&client.frames()[0]), ("archetype.lua".to_string(), 4));
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("exited")["exitCode"], 0);
+    client.finish();
+}
@@ -0,0 +1,8 @@
+-- The debugger tests stop on these lines by number.
+local greet = require("greet")
+
+local context = Context.new()
+context:prompt_text("Name:", "name")
+local greeting = greet.hello(context:get("name"))
+output.print(greeting)
+directory.render("contents", context)
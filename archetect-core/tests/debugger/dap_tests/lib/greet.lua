@@ -0,0 +1,8 @@
+local greet = {}
+
+function greet.hello(name)
+    local message = "Hello, " .. name .. "!"
+    return message
+end
+
+return greet
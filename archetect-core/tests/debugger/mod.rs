@@ -0,0 +1 @@
+mod dap_tests;
@@ -1,8 +1,9 @@
 use function_name::named;
 
+use archetect_api::ScriptMessage;
 use archetect_core::errors::ArchetectError;
 
-use crate::test_utils::TestHarnessBuilder;
+use crate::test_utils::{TestHarness, TestHarnessBuilder};
 
 #[test]
 #[named]
@@ -32,3 +33,107 @@ fn test_nil_index() -> Result<(), ArchetectError> {
     assert!(!harness.render_succeeded());
     Ok(())
 }
+
+/// The error a dry run fails with, past whatever it displayed first.
+fn dry_run_error(harness: &TestHarness) -> String {
+    loop {
+        match harness.receive() {
+            ScriptMessage::Display(_) => continue,
+            ScriptMessage::LogError(message) => return message,
+            other => panic!("Expected LogError, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+#[named]
+fn test_template_error_in_partial() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_switch(function_name!())
+        .dry_run()
+        .build()?;
+
+    // The error is raised by the included partial's line, not the page's,
+    // and not a line of the Lua the page compiled to.
+    let error = dry_run_error(&harness);
+    assert!(error.contains("attempt to index a nil value (field 'owner')"), "{}", error);
+    assert!(error.contains("--> footer.atl:2:11"), "{}", error);
+    assert!(error.contains("2 | Owner: {{ service.owner.email }}\n  |           ^"), "{}", error);
+    assert!(!error.contains("<atl:"), "{}", error);
+
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+#[named]
+fn test_template_error_after_front_matter() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_switch(function_name!())
+        .dry_run()
+        .build()?;
+
+    // Lines count from the top of the file, front matter included.
+    let error = dry_run_error(&harness);
+    assert!(error.contains("attempt to perform arithmetic on a nil value (field 'port')"), "{}", error);
+    assert!(error.contains("front_matter/README.md:5:10"), "{}", error);
+    assert!(error.contains("5 | Port: {{ service.port + 1 }}"), "{}", error);
+
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+#[named]
+fn test_template_error_in_dynamic_include() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_switch(function_name!())
+        .dry_run()
+        .build()?;
+
+    // The partial was compiled on its own at render time; its error still
+    // points into it, followed by the include that ran it.
+    let error = dry_run_error(&harness);
+    assert!(error.contains("attempt to index a nil value (field 'owner')"), "{}", error);
+    assert!(error.contains("2 | Owner: {{ service.owner.email }}\n  |           ^"), "{}", error);
+    assert!(error.contains("dynamic/README.md:3:4"), "{}", error);
+    assert!(!error.contains("<atl:"), "{}", error);
+
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+#[named]
+fn test_dynamic_include_not_found() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_switch(function_name!())
+        .dry_run()
+        .build()?;
+
+    let error = dry_run_error(&harness);
+    assert!(error.contains("include `header.atl` not found"), "{}", error);
+    assert!(error.contains("dynamic/README.md:3:4"), "{}", error);
+
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+#[named]
+fn test_dynamic_include_too_deep() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_switch(function_name!())
+        .dry_run()
+        .build()?;
+
+    // A partial that always includes itself stops at the depth limit.
+    let error = dry_run_error(&harness);
+    assert!(error.contains("include `loop.atl` nested more than 64 deep"), "{}", error);
+    // Each level doesn't repeat the partial's location.
+    assert_eq!(error.matches("--> loop.atl:1:5").count(), 1, "{}", error);
+    assert!(error.contains("deep/README.md:1:4"), "{}", error);
+
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
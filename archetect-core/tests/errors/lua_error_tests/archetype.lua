@@ -6,3 +6,33 @@ if archetype.switches.is_enabled("test_nil_index") then
     local x = nil
     local y = x.foo
 end
+
+if archetype.switches.is_enabled("test_template_error_in_partial") then
+    local ctx = Context.new()
+    ctx:set("service", { name = "orders" })
+    directory.render("contents/partial", ctx)
+end
+
+if archetype.switches.is_enabled("test_template_error_after_front_matter") then
+    local ctx = Context.new()
+    ctx:set("service", { name = "orders" })
+    directory.render("contents/front_matter", ctx)
+end
+
+if archetype.switches.is_enabled("test_template_error_in_dynamic_include") then
+    local ctx = Context.new()
+    ctx:set("service", { name = "orders" })
+    ctx:set("footer", "footer")
+    directory.render("contents/dynamic", ctx)
+end
+
+if archetype.switches.is_enabled("test_dynamic_include_not_found") then
+    local ctx = Context.new()
+    ctx:set("service", { name = "orders" })
+    ctx:set("footer", "header")
+    directory.render("contents/dynamic", ctx)
+end
+
+if archetype.switches.is_enabled("test_dynamic_include_too_deep") then
+    directory.render("contents/deep", Context.new())
+end
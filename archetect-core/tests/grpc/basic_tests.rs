@@ -5,8 +5,10 @@ use tokio_stream::StreamExt;
 use archetect_core::catalog::catalog_indexer::CatalogIndexer;
 use archetect_core::client::ClientOptions;
 use archetect_core::configuration::Configuration;
+use archetect_core::limits::ResourceLimits;
 use archetect_core::manifest::{CatalogEntry, CatalogEntryServer};
 use archetect_core::proto::grpc::script_message::Message as SMessage;
+use archetect_core::proto::grpc::FileOutcome;
 use archetect_core::Archetect;
 
 use super::harness::{build_catalog, build_nested_entry, msg, TestServer};
@@ -93,6 +95,45 @@ async fn grpc_basic_roundtrip() {
     assert!(saw_write_file, "expected WriteFile for greeting.txt");
 }
 
+/// The outcome a client reports for each write comes back in the completion
+/// manifest, alongside a hash of what the server rendered, so a remote caller
+/// learns what happened to every file without looking at the destination.
+#[tokio::test]
+async fn grpc_complete_success_reports_file_outcomes() {
+    use sha2::{Digest, Sha256};
+
+    let mut server = TestServer::start("grpc_basic").await.expect("server up");
+
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let destination = tmp.path().to_string_lossy().to_string();
+    let (tx, mut stream) = server.open_stream().await.expect("open stream");
+    tx.send(msg::initialize(destination, String::new()))
+        .await
+        .expect("initialize send");
+
+    let mut contents = Vec::new();
+    let artifacts = loop {
+        match next(&mut stream).await {
+            SMessage::PromptForText(_) => tx.send(msg::string("world".to_string())).await.expect("string resp"),
+            SMessage::WriteDirectory(_) => tx.send(msg::ack()).await.expect("ack dir"),
+            SMessage::WriteFile(wf) => {
+                contents = wf.contents;
+                tx.send(msg::written(FileOutcome::Created)).await.expect("written");
+            }
+            SMessage::CompleteSuccess(success) => break success.artifacts,
+            SMessage::CompleteError(err) => panic!("render failed: {:?}", err),
+            _ => {}
+        }
+    };
+
+    let file = artifacts
+        .iter()
+        .find(|artifact| artifact.path == "greeting.txt")
+        .unwrap_or_else(|| panic!("expected an artifact for greeting.txt, got {:?}", artifacts));
+    assert_eq!(file.outcome(), FileOutcome::Created);
+    assert_eq!(file.sha256, format!("{:x}", Sha256::digest(&contents)));
+}
+
 /// Script that unconditionally raises a Lua error. Confirms the error path
 /// surfaces through the gRPC stream rather than hanging or panicking the
 /// server. The current server implementation (core.rs) emits a LogError
@@ -134,6 +175,66 @@ async fn grpc_script_error_propagates() {
     );
 }
 
+#[tokio::test]
+async fn grpc_resource_limit_ends_stream_with_resource_exhausted() {
+    let limits = ResourceLimits::default().with_instructions(1_000_000);
+    let mut server = TestServer::start_with_limits(build_catalog(&[("default", "grpc_runaway")]), limits)
+        .await
+        .expect("server up");
+
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let destination = tmp.path().to_string_lossy().to_string();
+    let (tx, mut stream) = server.open_stream().await.expect("open stream");
+    tx.send(msg::initialize(destination, String::new()))
+        .await
+        .expect("initialize send");
+
+    // Anything the script managed to send comes first; then the status.
+    loop {
+        let item = tokio::time::timeout(Duration::from_secs(10), stream.next())
+            .await
+            .expect("timed out waiting for the limit to stop the script")
+            .expect("stream ended without a status");
+        match item {
+            Ok(message) => match message.message {
+                Some(SMessage::CompleteSuccess(_)) => panic!("a runaway script completed"),
+                _ => continue,
+            },
+            Err(status) => {
+                assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+                assert!(status.message().contains("1000000 Lua instructions"), "{}", status.message());
+                break;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn grpc_deadline_ends_a_render_left_waiting_on_a_prompt() {
+    // The script never runs another instruction once it prompts, so only the
+    // server can notice that its time is up.
+    let limits = ResourceLimits::default().with_timeout(1);
+    let mut server = TestServer::start_with_limits(build_catalog(&[("default", "grpc_basic")]), limits)
+        .await
+        .expect("server up");
+
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let destination = tmp.path().to_string_lossy().to_string();
+    let (tx, mut stream) = server.open_stream().await.expect("open stream");
+    tx.send(msg::initialize(destination, String::new()))
+        .await
+        .expect("initialize send");
+
+    assert!(matches!(next(&mut stream).await, SMessage::PromptForText(_)));
+    let status = tokio::time::timeout(Duration::from_secs(10), stream.next())
+        .await
+        .expect("timed out waiting for the deadline to stop the render")
+        .expect("stream ended without a status")
+        .expect_err("the render should be stopped, not answered");
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    assert!(status.message().contains("1-second deadline"), "{}", status.message());
+}
+
 /// Phase 3 of federated-catalog: a local CatalogIndexer that encounters a
 /// `server:` entry should fetch the remote tree via BrowseCatalog and
 /// splice the children in with `path` prefixed and `remote` populated.
@@ -0,0 +1,3 @@
+-- Never finishes, so the gRPC flow can verify that a server's resource
+-- limits stop it and end the stream with RESOURCE_EXHAUSTED.
+while true do end
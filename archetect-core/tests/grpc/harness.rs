@@ -8,6 +8,7 @@ use tokio_stream::wrappers::ReceiverStream;
 use tonic::transport::Channel;
 
 use archetect_core::configuration::Configuration;
+use archetect_core::limits::ResourceLimits;
 use archetect_core::manifest::CatalogEntry;
 use archetect_core::proto::grpc;
 use archetect_core::proto::grpc::archetect_service_client::ArchetectServiceClient;
@@ -96,6 +97,14 @@ impl TestServer {
     pub async fn start_with_catalog(
         catalog: LinkedHashMap<String, CatalogEntry>,
     ) -> anyhow::Result<Self> {
+        Self::start_with_limits(catalog, ResourceLimits::default()).await
+    }
+
+    /// Like `start_with_catalog`, with every render held to `limits`.
+    pub async fn start_with_limits(
+        catalog: LinkedHashMap<String, CatalogEntry>,
+        limits: ResourceLimits,
+    ) -> anyhow::Result<Self> {
 
         // A minimal Archetect for the server prototype. Temp layout keeps
         // the cache/state out of the user's XDG dirs.
@@ -103,6 +112,7 @@ impl TestServer {
         let prototype = Archetect::builder()
             .with_configuration(configuration)
             .with_temp_layout()?
+            .with_limits(limits)
             .build()?;
 
         let core = ArchetectServiceCore::builder(prototype).build().await?;
@@ -208,4 +218,10 @@ pub mod msg {
             message: Some(grpc::client_message::Message::Ack(())),
         }
     }
+
+    pub fn written(outcome: grpc::FileOutcome) -> grpc::ClientMessage {
+        grpc::ClientMessage {
+            message: Some(grpc::client_message::Message::Written(outcome as i32)),
+        }
+    }
 }
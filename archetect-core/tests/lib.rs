@@ -1,6 +1,7 @@
 mod catalog;
 mod cases;
 mod context;
+mod debugger;
 mod errors;
 mod git;
 mod github;
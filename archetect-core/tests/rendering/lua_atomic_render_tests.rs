@@ -0,0 +1,65 @@
+use archetect_api::{ClientMessage, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+#[test]
+fn test_atomic_manifest_begins_transaction_before_writes() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-atomic-render");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    assert_eq!(harness.expect_begin_transaction(), dest.as_str());
+
+    let _ = harness.expect_text_prompt();
+    harness.respond_text("Demo");
+
+    assert_eq!(harness.expect_write_directory(), dest.as_str());
+    let readme = harness.expect_write_file();
+    assert_eq!(readme.destination, dest.join("README.md").as_str());
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_atomic_configuration_begins_a_single_transaction() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-atomic-render-configured");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .atomic()
+        .build()?;
+
+    // Both the configuration and the manifest ask for a transaction; the
+    // client only hears about it once.
+    assert_eq!(harness.expect_begin_transaction(), dest.as_str());
+
+    let _ = harness.expect_text_prompt();
+    harness.respond_text("Demo");
+
+    let _ = harness.expect_write_directory();
+    let _ = harness.expect_write_file();
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_atomic_render_fails_when_writes_cannot_be_staged() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-atomic-render-unstaged");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    match harness.receive() {
+        ScriptMessage::BeginTransaction(destination) => assert_eq!(destination, dest.as_str()),
+        other => panic!("Expected BeginTransaction, got {:?}", other),
+    }
+    harness.respond(ClientMessage::Error("Unable to stage writes".to_string()));
+
+    // The render stops there, rather than writing straight through.
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,5 @@
+local ctx = Context.new()
+
+ctx:prompt_text("Project Name:", "project_name")
+
+directory.render("default", ctx)
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+
+use archetect_api::{ClientMessage, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+#[test]
+fn test_autoescape_by_extension_and_region() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-autoescape");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    let mut files = BTreeMap::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::WriteDirectory(_) => harness.respond(ClientMessage::Ack),
+            ScriptMessage::WriteFile(info) => {
+                harness.respond(ClientMessage::Ack);
+                let path = Utf8PathBuf::from(info.destination);
+                let path = path.strip_prefix(&dest).expect("Beneath destination").to_string();
+                files.insert(path, String::from_utf8(info.contents).expect("Text contents"));
+            }
+            other => panic!("Expected a write, got {:?}", other),
+        }
+    }
+
+    // `templating.autoescape` picks the escaper by extension; `safe` opts a
+    // value out, and a region escapes part of a file that has none.
+    assert_eq!(
+        files["package.json"],
+        "{\n  \"description\": \"Say \\\"hi\\\" to $USER\",\n  \"example\": { \"ok\": true }\n}\n"
+    );
+    assert_eq!(files["run.sh"], "#!/bin/sh\necho \"Say \\\"hi\\\" to \\$USER\"\n");
+    assert_eq!(
+        files["README.md"],
+        "Say \"hi\" to $USER\n<p>Say &quot;hi&quot; to $USER</p>\n"
+    );
+    serde_json::from_str::<serde_json::Value>(&files["package.json"]).expect("Valid JSON");
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
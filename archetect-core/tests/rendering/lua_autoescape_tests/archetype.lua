@@ -0,0 +1,5 @@
+local ctx = Context.new()
+ctx:set("description", 'Say "hi" to $USER')
+ctx:set("snippet", '{ "ok": true }')
+
+directory.render("default", ctx)
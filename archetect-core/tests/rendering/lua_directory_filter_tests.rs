@@ -0,0 +1,62 @@
+use std::collections::BTreeSet;
+
+use archetect_api::{ClientMessage, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::{TestHarness, TestHarnessBuilder};
+
+/// Acknowledge writes until the render finishes, returning the files written
+/// relative to `dest`. Directory listing order is platform-dependent, so the
+/// result is a set.
+fn written_files(harness: &TestHarness, dest: &Utf8PathBuf) -> BTreeSet<String> {
+    let mut files = BTreeSet::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::WriteDirectory(_) => harness.respond(ClientMessage::Ack),
+            ScriptMessage::WriteFile(info) => {
+                harness.respond(ClientMessage::Ack);
+                let path = Utf8PathBuf::from(info.destination);
+                files.insert(path.strip_prefix(dest).expect("Beneath destination").to_string());
+            }
+            other => panic!("Expected a write, got {:?}", other),
+        }
+    }
+    files
+}
+
+#[test]
+fn test_exclude_globs_and_ignore_files() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-directory-filter");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    let files = written_files(&harness, &dest);
+
+    // `*.bak` by source name, `demo-notes.txt` by rendered name, `scratch`
+    // by the root `.archetectignore`, `*.orig` by the one in `src`. The
+    // ignore files themselves are never rendered.
+    let expected: BTreeSet<String> = ["README.md", "src/main.rs"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(files, expected);
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_include_globs_narrow_files() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-directory-filter-include");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("docs_only")
+        .build()?;
+
+    let files = written_files(&harness, &dest);
+
+    let expected: BTreeSet<String> = ["README.md"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(files, expected);
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,8 @@
+local ctx = Context.new()
+ctx:set("name", "demo")
+
+if archetype.switches.is_enabled("docs_only") then
+  directory.render("default", ctx, { include = "*.md" })
+else
+  directory.render("default", ctx, { exclude = { "*.bak", "demo-notes.txt" } })
+end
@@ -0,0 +1,79 @@
+use std::fs;
+
+use archetect_api::{ExistingFilePolicy, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+fn destination_with_configs(name: &str) -> Utf8PathBuf {
+    let dest = Utf8PathBuf::from(format!("/tmp/{}", name));
+    let _ = fs::remove_dir_all(&dest);
+    fs::create_dir_all(&dest).unwrap();
+    fs::write(
+        dest.join("Cargo.toml"),
+        "[package]\nname = \"demo\" # crate name\n\n[dependencies]\nserde = \"1\"\n\n[features]\ndefault = []\n",
+    )
+    .unwrap();
+    fs::write(dest.join("package.json"), "{\n    \"name\": \"demo\",\n    \"private\": true\n}\n").unwrap();
+    fs::write(dest.join("config.yaml"), "# server settings\nserver:\n  host: localhost\n  port: 80 # http\n").unwrap();
+    dest
+}
+
+#[test]
+fn test_edit_structured_preserves_layout() -> Result<(), ArchetectError> {
+    let dest = destination_with_configs("archetect-test-lua-file-edit-structured");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    assert_eq!(harness.expect_read_file(), dest.join("Cargo.toml").as_str());
+    let written = harness.expect_write_file();
+    assert_eq!(written.destination, dest.join("Cargo.toml").as_str());
+    assert!(matches!(written.existing_file_policy, ExistingFilePolicy::Overwrite));
+    assert_eq!(
+        String::from_utf8(written.contents).unwrap(),
+        "[package]\nname = \"demo\" # crate name\n\n[dependencies]\nserde = \"1\"\ntokio = { version = \"1\" }\n\n[features]\ndefault = [\"tokio\"]\n"
+    );
+    assert_eq!(harness.expect_log_info(), "true");
+
+    assert_eq!(harness.expect_read_file(), dest.join("package.json").as_str());
+    assert_eq!(harness.expect_log_info(), "demo");
+    let written = harness.expect_write_file();
+    assert_eq!(
+        String::from_utf8(written.contents).unwrap(),
+        "{\n    \"name\": \"demo\",\n    \"private\": true,\n    \"scripts\": {\n        \"build\": \"tsc\"\n    }\n}\n"
+    );
+    assert_eq!(harness.expect_log_info(), "true");
+
+    assert_eq!(harness.expect_read_file(), dest.join("config.yaml").as_str());
+    let written = harness.expect_write_file();
+    assert_eq!(
+        String::from_utf8(written.contents).unwrap(),
+        "# server settings\nserver:\n  host: localhost\n  port: 8080 # http\n"
+    );
+    assert_eq!(harness.expect_log_info(), "true");
+
+    let _ = harness.expect_read_file();
+    assert_eq!(harness.expect_log_info(), "false");
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_edit_structured_needs_a_known_format() -> Result<(), ArchetectError> {
+    let dest = destination_with_configs("archetect-test-lua-file-edit-structured-format");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest)
+        .with_switch("unknown_format")
+        .build()?;
+
+    match harness.receive() {
+        ScriptMessage::LogError(message) => {
+            assert!(message.contains("can't tell the format"), "{}", message);
+        }
+        other => panic!("Expected LogError, got {:?}", other),
+    }
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,23 @@
+if archetype.switches.is_enabled("unknown_format") then
+  file.edit_structured("Cargo.lock.txt", function(doc) end)
+  return
+end
+
+log.info(tostring(file.edit_structured("Cargo.toml", function(doc)
+  doc:set("dependencies.tokio", { version = "1" })
+  doc:add({ "features", "default" }, "tokio")
+end)))
+
+log.info(tostring(file.edit_structured("package.json", function(doc)
+  doc:merge({ scripts = { build = "tsc" } })
+  log.info(doc:get("name"))
+end)))
+
+log.info(tostring(file.edit_structured("config.yaml", function(doc)
+  doc:set("server.port", 8080)
+end)))
+
+-- Already there: nothing to write.
+log.info(tostring(file.edit_structured("Cargo.toml", function(doc)
+  doc:set("dependencies.serde", "1")
+end)))
@@ -0,0 +1,105 @@
+use std::fs;
+
+use archetect_api::{ClientMessage, ExistingFilePolicy, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+fn destination_with_manifest(name: &str) -> Utf8PathBuf {
+    let dest = Utf8PathBuf::from(format!("/tmp/{}", name));
+    let _ = fs::remove_dir_all(&dest);
+    fs::create_dir_all(&dest).unwrap();
+    fs::write(dest.join("Cargo.toml"), "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1\"\n").unwrap();
+    dest
+}
+
+#[test]
+fn test_inject_after_anchor_once() -> Result<(), ArchetectError> {
+    let dest = destination_with_manifest("archetect-test-lua-file-inject");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    assert_eq!(harness.expect_read_file(), dest.join("Cargo.toml").as_str());
+    let written = harness.expect_write_file();
+    assert_eq!(written.destination, dest.join("Cargo.toml").as_str());
+    assert!(matches!(written.existing_file_policy, ExistingFilePolicy::Overwrite));
+    assert_eq!(
+        String::from_utf8(written.contents).unwrap(),
+        "[package]\nname = \"demo\"\n\n[dependencies]\ntokio = \"1\"\nserde = \"1\"\n"
+    );
+    assert_eq!(harness.expect_log_info(), "true");
+
+    // `serde` is already there, so the second injection writes nothing.
+    let _ = harness.expect_read_file();
+    assert_eq!(harness.expect_log_info(), "false");
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_inject_missing_anchor_fails() -> Result<(), ArchetectError> {
+    let dest = destination_with_manifest("archetect-test-lua-file-inject-missing");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest)
+        .with_switch("missing_anchor")
+        .build()?;
+
+    let _ = harness.expect_read_file();
+    match harness.receive() {
+        ScriptMessage::LogError(message) => {
+            assert!(message.contains("anchor not found"), "{}", message);
+            assert!(message.contains("[dev-dependencies]"), "{}", message);
+        }
+        other => panic!("Expected LogError, got {:?}", other),
+    }
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_inject_reads_the_file_the_client_holds() -> Result<(), ArchetectError> {
+    // Nothing on this machine: the file is wherever the client is, or only
+    // staged so far.
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-inject-remote");
+    let _ = fs::remove_dir_all(&dest);
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    match harness.receive() {
+        ScriptMessage::ReadFile(path) => assert_eq!(path, dest.join("Cargo.toml").as_str()),
+        other => panic!("Expected ReadFile, got {:?}", other),
+    }
+    harness.respond(ClientMessage::String("[dependencies]\n".to_string()));
+    let written = harness.expect_write_file();
+    assert_eq!(String::from_utf8(written.contents).unwrap(), "[dependencies]\ntokio = \"1\"\n");
+    Ok(())
+}
+
+#[test]
+fn test_dry_run_injects_into_what_it_rendered() -> Result<(), ArchetectError> {
+    let dest = destination_with_manifest("archetect-test-lua-file-inject-dry-run");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("rendered")
+        .dry_run()
+        .build()?;
+
+    let mut logged = Vec::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::Display(_) => {}
+            ScriptMessage::LogInfo(message) => logged.push(message),
+            other => panic!("Expected only dry-run reports and logs, got {:?}", other),
+        }
+    }
+    // The second injection finds the first in what the dry run would have written.
+    assert_eq!(logged, ["true", "false"]);
+    assert!(!dest.join("src/lib.rs").exists());
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,14 @@
+if archetype.switches.is_enabled("rendered") then
+  file.write("src/lib.rs", "pub mod api;\n")
+  log.info(tostring(file.inject("src/lib.rs", "pub mod web;", { after = "pub mod api;" })))
+  log.info(tostring(file.inject("src/lib.rs", "pub mod web;", { after = "pub mod api;" })))
+  return
+end
+
+if archetype.switches.is_enabled("missing_anchor") then
+  file.inject("Cargo.toml", 'tokio = "1"', { after = "[dev-dependencies]" })
+  return
+end
+
+log.info(tostring(file.inject("Cargo.toml", 'tokio = "1"', { after = "[dependencies]" })))
+log.info(tostring(file.inject("Cargo.toml", 'serde = "1"', { before = { regex = "^\\[dependencies\\]" } })))
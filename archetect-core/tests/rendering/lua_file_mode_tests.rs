@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use archetect_api::{ClientMessage, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::{TestHarness, TestHarnessBuilder};
+
+/// What the render asked for at one path: a file and its mode, or a link and
+/// its target.
+#[derive(Debug, PartialEq)]
+enum Written {
+    File(Option<u32>),
+    Link(String),
+}
+
+/// Acknowledge writes until the render finishes, returning what was written
+/// relative to `dest`.
+fn written(harness: &TestHarness, dest: &Utf8PathBuf) -> BTreeMap<String, Written> {
+    let relative = |path: String| Utf8PathBuf::from(path).strip_prefix(dest).expect("Beneath destination").to_string();
+    let mut written = BTreeMap::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::WriteDirectory(_) => harness.respond(ClientMessage::Ack),
+            ScriptMessage::WriteFile(info) => {
+                harness.respond(ClientMessage::Ack);
+                written.insert(relative(info.destination), Written::File(info.mode));
+            }
+            ScriptMessage::WriteSymlink(info) => {
+                harness.respond(ClientMessage::Ack);
+                written.insert(relative(info.path), Written::Link(info.target));
+            }
+            other => panic!("Expected a write, got {:?}", other),
+        }
+    }
+    written
+}
+
+#[test]
+fn test_directory_render_keeps_exec_bits_and_symlinks() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-mode");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    let written = written(&harness, &dest);
+
+    // The link's target is rendered like any other name.
+    let expected = BTreeMap::from([
+        ("README.md".to_string(), Written::File(None)),
+        ("bin/demo.sh".to_string(), Written::File(Some(0o755))),
+        ("bin/run".to_string(), Written::Link("demo.sh".to_string())),
+    ]);
+    assert_eq!(written, expected);
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_file_render_mode_option() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-mode-option");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("file_mode")
+        .build()?;
+
+    // `mode = 600` reads as octal, the same as `mode = "0600"` would.
+    let expected = BTreeMap::from([
+        ("config.txt".to_string(), Written::File(Some(0o600))),
+        ("run.sh".to_string(), Written::File(Some(0o750))),
+    ]);
+    assert_eq!(written(&harness, &dest), expected);
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_symlink_escaping_destination_fails() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-mode-escape");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("escape")
+        .build()?;
+
+    harness.expect_write_directory();
+    match harness.receive() {
+        ScriptMessage::LogError(message) => assert!(message.contains("points outside"), "{}", message),
+        other => panic!("Expected LogError, got {:?}", other),
+    }
+
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,11 @@
+local ctx = Context.new()
+ctx:set("name", "demo")
+
+if archetype.switches.is_enabled("escape") then
+  directory.render("escape", ctx)
+elseif archetype.switches.is_enabled("file_mode") then
+  file.render("templates/config.txt", ctx, { destination = "config.txt", mode = 600 })
+  file.render("templates/config.txt", ctx, { destination = "run.sh", mode = "0750" })
+else
+  directory.render("default", ctx)
+end
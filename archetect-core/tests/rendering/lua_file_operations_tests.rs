@@ -0,0 +1,157 @@
+use std::fs;
+
+use archetect_api::{ClientMessage, ExistingFilePolicy, FileOperation, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+fn expect_file_operation(message: ScriptMessage) -> FileOperation {
+    match message {
+        ScriptMessage::FileOperation(operation) => operation,
+        other => panic!("Expected FileOperation, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_file_operations_cross_the_io_channel() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-operations");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    assert_eq!(harness.expect_write_directory(), dest.join("src").as_str());
+    let written = harness.expect_write_file();
+    assert_eq!(written.destination, dest.join("src/order.rs").as_str());
+    assert_eq!(written.contents, b"pub struct Order;\n");
+    assert!(matches!(written.existing_file_policy, ExistingFilePolicy::Overwrite));
+    assert_eq!(written.mode, Some(0o644));
+
+    match expect_file_operation(harness.receive()) {
+        FileOperation::Append { path, contents } => {
+            assert_eq!(path, dest.join("src/lib.rs").as_str());
+            assert_eq!(contents, b"pub mod order;\n");
+        }
+        other => panic!("Expected Append, got {:?}", other),
+    }
+    harness.respond(ClientMessage::Ack);
+
+    match expect_file_operation(harness.receive()) {
+        FileOperation::Copy {
+            source,
+            destination,
+            existing_file_policy,
+        } => {
+            assert_eq!(source, dest.join("src/order.rs").as_str());
+            assert_eq!(destination, dest.join("src/order_copy.rs").as_str());
+            assert!(matches!(existing_file_policy, ExistingFilePolicy::Preserve));
+        }
+        other => panic!("Expected Copy, got {:?}", other),
+    }
+    harness.respond(ClientMessage::Ack);
+
+    match expect_file_operation(harness.receive()) {
+        FileOperation::Move {
+            destination,
+            existing_file_policy,
+            ..
+        } => {
+            assert_eq!(destination, dest.join("src/legacy/order.rs").as_str());
+            assert!(matches!(existing_file_policy, ExistingFilePolicy::Error));
+        }
+        other => panic!("Expected Move, got {:?}", other),
+    }
+    harness.respond(ClientMessage::Ack);
+
+    match expect_file_operation(harness.receive()) {
+        FileOperation::Delete { path } => assert_eq!(path, dest.join("src/legacy").as_str()),
+        other => panic!("Expected Delete, got {:?}", other),
+    }
+    harness.respond(ClientMessage::Ack);
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_client_error_fails_the_render() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-operations-error");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    let _ = harness.expect_write_directory();
+    let _ = harness.expect_write_file();
+    let _ = expect_file_operation(harness.receive());
+    harness.respond(ClientMessage::Error("disk full".to_string()));
+
+    match harness.receive() {
+        ScriptMessage::LogError(message) => assert!(message.contains("disk full"), "{}", message),
+        other => panic!("Expected LogError, got {:?}", other),
+    }
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_paths_may_not_leave_the_destination() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-operations-escape");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("escape")
+        .build()?;
+
+    match harness.receive() {
+        ScriptMessage::LogError(message) => assert!(message.contains("file.delete"), "{}", message),
+        other => panic!("Expected LogError, got {:?}", other),
+    }
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_list_and_glob_read_the_destination() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-operations-inspect");
+    let _ = fs::remove_dir_all(&dest);
+    fs::create_dir_all(dest.join("src/entities")).unwrap();
+    fs::write(dest.join("src/lib.rs"), "").unwrap();
+    fs::write(dest.join("src/entities/user.rs"), "").unwrap();
+    fs::write(dest.join("README.md"), "").unwrap();
+
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("inspect")
+        .build()?;
+
+    assert_eq!(harness.expect_list_files(), dest.join("src").as_str());
+    assert_eq!(harness.expect_log_info(), "entities,lib.rs");
+    assert_eq!(harness.expect_list_files(), dest.as_str());
+    assert_eq!(harness.expect_log_info(), "src/entities/user.rs,src/lib.rs");
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_list_and_glob_see_what_a_dry_run_would_have_written() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-file-operations-staged");
+    let _ = fs::remove_dir_all(&dest);
+    fs::create_dir_all(dest.join("src/entities")).unwrap();
+    fs::write(dest.join("src/lib.rs"), "").unwrap();
+    fs::write(dest.join("src/entities/user.rs"), "").unwrap();
+
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("staged")
+        .dry_run_patch()
+        .build()?;
+
+    let _ = harness.expect_list_files();
+    assert_eq!(harness.expect_log_info(), "entities,order.rs");
+    let _ = harness.expect_list_files();
+    assert_eq!(harness.expect_log_info(), "src/entities/user.rs,src/order.rs");
+
+    assert!(harness.render_succeeded());
+    assert!(dest.join("src/lib.rs").exists());
+    Ok(())
+}
@@ -0,0 +1,24 @@
+if archetype.switches.is_enabled("inspect") then
+  log.info(table.concat(file.list("src"), ","))
+  log.info(table.concat(file.glob("**/*.rs"), ","))
+  return
+end
+
+if archetype.switches.is_enabled("staged") then
+  file.write("src/order.rs", "pub struct Order;\n")
+  file.delete("src/lib.rs")
+  log.info(table.concat(file.list("src"), ","))
+  log.info(table.concat(file.glob("**/*.rs"), ","))
+  return
+end
+
+if archetype.switches.is_enabled("escape") then
+  file.delete("src/../../elsewhere")
+  return
+end
+
+file.write("src/order.rs", "pub struct Order;\n", { if_exists = Existing.Overwrite, mode = 644 })
+file.append("src/lib.rs", "pub mod order;\n")
+file.copy("src/order.rs", "src/order_copy.rs")
+file.move("src/order_copy.rs", "src/legacy/order.rs", { if_exists = Existing.Error })
+file.delete("src/legacy")
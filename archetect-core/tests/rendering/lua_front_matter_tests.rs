@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use archetect_api::{ClientMessage, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::{TestHarness, TestHarnessBuilder};
+
+/// Acknowledge writes until the render finishes, returning each file's
+/// contents and existing-file policy keyed by its path relative to `dest`.
+fn written_files(harness: &TestHarness, dest: &Utf8PathBuf) -> BTreeMap<String, (String, String)> {
+    let mut files = BTreeMap::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::WriteDirectory(_) => harness.respond(ClientMessage::Ack),
+            ScriptMessage::WriteFile(info) => {
+                harness.respond(ClientMessage::Ack);
+                let path = Utf8PathBuf::from(info.destination);
+                let path = path.strip_prefix(dest).expect("Beneath destination").to_string();
+                let contents = String::from_utf8(info.contents).expect("Text contents");
+                files.insert(path, (contents, format!("{:?}", info.existing_file_policy)));
+            }
+            other => panic!("Expected a write, got {:?}", other),
+        }
+    }
+    files
+}
+
+#[test]
+fn test_front_matter_directives() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-front-matter");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    let files = written_files(&harness, &dest);
+
+    // The Dockerfile's `when:` is false, README.md moves and overwrites,
+    // raw.txt is not templated, and compose.yaml's leading YAML document
+    // names no directive, so it stays part of the template.
+    let expected: BTreeMap<String, (String, String)> = [
+        ("docs/demo.md", "# demo\n", "Overwrite"),
+        ("raw.txt", "{{ untouched }}\n", "Preserve"),
+        ("compose.yaml", "---\nservices: {}\n---\nname: demo\n", "Preserve"),
+        ("single/docs/demo.md", "# demo\n", "Overwrite"),
+    ]
+    .into_iter()
+    .map(|(path, contents, policy)| (path.to_string(), (contents.to_string(), policy.to_string())))
+    .collect();
+    assert_eq!(files, expected);
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_when_renders_file_once_true() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-front-matter-when");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("docker")
+        .build()?;
+
+    let files = written_files(&harness, &dest);
+    assert_eq!(files["Dockerfile"].0, "FROM demo\n");
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_dry_run_reports_skipped_files() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-front-matter-dry-run");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .dry_run()
+        .build()?;
+
+    let mut displayed = Vec::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::Display(line) => displayed.push(line),
+            other => panic!("Expected only dry-run output, got {:?}", other),
+        }
+    }
+    assert!(displayed.contains(&format!("[dry-run] skip    {}/Dockerfile", dest)));
+    assert!(displayed
+        .iter()
+        .any(|line| line.starts_with(&format!("[dry-run] write   {}/docs/demo.md", dest))));
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_dry_run_patch_leaves_the_writes_to_the_patch() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-front-matter-dry-run-patch");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .dry_run_patch()
+        .build()?;
+
+    let mut displayed = Vec::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::Display(line) => displayed.push(line),
+            other => panic!("Expected only dry-run output, got {:?}", other),
+        }
+    }
+    // The patch says what would be written; what the filter left out, it
+    // can't.
+    assert_eq!(displayed, vec![format!("[dry-run] skip    {}/Dockerfile", dest)]);
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
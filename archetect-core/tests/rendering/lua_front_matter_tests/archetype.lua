@@ -0,0 +1,6 @@
+local ctx = Context.new()
+ctx:set("name", "demo")
+ctx:set("use_docker", archetype.switches.is_enabled("docker"))
+
+directory.render("default", ctx)
+file.render("default/README.md", ctx, { destination = "single/README.md" })
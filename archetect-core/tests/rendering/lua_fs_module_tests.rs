@@ -0,0 +1,118 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use archetect_api::ScriptMessage;
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+/// A project to inspect, in `<root>/project`, beneath a marked root.
+fn project(root: &str) -> Utf8PathBuf {
+    let root = Utf8PathBuf::from(root);
+    let _ = fs::remove_dir_all(&root);
+    let project = root.join("project");
+    fs::create_dir_all(project.join("src/entities")).unwrap();
+    fs::write(root.join(".fs-test-root"), "").unwrap();
+    fs::write(project.join("Cargo.toml"), "[package]\nname = \"orders\"\n").unwrap();
+    fs::write(project.join("src/lib.rs"), "").unwrap();
+    fs::write(project.join("src/entities/user.rs"), "").unwrap();
+    project
+}
+
+#[test]
+fn test_fs_inspects_the_destination() -> Result<(), ArchetectError> {
+    let dest = project("/tmp/archetect-test-lua-fs");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    assert_eq!(harness.expect_log_info(), "archetype.lua,archetype.yaml");
+    assert_eq!(harness.expect_log_info(), "src/entities/user.rs,src/lib.rs");
+    assert_eq!(harness.expect_log_info(), "entities,lib.rs");
+    let mode = fs::metadata(dest.join("Cargo.toml")).unwrap().permissions().mode() & 0o777;
+    assert_eq!(harness.expect_log_info(), format!("26 false true {:o}", mode));
+    assert_eq!(harness.expect_log_info(), "true nil");
+    assert_eq!(
+        harness.expect_log_info(),
+        "1:Cargo.toml,1:src/,2:src/entities/,2:src/lib.rs"
+    );
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_fs_finds_the_enclosing_root() -> Result<(), ArchetectError> {
+    let dest = project("/tmp/archetect-test-lua-fs-find");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest)
+        .with_switch("find")
+        .build()?;
+
+    assert_eq!(harness.expect_log_info(), "/tmp/archetect-test-lua-fs-find");
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_fs_stays_in_the_root_in_the_sandbox() -> Result<(), ArchetectError> {
+    let dest = project("/tmp/archetect-test-lua-fs-sandboxed");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest)
+        .with_switch("find")
+        .sandboxed()
+        .build()?;
+
+    assert_eq!(harness.expect_log_info(), "nil");
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_fs_paths_may_not_climb() -> Result<(), ArchetectError> {
+    let dest = project("/tmp/archetect-test-lua-fs-escape");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest)
+        .with_switch("escape")
+        .build()?;
+
+    match harness.receive() {
+        ScriptMessage::LogError(message) => assert!(message.contains("Path manipulation"), "{}", message),
+        other => panic!("Expected LogError, got {:?}", other),
+    }
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_fs_does_not_follow_symlinks_out_of_the_root() -> Result<(), ArchetectError> {
+    let dest = project("/tmp/archetect-test-lua-fs-symlink");
+    std::os::unix::fs::symlink("/", dest.join("link")).unwrap();
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest)
+        .with_switch("symlink")
+        .build()?;
+
+    assert_eq!(harness.expect_log_info(), "true");
+    let error = harness.expect_log_error();
+    assert!(error.contains("fs.walk: link leads outside its root"), "{}", error);
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_fs_cwd_needs_the_filesystem_capability_in_the_sandbox() -> Result<(), ArchetectError> {
+    let dest = project("/tmp/archetect-test-lua-fs-cwd");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest)
+        .with_switch("cwd")
+        .sandboxed()
+        .with_capabilities(&[])
+        .build()?;
+
+    let error = harness.expect_log_error();
+    assert!(error.contains("fs.list_dir(") && error.contains("`filesystem` capability"), "{}", error);
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,38 @@
+local fs = require("archetect.fs")
+local here = { within = Location.Destination }
+
+if archetype.switches.is_enabled("escape") then
+  fs.list_dir("../", here)
+  return
+end
+
+if archetype.switches.is_enabled("symlink") then
+  -- `link` points out of the destination: reported, never read through.
+  log.info(tostring(fs.stat("link", here).is_symlink))
+  fs.walk("link", here)
+  return
+end
+
+if archetype.switches.is_enabled("cwd") then
+  fs.list_dir(".", { within = Location.Cwd })
+  return
+end
+
+if archetype.switches.is_enabled("find") then
+  log.info(tostring(fs.find_up(".fs-test-root", here)))
+  return
+end
+
+log.info(table.concat(fs.list_dir(), ","))
+log.info(table.concat(fs.glob("src/**/*.rs", here), ","))
+log.info(table.concat(fs.list_dir("src", here), ","))
+
+local cargo = fs.stat("Cargo.toml", here)
+log.info(string.format("%d %s %s %o", cargo.size, tostring(cargo.is_dir), tostring(cargo.is_file), cargo.mode))
+log.info(tostring(fs.stat("src", here).is_dir) .. " " .. tostring(fs.stat("missing.toml", here)))
+
+local walked = {}
+for _, entry in ipairs(fs.walk(".", { within = Location.Destination, max_depth = 2 })) do
+  table.insert(walked, entry.depth .. ":" .. entry.path .. (entry.is_dir and "/" or ""))
+end
+log.info(table.concat(walked, ","))
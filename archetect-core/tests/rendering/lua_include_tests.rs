@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+
+use archetect_api::{ClientMessage, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+#[test]
+fn test_includes_with_arguments_and_dynamic_paths() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-include");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    let mut files = BTreeMap::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::WriteDirectory(_) => harness.respond(ClientMessage::Ack),
+            ScriptMessage::WriteFile(info) => {
+                harness.respond(ClientMessage::Ack);
+                let path = Utf8PathBuf::from(info.destination);
+                let path = path.strip_prefix(&dest).expect("Beneath destination").to_string();
+                files.insert(path, String::from_utf8(info.contents).expect("Text contents"));
+            }
+            other => panic!("Expected a write, got {:?}", other),
+        }
+    }
+
+    // The path is built from an answer, and the partial sees the context.
+    assert_eq!(files["ci.yml"], "name: orders\non: [push]\n\n");
+    // With arguments, the partial sees them alone: no `project`.
+    assert_eq!(
+        files["model.rs"],
+        "pub struct Order {\n    pub id: Uuid,\n    pub note: String,\n}\n"
+    );
+    // A dynamic include may include itself, one level down each time.
+    assert_eq!(files["tree.md"], "- src\n  - main.rs\n  - model\n    - order.rs\n\n");
+    // The partial is escaped for the region it is included into.
+    assert_eq!(files["package.json"], "{ \"description\": \"say \\\"hi\\\"\"\n }\n");
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,16 @@
+local ctx = Context.new()
+ctx:set("project", "orders")
+ctx:set("ci_provider", "github")
+ctx:set("fields", {
+    { name = "id", type = "Uuid" },
+    { name = "note", type = "String" },
+})
+ctx:set("tree", {
+    name = "src",
+    children = {
+        { name = "main.rs" },
+        { name = "model", children = { { name = "order.rs" } } },
+    },
+})
+
+directory.render("default", ctx)
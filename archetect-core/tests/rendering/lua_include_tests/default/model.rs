@@ -0,0 +1,2 @@
+pub struct Order {
+{% for _, f in ipairs(fields) do %}{% include "field.atl" with { name = f.name, type = f.type } %}{% end %}}
@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use archetect_api::{ClientMessage, ScriptMessage};
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+#[test]
+fn test_license_header_in_the_destination_comment_style() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-license");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    let mut files = BTreeMap::new();
+    while let Some(message) = harness.try_receive() {
+        match message {
+            ScriptMessage::WriteDirectory(_) => harness.respond(ClientMessage::Ack),
+            ScriptMessage::WriteFile(info) => {
+                harness.respond(ClientMessage::Ack);
+                let path = Utf8PathBuf::from(info.destination);
+                let path = path.strip_prefix(&dest).expect("Beneath destination").to_string();
+                files.insert(path, String::from_utf8(info.contents).expect("Text contents"));
+            }
+            other => panic!("Expected a write, got {:?}", other),
+        }
+    }
+
+    let mpl = [
+        "Copyright (c) 2031 Acme Corp",
+        "",
+        "This Source Code Form is subject to the terms of the Mozilla Public",
+        "License, v. 2.0. If a copy of the MPL was not distributed with this",
+        "file, You can obtain one at https://mozilla.org/MPL/2.0/.",
+    ];
+    let commented = |marker: &str| {
+        mpl.iter()
+            .map(|line| if line.is_empty() { marker.to_string() } else { format!("{} {}", marker, line) })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(files["src/main.rs"], format!("{}\n\nfn main() {{}}\n", commented("//")));
+    assert_eq!(files["Dockerfile"], format!("{}\nFROM scratch\n", commented("#")));
+    // The style follows where the front matter moved the file, not its source.
+    assert!(
+        files["db/schema.sql"].starts_with("-- Copyright (c) 2031 Acme Corp. All rights reserved.\n--\n-- "),
+        "{}",
+        files["db/schema.sql"]
+    );
+    assert_eq!(files["pom.xml"], "<!--\n  Generated; do not edit.\n-->\n<project/>\n");
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,4 @@
+local ctx = Context.new()
+ctx:set("owner", "Acme Corp")
+
+directory.render("default", ctx)
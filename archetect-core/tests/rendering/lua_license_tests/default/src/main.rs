@@ -0,0 +1,3 @@
+{{ license("MPL-2.0", { owner = owner, year = 2031 }) | comment_block(destination) }}
+
+fn main() {}
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use archetect_api::{ClientMessage, ExistingFilePolicy, ScriptMessage, WriteFileInfo};
+use archetect_core::errors::ArchetectError;
+use archetect_core::generation::{checksum, GenerationManifest};
+use camino::Utf8PathBuf;
+
+use crate::test_utils::{TestHarness, TestHarnessBuilder};
+
+/// Acknowledge every write until the generation manifest arrives, returning
+/// the rendered files keyed by destination and the manifest itself. Directory
+/// listing order is platform-dependent, so nothing here relies on sequence.
+fn collect_generation(harness: &TestHarness) -> (BTreeMap<String, WriteFileInfo>, GenerationManifest) {
+    let mut files = BTreeMap::new();
+    loop {
+        match harness.receive() {
+            ScriptMessage::WriteDirectory(_) => harness.respond(ClientMessage::Ack),
+            ScriptMessage::WriteFile(info) => {
+                harness.respond(ClientMessage::Ack);
+                if info.destination.ends_with(".archetect/manifest.yaml") {
+                    let manifest = serde_yaml::from_slice(&info.contents).expect("Valid generation manifest");
+                    return (files, manifest);
+                }
+                files.insert(info.destination.clone(), info);
+            }
+            other => panic!("Expected a write, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_regeneration_records_shadow_and_policy() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-regeneration");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .build()?;
+
+    let _ = harness.expect_text_prompt();
+    harness.respond_text("Demo");
+
+    let (files, _) = collect_generation(&harness);
+
+    let readme = &files[dest.join("README.md").as_str()];
+    assert!(matches!(readme.existing_file_policy, ExistingFilePolicy::Merge));
+    assert_eq!(
+        readme.shadow.as_deref(),
+        Some(dest.join(".archetect/shadow/README.md").as_str())
+    );
+
+    let ci = &files[dest.join(".github/ci.yaml").as_str()];
+    assert!(matches!(ci.existing_file_policy, ExistingFilePolicy::Overwrite));
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_regeneration_manifest_records_answers_and_checksums() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-regeneration-manifest");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_switch("ci")
+        .build()?;
+
+    let _ = harness.expect_text_prompt();
+    harness.respond_text("Demo");
+
+    let (files, manifest) = collect_generation(&harness);
+
+    assert_eq!(manifest.answers.get("project_name").and_then(|v| v.as_str()), Some("Demo"));
+    assert_eq!(manifest.switches, vec!["ci".to_string()]);
+    assert!(manifest.archetype.source.ends_with("lua_regeneration_tests"));
+    assert!(manifest.archetype.oid.is_none());
+
+    let readme = &files[dest.join("README.md").as_str()];
+    assert_eq!(manifest.files["README.md"].checksum, checksum(&readme.contents));
+    assert!(manifest.files.contains_key(".github/ci.yaml"));
+    assert_eq!(manifest.files.len(), 2);
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
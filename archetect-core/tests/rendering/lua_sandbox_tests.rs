@@ -0,0 +1,91 @@
+use archetect_api::ScriptMessage;
+use archetect_core::errors::ArchetectError;
+use camino::Utf8PathBuf;
+
+use crate::test_utils::TestHarnessBuilder;
+
+#[test]
+fn test_sandboxed_os_execute_asks_like_archetect_shell() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(Utf8PathBuf::from("/tmp/archetect-test-lua-sandbox-exec"))
+        .with_answer("probe", "exec")
+        .sandboxed()
+        .build()?;
+
+    assert!(harness.expect_display().contains("sh -c echo hello"));
+    let _ = harness.expect_bool_prompt();
+    harness.respond_bool(false);
+
+    assert!(harness.expect_log_error().contains("Shell execution denied by user"));
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_sandboxed_getenv_needs_the_environment_capability() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(Utf8PathBuf::from("/tmp/archetect-test-lua-sandbox-env-denied"))
+        .with_answer("probe", "env")
+        .sandboxed()
+        .with_capabilities(&[])
+        .build()?;
+
+    let error = harness.expect_log_error();
+    assert!(error.contains("os.getenv(\"PATH\")"), "{}", error);
+    assert!(error.contains("`environment` capability") && error.contains("--allow environment"), "{}", error);
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_sandboxed_getenv_with_the_capability_granted() -> Result<(), ArchetectError> {
+    let dest = Utf8PathBuf::from("/tmp/archetect-test-lua-sandbox-env-granted");
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(dest.clone())
+        .with_answer("probe", "env")
+        .sandboxed()
+        .with_capabilities(&["environment"])
+        .build()?;
+
+    assert_eq!(harness.expect_write_directory(), dest.as_str());
+    let readme = harness.expect_write_file();
+    assert_eq!(String::from_utf8(readme.contents).expect("Text contents"), "found: true\n");
+    assert!(harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_sandboxed_stdio_goes_through_the_io_channel() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(Utf8PathBuf::from("/tmp/archetect-test-lua-sandbox-stdio"))
+        .with_answer("probe", "stdio")
+        .sandboxed()
+        .build()?;
+
+    for expected in ["printed\t1", "written", "chained", " on"] {
+        match harness.receive() {
+            ScriptMessage::Print(message) => assert_eq!(message, expected),
+            other => panic!("Expected Print, got {:?}", other),
+        }
+    }
+    let error = harness.expect_log_error();
+    assert!(error.contains("io.read is not available in the sandbox"), "{}", error);
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
+
+#[test]
+fn test_sandboxed_cwd_reads_need_the_filesystem_capability() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!())
+        .with_destination(Utf8PathBuf::from("/tmp/archetect-test-lua-sandbox-cwd"))
+        .with_answer("probe", "cwd")
+        .sandboxed()
+        .with_capabilities(&[])
+        .build()?;
+
+    let error = harness.expect_log_error();
+    assert!(error.contains("file.exists(") && error.contains("Cargo.toml"), "{}", error);
+    assert!(error.contains("`filesystem` capability"), "{}", error);
+    assert!(!harness.render_succeeded());
+    Ok(())
+}
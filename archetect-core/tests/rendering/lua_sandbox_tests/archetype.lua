@@ -0,0 +1,19 @@
+local ctx = Context.new()
+
+ctx:prompt_text("Probe:", "probe")
+
+if ctx:get("probe") == "exec" then
+    os.execute("echo hello")
+elseif ctx:get("probe") == "env" then
+    ctx:set("found", os.getenv("PATH") ~= nil)
+elseif ctx:get("probe") == "stdio" then
+    print("printed", 1)
+    io.write("written", "\n")
+    io.stdout:write("chained"):write(" on")
+    io.read()
+elseif ctx:get("probe") == "cwd" then
+    -- The test's working directory is the crate's, beside the archetype.
+    ctx:set("found", file.exists("Cargo.toml", { within = Location.Cwd }))
+end
+
+directory.render("default", ctx)
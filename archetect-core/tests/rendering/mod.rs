@@ -1,2 +1,15 @@
+mod lua_atomic_render_tests;
+mod lua_autoescape_tests;
+mod lua_directory_filter_tests;
+mod lua_file_edit_structured_tests;
+mod lua_file_inject_tests;
+mod lua_file_mode_tests;
+mod lua_file_operations_tests;
+mod lua_fs_module_tests;
+mod lua_front_matter_tests;
+mod lua_include_tests;
+mod lua_license_tests;
+mod lua_regeneration_tests;
 mod lua_render_tests;
+mod lua_sandbox_tests;
 mod lua_template_render_tests;
@@ -13,6 +13,7 @@ use archetect_core::archetype::render_context::RenderContext;
 use archetect_core::configuration::Configuration;
 use archetect_core::errors::ArchetectError;
 use archetect_core::Archetect;
+use archetect_terminal_io::overlay::Overlay;
 
 pub fn get_archetype_path(rs_file: &str) -> Utf8PathBuf {
     let rust_file = Utf8PathBuf::from(rs_file);
@@ -34,16 +35,23 @@ impl TestHarness {
         test_file: &str,
         configuration: Configuration,
         render_context: RenderContext,
-
+        capabilities: Option<Vec<String>>,
+        patch: bool,
     ) -> Result<TestHarness, ArchetectError> {
         let archetype_dir = get_archetype_path(test_file);
 
         let (script_handle, client_handle) = sync_io_channel();
-        let archetect = Archetect::builder()
+        let mut builder = Archetect::builder()
             .with_driver(script_handle)
             .with_configuration(configuration)
-            .with_temp_layout()?
-            .build()?;
+            .with_temp_layout()?;
+        if let Some(capabilities) = capabilities {
+            builder = builder.with_capabilities(capabilities);
+        }
+        if patch {
+            builder = builder.with_dry_run_patch();
+        }
+        let archetect = builder.build()?;
 
         let archetype = archetect.new_archetype(archetype_dir.as_str())?;
         let (status_tx, status_rx) = mpsc::sync_channel(1);
@@ -67,6 +75,12 @@ impl TestHarness {
         self.handle.receive().expect("Expected ScriptMessage")
     }
 
+    /// The next message, or `None` once the render has finished and dropped
+    /// its end of the channel.
+    pub fn try_receive(&self) -> Option<ScriptMessage> {
+        self.handle.receive().ok()
+    }
+
     pub fn render_succeeded(&self) -> bool {
         self.status_rx.recv_timeout(Duration::from_millis(100)).expect("Expected Render Status")
     }
@@ -173,6 +187,44 @@ impl TestHarness {
         }
     }
 
+    pub fn expect_begin_transaction(&self) -> String {
+        match self.receive() {
+            ScriptMessage::BeginTransaction(destination) => {
+                self.respond(ClientMessage::Ack);
+                destination
+            }
+            other => panic!("Expected BeginTransaction, got {:?}", other),
+        }
+    }
+
+    /// Answer a `ReadFile` from disk, as a client without a transaction
+    /// would, returning the path asked for.
+    pub fn expect_read_file(&self) -> String {
+        match self.receive() {
+            ScriptMessage::ReadFile(path) => {
+                self.respond(match std::fs::read_to_string(&path) {
+                    Ok(contents) => ClientMessage::String(contents),
+                    Err(_) => ClientMessage::None,
+                });
+                path
+            }
+            other => panic!("Expected ReadFile, got {:?}", other),
+        }
+    }
+
+    /// Answer a `ListFiles` from disk, as a client without a transaction
+    /// would, returning the directory asked about.
+    pub fn expect_list_files(&self) -> String {
+        match self.receive() {
+            ScriptMessage::ListFiles(path) => {
+                let listed = Overlay::default().list(camino::Utf8Path::new(&path));
+                self.respond(ClientMessage::Array(listed.into_iter().map(|path| path.into_string()).collect()));
+                path
+            }
+            other => panic!("Expected ListFiles, got {:?}", other),
+        }
+    }
+
     // --- Write expectations (auto-Ack) ---
 
     pub fn expect_write_directory(&self) -> String {
@@ -229,6 +281,8 @@ pub struct TestHarnessBuilder {
     switches: Vec<String>,
     use_defaults_all: bool,
     destination: Utf8PathBuf,
+    capabilities: Option<Vec<String>>,
+    patch: bool,
 }
 
 #[allow(dead_code)] // TestHarnessBuilder is a test API; some methods are reserved for future tests
@@ -241,6 +295,8 @@ impl TestHarnessBuilder {
             switches: Vec::new(),
             use_defaults_all: false,
             destination: Utf8PathBuf::new(),
+            capabilities: None,
+            patch: false,
         }
     }
 
@@ -249,6 +305,33 @@ impl TestHarnessBuilder {
         self
     }
 
+    pub fn atomic(mut self) -> Self {
+        self.configuration = self.configuration.with_atomic(true);
+        self
+    }
+
+    pub fn dry_run(mut self) -> Self {
+        self.configuration = self.configuration.with_dry_run(true);
+        self
+    }
+
+    /// A dry run that keeps track of its writes for `--patch`.
+    pub fn dry_run_patch(mut self) -> Self {
+        self.patch = true;
+        self.dry_run()
+    }
+
+    pub fn sandboxed(mut self) -> Self {
+        self.configuration = self.configuration.with_sandbox(true);
+        self
+    }
+
+    /// Restrict the session to `capabilities`, as a connected session is.
+    pub fn with_capabilities(mut self, capabilities: &[&str]) -> Self {
+        self.capabilities = Some(capabilities.iter().map(|capability| capability.to_string()).collect());
+        self
+    }
+
     pub fn with_switch(mut self, switch: &str) -> Self {
         self.switches.push(switch.to_string());
         self
@@ -277,6 +360,6 @@ impl TestHarnessBuilder {
         if self.use_defaults_all {
             render_context = render_context.with_use_defaults_all(true);
         }
-        TestHarness::new(&self.test_file, self.configuration, render_context)
+        TestHarness::new(&self.test_file, self.configuration, render_context, self.capabilities, self.patch)
     }
 }
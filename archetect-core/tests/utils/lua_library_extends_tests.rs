@@ -0,0 +1,24 @@
+use archetect_core::errors::ArchetectError;
+
+use crate::test_utils::TestHarnessBuilder;
+
+#[test]
+fn test_library_base_template_resolves_through_staging() -> Result<(), ArchetectError> {
+    // The consumer's Dockerfile extends `test-lib/Dockerfile.atl`, which
+    // lives in the staged library's includes/. The parent supplies the
+    // layout and the `image` block; the child replaces `build` and pulls
+    // the library's version back in with `super()`.
+    let harness = TestHarnessBuilder::new(file!()).build()?;
+
+    let _dir = harness.expect_write_directory();
+    let file_info = harness.expect_write_file();
+    let contents = String::from_utf8(file_info.contents).expect("Valid UTF-8");
+
+    assert_eq!(
+        contents,
+        "FROM debian:stable\nRUN cargo build --release\nRUN make\nLABEL name=\"smoke-test\"\n"
+    );
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
@@ -0,0 +1,8 @@
+-- The library `test-lib` was eagerly staged at archetype load. The
+-- template at contents/Dockerfile extends the library's base
+-- `test-lib/Dockerfile.atl`, found the same way an include is, and
+-- overrides one of its blocks.
+local context = Context.new()
+context:set("project_name", "smoke-test")
+
+directory.render("contents", context)
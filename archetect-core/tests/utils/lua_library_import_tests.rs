@@ -0,0 +1,17 @@
+use archetect_core::errors::ArchetectError;
+
+use crate::test_utils::TestHarnessBuilder;
+
+#[test]
+fn test_macros_import_from_consumer_and_library_includes() -> Result<(), ArchetectError> {
+    let harness = TestHarnessBuilder::new(file!()).build()?;
+
+    let _dir = harness.expect_write_directory();
+    let file_info = harness.expect_write_file();
+    let contents = String::from_utf8(file_info.contents).expect("Valid UTF-8");
+
+    assert_eq!(contents, "env:\n- name: PROJECT_NAME\n  value: \"smoke-test\"\n");
+
+    assert!(harness.render_succeeded());
+    Ok(())
+}
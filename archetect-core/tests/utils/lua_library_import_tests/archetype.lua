@@ -0,0 +1,7 @@
+-- contents/deployment.yaml imports macros from the consumer's own
+-- includes/ and from the staged library `test-lib`, both found the same
+-- way an include is.
+local context = Context.new()
+context:set("project_name", "smoke-test")
+
+directory.render("contents", context)
@@ -4,3 +4,5 @@ mod lua_local_lib_tests;
 mod lua_self_require_lib_tests;
 mod lua_staged_library_tests;
 mod lua_library_include_tests;
+mod lua_library_extends_tests;
+mod lua_library_import_tests;
@@ -3,7 +3,7 @@ use serde::Serialize;
 #[allow(unused_imports)]
 pub use archetect_api::{EnvelopeOption, PromptConstraints, PromptEnvelope, PromptType};
 
-use archetect_api::ScriptMessage;
+use archetect_api::{Artifact, ScriptMessage};
 
 #[derive(Clone, Debug, Serialize)]
 pub struct LogEntry {
@@ -99,6 +99,10 @@ pub struct ToolResponse {
     pub logs: Vec<LogEntry>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub files_written: Vec<String>,
+    /// What a completed render produced, each file with its outcome and
+    /// content hash — the same manifest a gRPC client gets.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<Artifact>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prompt: Option<PromptEnvelope>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -111,16 +115,18 @@ impl ToolResponse {
             status: "prompting".into(),
             logs,
             files_written,
+            artifacts: vec![],
             prompt: Some(prompt),
             message: None,
         }
     }
 
-    pub fn complete(logs: Vec<LogEntry>, files_written: Vec<String>) -> Self {
+    pub fn complete(logs: Vec<LogEntry>, files_written: Vec<String>, artifacts: Vec<Artifact>) -> Self {
         Self {
             status: "complete".into(),
             logs,
             files_written,
+            artifacts,
             prompt: None,
             message: None,
         }
@@ -131,6 +137,7 @@ impl ToolResponse {
             status: "error".into(),
             logs: vec![],
             files_written: vec![],
+            artifacts: vec![],
             prompt: None,
             message: Some(msg.into()),
         }
@@ -141,6 +148,7 @@ impl ToolResponse {
             status: "cancelled".into(),
             logs: vec![],
             files_written: vec![],
+            artifacts: vec![],
             prompt: None,
             message: None,
         }
@@ -22,7 +22,7 @@ use crate::prompt_envelope::{
     CatalogBrowseResponse, CatalogEntryInfo, CatalogSearchResponse, ToolResponse,
 };
 use crate::session::{
-    drain_until_prompt_or_complete, json_to_client_message, DrainOutcome, SessionState,
+    drain_within, json_to_client_message, Deadline, DrainOutcome, SessionState,
 };
 
 #[derive(Debug, Clone)]
@@ -345,6 +345,7 @@ impl ArchetectMcpServer {
         let archetect = match Archetect::builder()
             .with_driver(io_handle)
             .with_configuration(self.archetect.configuration().clone())
+            .with_limits(self.archetect.limits())
             .with_layout(match archetect_core::system::XdgSystemLayout::new() {
                 Ok(layout) => layout,
                 Err(e) => {
@@ -361,6 +362,7 @@ impl ArchetectMcpServer {
 
         // Resolve source and spawn render
         let source_str = req.source.clone();
+        let deadline = Deadline::of(&archetect);
         let render_handle = tokio::task::spawn_blocking(move || {
             let result = (|| -> Result<(), String> {
                 let source = archetect
@@ -398,7 +400,8 @@ impl ArchetectMcpServer {
 
         // Drain until first prompt or completion
         let mut segments = Vec::new();
-        let drain_result = match drain_until_prompt_or_complete(&mut script_rx, &client_tx, &mut segments).await {
+        let mut transaction = None;
+        let drain_result = match drain_within(deadline, &mut script_rx, &client_tx, &mut segments, &mut transaction).await {
             Ok(r) => r,
             Err(e) => {
                 return to_json(&ToolResponse::error(e));
@@ -415,18 +418,21 @@ impl ArchetectMcpServer {
                 *session = SessionState::Prompting {
                     pending_prompt: envelope,
                     segments,
+                    transaction,
                     client_tx,
                     script_rx,
+                    deadline,
                     render_handle,
                 };
                 to_json(&response)
             }
-            DrainOutcome::Complete { success, message } => {
+            DrainOutcome::Complete { success, message, artifacts } => {
                 *session = SessionState::Idle;
                 if success {
                     to_json(&ToolResponse::complete(
                         drain_result.logs,
                         drain_result.files_written,
+                        artifacts,
                     ))
                 } else {
                     to_json(&ToolResponse::error(
@@ -504,15 +510,17 @@ impl ArchetectMcpServer {
     ) -> String {
         let mut session = self.session.lock().await;
 
-        let (pending_prompt, mut segments, client_tx, mut script_rx, render_handle) =
+        let (pending_prompt, mut segments, mut transaction, client_tx, mut script_rx, deadline, render_handle) =
             match std::mem::replace(&mut *session, SessionState::Idle) {
                 SessionState::Prompting {
                     pending_prompt,
                     segments,
+                    transaction,
                     client_tx,
                     script_rx,
+                    deadline,
                     render_handle,
-                } => (pending_prompt, segments, client_tx, script_rx, render_handle),
+                } => (pending_prompt, segments, transaction, client_tx, script_rx, deadline, render_handle),
                 SessionState::Idle => {
                     return to_json(&ToolResponse::error(
                         "No active render session. Use 'render' to start one.",
@@ -528,14 +536,23 @@ impl ArchetectMcpServer {
                 *session = SessionState::Prompting {
                     pending_prompt,
                     segments,
+                    transaction,
                     client_tx,
                     script_rx,
+                    deadline,
                     render_handle,
                 };
                 return to_json(&ToolResponse::error(format!("Invalid response: {}", e)));
             }
         };
 
+        // An answer that comes too late ends the session, as a drain that runs
+        // too long does.
+        if let Some(deadline) = deadline.filter(Deadline::has_passed) {
+            *session = SessionState::Idle;
+            return to_json(&ToolResponse::error(deadline.exceeded()));
+        }
+
         // Send response to render thread
         if client_tx.send(client_msg).await.is_err() {
             *session = SessionState::Idle;
@@ -543,7 +560,7 @@ impl ArchetectMcpServer {
         }
 
         // Drain until next prompt or completion
-        let drain_result = match drain_until_prompt_or_complete(&mut script_rx, &client_tx, &mut segments).await {
+        let drain_result = match drain_within(deadline, &mut script_rx, &client_tx, &mut segments, &mut transaction).await {
             Ok(r) => r,
             Err(e) => {
                 *session = SessionState::Idle;
@@ -561,18 +578,21 @@ impl ArchetectMcpServer {
                 *session = SessionState::Prompting {
                     pending_prompt: envelope,
                     segments,
+                    transaction,
                     client_tx,
                     script_rx,
+                    deadline,
                     render_handle,
                 };
                 to_json(&response)
             }
-            DrainOutcome::Complete { success, message } => {
+            DrainOutcome::Complete { success, message, artifacts } => {
                 *session = SessionState::Idle;
                 if success {
                     to_json(&ToolResponse::complete(
                         drain_result.logs,
                         drain_result.files_written,
+                        artifacts,
                     ))
                 } else {
                     to_json(&ToolResponse::error(
@@ -774,6 +794,7 @@ impl ArchetectMcpServer {
         let archetect = match Archetect::builder()
             .with_driver(io_handle)
             .with_configuration(self.archetect.configuration().clone())
+            .with_limits(self.archetect.limits())
             .with_layout(match archetect_core::system::XdgSystemLayout::new() {
                 Ok(layout) => layout,
                 Err(e) => {
@@ -788,6 +809,7 @@ impl ArchetectMcpServer {
             }
         };
 
+        let deadline = Deadline::of(&archetect);
         let render_handle = tokio::task::spawn_blocking(move || {
             let result = (|| -> Result<(), String> {
                 let source = archetect
@@ -825,7 +847,8 @@ impl ArchetectMcpServer {
 
         // Drain until first prompt or completion
         let mut segments = Vec::new();
-        let drain_result = match drain_until_prompt_or_complete(&mut script_rx, &client_tx, &mut segments).await {
+        let mut transaction = None;
+        let drain_result = match drain_within(deadline, &mut script_rx, &client_tx, &mut segments, &mut transaction).await {
             Ok(r) => r,
             Err(e) => {
                 return to_json(&ToolResponse::error(e));
@@ -842,18 +865,21 @@ impl ArchetectMcpServer {
                 *session = SessionState::Prompting {
                     pending_prompt: envelope,
                     segments,
+                    transaction,
                     client_tx,
                     script_rx,
+                    deadline,
                     render_handle,
                 };
                 to_json(&response)
             }
-            DrainOutcome::Complete { success, message } => {
+            DrainOutcome::Complete { success, message, artifacts } => {
                 *session = SessionState::Idle;
                 if success {
                     to_json(&ToolResponse::complete(
                         drain_result.logs,
                         drain_result.files_written,
+                        artifacts,
                     ))
                 } else {
                     to_json(&ToolResponse::error(
@@ -874,3 +900,91 @@ fn to_json_generic<T: serde::Serialize>(value: &T) -> String {
     serde_json::to_string_pretty(value)
         .unwrap_or_else(|e| format!("{{\"error\": \"{}\"}}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use archetect_core::configuration::Configuration;
+    use archetect_core::limits::ResourceLimits;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sandboxed_render_cannot_write_outside_its_destination() {
+        let archetype = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let outside = elsewhere.path().join("outside.txt");
+        std::fs::write(
+            archetype.path().join("archetype.yaml"),
+            "description: \"Escapes\"\nrequires:\n  archetect: \"3.0.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            archetype.path().join("archetype.lua"),
+            format!("io.open({:?}, \"w\"):write(\"escaped\")\n", outside.to_str().unwrap()),
+        )
+        .unwrap();
+        let destination = tempfile::tempdir().unwrap();
+
+        let archetect = Archetect::builder()
+            .with_configuration(Configuration::default().with_sandbox(true))
+            .with_temp_layout()
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = ArchetectMcpServer::new(archetect);
+        let response = server
+            .render(Parameters(RenderRequest {
+                source: archetype.path().to_str().unwrap().to_string(),
+                destination: destination.path().to_str().unwrap().to_string(),
+                answers: None,
+                switches: None,
+                use_defaults_all: None,
+            }))
+            .await;
+
+        assert!(response.contains("`filesystem` capability"), "{}", response);
+        assert!(!outside.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn an_answer_after_the_deadline_ends_the_render() {
+        let archetype = tempfile::tempdir().unwrap();
+        std::fs::write(
+            archetype.path().join("archetype.yaml"),
+            "description: \"Waits\"\nrequires:\n  archetect: \"3.0.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            archetype.path().join("archetype.lua"),
+            "local context = Context.new()\ncontext:prompt_text(\"Name:\", \"name\")\n",
+        )
+        .unwrap();
+        let destination = tempfile::tempdir().unwrap();
+
+        let archetect = Archetect::builder()
+            .with_temp_layout()
+            .unwrap()
+            .with_limits(ResourceLimits::default().with_timeout(1))
+            .build()
+            .unwrap();
+        let server = ArchetectMcpServer::new(archetect);
+        let response = server
+            .render(Parameters(RenderRequest {
+                source: archetype.path().to_str().unwrap().to_string(),
+                destination: destination.path().to_str().unwrap().to_string(),
+                answers: None,
+                switches: None,
+                use_defaults_all: None,
+            }))
+            .await;
+        assert!(response.contains("Name:"), "{}", response);
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let response = server
+            .respond(Parameters(RespondRequest {
+                value: serde_json::Value::String("Late".to_string()),
+            }))
+            .await;
+        assert!(response.contains("1-second deadline"), "{}", response);
+        assert!(server.session.lock().await.is_idle());
+    }
+}
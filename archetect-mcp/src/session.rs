@@ -3,8 +3,14 @@ use std::path::Path;
 
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+use tokio::time::Instant;
 
-use archetect_api::{ClientMessage, ScriptMessage, SegmentRef};
+use archetect_core::errors::ArchetypeError;
+use archetect_core::limits::LimitExceeded;
+use archetect_core::Archetect;
+use archetect_api::{Artifact, ClientMessage, FileOperation, FileOutcome, ScriptMessage, SegmentRef, WriteFileInfo, WriteSymlinkInfo};
+use archetect_terminal_io::overlay::Overlay;
+use archetect_terminal_io::transaction::{Staged, Transaction};
 
 use crate::prompt_envelope::{LogEntry, PromptEnvelope, PromptType};
 
@@ -17,10 +23,14 @@ pub struct DrainResult {
 
 pub enum DrainOutcome {
     Prompt(PromptEnvelope),
-    Complete { success: bool, message: Option<String> },
+    /// `artifacts` is the server's manifest of what the render produced,
+    /// empty when it failed.
+    Complete { success: bool, message: Option<String>, artifacts: Vec<Artifact> },
 }
 
 /// The state of the current render session.
+// There is one of these per server, so the size of `Prompting` costs nothing.
+#[allow(clippy::large_enum_variant)]
 pub enum SessionState {
     /// No render in progress.
     Idle,
@@ -31,8 +41,13 @@ pub enum SessionState {
         /// because a page opens in one drain and its prompts arrive in later
         /// ones — the stack has to outlive a single `respond`.
         segments: Vec<SegmentRef>,
+        /// Writes staged by a transactional render, held for the same
+        /// reason: they are committed only when the render completes.
+        transaction: Option<Transaction>,
         client_tx: mpsc::Sender<ClientMessage>,
         script_rx: mpsc::Receiver<ScriptMessage>,
+        /// When the render's time is up. Waiting on the agent counts too.
+        deadline: Option<Deadline>,
         #[allow(dead_code)]
         render_handle: JoinHandle<()>,
     },
@@ -53,17 +68,67 @@ impl SessionState {
     }
 }
 
+/// When a render's time is up, and how long it was given.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    at: Instant,
+    timeout: u64,
+}
+
+impl Deadline {
+    /// The session's deadline, if it has a timeout. Asking starts its clock.
+    pub fn of(archetect: &Archetect) -> Option<Self> {
+        Some(Self {
+            at: archetect.deadline()?.into(),
+            timeout: archetect.limits().timeout()?.as_secs(),
+        })
+    }
+
+    pub fn has_passed(&self) -> bool {
+        Instant::now() >= self.at
+    }
+
+    /// The error a render stopped at its deadline reports.
+    pub fn exceeded(&self) -> String {
+        ArchetypeError::ResourceLimitExceeded(LimitExceeded::Timeout(self.timeout)).to_string()
+    }
+}
+
+/// Drain as [`drain_until_prompt_or_complete`] does, for no longer than the
+/// render has left. Scripts only check their deadline between Lua
+/// instructions; this holds a render blocked in Rust — in a long `shell`
+/// call, say — to it as well. Once the caller drops the session's channels,
+/// the render has nothing left to talk to and winds down.
+pub async fn drain_within(
+    deadline: Option<Deadline>,
+    script_rx: &mut mpsc::Receiver<ScriptMessage>,
+    client_tx: &mpsc::Sender<ClientMessage>,
+    segments: &mut Vec<SegmentRef>,
+    transaction: &mut Option<Transaction>,
+) -> Result<DrainResult, String> {
+    let drain = drain_until_prompt_or_complete(script_rx, client_tx, segments, transaction);
+    let Some(deadline) = deadline else {
+        return drain.await;
+    };
+    tokio::time::timeout_at(deadline.at, drain)
+        .await
+        .unwrap_or_else(|_| Err(deadline.exceeded()))
+}
+
 /// Drain messages from the render thread until we hit a prompt or completion.
-/// Auto-Acks WriteFile/WriteDirectory, accumulates logs, and tracks the open
-/// page/section stack so each prompt can say where it is.
+/// Carries out and Acks writes and file operations, accumulates logs, and
+/// tracks the open page/section stack so each prompt can say where it is.
 ///
 /// `segments` is borrowed rather than owned because it spans drains: an agent
 /// answering the third field of a page is four calls into a stack that opened
-/// on the first.
+/// on the first. `transaction` spans drains for the same reason — a
+/// transactional render stages its writes there and only commits them, and
+/// reports them as written, when it completes successfully.
 pub async fn drain_until_prompt_or_complete(
     script_rx: &mut mpsc::Receiver<ScriptMessage>,
     client_tx: &mpsc::Sender<ClientMessage>,
     segments: &mut Vec<SegmentRef>,
+    transaction: &mut Option<Transaction>,
 ) -> Result<DrainResult, String> {
     let mut logs = Vec::new();
     let mut files_written = Vec::new();
@@ -71,41 +136,127 @@ pub async fn drain_until_prompt_or_complete(
     loop {
         match script_rx.recv().await {
             Some(ScriptMessage::WriteFile(info)) => {
-                // Write the file to disk
-                let path = Path::new(&info.destination);
-                if let Some(parent) = path.parent() {
-                    let _ = fs::create_dir_all(parent);
+                let response = match transaction.as_mut() {
+                    Some(transaction) => transaction.stage_file(info).map(|()| ClientMessage::Ack),
+                    None => {
+                        let result = write_file(&info);
+                        files_written.push(info.destination.clone());
+                        result.map(ClientMessage::Written)
+                    }
                 }
-                let write_result = fs::write(path, &info.contents);
-                files_written.push(info.destination.clone());
-                let response = match write_result {
-                    Ok(()) => ClientMessage::Ack,
-                    Err(e) => ClientMessage::Error(format!("Failed to write {}: {}", info.destination, e)),
-                };
+                .unwrap_or_else(ClientMessage::Error);
                 client_tx.send(response).await
                     .map_err(|_| "Render thread died while sending Ack".to_string())?;
             }
             Some(ScriptMessage::WriteDirectory(info)) => {
-                // Create the directory
-                let _ = fs::create_dir_all(&info.path);
-                files_written.push(info.path.clone());
+                match transaction.as_mut() {
+                    Some(transaction) => transaction.stage_directory(info),
+                    None => {
+                        // Create the directory
+                        let _ = fs::create_dir_all(&info.path);
+                        files_written.push(info.path.clone());
+                    }
+                }
                 client_tx.send(ClientMessage::Ack).await
                     .map_err(|_| "Render thread died while sending Ack".to_string())?;
             }
-            // Artifacts need no special handling here: an archive is emitted as
-            // an ordinary WriteFile, so it is already in `files_written`.
-            Some(ScriptMessage::CompleteSuccess(_)) => {
+            Some(ScriptMessage::WriteSymlink(info)) => {
+                let response = match transaction.as_mut() {
+                    Some(transaction) => transaction.stage_symlink(info).map(|()| ClientMessage::Ack),
+                    None => {
+                        let result = write_symlink(&info);
+                        files_written.push(info.path.clone());
+                        result.map(|()| ClientMessage::Ack)
+                    }
+                }
+                .unwrap_or_else(ClientMessage::Error);
+                client_tx.send(response).await
+                    .map_err(|_| "Render thread died while sending Ack".to_string())?;
+            }
+            Some(ScriptMessage::FileOperation(operation)) => {
+                let response = match transaction.as_mut() {
+                    Some(transaction) => {
+                        transaction.stage_operation(operation);
+                        Ok(ClientMessage::Ack)
+                    }
+                    None => {
+                        let result = apply_file_operation(&operation);
+                        if !matches!(operation, FileOperation::Delete { .. }) {
+                            files_written.push(operation.path().to_string());
+                        }
+                        result.map(|()| ClientMessage::Ack)
+                    }
+                }
+                .unwrap_or_else(ClientMessage::Error);
+                client_tx.send(response).await
+                    .map_err(|_| "Render thread died while sending Ack".to_string())?;
+            }
+            Some(ScriptMessage::ReadFile(path)) => {
+                let response = read_file(transaction.as_ref(), &path);
+                client_tx.send(response).await
+                    .map_err(|_| "Render thread died while reading back a file".to_string())?;
+            }
+            Some(ScriptMessage::ListFiles(path)) => {
+                let response = list_files(transaction.as_ref(), &path);
+                client_tx.send(response).await
+                    .map_err(|_| "Render thread died while listing files".to_string())?;
+            }
+            Some(ScriptMessage::BeginTransaction(destination)) => {
+                let response = match transaction {
+                    Some(_) => ClientMessage::Ack,
+                    None => match Transaction::begin(camino::Utf8Path::new(&destination)) {
+                        Ok(opened) => {
+                            *transaction = Some(opened);
+                            ClientMessage::Ack
+                        }
+                        Err(e) => ClientMessage::Error(format!("Unable to stage writes for {}: {}", destination, e)),
+                    },
+                };
+                client_tx.send(response).await
+                    .map_err(|_| "Render thread died while sending Ack".to_string())?;
+            }
+            // An archive is emitted as an ordinary WriteFile, so it is already
+            // in `files_written`. Staged files only learn their outcomes at
+            // commit, and are settled into the manifest then.
+            Some(ScriptMessage::CompleteSuccess(mut artifacts)) => {
+                if let Some(transaction) = transaction.take() {
+                    let committed = transaction.commit(|staged| match staged {
+                        Staged::File(info) => write_file(info).map(Some),
+                        Staged::Symlink(info) => write_symlink(info).map(|()| None),
+                        Staged::Operation(operation) => apply_file_operation(operation).map(|()| None),
+                    });
+                    match committed {
+                        Ok(committed) => {
+                            committed.settle(&mut artifacts);
+                            files_written.extend(committed.paths().map(String::from));
+                        }
+                        Err(message) => {
+                            return Ok(DrainResult {
+                                logs,
+                                files_written,
+                                outcome: DrainOutcome::Complete {
+                                    success: false,
+                                    message: Some(format!("Commit failed: {}", message)),
+                                    artifacts: vec![],
+                                },
+                            });
+                        }
+                    }
+                }
                 return Ok(DrainResult {
                     logs,
                     files_written,
-                    outcome: DrainOutcome::Complete { success: true, message: None },
+                    outcome: DrainOutcome::Complete { success: true, message: None, artifacts },
                 });
             }
             Some(ScriptMessage::CompleteError(msg)) => {
+                if let Some(transaction) = transaction.take() {
+                    transaction.discard();
+                }
                 return Ok(DrainResult {
                     logs,
                     files_written,
-                    outcome: DrainOutcome::Complete { success: false, message: Some(msg) },
+                    outcome: DrainOutcome::Complete { success: false, message: Some(msg), artifacts: vec![] },
                 });
             }
             // Containers: no reply expected, but the agent gets to know which
@@ -136,6 +287,135 @@ pub async fn drain_until_prompt_or_complete(
     }
 }
 
+/// A destination file as the render has left it: as staged, if there's a
+/// transaction, or on disk.
+fn read_file(transaction: Option<&Transaction>, path: &str) -> ClientMessage {
+    let contents = match transaction {
+        Some(transaction) => transaction.read(camino::Utf8Path::new(path)),
+        None => match fs::read(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read {}: {}", path, e)),
+        },
+    };
+    match contents {
+        Ok(Some(contents)) => String::from_utf8(contents)
+            .map(ClientMessage::String)
+            .unwrap_or_else(|_| ClientMessage::Error(format!("Failed to read {}: not UTF-8 text", path))),
+        Ok(None) => ClientMessage::None,
+        Err(message) => ClientMessage::Error(message),
+    }
+}
+
+/// What is beneath a destination directory as the render has left it: as
+/// staged, if there's a transaction, or on disk.
+fn list_files(transaction: Option<&Transaction>, path: &str) -> ClientMessage {
+    let path = camino::Utf8Path::new(path);
+    let listed = match transaction {
+        Some(transaction) => transaction.list(path),
+        None => Ok(Overlay::default().list(path)),
+    };
+    match listed {
+        Ok(listed) => ClientMessage::Array(listed.into_iter().map(|path| path.into_string()).collect()),
+        Err(message) => ClientMessage::Error(message),
+    }
+}
+
+/// Write a file to disk, creating its parent directory first, and say what
+/// became of it. A file that already holds the contents is left alone.
+fn write_file(info: &WriteFileInfo) -> Result<FileOutcome, String> {
+    let path = Path::new(&info.destination);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let outcome = match fs::read(path) {
+        Ok(existing) if existing == info.contents => FileOutcome::Unchanged,
+        Ok(_) => FileOutcome::Overwritten,
+        Err(_) => FileOutcome::Created,
+    };
+    if outcome != FileOutcome::Unchanged {
+        fs::write(path, &info.contents).map_err(|e| format!("Failed to write {}: {}", info.destination, e))?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = info.mode {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .map_err(|e| format!("Failed to set mode on {}: {}", info.destination, e))?;
+    }
+    Ok(outcome)
+}
+
+/// Create a symlink, replacing whatever was at its path. Symlinks are a Unix
+/// affair here; elsewhere the link is reported as an error.
+fn write_symlink(info: &WriteSymlinkInfo) -> Result<(), String> {
+    let path = Path::new(&info.path);
+    if path.symlink_metadata().is_ok() {
+        fs::remove_file(path).map_err(|e| format!("Failed to replace {}: {}", info.path, e))?;
+    }
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(&info.target, path);
+    #[cfg(not(unix))]
+    let linked = Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "symlinks are not supported"));
+    linked.map_err(|e| format!("Failed to link {}: {}", info.path, e))
+}
+
+/// Append, delete, move, or copy. Like `write_file`, whatever is already at
+/// a destination is replaced: there is nobody here to ask.
+fn apply_file_operation(operation: &FileOperation) -> Result<(), String> {
+    let failed = |path: &str, e: std::io::Error| format!("Failed to update {}: {}", path, e);
+    match operation {
+        FileOperation::Append { path, contents } => {
+            use std::io::Write;
+            if let Some(parent) = Path::new(path).parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(contents))
+                .map_err(|e| failed(path, e))
+        }
+        FileOperation::Delete { path } => remove_path(Path::new(path)).map_err(|e| failed(path, e)),
+        FileOperation::Move { source, destination, .. } => {
+            let target = Path::new(destination);
+            remove_path(target).map_err(|e| failed(destination, e))?;
+            if let Some(parent) = target.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            fs::rename(source, target).map_err(|e| failed(destination, e))
+        }
+        FileOperation::Copy { source, destination, .. } => {
+            copy_path(Path::new(source), Path::new(destination)).map_err(|e| failed(destination, e))
+        }
+    }
+}
+
+/// Remove a file or directory tree; a missing path is already removed.
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(_) => Ok(()),
+    }
+}
+
+fn copy_path(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if source.is_dir() {
+        fs::create_dir_all(destination)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source, destination).map(|_| ())
+    }
+}
+
 /// Interpret a string a client sent where a list was expected. Tries a
 /// JSON array first (stringifying clients commonly send the encoded
 /// array), then falls back to comma-splitting with optional surrounding
@@ -4,19 +4,139 @@
 //! job wants to read the archive path or repo URL out of the output. Silence on
 //! success is fine; silence when an artifact exists is a dead end for whoever
 //! has to find the zip afterwards.
+//!
+//! Files are summed up in a line rather than listed — a render writes
+//! hundreds. A caller that wants each one, with its outcome and hash, asks
+//! for a [`Report`] file instead.
+
+use std::fs;
+
+use camino::Utf8PathBuf;
+use log::error;
+use serde::Serialize;
+
+use archetect_api::{Artifact, ArtifactKind, FileOutcome};
+
+/// The format of a [`Report`] file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Yaml,
+}
 
-use archetect_api::{Artifact, ArtifactKind};
+impl ReportFormat {
+    pub fn from_name(name: &str) -> Option<ReportFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(ReportFormat::Json),
+            "yaml" | "yml" => Some(ReportFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Where to write a machine-readable account of the render: every artifact
+/// on success, or the error on failure.
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub path: Utf8PathBuf,
+    pub format: ReportFormat,
+}
+
+impl Report {
+    pub fn new<P: Into<Utf8PathBuf>>(path: P, format: ReportFormat) -> Report {
+        Report { path: path.into(), format }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Success,
+    Error,
+}
+
+#[derive(Serialize)]
+struct Contents<'a> {
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+    artifacts: &'a [Artifact],
+}
 
 pub fn report_artifacts(artifacts: &[Artifact]) {
-    if artifacts.is_empty() {
-        return;
+    let listed = artifacts
+        .iter()
+        .filter(|artifact| matches!(artifact.kind, ArtifactKind::Archive | ArtifactKind::Repository))
+        .collect::<Vec<_>>();
+    if !listed.is_empty() {
+        println!("Artifacts:");
+        for artifact in listed {
+            let kind = match artifact.kind {
+                ArtifactKind::Archive => "archive",
+                _ => "repository",
+            };
+            println!("  {}: {}", kind, artifact.locator());
+        }
+    }
+    if let Some(summary) = summarize_files(artifacts) {
+        println!("Files: {}", summary);
     }
-    println!("Artifacts:");
+}
+
+/// `3 created, 1 unchanged, 1 removed`, counting only what happened.
+fn summarize_files(artifacts: &[Artifact]) -> Option<String> {
+    let labels = [
+        "created",
+        "overwritten",
+        "merged",
+        "conflicted",
+        "preserved",
+        "unchanged",
+        "written",
+        "removed",
+    ];
+    let mut counts = [0usize; 8];
     for artifact in artifacts {
-        let kind = match artifact.kind {
-            ArtifactKind::Archive => "archive",
-            ArtifactKind::Repository => "repository",
+        let slot = match (&artifact.kind, artifact.outcome) {
+            (ArtifactKind::File, Some(FileOutcome::Created)) => 0,
+            (ArtifactKind::File, Some(FileOutcome::Overwritten)) => 1,
+            (ArtifactKind::File, Some(FileOutcome::Merged)) => 2,
+            (ArtifactKind::File, Some(FileOutcome::Conflicted)) => 3,
+            (ArtifactKind::File, Some(FileOutcome::Preserved)) => 4,
+            (ArtifactKind::File, Some(FileOutcome::Unchanged)) => 5,
+            (ArtifactKind::File, None) => 6,
+            (ArtifactKind::Removed, _) => 7,
+            _ => continue,
         };
-        println!("  {}: {}", kind, artifact.locator());
+        counts[slot] += 1;
+    }
+    let parts = labels
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(label, count)| format!("{} {}", count, label))
+        .collect::<Vec<_>>();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// Write `report` for a render that produced `artifacts`, or that failed
+/// with `error`. A report that can't be written is logged rather than
+/// failing a render that has already happened.
+pub fn write_report(report: &Report, artifacts: &[Artifact], error: Option<&str>) {
+    let contents = Contents {
+        status: if error.is_some() { Status::Error } else { Status::Success },
+        error,
+        artifacts,
+    };
+    let serialized = match report.format {
+        ReportFormat::Json => serde_json::to_string_pretty(&contents)
+            .map(|json| json + "\n")
+            .map_err(|error| error.to_string()),
+        ReportFormat::Yaml => serde_yaml::to_string(&contents).map_err(|error| error.to_string()),
+    };
+    let written = serialized.and_then(|serialized| fs::write(&report.path, serialized).map_err(|error| error.to_string()));
+    if let Err(message) = written {
+        error!("Unable to write report to {}: {}", report.path, message);
     }
 }
@@ -0,0 +1,74 @@
+use log::{debug, error};
+
+use archetect_api::Artifact;
+
+use crate::artifact_report::{report_artifacts, write_report, Report};
+use crate::transaction::Transaction;
+use crate::transaction_handler::{handle_commit, handle_discard};
+
+/// Commit anything staged, then say what the render produced. Files staged
+/// by a transaction only learn their outcomes here, so they're settled
+/// before anything is reported. Fails if the commit did: the render's
+/// writes never reached the destination, so it didn't succeed after all.
+pub fn handle_complete_success(
+    transaction: Option<Transaction>,
+    mut artifacts: Vec<Artifact>,
+    report: Option<&Report>,
+) -> Result<(), String> {
+    debug!("Archetype completed successfully");
+    match handle_commit(transaction) {
+        Ok(committed) => {
+            if let Some(committed) = committed {
+                committed.settle(&mut artifacts);
+            }
+            report_artifacts(&artifacts);
+            if let Some(report) = report {
+                write_report(report, &artifacts, None);
+            }
+            Ok(())
+        }
+        Err(message) => {
+            let message = format!("Commit failed: {}", message);
+            error!("{}", message);
+            if let Some(report) = report {
+                write_report(report, &[], Some(&message));
+            }
+            Err(message)
+        }
+    }
+}
+
+pub fn handle_complete_error(transaction: Option<Transaction>, message: &str, report: Option<&Report>) {
+    // CompleteError is a session-termination signal. The actionable error
+    // was already surfaced via LogError at the call site — logging it again
+    // here just duplicates it for the user.
+    debug!("Archetype completed with error: {}", message);
+    handle_discard(transaction);
+    if let Some(report) = report {
+        write_report(report, &[], Some(message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use archetect_api::{ExistingFilePolicy, FileOperation};
+    use camino::Utf8PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn a_failed_commit_fails_the_completion() {
+        let root = tempfile::tempdir().unwrap();
+        let destination = Utf8PathBuf::from_path_buf(root.path().join("project")).unwrap();
+        let mut transaction = Transaction::begin(&destination).unwrap();
+        transaction.stage_operation(FileOperation::Move {
+            source: destination.join("missing.txt").to_string(),
+            destination: destination.join("moved.txt").to_string(),
+            existing_file_policy: ExistingFilePolicy::Overwrite,
+        });
+
+        let err = handle_complete_success(Some(transaction), Vec::new(), None).unwrap_err();
+        assert!(err.starts_with("Commit failed:"), "{}", err);
+        assert!(handle_complete_success(None, Vec::new(), None).is_ok());
+    }
+}
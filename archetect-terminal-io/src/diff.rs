@@ -0,0 +1,173 @@
+//! The diff shown when a write changes a file that is already there, and the
+//! patch a `--dry-run --patch` render writes instead of changing anything.
+
+use std::fmt::Write;
+use std::io::Write as _;
+
+use camino::Utf8Path;
+use content_inspector::ContentType;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use sha1::{Digest, Sha1};
+use similar::{ChangeTag, TextDiff};
+
+/// Cap diff output so a giant generated file doesn't flood the terminal.
+/// Authors can still inspect the full file on disk after the render.
+const MAX_DIFF_LINES: usize = 200;
+
+/// A unified diff from `existing` to `new_contents`, headed with `path`, one
+/// line per change and each line newline-terminated. Binary contents get a
+/// size summary instead.
+pub fn format_diff(path: &Utf8Path, existing: &[u8], new_contents: &[u8]) -> String {
+    let mut out = String::new();
+
+    if existing == new_contents {
+        let _ = writeln!(out, "--- {} (no change) ---", path);
+        return out;
+    }
+
+    let existing_binary = matches!(
+        content_inspector::inspect(existing),
+        ContentType::BINARY
+    );
+    let new_binary = matches!(
+        content_inspector::inspect(new_contents),
+        ContentType::BINARY
+    );
+
+    if existing_binary || new_binary {
+        let _ = writeln!(
+            out,
+            "--- {} (binary, {} → {} bytes) ---",
+            path,
+            existing.len(),
+            new_contents.len()
+        );
+        return out;
+    }
+
+    let old_text = String::from_utf8_lossy(existing);
+    let new_text = String::from_utf8_lossy(new_contents);
+    let diff = TextDiff::from_lines(&old_text, &new_text);
+
+    let _ = writeln!(out, "--- {} ---", path);
+    for (emitted, change) in diff.iter_all_changes().enumerate() {
+        if emitted >= MAX_DIFF_LINES {
+            let _ = writeln!(out, "... (diff truncated at {} lines)", MAX_DIFF_LINES);
+            return out;
+        }
+        let prefix = match change.tag() {
+            ChangeTag::Delete => "-",
+            ChangeTag::Insert => "+",
+            ChangeTag::Equal => " ",
+        };
+        // change.value() includes the trailing newline; trim it so we can
+        // re-emit one consistently.
+        let line = change.value().trim_end_matches('\n');
+        let _ = writeln!(out, "{}{}", prefix, line);
+    }
+    out
+}
+
+/// One file's section of a `git apply`-compatible patch, from `old` to `new`.
+/// Each side is the file's contents and git mode (`0o100644`, `0o100755`, or
+/// `0o120000` for a symlink, whose contents are its target), or `None` where
+/// there is no file. Empty when nothing would change.
+///
+/// A binary change is written as a `GIT binary patch` carrying both
+/// versions, as `git diff --binary` writes it.
+pub fn format_patch(path: &str, old: Option<(&[u8], u32)>, new: Option<(&[u8], u32)>) -> String {
+    let mut out = String::new();
+    if old == new {
+        return out;
+    }
+
+    let _ = writeln!(out, "diff --git a/{0} b/{0}", path);
+    match (old, new) {
+        (None, Some((_, mode))) => {
+            let _ = writeln!(out, "new file mode {:o}", mode);
+        }
+        (Some((_, mode)), None) => {
+            let _ = writeln!(out, "deleted file mode {:o}", mode);
+        }
+        (Some((_, old_mode)), Some((_, new_mode))) if old_mode != new_mode => {
+            let _ = writeln!(out, "old mode {:o}\nnew mode {:o}", old_mode, new_mode);
+        }
+        _ => {}
+    }
+
+    let existing = old.map(|(contents, _)| contents).unwrap_or_default();
+    let new_contents = new.map(|(contents, _)| contents).unwrap_or_default();
+    if existing == new_contents {
+        return out;
+    }
+    let old_name = old.map_or_else(|| "/dev/null".to_string(), |_| format!("a/{}", path));
+    let new_name = new.map_or_else(|| "/dev/null".to_string(), |_| format!("b/{}", path));
+
+    let binary = |contents: &[u8]| matches!(content_inspector::inspect(contents), ContentType::BINARY);
+    if binary(existing) || binary(new_contents) {
+        let _ = writeln!(
+            out,
+            "index {}..{}",
+            blob_id(old.map(|(contents, _)| contents)),
+            blob_id(new.map(|(contents, _)| contents))
+        );
+        let _ = writeln!(out, "GIT binary patch");
+        write_literal(&mut out, new_contents);
+        write_literal(&mut out, existing);
+        return out;
+    }
+
+    let old_text = String::from_utf8_lossy(existing);
+    let new_text = String::from_utf8_lossy(new_contents);
+    let diff = TextDiff::from_lines(&old_text, &new_text);
+    let _ = write!(out, "{}", diff.unified_diff().header(&old_name, &new_name));
+    out
+}
+
+/// Git's name for a blob holding `contents`; all zeroes where there is none.
+/// `git apply` checks a binary patch against these before applying it.
+fn blob_id(contents: Option<&[u8]>) -> String {
+    match contents {
+        Some(contents) => {
+            let mut hasher = Sha1::new();
+            hasher.update(format!("blob {}\0", contents.len()));
+            hasher.update(contents);
+            format!("{:x}", hasher.finalize())
+        }
+        None => "0".repeat(40),
+    }
+}
+
+/// A `literal` hunk: `contents` deflated and base85-encoded, 52 bytes to a
+/// line, each line led by a letter giving its length.
+fn write_literal(out: &mut String, contents: &[u8]) {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let deflated = encoder
+        .write_all(contents)
+        .and_then(|()| encoder.finish())
+        .expect("deflating into memory can't fail");
+
+    let _ = writeln!(out, "literal {}", contents.len());
+    for line in deflated.chunks(52) {
+        out.push(match line.len() {
+            length @ 1..=26 => (b'A' + length as u8 - 1) as char,
+            length => (b'a' + length as u8 - 27) as char,
+        });
+        for word in line.chunks(4) {
+            let mut bytes = [0u8; 4];
+            bytes[..word.len()].copy_from_slice(word);
+            let mut value = u32::from_be_bytes(bytes);
+            let mut digits = [0u8; 5];
+            for digit in digits.iter_mut().rev() {
+                *digit = ALPHABET[(value % 85) as usize];
+                value /= 85;
+            }
+            out.extend(digits.iter().map(|digit| *digit as char));
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+}
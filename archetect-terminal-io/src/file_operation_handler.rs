@@ -0,0 +1,153 @@
+use std::fs;
+use std::io::Write;
+
+use camino::Utf8Path;
+use inquire::Confirm;
+use log::{debug, warn};
+
+use archetect_api::{ClientMessage, ExistingFilePolicy, FileOperation, WriteFileInfo};
+use crate::responder::Responder;
+use crate::write_file_handler::apply_write_file;
+
+pub fn handle_file_operation(operation: FileOperation, responses: &dyn Responder) {
+    match apply_file_operation(&operation) {
+        Ok(()) => responses.respond(ClientMessage::Ack),
+        Err(message) => responses.respond(ClientMessage::Error(message)),
+    }
+}
+
+pub(crate) fn apply_file_operation(operation: &FileOperation) -> Result<(), String> {
+    match operation {
+        FileOperation::Append { path, contents } => append(Utf8Path::new(path), contents),
+        FileOperation::Delete { path } => delete(Utf8Path::new(path)),
+        FileOperation::Move {
+            source,
+            destination,
+            existing_file_policy,
+        } => move_path(Utf8Path::new(source), Utf8Path::new(destination), existing_file_policy),
+        FileOperation::Copy {
+            source,
+            destination,
+            existing_file_policy,
+        } => copy_path(Utf8Path::new(source), Utf8Path::new(destination), existing_file_policy),
+    }
+}
+
+fn append(path: &Utf8Path, contents: &[u8]) -> Result<(), String> {
+    debug!("Appending to {:?}", path);
+    create_parent(path)?;
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .map_err(|error| format!("{}: {}", path, error))
+}
+
+fn delete(path: &Utf8Path) -> Result<(), String> {
+    let Ok(metadata) = path.symlink_metadata() else {
+        debug!("Nothing to delete at {:?}", path);
+        return Ok(());
+    };
+    debug!("Deleting {:?}", path);
+    let removed = if metadata.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+    removed.map_err(|error| format!("{}: {}", path, error))
+}
+
+/// A move onto something that already exists goes through the policy first.
+/// Whenever the policy keeps what is there, the source stays put as well, so
+/// nothing is lost either way. `Merge` only applies between two files; it
+/// merges the source into the destination and then removes the source.
+fn move_path(source: &Utf8Path, destination: &Utf8Path, policy: &ExistingFilePolicy) -> Result<(), String> {
+    if source.symlink_metadata().is_err() {
+        return Err(format!("Cannot move {}: no such file or directory", source));
+    }
+    if destination.symlink_metadata().is_ok() {
+        match policy {
+            ExistingFilePolicy::Overwrite => {}
+            ExistingFilePolicy::Preserve => {
+                debug!("Preserving {:?}", destination);
+                return Ok(());
+            }
+            ExistingFilePolicy::Prompt => {
+                if !confirm(&format!("Replace '{}' with '{}'?", destination, source)) {
+                    debug!("Preserving {:?}", destination);
+                    return Ok(());
+                }
+            }
+            ExistingFilePolicy::Error => {
+                return Err(format!("File already exists: {} (if_exists = Existing.Error)", destination));
+            }
+            ExistingFilePolicy::Merge => {
+                if source.is_file() && destination.is_file() {
+                    copy_file(source, destination, policy)?;
+                    return delete(source);
+                }
+                warn!("CONFLICT: {} (cannot merge {} into it, kept both)", destination, source);
+                return Ok(());
+            }
+        }
+        delete(destination)?;
+    }
+    debug!("Moving {:?} to {:?}", source, destination);
+    create_parent(destination)?;
+    fs::rename(source, destination).map_err(|error| format!("{} -> {}: {}", source, destination, error))
+}
+
+/// Copy a file, or a directory's files one by one, each through the usual
+/// write path — so the policy, diffs, and merges behave as for a rendered file.
+fn copy_path(source: &Utf8Path, destination: &Utf8Path, policy: &ExistingFilePolicy) -> Result<(), String> {
+    if source.is_dir() {
+        debug!("Copying directory {:?} to {:?}", source, destination);
+        fs::create_dir_all(destination).map_err(|error| format!("{}: {}", destination, error))?;
+        for entry in source.read_dir_utf8().map_err(|error| format!("{}: {}", source, error))? {
+            let entry = entry.map_err(|error| format!("{}: {}", source, error))?;
+            copy_path(entry.path(), &destination.join(entry.file_name()), policy)?;
+        }
+        Ok(())
+    } else if source.is_file() {
+        copy_file(source, destination, policy)
+    } else {
+        Err(format!("Cannot copy {}: no such file or directory", source))
+    }
+}
+
+fn copy_file(source: &Utf8Path, destination: &Utf8Path, policy: &ExistingFilePolicy) -> Result<(), String> {
+    let contents = fs::read(source).map_err(|error| format!("{}: {}", source, error))?;
+    create_parent(destination)?;
+    apply_write_file(&WriteFileInfo {
+        destination: destination.to_string(),
+        contents,
+        existing_file_policy: policy.clone(),
+        shadow: None,
+        mode: file_mode(source),
+    })
+    .map(|_| ())
+}
+
+fn create_parent(path: &Utf8Path) -> Result<(), String> {
+    match path.parent() {
+        Some(parent) if !parent.as_str().is_empty() => {
+            fs::create_dir_all(parent).map_err(|error| format!("{}: {}", parent, error))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn confirm(message: &str) -> bool {
+    Confirm::new(message)
+        .prompt_skippable()
+        .unwrap_or_default()
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn file_mode(path: &Utf8Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).ok().map(|metadata| metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_path: &Utf8Path) -> Option<u32> {
+    None
+}
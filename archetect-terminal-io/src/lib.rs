@@ -1,19 +1,30 @@
 mod artifact_report;
 mod bool_prompt_handler;
+mod completion_handler;
+pub mod diff;
 mod editor_prompt_info;
+mod file_operation_handler;
 mod int_prompt_handler;
+mod list_files_handler;
 mod list_prompt_handler;
+pub mod merge;
 mod multiselect_prompt_handler;
+pub mod overlay;
+mod read_file_handler;
 pub mod responder;
 mod segment_handler;
 mod select_prompt_handler;
 mod terminal_client;
 mod terminal_io_driver;
 mod text_prompt_handler;
+pub mod transaction;
+mod transaction_handler;
 mod write_directory_handler;
 mod write_file_handler;
+mod write_symlink_handler;
 
 use inquire::ui::{Color, RenderConfig, Styled};
+pub use artifact_report::{Report, ReportFormat};
 pub use terminal_client::TerminalClient;
 pub use terminal_io_driver::TerminalScriptIoHandle;
 
@@ -0,0 +1,23 @@
+use camino::Utf8Path;
+use log::debug;
+
+use archetect_api::ClientMessage;
+use crate::overlay::Overlay;
+use crate::responder::Responder;
+use crate::transaction::Transaction;
+
+/// Answer a `ListFiles` with what is beneath the directory as the render has
+/// left it: as `transaction` has staged it, if there is one, or as it is on
+/// disk.
+pub fn handle_list_files(transaction: Option<&Transaction>, path: &str, responses: &dyn Responder) {
+    debug!("Listing {:?}", path);
+    let path = Utf8Path::new(path);
+    let listed = match transaction {
+        Some(transaction) => transaction.list(path),
+        None => Ok(Overlay::default().list(path)),
+    };
+    responses.respond(match listed {
+        Ok(listed) => ClientMessage::Array(listed.into_iter().map(|path| path.into_string()).collect()),
+        Err(message) => ClientMessage::Error(message),
+    });
+}
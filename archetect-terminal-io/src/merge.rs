@@ -0,0 +1,98 @@
+use content_inspector::ContentType;
+
+/// Result of folding newly generated contents into a file the user may have
+/// edited since the last render.
+pub enum MergeOutcome {
+    /// Every hunk resolved; write these contents.
+    Clean(Vec<u8>),
+    /// At least one hunk collided; these contents carry conflict markers.
+    Conflicted(Vec<u8>),
+    /// Both sides changed a binary file. There is nothing sensible to mark
+    /// up, so the existing file stays as it is.
+    Unmergeable,
+    /// The two sides differ and there is no record of the last render to
+    /// merge against. Every differing line would be a conflict — there is no
+    /// way to tell an edit the user made from a change in the archetype — so
+    /// the caller decides as it would without merging.
+    NoBase,
+}
+
+/// Three-way merge of `ours` (the file on disk) and `theirs` (what the
+/// archetype generates now) against `base` (what it generated last time).
+pub fn three_way(base: Option<&[u8]>, ours: &[u8], theirs: &[u8]) -> MergeOutcome {
+    if ours == theirs || base == Some(theirs) {
+        return MergeOutcome::Clean(ours.to_vec());
+    }
+    if base == Some(ours) {
+        return MergeOutcome::Clean(theirs.to_vec());
+    }
+    let Some(base) = base else {
+        return MergeOutcome::NoBase;
+    };
+
+    let (Some(base), Some(ours), Some(theirs)) = (as_text(base), as_text(ours), as_text(theirs)) else {
+        return MergeOutcome::Unmergeable;
+    };
+
+    match diffy::merge(base, ours, theirs) {
+        Ok(merged) => MergeOutcome::Clean(merged.into_bytes()),
+        Err(conflicted) => MergeOutcome::Conflicted(conflicted.into_bytes()),
+    }
+}
+
+fn as_text(contents: &[u8]) -> Option<&str> {
+    if matches!(content_inspector::inspect(contents), ContentType::BINARY) {
+        return None;
+    }
+    std::str::from_utf8(contents).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(outcome: MergeOutcome) -> String {
+        match outcome {
+            MergeOutcome::Clean(contents) => String::from_utf8(contents).unwrap(),
+            _ => panic!("expected a clean merge"),
+        }
+    }
+
+    #[test]
+    fn edits_on_both_sides_merge_cleanly() {
+        let base = b"one\ntwo\nthree\nfour\nfive\n";
+        let ours = b"one\nTWO\nthree\nfour\nfive\n";
+        let theirs = b"one\ntwo\nthree\nfour\nFIVE\n";
+        assert_eq!(merged(three_way(Some(base), ours, theirs)), "one\nTWO\nthree\nfour\nFIVE\n");
+    }
+
+    #[test]
+    fn an_untouched_side_takes_the_other() {
+        let base = b"generated\n";
+        assert_eq!(merged(three_way(Some(base), base, b"regenerated\n")), "regenerated\n");
+        assert_eq!(merged(three_way(Some(base), b"edited\n", base)), "edited\n");
+    }
+
+    #[test]
+    fn colliding_hunks_are_marked() {
+        let outcome = three_way(Some(b"line\n"), b"ours\n", b"theirs\n");
+        let MergeOutcome::Conflicted(contents) = outcome else {
+            panic!("expected a conflict");
+        };
+        let contents = String::from_utf8(contents).unwrap();
+        assert!(contents.contains("<<<<<<<") && contents.contains(">>>>>>>"), "{}", contents);
+        assert!(contents.contains("ours") && contents.contains("theirs"), "{}", contents);
+    }
+
+    #[test]
+    fn binary_files_are_not_merged() {
+        let outcome = three_way(Some(b"\0base"), b"\0ours", b"\0theirs");
+        assert!(matches!(outcome, MergeOutcome::Unmergeable));
+    }
+
+    #[test]
+    fn without_a_base_nothing_is_merged() {
+        assert!(matches!(three_way(None, b"ours\n", b"theirs\n"), MergeOutcome::NoBase));
+        assert_eq!(merged(three_way(None, b"same\n", b"same\n")), "same\n");
+    }
+}
@@ -0,0 +1,463 @@
+//! What a dry run would have left in the destination, or a transaction
+//! will once it commits.
+//!
+//! `--dry-run` writes nothing, but still has to know what every write would
+//! have done: later edits read files earlier writes produced, and `--patch`
+//! is the difference between that and what is on disk. Each would-be
+//! write is applied to an in-memory layer over the destination instead,
+//! under the same existing-file policy the client would have honoured. A
+//! `Prompt` write is taken as accepted, so the patch shows what saying yes
+//! would do. A transaction replays its staged writes into one the same way
+//! when the render reads a file back or lists a directory.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use archetect_api::{
+    ExistingFilePolicy, FileOperation, ScriptMessage, WriteDirectoryInfo, WriteFileInfo, WriteSymlinkInfo,
+};
+use crate::diff::format_patch;
+use crate::merge::{three_way, MergeOutcome};
+
+const GIT_FILE: u32 = 0o100644;
+const GIT_EXECUTABLE: u32 = 0o100755;
+const GIT_SYMLINK: u32 = 0o120000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Entry {
+    File { contents: Vec<u8>, mode: Option<u32> },
+    Symlink(String),
+    /// Only listed: git has no place for a directory in a patch.
+    Directory,
+    Removed,
+}
+
+#[derive(Debug, Default)]
+pub struct Overlay {
+    entries: BTreeMap<Utf8PathBuf, Entry>,
+}
+
+impl Overlay {
+    /// Apply a write or file operation the client would have carried out.
+    pub fn apply(&mut self, command: &ScriptMessage) {
+        match command {
+            ScriptMessage::WriteDirectory(info) => self.write_directory(info),
+            ScriptMessage::WriteFile(info) => self.write_file(info),
+            ScriptMessage::WriteSymlink(info) => self.write_symlink(info),
+            ScriptMessage::FileOperation(operation) => self.apply_operation(operation),
+            _ => {}
+        }
+    }
+
+    /// What `path` would hold, if it would be a file.
+    pub fn read(&self, path: &Utf8Path) -> Option<Vec<u8>> {
+        self.written(path).unwrap_or_else(|| fs::read(path).ok())
+    }
+
+    /// What a write has left at `path`: `Some` of the file's contents, or of
+    /// `None` if it would be anything else or nothing, and `None` if no write
+    /// has touched it.
+    pub fn written(&self, path: &Utf8Path) -> Option<Option<Vec<u8>>> {
+        self.entries.get(path).map(|entry| match entry {
+            Entry::File { contents, .. } => Some(contents.clone()),
+            _ => None,
+        })
+    }
+
+    /// Every path beneath `directory` the render would leave, with what is
+    /// on disk there as the starting point.
+    pub fn list(&self, directory: &Utf8Path) -> BTreeSet<Utf8PathBuf> {
+        self.list_over(directory, list_disk(directory))
+    }
+
+    /// Every path beneath `directory` the render would leave, starting from
+    /// `listed` — what some client says is there. Whatever this overlay
+    /// removed goes, along with what was beneath it, and whatever it wrote
+    /// comes in, along with the directories leading to it.
+    pub fn list_over(&self, directory: &Utf8Path, mut listed: BTreeSet<Utf8PathBuf>) -> BTreeSet<Utf8PathBuf> {
+        let written = self
+            .entries
+            .range(directory.to_path_buf()..)
+            .take_while(|(path, _)| path.starts_with(directory))
+            .filter(|(path, _)| path.as_path() != directory)
+            .collect::<Vec<_>>();
+        for (path, entry) in &written {
+            if **entry == Entry::Removed {
+                listed.retain(|listed| !listed.starts_with(path));
+            }
+        }
+        for (path, entry) in written {
+            if *entry == Entry::Removed {
+                continue;
+            }
+            for ancestor in path.ancestors().take_while(|ancestor| *ancestor != directory) {
+                listed.insert(ancestor.to_path_buf());
+            }
+        }
+        listed
+    }
+
+    /// The patch from what is on disk to what the render would leave, with
+    /// paths relative to `root`. Anything written outside `root` is left out.
+    pub fn patch(&self, root: &Utf8Path) -> String {
+        let mut patch = String::new();
+        for (path, entry) in &self.entries {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            if *entry == Entry::Directory {
+                continue;
+            }
+            let relative = relative.as_str().replace('\\', "/");
+            let old = disk_entry(path);
+            let old_side = old.as_ref().and_then(|old| git_side(old, None));
+            let new_side = git_side(entry, old.as_ref());
+            patch.push_str(&format_patch(
+                &relative,
+                old_side.as_ref().map(|(contents, mode)| (contents.as_slice(), *mode)),
+                new_side.as_ref().map(|(contents, mode)| (contents.as_slice(), *mode)),
+            ));
+        }
+        patch
+    }
+
+    fn exists(&self, path: &Utf8Path) -> bool {
+        match self.entries.get(path) {
+            Some(entry) => *entry != Entry::Removed,
+            None => path.symlink_metadata().is_ok(),
+        }
+    }
+
+    /// Whether a write to `path` under `policy` goes ahead. `Merge` is
+    /// settled by the caller.
+    fn admits(&self, path: &Utf8Path, policy: &ExistingFilePolicy) -> bool {
+        !self.exists(path) || !matches!(policy, ExistingFilePolicy::Preserve | ExistingFilePolicy::Error)
+    }
+
+    fn write_file(&mut self, info: &WriteFileInfo) {
+        let path = Utf8PathBuf::from(&info.destination);
+        if !self.admits(&path, &info.existing_file_policy) {
+            return;
+        }
+        let contents = match (&info.existing_file_policy, self.read(&path)) {
+            (ExistingFilePolicy::Merge, Some(existing)) => {
+                let base = info.shadow.as_ref().and_then(|shadow| fs::read(shadow).ok());
+                match three_way(base.as_deref(), &existing, &info.contents) {
+                    MergeOutcome::Clean(merged) | MergeOutcome::Conflicted(merged) => merged,
+                    MergeOutcome::Unmergeable => return,
+                    // Settled as `Prompt` is: as if the user said yes.
+                    MergeOutcome::NoBase => info.contents.clone(),
+                }
+            }
+            _ => info.contents.clone(),
+        };
+        self.entries.insert(
+            path,
+            Entry::File {
+                contents,
+                mode: info.mode,
+            },
+        );
+    }
+
+    fn write_directory(&mut self, info: &WriteDirectoryInfo) {
+        let path = Utf8PathBuf::from(&info.path);
+        if !self.exists(&path) {
+            self.entries.insert(path, Entry::Directory);
+        }
+    }
+
+    fn write_symlink(&mut self, info: &WriteSymlinkInfo) {
+        let path = Utf8PathBuf::from(&info.path);
+        if self.admits(&path, &info.existing_file_policy) {
+            self.entries.insert(path, Entry::Symlink(info.target.clone()));
+        }
+    }
+
+    fn apply_operation(&mut self, operation: &FileOperation) {
+        match operation {
+            FileOperation::Append { path, contents } => {
+                let path = Utf8PathBuf::from(path);
+                let mode = match self.entries.get(&path) {
+                    Some(Entry::File { mode, .. }) => *mode,
+                    _ => None,
+                };
+                let mut appended = self.read(&path).unwrap_or_default();
+                appended.extend_from_slice(contents);
+                self.entries.insert(
+                    path,
+                    Entry::File {
+                        contents: appended,
+                        mode,
+                    },
+                );
+            }
+            FileOperation::Delete { path } => self.delete(Utf8Path::new(path)),
+            FileOperation::Move {
+                source,
+                destination,
+                existing_file_policy,
+            } => {
+                self.copy(Utf8Path::new(source), Utf8Path::new(destination), existing_file_policy);
+                self.delete(Utf8Path::new(source));
+            }
+            FileOperation::Copy {
+                source,
+                destination,
+                existing_file_policy,
+            } => self.copy(Utf8Path::new(source), Utf8Path::new(destination), existing_file_policy),
+        }
+    }
+
+    fn delete(&mut self, path: &Utf8Path) {
+        for (file, _) in self.beneath(path) {
+            self.entries.insert(file, Entry::Removed);
+        }
+        self.entries.insert(path.to_path_buf(), Entry::Removed);
+    }
+
+    fn copy(&mut self, source: &Utf8Path, destination: &Utf8Path, policy: &ExistingFilePolicy) {
+        for (file, entry) in self.beneath(source) {
+            let Ok(relative) = file.strip_prefix(source) else {
+                continue;
+            };
+            let target = if relative.as_str().is_empty() {
+                destination.to_path_buf()
+            } else {
+                destination.join(relative)
+            };
+            if self.admits(&target, policy) {
+                self.entries.insert(target, entry);
+            }
+        }
+    }
+
+    /// Every file and symlink at or beneath `root` as the render would leave
+    /// it: what is on disk, under whatever the overlay says.
+    fn beneath(&self, root: &Utf8Path) -> BTreeMap<Utf8PathBuf, Entry> {
+        let mut found = BTreeMap::new();
+        collect_disk(root, &mut found);
+        for (path, entry) in self.entries.range(root.to_path_buf()..) {
+            if !path.starts_with(root) {
+                break;
+            }
+            if *entry == Entry::Removed {
+                found.remove(path);
+            } else {
+                found.insert(path.clone(), entry.clone());
+            }
+        }
+        found
+    }
+}
+
+fn collect_disk(path: &Utf8Path, found: &mut BTreeMap<Utf8PathBuf, Entry>) {
+    match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => {
+            if let Ok(children) = path.read_dir_utf8() {
+                for child in children.flatten() {
+                    collect_disk(child.path(), found);
+                }
+            }
+        }
+        Ok(_) => {
+            if let Some(entry) = disk_entry(path) {
+                found.insert(path.to_path_buf(), entry);
+            }
+        }
+        Err(_) => {}
+    }
+}
+
+/// Every file, symlink, and directory beneath `directory` on disk.
+/// Symlinked directories are listed but not followed.
+fn list_disk(directory: &Utf8Path) -> BTreeSet<Utf8PathBuf> {
+    let mut listed = BTreeSet::new();
+    let mut pending = vec![directory.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let Ok(children) = directory.read_dir_utf8() else {
+            continue;
+        };
+        for child in children.flatten() {
+            if child.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                pending.push(child.path().to_path_buf());
+            }
+            listed.insert(child.into_path());
+        }
+    }
+    listed
+}
+
+/// The file or symlink at `path` on disk, if there is one.
+fn disk_entry(path: &Utf8Path) -> Option<Entry> {
+    let metadata = path.symlink_metadata().ok()?;
+    if metadata.is_symlink() {
+        let target = fs::read_link(path).ok()?;
+        return Some(Entry::Symlink(target.to_string_lossy().into_owned()));
+    }
+    if !metadata.is_file() {
+        return None;
+    }
+    Some(Entry::File {
+        contents: fs::read(path).ok()?,
+        mode: permissions(&metadata),
+    })
+}
+
+#[cfg(unix)]
+fn permissions(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn permissions(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// An entry as one side of a git patch. A write that doesn't set a mode
+/// keeps the mode of the file it replaces, as the client would.
+fn git_side(entry: &Entry, replacing: Option<&Entry>) -> Option<(Vec<u8>, u32)> {
+    match entry {
+        Entry::File { contents, mode } => {
+            let mode = mode.or(match replacing {
+                Some(Entry::File { mode, .. }) => *mode,
+                _ => None,
+            });
+            let git_mode = match mode {
+                Some(mode) if mode & 0o111 != 0 => GIT_EXECUTABLE,
+                _ => GIT_FILE,
+            };
+            Some((contents.clone(), git_mode))
+        }
+        Entry::Symlink(target) => Some((target.clone().into_bytes(), GIT_SYMLINK)),
+        Entry::Directory | Entry::Removed => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Utf8Path, contents: &str, policy: ExistingFilePolicy) -> ScriptMessage {
+        ScriptMessage::WriteFile(WriteFileInfo {
+            destination: path.to_string(),
+            contents: contents.as_bytes().to_vec(),
+            existing_file_policy: policy,
+            shadow: None,
+            mode: None,
+        })
+    }
+
+    #[test]
+    fn test_patch_covers_new_changed_and_removed_files() {
+        let root = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(root.path()).unwrap();
+        fs::write(root.join("kept.txt"), "one\ntwo\n").unwrap();
+        fs::write(root.join("gone.txt"), "bye\n").unwrap();
+
+        let mut overlay = Overlay::default();
+        overlay.apply(&write(
+            &root.join("kept.txt"),
+            "one\n2\n",
+            ExistingFilePolicy::Overwrite,
+        ));
+        overlay.apply(&write(
+            &root.join("src/new.txt"),
+            "hello\n",
+            ExistingFilePolicy::Overwrite,
+        ));
+        overlay.apply(&ScriptMessage::FileOperation(FileOperation::Delete {
+            path: root.join("gone.txt").to_string(),
+        }));
+
+        assert_eq!(
+            overlay.patch(root),
+            "diff --git a/gone.txt b/gone.txt\n\
+             deleted file mode 100644\n\
+             --- a/gone.txt\n\
+             +++ /dev/null\n\
+             @@ -1 +0,0 @@\n\
+             -bye\n\
+             diff --git a/kept.txt b/kept.txt\n\
+             --- a/kept.txt\n\
+             +++ b/kept.txt\n\
+             @@ -1,2 +1,2 @@\n \
+             one\n\
+             -two\n\
+             +2\n\
+             diff --git a/src/new.txt b/src/new.txt\n\
+             new file mode 100644\n\
+             --- /dev/null\n\
+             +++ b/src/new.txt\n\
+             @@ -0,0 +1 @@\n\
+             +hello\n"
+        );
+    }
+
+    #[test]
+    fn test_later_edits_see_earlier_writes() {
+        let root = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(root.path()).unwrap();
+        fs::write(root.join("existing.txt"), "on disk\n").unwrap();
+
+        let mut overlay = Overlay::default();
+        overlay.apply(&write(&root.join("a.txt"), "first\n", ExistingFilePolicy::Overwrite));
+        overlay.apply(&ScriptMessage::FileOperation(FileOperation::Append {
+            path: root.join("a.txt").to_string(),
+            contents: b"second\n".to_vec(),
+        }));
+        overlay.apply(&write(
+            &root.join("existing.txt"),
+            "replaced\n",
+            ExistingFilePolicy::Preserve,
+        ));
+        overlay.apply(&ScriptMessage::FileOperation(FileOperation::Move {
+            source: root.join("a.txt").to_string(),
+            destination: root.join("b.txt").to_string(),
+            existing_file_policy: ExistingFilePolicy::Error,
+        }));
+
+        assert_eq!(overlay.read(&root.join("a.txt")), None);
+        assert_eq!(overlay.read(&root.join("b.txt")).unwrap(), b"first\nsecond\n");
+        assert_eq!(overlay.read(&root.join("existing.txt")).unwrap(), b"on disk\n");
+        let patch = overlay.patch(root);
+        assert!(
+            patch.starts_with("diff --git a/b.txt b/b.txt\nnew file mode 100644\n"),
+            "{}",
+            patch
+        );
+        assert!(!patch.contains("a.txt"), "{}", patch);
+        assert!(!patch.contains("existing.txt"), "{}", patch);
+    }
+
+    #[test]
+    fn test_binary_files_carry_their_contents() {
+        let root = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(root.path()).unwrap();
+
+        let mut overlay = Overlay::default();
+        overlay.apply(&ScriptMessage::WriteFile(WriteFileInfo {
+            destination: root.join("logo.bin").to_string(),
+            contents: vec![0, 1, 2, 3],
+            existing_file_policy: ExistingFilePolicy::Overwrite,
+            shadow: None,
+            mode: Some(0o755),
+        }));
+
+        let patch = overlay.patch(root);
+        assert!(
+            patch.starts_with(
+                "diff --git a/logo.bin b/logo.bin\n\
+                 new file mode 100755\n\
+                 index 0000000000000000000000000000000000000000.."
+            ),
+            "{}",
+            patch
+        );
+        assert!(patch.contains("\nGIT binary patch\nliteral 4\n"), "{}", patch);
+        assert!(patch.ends_with("literal 0\nHc$@<O00001\n\n"), "{}", patch);
+    }
+}
@@ -0,0 +1,32 @@
+use std::fs;
+use std::io;
+
+use camino::Utf8Path;
+use log::debug;
+
+use archetect_api::ClientMessage;
+use crate::responder::Responder;
+use crate::transaction::Transaction;
+
+/// Answer a `ReadFile` with the file as the render has left it: as
+/// `transaction` has staged it, if there is one, or as it is on disk.
+pub fn handle_read_file(transaction: Option<&Transaction>, path: &str, responses: &dyn Responder) {
+    debug!("Reading back {:?}", path);
+    let path = Utf8Path::new(path);
+    let contents = match transaction {
+        Some(transaction) => transaction.read(path),
+        None => match fs::read(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(format!("{}: {}", path, error)),
+        },
+    };
+    responses.respond(match contents {
+        Ok(Some(contents)) => match String::from_utf8(contents) {
+            Ok(text) => ClientMessage::String(text),
+            Err(_) => ClientMessage::Error(format!("{}: not UTF-8 text", path)),
+        },
+        Ok(None) => ClientMessage::None,
+        Err(message) => ClientMessage::Error(message),
+    });
+}
@@ -1,21 +1,37 @@
+use std::sync::Mutex;
+
+use camino::Utf8Path;
 use log::{debug, error, info, trace, warn};
 
 use archetect_api::{ClientIoHandle, ScriptMessage};
 
+use crate::artifact_report::Report;
 use crate::bool_prompt_handler::handle_prompt_bool;
+use crate::completion_handler::{handle_complete_error, handle_complete_success};
 use crate::editor_prompt_info::handle_editor_prompt;
+use crate::file_operation_handler::handle_file_operation;
 use crate::int_prompt_handler::handle_prompt_int;
+use crate::list_files_handler::handle_list_files;
 use crate::list_prompt_handler::handle_list_prompt;
 use crate::multiselect_prompt_handler::handle_multiselect_prompt;
+use crate::read_file_handler::handle_read_file;
 use crate::responder::Responder;
 use crate::segment_handler::handle_begin_segment;
 use crate::select_prompt_handler::handle_select_prompt;
 use crate::text_prompt_handler::handle_prompt_text;
+use crate::transaction::Transaction;
+use crate::transaction_handler::{
+    begin_transaction, handle_begin_transaction, handle_staged_file_operation, handle_staged_write_directory,
+    handle_staged_write_file, handle_staged_write_symlink,
+};
 use crate::write_directory_handler::handle_write_directory;
 use crate::write_file_handler::handle_write_file;
+use crate::write_symlink_handler::handle_write_symlink;
 
 pub struct TerminalClient<IO> {
     client_handle: IO,
+    transaction: Mutex<Option<Transaction>>,
+    report: Option<Report>,
 }
 
 impl<IO> TerminalClient<IO>
@@ -23,26 +39,48 @@ where
     IO: ClientIoHandle,
 {
     pub fn new(client_handle: IO) -> Self {
-        Self { client_handle }
+        Self {
+            client_handle,
+            transaction: Mutex::new(None),
+            report: None,
+        }
     }
 
-    pub fn run(&self) {
+    /// Stage every write for `destination` until the render completes,
+    /// whether or not the archetype asks for a transaction itself. Fails if
+    /// the writes can't be staged.
+    pub fn with_transaction(self, destination: &Utf8Path) -> Result<Self, String> {
+        begin_transaction(&mut self.transaction.lock().expect("Lock Error"), destination.as_str())?;
+        Ok(self)
+    }
+
+    /// Write a report of the render to `report` once it completes.
+    pub fn with_report(mut self, report: Report) -> Self {
+        self.report = Some(report);
+        self
+    }
+
+    /// Answer the render until it completes. Fails if it completed but its
+    /// staged writes couldn't be committed; a render that failed outright
+    /// says so with its `CompleteError`.
+    pub fn run(&self) -> Result<(), String> {
         loop {
             match self.client_handle.receive() {
                 Ok(script_message) => {
-                    if !self.handle_message(script_message) {
-                        break;
+                    if let Some(outcome) = self.handle_message(script_message) {
+                        return outcome;
                     }
                 }
                 Err(_) => {
                     debug!("Script channel closed");
-                    break;
+                    return Ok(());
                 }
             }
         }
     }
 
-    fn handle_message(&self, message: ScriptMessage) -> bool {
+    /// Handle one message; `Some` with the outcome once the render is over.
+    fn handle_message(&self, message: ScriptMessage) -> Option<Result<(), String>> {
         let responder = ClientIoResponder(&self.client_handle);
         match message {
             ScriptMessage::PromptForText(info) => handle_prompt_text(info, &responder),
@@ -65,23 +103,48 @@ where
             ScriptMessage::LogError(msg) => error!("{}", msg),
             ScriptMessage::Print(msg) => println!("{}", msg),
             ScriptMessage::Display(msg) => eprintln!("{}", msg),
-            ScriptMessage::WriteFile(info) => handle_write_file(info, &responder),
-            ScriptMessage::WriteDirectory(info) => handle_write_directory(info, &responder),
+            ScriptMessage::WriteFile(info) => match self.transaction.lock().expect("Lock Error").as_mut() {
+                Some(transaction) => handle_staged_write_file(transaction, info, &responder),
+                None => handle_write_file(info, &responder),
+            },
+            ScriptMessage::WriteDirectory(info) => match self.transaction.lock().expect("Lock Error").as_mut() {
+                Some(transaction) => handle_staged_write_directory(transaction, info, &responder),
+                None => handle_write_directory(info, &responder),
+            },
+            ScriptMessage::WriteSymlink(info) => match self.transaction.lock().expect("Lock Error").as_mut() {
+                Some(transaction) => handle_staged_write_symlink(transaction, info, &responder),
+                None => handle_write_symlink(info, &responder),
+            },
+            ScriptMessage::FileOperation(operation) => match self.transaction.lock().expect("Lock Error").as_mut() {
+                Some(transaction) => handle_staged_file_operation(transaction, operation, &responder),
+                None => handle_file_operation(operation, &responder),
+            },
+            ScriptMessage::ReadFile(path) => {
+                handle_read_file(self.transaction.lock().expect("Lock Error").as_ref(), &path, &responder)
+            }
+            ScriptMessage::ListFiles(path) => {
+                handle_list_files(self.transaction.lock().expect("Lock Error").as_ref(), &path, &responder)
+            }
+            ScriptMessage::BeginTransaction(destination) => {
+                handle_begin_transaction(&mut self.transaction.lock().expect("Lock Error"), &destination, &responder)
+            }
             ScriptMessage::CompleteSuccess(artifacts) => {
-                debug!("Archetype completed successfully");
-                crate::artifact_report::report_artifacts(&artifacts);
-                return false;
+                return Some(handle_complete_success(
+                    self.transaction.lock().expect("Lock Error").take(),
+                    artifacts,
+                    self.report.as_ref(),
+                ));
             }
-            ScriptMessage::CompleteError(msg) => {
-                // CompleteError is a session-termination signal. The
-                // actionable error was already surfaced via LogError at
-                // the call site — logging it again here just duplicates
-                // it for the user.
-                debug!("Archetype completed with error: {}", msg);
-                return false;
+            ScriptMessage::CompleteError(message) => {
+                handle_complete_error(
+                    self.transaction.lock().expect("Lock Error").take(),
+                    &message,
+                    self.report.as_ref(),
+                );
+                return Some(Ok(()));
             }
         }
-        true
+        None
     }
 }
 
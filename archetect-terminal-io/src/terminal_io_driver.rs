@@ -6,20 +6,41 @@ use log::{debug, error, info, trace, warn};
 use crate::list_prompt_handler::handle_list_prompt;
 use archetect_api::{ScriptMessage, ClientMessage, IoError, ScriptIoHandle};
 
+use crate::artifact_report::Report;
 use crate::bool_prompt_handler::handle_prompt_bool;
+use crate::completion_handler::{handle_complete_error, handle_complete_success};
 use crate::editor_prompt_info::handle_editor_prompt;
+use crate::file_operation_handler::handle_file_operation;
 use crate::int_prompt_handler::handle_prompt_int;
+use crate::list_files_handler::handle_list_files;
 use crate::multiselect_prompt_handler::handle_multiselect_prompt;
+use crate::read_file_handler::handle_read_file;
 use crate::segment_handler::handle_begin_segment;
 use crate::select_prompt_handler::handle_select_prompt;
 use crate::text_prompt_handler::handle_prompt_text;
+use crate::transaction::Transaction;
+use crate::transaction_handler::{
+    handle_begin_transaction, handle_staged_file_operation, handle_staged_write_directory,
+    handle_staged_write_file, handle_staged_write_symlink,
+};
 use crate::write_directory_handler::handle_write_directory;
 use crate::write_file_handler::handle_write_file;
+use crate::write_symlink_handler::handle_write_symlink;
 
 #[derive(Clone, Debug)]
 pub struct TerminalScriptIoHandle {
     responses_tx: SyncSender<ClientMessage>,
     responses_rx: Arc<Mutex<Receiver<ClientMessage>>>,
+    transaction: Arc<Mutex<Option<Transaction>>>,
+    report: Option<Report>,
+}
+
+impl TerminalScriptIoHandle {
+    /// Write a report of the render to `report` once it completes.
+    pub fn with_report(mut self, report: Report) -> Self {
+        self.report = Some(report);
+        self
+    }
 }
 
 impl ScriptIoHandle for TerminalScriptIoHandle {
@@ -71,23 +92,48 @@ impl ScriptIoHandle for TerminalScriptIoHandle {
             ScriptMessage::Display(message) => {
                 eprintln!("{}", message)
             }
-            ScriptMessage::WriteFile(write_info) => {
-                handle_write_file(write_info, &self.responses_tx);
-            }
-            ScriptMessage::WriteDirectory(write_info) => {
-                handle_write_directory(write_info, &self.responses_tx);
+            ScriptMessage::WriteFile(write_info) => match self.transaction.lock().expect("Lock Error").as_mut() {
+                Some(transaction) => handle_staged_write_file(transaction, write_info, &self.responses_tx),
+                None => handle_write_file(write_info, &self.responses_tx),
+            },
+            ScriptMessage::WriteDirectory(write_info) => match self.transaction.lock().expect("Lock Error").as_mut() {
+                Some(transaction) => handle_staged_write_directory(transaction, write_info, &self.responses_tx),
+                None => handle_write_directory(write_info, &self.responses_tx),
+            },
+            ScriptMessage::WriteSymlink(write_info) => match self.transaction.lock().expect("Lock Error").as_mut() {
+                Some(transaction) => handle_staged_write_symlink(transaction, write_info, &self.responses_tx),
+                None => handle_write_symlink(write_info, &self.responses_tx),
+            },
+            ScriptMessage::FileOperation(operation) => match self.transaction.lock().expect("Lock Error").as_mut() {
+                Some(transaction) => handle_staged_file_operation(transaction, operation, &self.responses_tx),
+                None => handle_file_operation(operation, &self.responses_tx),
+            },
+            ScriptMessage::ReadFile(path) => {
+                handle_read_file(self.transaction.lock().expect("Lock Error").as_ref(), &path, &self.responses_tx)
+            }
+            ScriptMessage::ListFiles(path) => {
+                handle_list_files(self.transaction.lock().expect("Lock Error").as_ref(), &path, &self.responses_tx)
+            }
+            ScriptMessage::BeginTransaction(destination) => {
+                handle_begin_transaction(
+                    &mut self.transaction.lock().expect("Lock Error"),
+                    &destination,
+                    &self.responses_tx,
+                );
             }
             ScriptMessage::CompleteSuccess(artifacts) => {
-                debug!("Archetype completed successfully");
-                crate::artifact_report::report_artifacts(&artifacts);
-            }
-            ScriptMessage::CompleteError(message) => {
-                // CompleteError is a session-termination signal. The
-                // actionable error was already surfaced via LogError at
-                // the call site — logging it again here just duplicates
-                // it for the user.
-                debug!("Archetype completed with error: {}", message);
-            }
+                return handle_complete_success(
+                    self.transaction.lock().expect("Lock Error").take(),
+                    artifacts,
+                    self.report.as_ref(),
+                )
+                .map_err(|message| IoError::ClientError { message });
+            }
+            ScriptMessage::CompleteError(message) => handle_complete_error(
+                self.transaction.lock().expect("Lock Error").take(),
+                &message,
+                self.report.as_ref(),
+            ),
         }
         Ok(())
     }
@@ -107,6 +153,8 @@ impl Default for TerminalScriptIoHandle {
         Self {
             responses_tx,
             responses_rx: Arc::new(Mutex::new(responses_rx)),
+            transaction: Arc::new(Mutex::new(None)),
+            report: None,
         }
     }
 }
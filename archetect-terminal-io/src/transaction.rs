@@ -0,0 +1,500 @@
+//! Staged writes for transactional renders.
+//!
+//! Between `BeginTransaction` and the end of a render, writes land in a
+//! staging directory beside the destination instead of in it. A successful
+//! render commits them — in the order they arrived, through the usual
+//! existing-file policies — and a failed one throws them away. A transaction
+//! dropped without either (a client that lost its server, say) is discarded
+//! too, so the destination is only ever touched by a render that finished.
+//!
+//! A commit that fails partway puts back everything it had touched: before
+//! each write, whatever is at the paths it affects is copied aside, and the
+//! copies are restored, latest first, if a later write fails.
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use log::{debug, warn};
+
+use archetect_api::{
+    Artifact, ExistingFilePolicy, FileOperation, FileOutcome, ScriptMessage, WriteDirectoryInfo, WriteFileInfo,
+    WriteSymlinkInfo,
+};
+
+use crate::overlay::Overlay;
+
+#[derive(Debug)]
+pub struct Transaction {
+    destination: Utf8PathBuf,
+    staging: Utf8PathBuf,
+    writes: Vec<StagedWrite>,
+}
+
+#[derive(Debug)]
+enum StagedWrite {
+    Directory(WriteDirectoryInfo),
+    /// The contents wait on disk, in `staged`, rather than in memory; `info`
+    /// carries everything else about the write.
+    File { info: WriteFileInfo, staged: Utf8PathBuf },
+    Symlink(WriteSymlinkInfo),
+    Operation(FileOperation),
+}
+
+/// A staged write, as handed back at commit. Directories are not among them:
+/// the transaction creates those itself.
+pub enum Staged<'a> {
+    File(&'a WriteFileInfo),
+    Symlink(&'a WriteSymlinkInfo),
+    Operation(&'a FileOperation),
+}
+
+/// What a commit applied, in order, with what became of each file written.
+#[derive(Debug)]
+pub struct Committed {
+    destination: Utf8PathBuf,
+    writes: Vec<(String, Option<FileOutcome>)>,
+}
+
+impl Transaction {
+    /// Open a transaction for `destination`, staging into a hidden sibling
+    /// directory. Staging on the same filesystem keeps large renders out of
+    /// memory and fails early if the destination's parent is not writable.
+    pub fn begin(destination: &Utf8Path) -> io::Result<Transaction> {
+        let parent = destination.parent().unwrap_or(Utf8Path::new("."));
+        let name = destination.file_name().unwrap_or("archetect");
+        let staging = parent.join(format!(".{}.archetect-staging-{}", name, std::process::id()));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        fs::create_dir_all(&staging)?;
+        debug!("Staging writes for {} in {}", destination, staging);
+        Ok(Transaction {
+            destination: destination.to_path_buf(),
+            staging,
+            writes: Vec::new(),
+        })
+    }
+
+    pub fn destination(&self) -> &Utf8Path {
+        &self.destination
+    }
+
+    /// Stage a file write. `Existing.Error` is checked now rather than at
+    /// commit, so that the script fails where the collision happens and
+    /// nothing gets committed.
+    pub fn stage_file(&mut self, mut info: WriteFileInfo) -> Result<(), String> {
+        if matches!(info.existing_file_policy, ExistingFilePolicy::Error) && Utf8Path::new(&info.destination).exists()
+        {
+            return Err(format!("File already exists: {} (if_exists = Existing.Error)", info.destination));
+        }
+        let staged = self.staging.join(self.writes.len().to_string());
+        fs::write(&staged, std::mem::take(&mut info.contents)).map_err(|error| error.to_string())?;
+        debug!("Staged {:?}", info.destination);
+        self.writes.push(StagedWrite::File { info, staged });
+        Ok(())
+    }
+
+    pub fn stage_directory(&mut self, info: WriteDirectoryInfo) {
+        self.writes.push(StagedWrite::Directory(info));
+    }
+
+    /// Stage a symlink. As with files, `Existing.Error` is checked now.
+    pub fn stage_symlink(&mut self, info: WriteSymlinkInfo) -> Result<(), String> {
+        if matches!(info.existing_file_policy, ExistingFilePolicy::Error)
+            && Utf8Path::new(&info.path).symlink_metadata().is_ok()
+        {
+            return Err(format!("File already exists: {} (if_exists = Existing.Error)", info.path));
+        }
+        self.writes.push(StagedWrite::Symlink(info));
+        Ok(())
+    }
+
+    /// Stage an append, delete, move, or copy. These act on whatever the
+    /// writes staged before them left behind, so they wait their turn too.
+    pub fn stage_operation(&mut self, operation: FileOperation) {
+        self.writes.push(StagedWrite::Operation(operation));
+    }
+
+    /// What `path` will hold once this commits, if it will be a file: the
+    /// staged writes, replayed over what is on disk. Every staged file is
+    /// read back to do it, so this is for the occasional edit of a file the
+    /// render already wrote, not for every write.
+    pub fn read(&self, path: &Utf8Path) -> Result<Option<Vec<u8>>, String> {
+        Ok(self.replay()?.read(path))
+    }
+
+    /// Every path beneath `directory` once this commits: what is on disk,
+    /// with the staged writes replayed over it. As costly as [`read`](Self::read).
+    pub fn list(&self, directory: &Utf8Path) -> Result<BTreeSet<Utf8PathBuf>, String> {
+        Ok(self.replay()?.list(directory))
+    }
+
+    fn replay(&self) -> Result<Overlay, String> {
+        let mut overlay = Overlay::default();
+        for write in &self.writes {
+            let command = match write {
+                StagedWrite::Directory(info) => ScriptMessage::WriteDirectory(info.clone()),
+                StagedWrite::File { info, staged } => ScriptMessage::WriteFile(WriteFileInfo {
+                    contents: fs::read(staged).map_err(|error| format!("{}: {}", staged, error))?,
+                    ..info.clone()
+                }),
+                StagedWrite::Symlink(info) => ScriptMessage::WriteSymlink(info.clone()),
+                StagedWrite::Operation(operation) => ScriptMessage::FileOperation(operation.clone()),
+            };
+            overlay.apply(&command);
+        }
+        Ok(overlay)
+    }
+
+    /// Apply every staged write, creating directories directly and handing
+    /// the rest to `apply` — files with their contents restored — which says
+    /// what became of each file. Stops at the first failure, leaving the
+    /// destination as it was before the commit.
+    pub fn commit(
+        mut self,
+        mut apply: impl FnMut(Staged<'_>) -> Result<Option<FileOutcome>, String>,
+    ) -> Result<Committed, String> {
+        debug!("Committing {} staged writes to {}", self.writes.len(), self.destination);
+        let mut backups = Backups::new(self.staging.join("backups"));
+        let mut writes = Vec::with_capacity(self.writes.len());
+        for write in std::mem::take(&mut self.writes) {
+            let applied = match write {
+                // A directory already there is left alone, so only a new
+                // one needs noting.
+                StagedWrite::Directory(info) if Utf8Path::new(&info.path).is_dir() => {
+                    writes.push((info.path, None));
+                    Ok(())
+                }
+                StagedWrite::Directory(info) => backups.preserve(&[&info.path]).and_then(|()| {
+                    fs::create_dir_all(&info.path).map_err(|error| format!("{}: {}", info.path, error))?;
+                    writes.push((info.path, None));
+                    Ok(())
+                }),
+                StagedWrite::File { mut info, staged } => backups.preserve(&written(&info)).and_then(|()| {
+                    info.contents = fs::read(&staged).map_err(|error| format!("{}: {}", staged, error))?;
+                    let outcome = apply(Staged::File(&info))?;
+                    writes.push((info.destination, outcome));
+                    Ok(())
+                }),
+                StagedWrite::Symlink(info) => backups.preserve(&[&info.path]).and_then(|()| {
+                    apply(Staged::Symlink(&info))?;
+                    writes.push((info.path, None));
+                    Ok(())
+                }),
+                StagedWrite::Operation(operation) => backups.preserve(&affected(&operation)).and_then(|()| {
+                    apply(Staged::Operation(&operation))?;
+                    writes.push((operation.path().to_string(), None));
+                    Ok(())
+                }),
+            };
+            if let Err(message) = applied {
+                debug!("Commit to {} failed; restoring what it had touched", self.destination);
+                backups.restore();
+                return Err(message);
+            }
+        }
+        Ok(Committed {
+            destination: self.destination.clone(),
+            writes,
+        })
+    }
+
+    pub fn discard(self) {
+        debug!("Discarding {} staged writes for {}", self.writes.len(), self.destination);
+    }
+}
+
+impl Committed {
+    /// The paths committed, in order.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.writes.iter().map(|(path, _)| path.as_str())
+    }
+
+    /// Fill in the outcome of each written file's artifact, which the server
+    /// recorded without one because the write was only staged. A path written
+    /// twice takes its outcomes in order.
+    pub fn settle(&self, artifacts: &mut [Artifact]) {
+        let mut outcomes: HashMap<&str, VecDeque<FileOutcome>> = HashMap::new();
+        for (path, outcome) in &self.writes {
+            if let Some(outcome) = outcome {
+                outcomes.entry(path.as_str()).or_default().push_back(*outcome);
+            }
+        }
+        for artifact in artifacts.iter_mut().filter(|artifact| artifact.sha256.is_some() && artifact.outcome.is_none()) {
+            let Some(path) = artifact.path.as_deref() else {
+                continue;
+            };
+            let full = self.destination.join(path);
+            artifact.outcome = outcomes.get_mut(full.as_str()).and_then(VecDeque::pop_front);
+        }
+    }
+}
+
+/// The paths a file write changes: the file, and its shadow copy.
+fn written(info: &WriteFileInfo) -> Vec<&str> {
+    [Some(info.destination.as_str()), info.shadow.as_deref()].into_iter().flatten().collect()
+}
+
+/// The paths an operation changes.
+fn affected(operation: &FileOperation) -> Vec<&str> {
+    match operation {
+        FileOperation::Append { path, .. } | FileOperation::Delete { path } => vec![path],
+        FileOperation::Move { source, destination, .. } => vec![source, destination],
+        FileOperation::Copy { destination, .. } => vec![destination],
+    }
+}
+
+/// What a commit found at each path before it first touched it.
+struct Backups {
+    directory: Utf8PathBuf,
+    seen: HashSet<Utf8PathBuf>,
+    /// In the order taken: each path, and where its copy is — `None` if
+    /// nothing was there.
+    taken: Vec<(Utf8PathBuf, Option<Utf8PathBuf>)>,
+}
+
+impl Backups {
+    fn new(directory: Utf8PathBuf) -> Backups {
+        Backups {
+            directory,
+            seen: HashSet::new(),
+            taken: Vec::new(),
+        }
+    }
+
+    /// Copy aside whatever is at `paths`, and note the directories above
+    /// them that writing there would create.
+    fn preserve(&mut self, paths: &[&str]) -> Result<(), String> {
+        for path in paths {
+            let path = Utf8Path::new(path);
+            let mut missing = path
+                .ancestors()
+                .skip(1)
+                .take_while(|ancestor| !ancestor.as_str().is_empty() && ancestor.symlink_metadata().is_err())
+                .collect::<Vec<_>>();
+            missing.reverse();
+            for path in missing.into_iter().chain([path]) {
+                if !self.seen.insert(path.to_path_buf()) {
+                    continue;
+                }
+                let copy = if path.symlink_metadata().is_ok() {
+                    let copy = self.directory.join(self.taken.len().to_string());
+                    fs::create_dir_all(&self.directory)
+                        .and_then(|()| copy_tree(path, &copy))
+                        .map_err(|error| format!("Unable to back up {}: {}", path, error))?;
+                    Some(copy)
+                } else {
+                    None
+                };
+                self.taken.push((path.to_path_buf(), copy));
+            }
+        }
+        Ok(())
+    }
+
+    /// Put every path back as it was, latest first, so that a path touched
+    /// again under a restored directory ends up as it first was.
+    fn restore(self) {
+        for (path, copy) in self.taken.into_iter().rev() {
+            if let Ok(metadata) = path.symlink_metadata() {
+                let removed = if metadata.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+                if let Err(error) = removed {
+                    warn!("Unable to restore {}: {}", path, error);
+                    continue;
+                }
+            }
+            if let Some(copy) = copy {
+                if let Err(error) = fs::rename(&copy, &path) {
+                    warn!("Unable to restore {}: {}", path, error);
+                }
+            }
+        }
+    }
+}
+
+/// Copy a file, symlink, or directory tree, leaving symlinks as links.
+fn copy_tree(from: &Utf8Path, to: &Utf8Path) -> io::Result<()> {
+    let metadata = from.symlink_metadata()?;
+    if metadata.is_symlink() {
+        copy_symlink(from, to)
+    } else if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in from.read_dir_utf8()? {
+            let entry = entry?;
+            copy_tree(entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Utf8Path, to: &Utf8Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Utf8Path, to: &Utf8Path) -> io::Result<()> {
+    let target = fs::read_link(from)?;
+    if from.is_dir() {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.staging);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_operation_handler::apply_file_operation;
+    use crate::write_file_handler::apply_write_file;
+    use tempfile::TempDir;
+
+    fn file(path: &Utf8Path, contents: &str) -> WriteFileInfo {
+        WriteFileInfo {
+            destination: path.to_string(),
+            contents: contents.as_bytes().to_vec(),
+            existing_file_policy: ExistingFilePolicy::Overwrite,
+            shadow: None,
+            mode: None,
+        }
+    }
+
+    fn apply(staged: Staged<'_>) -> Result<Option<FileOutcome>, String> {
+        match staged {
+            Staged::File(info) => apply_write_file(info).map(Some),
+            Staged::Symlink(_) => Ok(None),
+            Staged::Operation(operation) => apply_file_operation(operation).map(|()| None),
+        }
+    }
+
+    fn setup() -> (TempDir, Utf8PathBuf) {
+        let root = TempDir::new().unwrap();
+        let destination = Utf8Path::from_path(root.path()).unwrap().join("project");
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(destination.join("kept.txt"), "original").unwrap();
+        (root, destination)
+    }
+
+    fn staging_is_gone(destination: &Utf8Path) -> bool {
+        destination.parent().unwrap().read_dir().unwrap().count() == 1
+    }
+
+    #[test]
+    fn staged_writes_wait_for_the_commit() {
+        let (_root, destination) = setup();
+        let mut transaction = Transaction::begin(&destination).unwrap();
+        transaction.stage_directory(WriteDirectoryInfo {
+            path: destination.join("src").to_string(),
+        });
+        transaction.stage_file(file(&destination.join("src/main.rs"), "fn main() {}")).unwrap();
+        transaction.stage_file(file(&destination.join("kept.txt"), "replaced")).unwrap();
+        assert!(!destination.join("src").exists());
+        assert_eq!(fs::read_to_string(destination.join("kept.txt")).unwrap(), "original");
+
+        let committed = transaction.commit(apply).unwrap();
+        assert_eq!(fs::read_to_string(destination.join("src/main.rs")).unwrap(), "fn main() {}");
+        assert_eq!(fs::read_to_string(destination.join("kept.txt")).unwrap(), "replaced");
+        assert_eq!(committed.paths().count(), 3);
+        assert!(staging_is_gone(&destination));
+    }
+
+    #[test]
+    fn reads_see_what_has_been_staged() {
+        let (_root, destination) = setup();
+        let mut transaction = Transaction::begin(&destination).unwrap();
+        transaction.stage_file(file(&destination.join("kept.txt"), "replaced")).unwrap();
+        transaction.stage_operation(FileOperation::Append {
+            path: destination.join("kept.txt").to_string(),
+            contents: b" twice".to_vec(),
+        });
+        transaction.stage_operation(FileOperation::Delete {
+            path: destination.join("new.txt").to_string(),
+        });
+
+        let read = |name: &str| transaction.read(&destination.join(name)).unwrap();
+        assert_eq!(read("kept.txt"), Some(b"replaced twice".to_vec()));
+        assert_eq!(read("new.txt"), None);
+        assert_eq!(fs::read_to_string(destination.join("kept.txt")).unwrap(), "original");
+    }
+
+    #[test]
+    fn listings_see_what_has_been_staged() {
+        let (_root, destination) = setup();
+        fs::create_dir_all(destination.join("old")).unwrap();
+        fs::write(destination.join("old/gone.txt"), "").unwrap();
+        let mut transaction = Transaction::begin(&destination).unwrap();
+        transaction.stage_directory(WriteDirectoryInfo {
+            path: destination.join("empty").to_string(),
+        });
+        transaction.stage_file(file(&destination.join("src/main.rs"), "fn main() {}")).unwrap();
+        transaction.stage_operation(FileOperation::Delete {
+            path: destination.join("old").to_string(),
+        });
+
+        let listed = transaction.list(&destination).unwrap();
+        let listed = listed
+            .iter()
+            .map(|path| path.strip_prefix(&destination).unwrap().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(listed, ["empty", "kept.txt", "src", "src/main.rs"]);
+        assert!(destination.join("old/gone.txt").exists());
+    }
+
+    #[test]
+    fn a_discarded_transaction_leaves_the_destination_alone() {
+        let (_root, destination) = setup();
+        let mut transaction = Transaction::begin(&destination).unwrap();
+        transaction.stage_file(file(&destination.join("kept.txt"), "replaced")).unwrap();
+        transaction.stage_file(file(&destination.join("new.txt"), "new")).unwrap();
+        transaction.discard();
+
+        assert_eq!(fs::read_to_string(destination.join("kept.txt")).unwrap(), "original");
+        assert!(!destination.join("new.txt").exists());
+        assert!(staging_is_gone(&destination));
+    }
+
+    #[test]
+    fn a_commit_that_fails_partway_restores_the_destination() {
+        let (_root, destination) = setup();
+        fs::create_dir_all(destination.join("old")).unwrap();
+        fs::write(destination.join("old/notes.txt"), "notes").unwrap();
+        let mut transaction = Transaction::begin(&destination).unwrap();
+        transaction.stage_file(file(&destination.join("kept.txt"), "replaced")).unwrap();
+        transaction.stage_directory(WriteDirectoryInfo {
+            path: destination.join("new/deep").to_string(),
+        });
+        transaction.stage_file(file(&destination.join("new/deep/file.txt"), "new")).unwrap();
+        transaction.stage_operation(FileOperation::Delete {
+            path: destination.join("old").to_string(),
+        });
+        transaction.stage_operation(FileOperation::Move {
+            source: destination.join("missing.txt").to_string(),
+            destination: destination.join("moved.txt").to_string(),
+            existing_file_policy: ExistingFilePolicy::Overwrite,
+        });
+
+        let err = transaction.commit(apply).unwrap_err();
+        assert!(err.contains("missing.txt"), "{}", err);
+        assert_eq!(fs::read_to_string(destination.join("kept.txt")).unwrap(), "original");
+        assert!(!destination.join("new").exists());
+        assert_eq!(fs::read_to_string(destination.join("old/notes.txt")).unwrap(), "notes");
+        assert!(staging_is_gone(&destination));
+    }
+
+    #[test]
+    fn begin_fails_where_nothing_can_be_staged() {
+        let (_root, destination) = setup();
+        let beneath_a_file = destination.join("kept.txt/project");
+        assert!(Transaction::begin(&beneath_a_file).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use camino::Utf8Path;
+use log::debug;
+
+use archetect_api::{ClientMessage, FileOperation, WriteDirectoryInfo, WriteFileInfo, WriteSymlinkInfo};
+use crate::responder::Responder;
+use crate::file_operation_handler::apply_file_operation;
+use crate::transaction::{Committed, Staged, Transaction};
+use crate::write_file_handler::apply_write_file;
+use crate::write_symlink_handler::apply_write_symlink;
+
+/// Open a transaction for `destination`. A render that asked for one must
+/// not carry on writing straight through, so failing to stage is an error.
+pub fn handle_begin_transaction(transaction: &mut Option<Transaction>, destination: &str, responses: &dyn Responder) {
+    match begin_transaction(transaction, destination) {
+        Ok(()) => responses.respond(ClientMessage::Ack),
+        Err(message) => responses.respond(ClientMessage::Error(message)),
+    }
+}
+
+/// Open a transaction for `destination`, unless one already is.
+pub fn begin_transaction(transaction: &mut Option<Transaction>, destination: &str) -> Result<(), String> {
+    if let Some(open) = transaction.as_ref() {
+        debug!("Transaction for {} already open", open.destination());
+        return Ok(());
+    }
+    let opened = Transaction::begin(Utf8Path::new(destination))
+        .map_err(|err| format!("Unable to stage writes for {}: {}", destination, err))?;
+    *transaction = Some(opened);
+    Ok(())
+}
+
+pub fn handle_staged_write_file(transaction: &mut Transaction, write_info: WriteFileInfo, responses: &dyn Responder) {
+    match transaction.stage_file(write_info) {
+        Ok(()) => responses.respond(ClientMessage::Ack),
+        Err(message) => responses.respond(ClientMessage::Error(message)),
+    }
+}
+
+pub fn handle_staged_write_directory(
+    transaction: &mut Transaction,
+    write_info: WriteDirectoryInfo,
+    responses: &dyn Responder,
+) {
+    transaction.stage_directory(write_info);
+    responses.respond(ClientMessage::Ack);
+}
+
+pub fn handle_staged_write_symlink(
+    transaction: &mut Transaction,
+    write_info: WriteSymlinkInfo,
+    responses: &dyn Responder,
+) {
+    match transaction.stage_symlink(write_info) {
+        Ok(()) => responses.respond(ClientMessage::Ack),
+        Err(message) => responses.respond(ClientMessage::Error(message)),
+    }
+}
+
+pub fn handle_staged_file_operation(
+    transaction: &mut Transaction,
+    operation: FileOperation,
+    responses: &dyn Responder,
+) {
+    transaction.stage_operation(operation);
+    responses.respond(ClientMessage::Ack);
+}
+
+/// Apply a transaction's staged writes, if there is one, saying what became
+/// of each file.
+pub fn handle_commit(transaction: Option<Transaction>) -> Result<Option<Committed>, String> {
+    let Some(transaction) = transaction else {
+        return Ok(None);
+    };
+    transaction
+        .commit(|staged| match staged {
+            Staged::File(info) => apply_write_file(info).map(Some),
+            Staged::Symlink(info) => apply_write_symlink(info).map(|()| None),
+            Staged::Operation(operation) => apply_file_operation(operation).map(|()| None),
+        })
+        .map(Some)
+}
+
+pub fn handle_discard(transaction: Option<Transaction>) {
+    if let Some(transaction) = transaction {
+        transaction.discard();
+    }
+}
@@ -1,22 +1,44 @@
 use std::fs;
 
 use camino::Utf8PathBuf;
-use content_inspector::ContentType;
-use log::debug;
-use similar::{ChangeTag, TextDiff};
+use log::{debug, warn};
 
-use archetect_api::{ClientMessage, ExistingFilePolicy, WriteFileInfo};
+use archetect_api::{ClientMessage, ExistingFilePolicy, FileOutcome, WriteFileInfo};
+use crate::diff::format_diff;
+use crate::merge::{three_way, MergeOutcome};
 use crate::responder::Responder;
 use inquire::Confirm;
 
-/// Cap diff output so a giant generated file doesn't flood the terminal.
-/// Authors can still inspect the full file on disk after the render.
-const MAX_DIFF_LINES: usize = 200;
-
 pub fn handle_write_file(write_info: WriteFileInfo, responses: &dyn Responder) {
+    match apply_write_file(&write_info) {
+        Ok(outcome) => responses.respond(ClientMessage::Written(outcome)),
+        Err(message) => responses.respond(ClientMessage::Error(message)),
+    }
+}
+
+/// Write a file through its existing-file policy, then refresh its shadow if
+/// the file took the new contents. Returns what became of the file.
+pub(crate) fn apply_write_file(write_info: &WriteFileInfo) -> Result<FileOutcome, String> {
+    let outcome = write_file(write_info)?;
+    if matches!(
+        outcome,
+        FileOutcome::Created | FileOutcome::Overwritten | FileOutcome::Merged | FileOutcome::Unchanged
+    ) {
+        refresh_shadow(write_info)?;
+    }
+    Ok(outcome)
+}
+
+fn write_file(write_info: &WriteFileInfo) -> Result<FileOutcome, String> {
     let path = Utf8PathBuf::from(&write_info.destination);
 
-    if path.exists() {
+    let outcome = if path.exists() {
+        if !matches!(write_info.existing_file_policy, ExistingFilePolicy::Error)
+            && fs::read(&path).is_ok_and(|existing| existing == write_info.contents)
+        {
+            debug!("Unchanged {:?}", path);
+            return set_mode_if_given(&path, write_info).map(|()| FileOutcome::Unchanged);
+        }
         match write_info.existing_file_policy {
             ExistingFilePolicy::Overwrite => {
                 debug!("Overwriting {:?}", path);
@@ -24,43 +46,116 @@ pub fn handle_write_file(write_info: WriteFileInfo, responses: &dyn Responder) {
             }
             ExistingFilePolicy::Preserve => {
                 debug!("Preserving {:?}", path);
-                responses.respond(ClientMessage::Ack);
-                return;
+                return Ok(FileOutcome::Preserved);
             }
             ExistingFilePolicy::Prompt => {
-                report_diff(&path, &write_info.contents);
-                let overwrite = Confirm::new(format!("Overwrite '{}'?", path).as_str())
-                    .prompt_skippable()
-                    .unwrap_or_default()
-                    .unwrap_or_default();
-                if !overwrite {
-                    debug!("Preserving {:?}", path);
-                    responses.respond(ClientMessage::Ack);
-                    return;
+                if !confirm_overwrite(&path, write_info) {
+                    return Ok(FileOutcome::Preserved);
                 }
-                debug!("Overwriting {:?}", path);
             }
             ExistingFilePolicy::Error => {
                 // Hard-fail — idempotent-render contract violation.
-                responses.respond(ClientMessage::Error(format!(
-                    "File already exists: {} (if_exists = Existing.Error)",
-                    path
-                )));
-                return;
+                return Err(format!("File already exists: {} (if_exists = Existing.Error)", path));
             }
+            ExistingFilePolicy::Merge => match merge_file(&path, write_info)? {
+                Some(outcome) => return Ok(outcome),
+                // Nothing to merge against: ask, as `Existing.Prompt` would.
+                None => {
+                    if !confirm_overwrite(&path, write_info) {
+                        return Ok(FileOutcome::Preserved);
+                    }
+                }
+            },
         }
+        FileOutcome::Overwritten
     } else {
         debug!("Writing {:?}", path);
+        FileOutcome::Created
+    };
+
+    fs::write(&path, &write_info.contents).map_err(|error| error.to_string())?;
+    set_mode_if_given(&path, write_info).map(|()| outcome)
+}
+
+fn set_mode_if_given(path: &Utf8PathBuf, write_info: &WriteFileInfo) -> Result<(), String> {
+    match write_info.mode {
+        Some(mode) => set_mode(path, mode),
+        None => Ok(()),
     }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Utf8PathBuf, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    debug!("Setting mode {:o} on {:?}", mode, path);
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|error| error.to_string())
+}
 
-    match fs::write(&path, write_info.contents) {
-        Ok(()) => {
-            responses.respond(ClientMessage::Ack);
+#[cfg(not(unix))]
+fn set_mode(_path: &Utf8PathBuf, _mode: u32) -> Result<(), String> {
+    Ok(())
+}
+
+/// Show what would change and ask whether to overwrite.
+fn confirm_overwrite(path: &Utf8PathBuf, write_info: &WriteFileInfo) -> bool {
+    report_diff(path, &write_info.contents);
+    let overwrite = Confirm::new(format!("Overwrite '{}'?", path).as_str())
+        .prompt_skippable()
+        .unwrap_or_default()
+        .unwrap_or_default();
+    if overwrite {
+        debug!("Overwriting {:?}", path);
+    } else {
+        debug!("Preserving {:?}", path);
+    }
+    overwrite
+}
+
+/// Merge into the file on disk. `None` if there's no record of the last
+/// render to merge against, leaving the write to the caller.
+fn merge_file(path: &Utf8PathBuf, write_info: &WriteFileInfo) -> Result<Option<FileOutcome>, String> {
+    let ours = fs::read(path).map_err(|error| error.to_string())?;
+    let base = write_info.shadow.as_ref().and_then(|shadow| fs::read(shadow).ok());
+    match three_way(base.as_deref(), &ours, &write_info.contents) {
+        MergeOutcome::Clean(merged) => {
+            if merged == ours {
+                debug!("Unchanged {:?}", path);
+                return set_mode_if_given(path, write_info).map(|()| Some(FileOutcome::Unchanged));
+            }
+            debug!("Merging {:?}", path);
+            report_diff(path, &merged);
+            fs::write(path, merged).map_err(|error| error.to_string())?;
+            set_mode_if_given(path, write_info).map(|()| Some(FileOutcome::Merged))
+        }
+        MergeOutcome::Conflicted(merged) => {
+            warn!("CONFLICT: {} (resolve the conflict markers by hand)", path);
+            fs::write(path, merged).map_err(|error| error.to_string())?;
+            Ok(Some(FileOutcome::Conflicted))
         }
-        Err(error) => {
-            responses.respond(ClientMessage::Error(error.to_string()));
+        MergeOutcome::Unmergeable => {
+            warn!("CONFLICT: {} (binary, kept existing file)", path);
+            Ok(Some(FileOutcome::Preserved))
         }
+        MergeOutcome::NoBase => {
+            debug!("No record of {:?} from the last render to merge against", path);
+            Ok(None)
+        }
+    }
+}
+
+/// Record what the archetype generated, whatever the user has made of it since.
+/// The next merge needs the archetype's side of the history, not the user's —
+/// but only once the file has taken it: a preserved or conflicted file still
+/// stands on the last render's, which the next merge is against.
+fn refresh_shadow(write_info: &WriteFileInfo) -> Result<(), String> {
+    let Some(shadow) = &write_info.shadow else {
+        return Ok(());
+    };
+    let shadow = Utf8PathBuf::from(shadow);
+    if let Some(parent) = shadow.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
     }
+    fs::write(&shadow, &write_info.contents).map_err(|error| error.to_string())
 }
 
 /// Print a unified diff between the existing file's contents on disk and the
@@ -71,51 +166,54 @@ fn report_diff(path: &Utf8PathBuf, new_contents: &[u8]) {
         Ok(b) => b,
         Err(_) => return,
     };
+    eprint!("{}", format_diff(path, &existing, new_contents));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camino::Utf8Path;
+    use tempfile::TempDir;
 
-    if existing == new_contents {
-        eprintln!("--- {} (no change) ---", path);
-        return;
+    fn setup(on_disk: &str, shadowed: &str) -> (TempDir, Utf8PathBuf, Utf8PathBuf) {
+        let root = TempDir::new().unwrap();
+        let root_path = Utf8Path::from_path(root.path()).unwrap();
+        let (path, shadow) = (root_path.join("notes.txt"), root_path.join("shadow/notes.txt"));
+        fs::write(&path, on_disk).unwrap();
+        fs::create_dir_all(shadow.parent().unwrap()).unwrap();
+        fs::write(&shadow, shadowed).unwrap();
+        (root, path, shadow)
     }
 
-    let existing_binary = matches!(
-        content_inspector::inspect(&existing),
-        ContentType::BINARY
-    );
-    let new_binary = matches!(
-        content_inspector::inspect(new_contents),
-        ContentType::BINARY
-    );
-
-    if existing_binary || new_binary {
-        eprintln!(
-            "--- {} (binary, {} → {} bytes) ---",
-            path,
-            existing.len(),
-            new_contents.len()
-        );
-        return;
+    fn write(path: &Utf8Path, shadow: &Utf8Path, contents: &str, policy: ExistingFilePolicy) -> WriteFileInfo {
+        WriteFileInfo {
+            destination: path.to_string(),
+            contents: contents.as_bytes().to_vec(),
+            existing_file_policy: policy,
+            shadow: Some(shadow.to_string()),
+            mode: Some(0o755),
+        }
+    }
+
+    #[test]
+    fn a_preserved_file_keeps_its_shadow() {
+        let (_root, path, shadow) = setup("edited\n", "rendered\n");
+        let outcome = apply_write_file(&write(&path, &shadow, "rerendered\n", ExistingFilePolicy::Preserve)).unwrap();
+        assert_eq!(outcome, FileOutcome::Preserved);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "edited\n");
+        assert_eq!(fs::read_to_string(&shadow).unwrap(), "rendered\n");
     }
 
-    let old_text = String::from_utf8_lossy(&existing);
-    let new_text = String::from_utf8_lossy(new_contents);
-    let diff = TextDiff::from_lines(&old_text, &new_text);
+    #[cfg(unix)]
+    #[test]
+    fn a_clean_merge_takes_the_mode_and_the_shadow() {
+        use std::os::unix::fs::PermissionsExt;
 
-    eprintln!("--- {} ---", path);
-    let mut emitted = 0usize;
-    for change in diff.iter_all_changes() {
-        if emitted >= MAX_DIFF_LINES {
-            eprintln!("... (diff truncated at {} lines)", MAX_DIFF_LINES);
-            return;
-        }
-        let prefix = match change.tag() {
-            ChangeTag::Delete => "-",
-            ChangeTag::Insert => "+",
-            ChangeTag::Equal => " ",
-        };
-        // change.value() includes the trailing newline; trim it so we can
-        // re-emit one consistently.
-        let line = change.value().trim_end_matches('\n');
-        eprintln!("{}{}", prefix, line);
-        emitted += 1;
+        let (_root, path, shadow) = setup("one\ntwo\nthree\nfour\nmine\n", "one\ntwo\nthree\nfour\n");
+        let info = write(&path, &shadow, "ONE\ntwo\nthree\nfour\n", ExistingFilePolicy::Merge);
+        assert_eq!(apply_write_file(&info).unwrap(), FileOutcome::Merged);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ONE\ntwo\nthree\nfour\nmine\n");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o755);
+        assert_eq!(fs::read_to_string(&shadow).unwrap(), "ONE\ntwo\nthree\nfour\n");
     }
 }
@@ -0,0 +1,85 @@
+use std::fs;
+
+use camino::Utf8PathBuf;
+use inquire::Confirm;
+use log::{debug, warn};
+
+use archetect_api::{ClientMessage, ExistingFilePolicy, WriteSymlinkInfo};
+use crate::responder::Responder;
+
+pub fn handle_write_symlink(write_info: WriteSymlinkInfo, responses: &dyn Responder) {
+    match apply_write_symlink(&write_info) {
+        Ok(()) => responses.respond(ClientMessage::Ack),
+        Err(message) => responses.respond(ClientMessage::Error(message)),
+    }
+}
+
+/// Create a symlink through its existing-file policy. Whatever is already at
+/// the path counts as existing, including a link that points nowhere.
+pub(crate) fn apply_write_symlink(write_info: &WriteSymlinkInfo) -> Result<(), String> {
+    let path = Utf8PathBuf::from(&write_info.path);
+
+    if path.symlink_metadata().is_ok() {
+        if fs::read_link(&path).is_ok_and(|target| target.as_os_str() == write_info.target.as_str()) {
+            debug!("Unchanged {:?}", path);
+            return Ok(());
+        }
+        match write_info.existing_file_policy {
+            ExistingFilePolicy::Overwrite => {
+                debug!("Replacing {:?}", path);
+            }
+            ExistingFilePolicy::Preserve => {
+                debug!("Preserving {:?}", path);
+                return Ok(());
+            }
+            ExistingFilePolicy::Prompt => {
+                let overwrite = Confirm::new(
+                    format!("Replace '{}' with a link to '{}'?", path, write_info.target).as_str(),
+                )
+                .prompt_skippable()
+                .unwrap_or_default()
+                .unwrap_or_default();
+                if !overwrite {
+                    debug!("Preserving {:?}", path);
+                    return Ok(());
+                }
+                debug!("Replacing {:?}", path);
+            }
+            ExistingFilePolicy::Error => {
+                return Err(format!("File already exists: {} (if_exists = Existing.Error)", path));
+            }
+            ExistingFilePolicy::Merge => {
+                warn!("CONFLICT: {} (symlink, kept existing)", path);
+                return Ok(());
+            }
+        }
+        remove_existing(&path)?;
+    } else {
+        debug!("Linking {:?} -> {:?}", path, write_info.target);
+    }
+
+    symlink(&write_info.target, &path).map_err(|error| format!("{}: {}", path, error))
+}
+
+fn remove_existing(path: &Utf8PathBuf) -> Result<(), String> {
+    let is_dir = path.symlink_metadata().is_ok_and(|metadata| metadata.is_dir());
+    let removed = if is_dir { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+    removed.map_err(|error| format!("{}: {}", path, error))
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, path: &Utf8PathBuf) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+/// Windows distinguishes links to files from links to directories, so look
+/// at what the target resolves to from the link's own directory.
+#[cfg(windows)]
+fn symlink(target: &str, path: &Utf8PathBuf) -> std::io::Result<()> {
+    let resolved = path.parent().map(|parent| parent.join(target)).unwrap_or_else(|| target.into());
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, path)
+    } else {
+        std::os::windows::fs::symlink_file(target, path)
+    }
+}